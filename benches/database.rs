@@ -0,0 +1,142 @@
+//! Benchmarks of database operations on synthetic databases of 10^3 to 10^6 elements.
+//! Run with `cargo bench`, reports are written to `target/criterion`.
+#[macro_use]
+extern crate criterion;
+extern crate rett;
+
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use std::collections::{HashSet, VecDeque};
+
+use rett::relations::{Atom, Database, Element, Index, Relation};
+
+/// Number of abstract elements of synthetic databases.
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+/// Abstract elements named by a text atom, each linked to the previous one: 3 relations per element.
+fn synthetic_database(size: usize) -> Database {
+    let mut db = Database::new();
+    let named = db.insert_atom(Atom::from("named"));
+    let follows = db.insert_atom(Atom::from("follows"));
+    let mut previous = None;
+    for i in 0..size {
+        let element = db.create_abstract_element();
+        let name = db.insert_atom(Atom::from(format!("element {}", i)));
+        let relation = |subject, descriptor, complement| Relation {
+            subject,
+            descriptor,
+            complement,
+        };
+        db.insert_relation(relation(element, named, Some(name)))
+            .unwrap();
+        if let Some(previous) = previous {
+            db.insert_relation(relation(element, follows, Some(previous)))
+                .unwrap();
+        }
+        previous = Some(element);
+    }
+    db
+}
+
+/// Number of elements reachable from start, following relations in both directions.
+fn breadth_first_search(db: &Database, start: Index) -> usize {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+    while let Some(index) = queue.pop_front() {
+        let element = db.element(index).unwrap();
+        let mut neighbours: Vec<Index> = Vec::new();
+        if let Element::Relation(r) = element.value() {
+            neighbours.extend(&[r.subject, r.descriptor]);
+            neighbours.extend(r.complement);
+        }
+        neighbours.extend(element.subject_of().iter().map(|r| r.index()));
+        neighbours.extend(element.descriptor_of().iter().map(|r| r.index()));
+        neighbours.extend(element.complement_of().iter().map(|r| r.index()));
+        for neighbour in neighbours {
+            if visited.insert(neighbour) {
+                queue.push_back(neighbour)
+            }
+        }
+    }
+    visited.len()
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_atoms");
+    group.sample_size(10);
+    for &size in SIZES.iter() {
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                let mut db = Database::new();
+                for i in 0..size as i64 {
+                    db.insert_atom(Atom::from(i));
+                }
+                db
+            })
+        });
+    }
+    group.finish();
+
+    // Databases are built once, and shared by the read-only benchmarks.
+    let databases: Vec<(usize, Database)> = SIZES
+        .iter()
+        .map(|&size| (size, synthetic_database(size)))
+        .collect();
+
+    let mut group = c.benchmark_group("relation_lookup");
+    for &(size, ref db) in databases.iter() {
+        let named = db.index_of_text_atom("named").unwrap();
+        let element = db
+            .index_of_text_atom(&format!("element {}", size / 2))
+            .unwrap();
+        let subject = db.element(element).unwrap().complement_of().iter().next();
+        let subject = subject.unwrap().value().subject;
+        group.bench_with_input(BenchmarkId::from_parameter(size), db, |b, db| {
+            b.iter(|| {
+                let relations = db.relations_with_subject(black_box(subject)).unwrap();
+                relations.filter(|r| r.value().descriptor == named).count()
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("iterate");
+    group.sample_size(10);
+    for &(size, ref db) in databases.iter() {
+        group.throughput(Throughput::Elements(db.iter().count() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), db, |b, db| {
+            b.iter(|| db.iter().count())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("breadth_first_search");
+    group.sample_size(10);
+    for &(size, ref db) in databases.iter() {
+        let start = db.index_of_text_atom("element 0").unwrap();
+        group.throughput(Throughput::Elements(db.iter().count() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), db, |b, db| {
+            b.iter(|| breadth_first_search(db, black_box(start)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("text_round_trip");
+    group.sample_size(10);
+    for &(size, ref db) in databases.iter() {
+        group.throughput(Throughput::Elements(db.iter().count() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), db, |b, db| {
+            b.iter(|| {
+                let mut serialized: Vec<u8> = Vec::new();
+                db.write_to(&mut serialized).unwrap();
+                Database::read_from(serialized.as_slice()).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(database, benchmarks);
+criterion_main!(database);
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use relations::query::{self, Clause, Pattern, Term};
+use relations::{Atom, Database, Element, Index, Relation};
+
+/// Operation on a database, written as one line of text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `new` or `create`: create an abstract element.
+    New,
+    /// `atom text`: create a text atom with the rest of the line.
+    Atom(String),
+    /// `relate subject -[descriptor]-> complement`, or `link`: create a relation, with the query
+    /// syntax. Texts designate text atoms, which are created if needed.
+    /// `tag element text` is a shorthand for `relate element -[text]`.
+    Relate(Clause),
+    /// `query pattern`: print elements matching a query.
+    Query(Pattern),
+    /// `show element`: print an element and relations using it.
+    Show(Term),
+    /// `remove element` or `delete`: remove an element and relations using it.
+    Remove(Term),
+}
+
+/// Result of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// Index of the created element.
+    Element(Index),
+    Text(String),
+}
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Element(index) => write!(f, "#{}\n", index),
+            Output::Text(text) => text.fmt(f),
+        }
+    }
+}
+
+/// Summary of commands, for interactive use.
+pub const HELP: &'static str = "\
+new, create                      create an abstract element
+atom <text>                      create a text atom
+relate, link <s> -[<d>]-> <c>    create a relation, complement is optional
+tag <element> <text>             create a relation without complement: <element> -[<text>]
+query <pattern>                  print matches, like: query ?x -[named]-> \"Joe\"
+show <element>                   print an element and relations using it
+remove, delete <element>         remove an element and relations using it
+?name = <command>                name the element created by new, atom or relate
+Elements are #index, ?name, or text atoms as \"quoted text\" or bare words.
+Lines starting with # are comments.";
+
+impl Command {
+    pub fn modifies_database(&self) -> bool {
+        match self {
+            Command::Query(_) | Command::Show(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Parse a line, returning None for empty lines and comments.
+pub fn parse(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (keyword, rest) = match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim_start()),
+        None => (line, ""),
+    };
+    let error = |e: query::ParseError| format!("{}: {}", keyword, e);
+    let command = match keyword {
+        "new" | "create" if rest.is_empty() => Command::New,
+        "atom" if !rest.is_empty() => Command::Atom(rest.into()),
+        "relate" | "link" => {
+            let mut pattern = query::parse(rest).map_err(error)?;
+            if pattern.clauses.len() != 1 {
+                return Err("relate: expected a single relation".into());
+            }
+            Command::Relate(pattern.clauses.pop().unwrap())
+        }
+        "tag" => {
+            let (element, tag) = parse_tag(rest).ok_or("tag: expected an element and a text")?;
+            Command::Relate(Clause {
+                subject: element,
+                descriptor: Term::Text(tag.into()),
+                complement: None,
+            })
+        }
+        "query" => Command::Query(query::parse(rest).map_err(error)?),
+        "show" => Command::Show(query::parse_term(rest).map_err(error)?),
+        "remove" | "delete" => Command::Remove(query::parse_term(rest).map_err(error)?),
+        "new" | "create" | "atom" => return Err(format!("{}: invalid arguments", keyword)),
+        _ => return Err(format!("unknown command: {}", keyword)),
+    };
+    Ok(Some(command))
+}
+
+/// `element text`: the element is the shortest prefix which is a valid term, the tag the rest.
+fn parse_tag(text: &str) -> Option<(Term, &str)> {
+    let split = |(end, _): (usize, &str)| {
+        let tag = text[end..].trim();
+        match query::parse_term(&text[..end]) {
+            Ok(element) if !tag.is_empty() => Some((element, tag)),
+            _ => None,
+        }
+    };
+    text.match_indices(char::is_whitespace).find_map(split)
+}
+
+/// Apply a command.
+pub fn execute(database: &mut Database, command: &Command) -> Result<Output, String> {
+    match command {
+        Command::New => Ok(Output::Element(database.create_abstract_element())),
+        Command::Atom(text) => {
+            let index = database.insert_atom(Atom::from(text.as_str()));
+            Ok(Output::Element(index))
+        }
+        Command::Relate(clause) => {
+            let terms = [
+                Some(&clause.subject),
+                Some(&clause.descriptor),
+                clause.complement.as_ref(),
+            ];
+            // Check everything before creating atoms, so that errors leave the database unchanged.
+            for term in terms.iter().filter_map(|t| *t) {
+                match term {
+                    Term::Text(_) => (),
+                    term => {
+                        resolve(database, term)?;
+                    }
+                }
+            }
+            let relation = {
+                let mut component = |term: &Term| match term {
+                    Term::Text(text) => database.insert_atom(Atom::from(text.as_str())),
+                    Term::Index(index) => *index,
+                    _ => unreachable!(),
+                };
+                Relation {
+                    subject: component(&clause.subject),
+                    descriptor: component(&clause.descriptor),
+                    complement: clause.complement.as_ref().map(component),
+                }
+            };
+            let index = database.insert_relation(relation).map_err(|e| e.to_string())?;
+            Ok(Output::Element(index))
+        }
+        Command::Query(pattern) => {
+            let results = database.query(pattern);
+            let table = query_table(database, &pattern.variables(), &results);
+            Ok(Output::Text(table))
+        }
+        Command::Show(term) => {
+            let index = resolve(database, term)?;
+            let element = database.element(index).unwrap();
+            let mut text = format!("#{} = {}\n", index, value_text(database, index));
+            let relations = element
+                .subject_of()
+                .iter()
+                .chain(element.descriptor_of().iter())
+                .chain(element.complement_of().iter());
+            for relation in relations {
+                let relation_text = value_text(database, relation.index());
+                write!(text, "#{} = {}\n", relation.index(), relation_text).unwrap()
+            }
+            Ok(Output::Text(text))
+        }
+        Command::Remove(term) => {
+            let index = resolve(database, term)?;
+            let removed = database
+                .remove_element_and_relations(index)
+                .map_err(|e| e.to_string())?;
+            let removed: Vec<String> = removed.iter().map(|i| format!("#{}", i)).collect();
+            Ok(Output::Text(format!("removed {}\n", removed.join(" "))))
+        }
+    }
+}
+
+/// Sequence of commands, where variables can name created elements.
+#[derive(Debug, Default)]
+pub struct Session {
+    variables: HashMap<String, Index>,
+    /// Set by commands modifying the database.
+    pub modified: bool,
+}
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Parse and execute a line, which can be an assignment: `?name = command`.
+    /// Named variables are replaced by their element in later commands, including queries.
+    pub fn run_line(&mut self, database: &mut Database, line: &str) -> Result<Output, String> {
+        let line = line.trim();
+        let (variable, line) = match line.find('=') {
+            Some(equal) if line.starts_with('?') => {
+                let name = line[1..equal].trim();
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(format!("invalid variable name: {}", name));
+                }
+                (Some(name), &line[equal + 1..])
+            }
+            _ => (None, line),
+        };
+        let mut command = match parse(line)? {
+            Some(command) => command,
+            None if variable.is_none() => return Ok(Output::Text(String::new())),
+            None => return Err("missing command after =".into()),
+        };
+        self.substitute(&mut command);
+        let output = execute(database, &command)?;
+        self.modified |= command.modifies_database();
+        if let Some(name) = variable {
+            match output {
+                Output::Element(index) => self.variables.insert(name.to_string(), index),
+                Output::Text(_) => return Err("only created elements can be named".into()),
+            };
+        }
+        Ok(output)
+    }
+
+    fn substitute(&self, command: &mut Command) {
+        let substitute = |term: &mut Term| {
+            let index = match *term {
+                Term::Variable(ref name) => self.variables.get(name).cloned(),
+                _ => None,
+            };
+            if let Some(index) = index {
+                *term = Term::Index(index)
+            }
+        };
+        let substitute_clause = |clause: &mut Clause| {
+            substitute(&mut clause.subject);
+            substitute(&mut clause.descriptor);
+            if let Some(complement) = clause.complement.as_mut() {
+                substitute(complement)
+            }
+        };
+        match command {
+            Command::Relate(clause) => substitute_clause(clause),
+            Command::Query(pattern) => pattern.clauses.iter_mut().for_each(substitute_clause),
+            Command::Show(term) | Command::Remove(term) => substitute(term),
+            Command::New | Command::Atom(_) => (),
+        }
+    }
+}
+
+/// Run all lines of a script, stopping at the first error.
+/// Returns the concatenated outputs, or the error with its line number.
+pub fn run_script(database: &mut Database, script: &str) -> Result<String, String> {
+    let mut session = Session::new();
+    let mut outputs = String::new();
+    for (line_number, line) in script.lines().enumerate() {
+        match session.run_line(database, line) {
+            Ok(output) => write!(outputs, "{}", output).unwrap(),
+            Err(e) => return Err(format!("line {}: {}", line_number + 1, e)),
+        }
+    }
+    Ok(outputs)
+}
+
+/// Existing element designated by a term.
+fn resolve(database: &Database, term: &Term) -> Result<Index, String> {
+    match term {
+        Term::Index(index) => match database.element(*index) {
+            Ok(_) => Ok(*index),
+            Err(e) => Err(format!("#{}: {}", index, e)),
+        },
+        Term::Text(text) => database
+            .index_of_text_atom(text.as_str())
+            .ok_or_else(|| format!("no text atom {}", QuotedText(text))),
+        Term::Variable(name) => Err(format!("unknown variable ?{}", name)),
+        Term::Any => Err("_ is only allowed in queries".into()),
+    }
+}
+
+/// Atoms in query syntax, and relations with their components.
+fn value_text(database: &Database, index: Index) -> String {
+    let term_text = |index: Index| match database.element(index).unwrap().value() {
+        Element::Atom(Atom::Text(text)) => QuotedText(text).to_string(),
+        Element::Atom(atom) => atom.to_string(),
+        _ => format!("#{}", index),
+    };
+    match database.element(index).unwrap().value() {
+        Element::Abstract => "abstract".into(),
+        Element::Atom(_) => term_text(index),
+        Element::Relation(r) => match r.complement {
+            Some(c) => format!(
+                "{} -[{}]-> {}",
+                term_text(r.subject),
+                term_text(r.descriptor),
+                term_text(c)
+            ),
+            None => format!("{} -[{}]", term_text(r.subject), term_text(r.descriptor)),
+        },
+    }
+}
+
+/// Text with quotes and escapes of the query syntax.
+struct QuotedText<'a>(&'a str);
+impl<'a> std::fmt::Display for QuotedText<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if c == '"' || c == '\\' {
+                f.write_char('\\')?
+            }
+            f.write_char(c)?
+        }
+        f.write_char('"')
+    }
+}
+
+/// Tab separated values, with text atoms shown as their text and other elements as #index.
+pub fn query_table(database: &Database, variables: &[&str], results: &[Vec<Index>]) -> String {
+    let mut table = format!("{}\n", variables.join("\t"));
+    for row in results {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|&index| match database.element(index).unwrap().value() {
+                Element::Atom(atom) => atom.to_string(),
+                _ => format!("#{}", index),
+            })
+            .collect();
+        write!(table, "{}\n", cells.join("\t")).unwrap()
+    }
+    table
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        assert_eq!(parse("  "), Ok(None));
+        assert_eq!(parse("# comment"), Ok(None));
+        assert_eq!(parse("new"), Ok(Some(Command::New)));
+        assert_eq!(parse("atom  Joe  Dalton "), Ok(Some(Command::Atom("Joe  Dalton".into()))));
+        assert_eq!(parse("show #3"), Ok(Some(Command::Show(Term::Index(3)))));
+        assert!(parse("new thing").is_err());
+        assert!(parse("atom").is_err());
+        assert!(parse("relate #1 -[a], #1 -[b]").is_err());
+        assert!(parse("show #3 #4").is_err());
+        assert!(parse("unknown").is_err());
+        assert_eq!(parse("create"), Ok(Some(Command::New)));
+        let remove = Command::Remove(Term::Index(3));
+        assert_eq!(parse("delete #3"), Ok(Some(remove)));
+        assert_eq!(parse("link #1 -[a]"), parse("relate #1 -[a]"));
+        assert_eq!(parse("tag #1 lives in"), parse("relate #1 -[lives in]"));
+        assert_eq!(
+            parse("tag \"Joe Dalton\" outlaw"),
+            parse("relate \"Joe Dalton\" -[outlaw]")
+        );
+        assert!(parse("tag #1").is_err());
+    }
+
+    #[test]
+    fn execution() {
+        let mut db = Database::new();
+        let mut run = |line: &str| {
+            let output = execute(&mut db, &parse(line).unwrap().unwrap());
+            output.map(|output| output.to_string())
+        };
+        assert_eq!(run("new"), Ok("#0\n".into()));
+        assert_eq!(run("relate #0 -[named]-> \"Joe \\\"Jr\\\"\""), Ok("#3\n".into()));
+        assert_eq!(run("atom named"), Ok("#1\n".into()));
+        assert_eq!(
+            run("show #0"),
+            Ok("#0 = abstract\n#3 = #0 -[\"named\"]-> \"Joe \\\"Jr\\\"\"\n".into())
+        );
+        assert_eq!(run("query ?x -[named]-> _"), Ok("x\n#0\n".into()));
+        assert!(run("relate #0 -[likes]-> #10").is_err());
+        assert!(run("relate #0 -[?x]").is_err());
+        assert_eq!(run("show likes"), Err("no text atom \"likes\"".into()));
+        assert_eq!(run("remove #0"), Ok("removed #3 #0\n".into()));
+    }
+
+    #[test]
+    fn script() {
+        let mut db = Database::new();
+        let script = "# Dalton family\n\
+                      ?joe = new\n\
+                      ?jack = new\n\
+                      relate ?joe -[named]-> Joe\n\
+                      ?link = relate ?jack -[brother of]-> ?joe\n\
+                      relate ?link -[since]-> birth\n\
+                      \n\
+                      query ?x -[brother of]-> ?joe\n";
+        assert_eq!(
+            run_script(&mut db, script),
+            Ok("#0\n#1\n#4\n#6\n#9\nx\n#1\n".into())
+        );
+        assert_eq!(
+            run_script(&mut db, "?x = new\nshow ?y"),
+            Err("line 2: unknown variable ?y".into())
+        );
+        assert!(run_script(&mut db, "?x = show #0").is_err());
+        assert!(run_script(&mut db, "?x y = new").is_err());
+        let mut session = Session::new();
+        assert_eq!(session.run_line(&mut db, "show #0").map(|_| ()), Ok(()));
+        assert!(!session.modified);
+        assert_eq!(session.run_line(&mut db, "?x = atom x"), Ok(Output::Element(11)));
+        assert!(session.modified);
+    }
+}
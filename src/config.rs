@@ -0,0 +1,258 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use toml;
+
+use relations::schema::Schema;
+use relations::Rules;
+use utils::Collation;
+
+/// Settings of the program: defaults, replaced by the configuration file, then by the command line.
+pub struct Config {
+    pub database_file: Option<PathBuf>,
+    pub wiki: Wiki,
+}
+
+/// Settings of the wiki server.
+pub struct Wiki {
+    pub addr: SocketAddr,
+    /// Backup of the main database file, see default_backup_file.
+    pub backup_file: Option<PathBuf>,
+    /// Databases served in addition to the main one, under /db/<name>/, with default backup files.
+    /// Configured by `[db.<name>]` tables containing a path.
+    pub databases: Vec<(String, PathBuf)>,
+    pub autosave_interval: Duration,
+    pub collation: Collation,
+    pub read_only: bool,
+    /// File containing the password required for modifications.
+    pub password_file: Option<PathBuf>,
+    /// Names of the compiled-in plugins to enable.
+    pub plugins: Vec<String>,
+    /// Constraints checked by the wiki forms creating relations.
+    pub schema: Schema,
+    /// Script of the cytoscape.js library, loaded by browsers for interactive graphs.
+    pub cytoscape_url: String,
+    /// Inference rules, shown on element pages. Also used by queries and materialize.
+    pub rules: Rules,
+}
+impl Wiki {
+    pub fn backup_file(&self, database_file: &Path) -> PathBuf {
+        match self.backup_file {
+            Some(ref path) => path.clone(),
+            None => default_backup_file(database_file),
+        }
+    }
+}
+
+/// Backup file used when none is configured: the database file with a .bak extension.
+pub fn default_backup_file(database_file: &Path) -> PathBuf {
+    let mut path = database_file.as_os_str().to_owned();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+/// Public CDN copy of cytoscape.js, to be replaced by a local copy for offline use.
+pub const DEFAULT_CYTOSCAPE_URL: &'static str =
+    "https://unpkg.com/cytoscape@3/dist/cytoscape.min.js";
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_file: None,
+            wiki: Wiki {
+                addr: ([0, 0, 0, 0], 8000).into(),
+                backup_file: None,
+                databases: Vec::new(),
+                autosave_interval: Duration::from_secs(10 * 60),
+                collation: Collation::Locale,
+                read_only: false,
+                password_file: None,
+                plugins: Vec::new(),
+                schema: Schema::default(),
+                cytoscape_url: DEFAULT_CYTOSCAPE_URL.to_string(),
+                rules: Rules::default(),
+            },
+        }
+    }
+}
+
+/// Value of a configuration entry.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<String>),
+}
+
+impl Config {
+    /// Read a configuration file, using defaults for missing keys.
+    pub fn read_from_file(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path.display(), e))?;
+        Config::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse a configuration in TOML, using defaults for missing keys.
+    /// Top level entries are set by name, and `[db.<name>]` tables add databases.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let table: toml::value::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("db", toml::Value::Table(databases)) => config.set_databases(databases)?,
+                (key, value) => config.set(key, Value::from_toml(key, value)?)?,
+            }
+        }
+        Ok(config)
+    }
+
+    /// Set an entry by its name in the configuration file.
+    pub fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match (key, value) {
+            ("database", Value::String(path)) => self.database_file = Some(path.into()),
+            ("addr", Value::String(addr)) => {
+                self.wiki.addr = addr
+                    .parse()
+                    .map_err(|_| format!("Unable to parse address: {}", addr))?
+            }
+            ("backup", Value::String(path)) => self.wiki.backup_file = Some(path.into()),
+            ("autosave", Value::Integer(minutes)) if minutes > 0 => {
+                self.wiki.autosave_interval = Duration::from_secs(minutes as u64 * 60)
+            }
+            ("autosave", _) => return Err("autosave must be a positive number of minutes".into()),
+            ("collation", Value::String(collation)) => self.wiki.collation = collation.parse()?,
+            ("read_only", Value::Boolean(b)) => self.wiki.read_only = b,
+            ("password_file", Value::String(path)) => self.wiki.password_file = Some(path.into()),
+            ("plugins", Value::Array(names)) => self.wiki.plugins = names,
+            ("cytoscape_url", Value::String(url)) => self.wiki.cytoscape_url = url,
+            ("inverses", Value::Array(entries)) => {
+                self.wiki.rules.inverses = entries
+                    .iter()
+                    .map(|entry| entry.parse())
+                    .collect::<Result<_, String>>()?
+            }
+            ("schema", Value::Array(constraints)) => {
+                self.wiki.schema = Schema {
+                    constraints: constraints
+                        .iter()
+                        .map(|constraint| constraint.parse())
+                        .collect::<Result<_, String>>()?,
+                }
+            }
+            (key, value) => return Err(format!("Invalid entry: {} = {:?}", key, value)),
+        }
+        Ok(())
+    }
+
+    /// Databases from the `db` table: `[db.<name>]` tables with a `path` entry.
+    /// Names are part of urls, so they are restricted to alphanumeric characters, '-' and '_'.
+    fn set_databases(&mut self, databases: toml::value::Table) -> Result<(), String> {
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        self.wiki.databases.clear();
+        for (name, database) in databases {
+            if name.is_empty() || !name.chars().all(is_name_char) {
+                return Err(format!("Invalid database name: {}", name));
+            }
+            let database = match database {
+                toml::Value::Table(database) => database,
+                _ => return Err(format!("Expected a table for database {}", name)),
+            };
+            if let Some(key) = database.keys().find(|key| *key != "path") {
+                return Err(format!("Invalid entry in [db.{}]: {}", name, key));
+            }
+            let path = match database.get("path") {
+                Some(toml::Value::String(path)) => PathBuf::from(path),
+                _ => return Err(format!("Expected a path string in [db.{}]", name)),
+            };
+            self.wiki.databases.push((name, path))
+        }
+        Ok(())
+    }
+}
+
+impl Value {
+    /// Convert a TOML value of the entry, which must be a scalar or an array of strings.
+    fn from_toml(key: &str, value: toml::Value) -> Result<Self, String> {
+        match value {
+            toml::Value::String(s) => Ok(Value::String(s)),
+            toml::Value::Integer(i) => Ok(Value::Integer(i)),
+            toml::Value::Boolean(b) => Ok(Value::Boolean(b)),
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    toml::Value::String(s) => Ok(s),
+                    _ => Err(format!("{}: expected an array of strings", key)),
+                })
+                .collect::<Result<_, String>>()
+                .map(Value::Array),
+            value => Err(format!("Invalid entry: {} = {}", key, value)),
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_file() {
+        let text = "# Wiki of the family\n\
+                    database = \"dalton.db\"\n\
+                    autosave = 5\n\
+                    read_only = true\n\
+                    schema = [\"born: date\", \"abstract has one named\"]\n\
+                    \n\
+                    [db.work]\n\
+                    path = \"work.db\"\n\
+                    [db.home-2]\n\
+                    path = \"/data/home.db\"\n";
+        let config = Config::from_toml(text).unwrap();
+        assert_eq!(config.database_file, Some(PathBuf::from("dalton.db")));
+        assert_eq!(config.wiki.autosave_interval, Duration::from_secs(300));
+        assert!(config.wiki.read_only);
+        assert_eq!(config.wiki.schema.constraints.len(), 2);
+        assert_eq!(
+            config.wiki.databases,
+            vec![
+                ("home-2".to_string(), PathBuf::from("/data/home.db")),
+                ("work".to_string(), PathBuf::from("work.db"))
+            ]
+        );
+        assert!(Config::from_toml("autosave = 5 minutes").is_err());
+        assert!(Config::from_toml("plugins = [\"a\", 1]").is_err());
+        assert!(Config::from_toml("autosave = 1.5").is_err());
+        assert!(Config::from_toml("[db.\"a/b\"]\npath = \"1.db\"").is_err());
+        assert!(Config::from_toml("[db.a]\nfile = \"1.db\"").is_err());
+        assert!(Config::from_toml("[db.a]\npath = \"1.db\"\nbackup = \"2.db\"").is_err());
+        assert!(Config::from_toml("[wiki]\nread_only = true").is_err());
+    }
+
+    #[test]
+    fn set_entries() {
+        let mut config = Config::default();
+        config.set("database", Value::String("db.txt".into())).unwrap();
+        config.set("autosave", Value::Integer(2)).unwrap();
+        config.set("collation", Value::String("binary".into())).unwrap();
+        assert_eq!(config.database_file, Some(PathBuf::from("db.txt")));
+        assert_eq!(config.wiki.autosave_interval, Duration::from_secs(120));
+        assert_eq!(config.wiki.collation, Collation::Binary);
+        assert_eq!(
+            config.wiki.backup_file(Path::new("db.txt")),
+            PathBuf::from("db.txt.bak")
+        );
+        let schema = vec!["born: date".to_string(), "abstract has one named".to_string()];
+        config.set("schema", Value::Array(schema)).unwrap();
+        assert_eq!(config.wiki.schema.constraints.len(), 2);
+        assert!(config.set("schema", Value::Array(vec!["born".into()])).is_err());
+        assert!(config.set("autosave", Value::Integer(0)).is_err());
+        assert!(config.set("addr", Value::String("nowhere".into())).is_err());
+        assert!(config.set("read_only", Value::Integer(1)).is_err());
+        assert!(config.set("unknown", Value::Boolean(true)).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Tool to store, manipulate and display relational information.
+//! The rett binary is a command line interface to this library.
+#![feature(proc_macro_hygiene)]
+#![warn(missing_docs)]
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)]
+extern crate test; // Benchmarks
+
+// Wiki
+extern crate base64;
+extern crate fs2; // File locking
+extern crate futures;
+extern crate hyper;
+extern crate maud; // HTML template engine
+extern crate percent_encoding;
+extern crate sha1; // Websocket handshake
+extern crate signal_hook;
+extern crate tokio;
+extern crate toml; // Configuration file
+
+// Database
+extern crate flate2; // Compressed database files
+extern crate uuid;
+
+/// Datastructures and utility functions.
+mod utils;
+pub use utils::{Collation, Set, SetOperation, SlotVec, SlotVecIter};
+
+/// Knowledge database as a set of sentences.
+#[macro_use]
+pub mod relations;
+// Main interface: the graph, its queries, and database files.
+pub use relations::query::{self, Pattern, Term};
+pub use relations::{read_database_from_file, read_database_from_file_with_repair};
+pub use relations::{write_database_to_file, write_database_to_file_with_backup};
+pub use relations::{Atom, Database, Element, ElementKind, Error, Index, Ref, Relation};
+
+/// Wiki interface
+pub mod wiki;
+
+/// Settings from a configuration file.
+/// Public for the rett binary, but not part of the documented interface.
+#[doc(hidden)]
+pub mod config;
+
+/// Line based commands to edit a database.
+/// Public for the rett binary, but not part of the documented interface.
+#[doc(hidden)]
+pub mod commands;
@@ -1,83 +1,287 @@
-#![feature(proc_macro_hygiene)]
-
-// Wiki
-extern crate hyper;
-extern crate maud; // HTML template engine
-extern crate percent_encoding;
-extern crate signal_hook;
-extern crate tokio;
-
 #[macro_use]
 extern crate clap; // Command line parser
+extern crate notify; // File change notifications
+extern crate rett;
+extern crate rustyline; // Line editing for the interactive shell
 
-/// Datastructures and utility functions.
-mod utils;
-
-/// Knowledge database as a set of sentences.
-mod relations;
-
-/// Wiki interface
-mod wiki;
-use std::borrow::Cow;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use rett::{commands, config, relations, wiki};
+
 fn main() -> Result<(), String> {
-    use clap::{AppSettings, Arg, SubCommand};
+    use clap::{AppSettings, Arg, ArgGroup, SubCommand};
     let matches = app_from_crate!()
         .setting(AppSettings::VersionlessSubcommands)
         .setting(AppSettings::SubcommandRequired)
         .arg(
             Arg::with_name("database_file")
-                .help("Path to database file")
-                .required(true),
+                .help("Path to database file, required if not set in the configuration file"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .help("Configuration file, overriden by command line options")
+                .long("config")
+                .value_name("file"),
         )
         .subcommand(
             SubCommand::with_name("wiki")
                 .about("Run a server with a wiki-like interface to the database")
                 .arg(
                     Arg::with_name("addr")
-                        .help("Address on which the server will bind")
-                        .default_value("0.0.0.0:8000"),
+                        .help("Address on which the server will bind [default: 0.0.0.0:8000]"),
                 )
                 .arg(
                     Arg::with_name("backup_file")
                         .help("Path used for backup database file")
-                        .long("backup"),
+                        .long("backup")
+                        .value_name("file"),
                 )
                 .arg(
                     Arg::with_name("autosave")
-                        .help("Interval (in minutes) between writing the database to disk")
+                        .help("Interval (in minutes) between writing the database to disk [default: 10]")
                         .long("autosave")
-                        .value_name("interval")
-                        .default_value("10"),
+                        .value_name("interval"),
+                )
+                .arg(
+                    Arg::with_name("collation")
+                        .help("Order used to sort element names [default: locale]")
+                        .long("collation")
+                        .possible_values(&["locale", "binary"]),
+                )
+                .arg(
+                    Arg::with_name("read_only")
+                        .help("Serve the database without allowing modifications")
+                        .long("read-only"),
+                )
+                .arg(
+                    Arg::with_name("password_file")
+                        .help("Require the password contained in this file for modifications")
+                        .long("password-file")
+                        .value_name("file"),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .help("Enable a compiled-in plugin")
+                        .long("plugin")
+                        .value_name("name")
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("Print elements matching a query, like: ?x -[named]-> \"Joe\"")
+                .arg(Arg::with_name("query").help("Query text").required(true))
+                .arg(
+                    Arg::with_name("format")
+                        .help("Output format")
+                        .long("format")
+                        .possible_values(&["table", "json", "dot"])
+                        .default_value("table"),
+                )
+                .arg(
+                    Arg::with_name("inverse")
+                        .help("Descriptors of relations implying each other in reverse, added to the configuration ones")
+                        .long("inverse")
+                        .value_name("descriptor=inverse")
+                        .multiple(true)
+                        .number_of_values(1),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("materialize")
+                .about("Insert the relations implied by the inference rules, keeping a backup")
+                .arg(
+                    Arg::with_name("inverse")
+                        .help("Descriptors of relations implying each other in reverse, added to the configuration ones")
+                        .long("inverse")
+                        .value_name("descriptor=inverse")
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Edit the database with an interactive shell, see its help command"),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Apply the commands of a script file, like the shell ones, then save")
+                .arg(Arg::with_name("script").help("Script file").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Print the whole database in another graph format")
+                .arg(
+                    Arg::with_name("format")
+                        .help("Output format: dot, cytoscape, cypher, jsonld, ntriples, or one of the enabled plugins")
+                        .long("format")
+                        .value_name("format")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .help("Only export elements of this namespace")
+                        .long("namespace")
+                        .value_name("name"),
+                )
+                .arg(
+                    Arg::with_name("cluster")
+                        .help("Group elements with this tag in a box, for the dot format")
+                        .long("cluster")
+                        .value_name("tag")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("term")
+                        .help("IRI of a descriptor, for the jsonld and ntriples formats")
+                        .long("term")
+                        .value_name("descriptor=iri")
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pages")
+                .about("Write a Markdown page per abstract element, as a static wiki snapshot")
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Output directory, created if needed")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Render the database graph to files each time the database file changes")
+                .arg(
+                    Arg::with_name("dot")
+                        .help("Output file for the graph in dot format")
+                        .long("dot")
+                        .value_name("file"),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .help("Output file for the graph in svg format (requires graphviz)")
+                        .long("svg")
+                        .value_name("file"),
+                )
+                .group(
+                    ArgGroup::with_name("outputs")
+                        .args(&["dot", "svg"])
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .help("Only render elements of this namespace")
+                        .long("namespace")
+                        .value_name("name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("duplicates")
+                .about("Print pairs of abstract elements with similar relations")
+                .arg(
+                    Arg::with_name("min_similarity")
+                        .help("Minimum fraction of shared relations, between 0 and 1")
+                        .long("min-similarity")
+                        .value_name("fraction")
+                        .default_value("0.5"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Print the number of elements by kind and the approximate memory used"),
+        )
+        .subcommand(
+            SubCommand::with_name("import-vault")
+                .about("Add notes from a directory of Markdown files linked by [[wiki-links]]")
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Directory of notes, searched recursively")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Add the content of a file in a format of an enabled plugin, keeping a backup")
+                .arg(
+                    Arg::with_name("format")
+                        .help("Input format")
+                        .long("format")
+                        .value_name("format")
+                        .required(true),
+                )
+                .arg(Arg::with_name("file").help("File to import").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("repair")
+                .about("Fix inconsistencies of a hand-edited database file, keeping a backup")
+                .arg(
+                    Arg::with_name("keep_duplicates")
+                        .help("Turn duplicated elements into abstract elements instead of merging")
+                        .long("keep-duplicates"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Add the changes made to another copy of the database, keeping a backup")
+                .arg(
+                    Arg::with_name("base")
+                        .help("Database file both copies were made from")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("theirs")
+                        .help("Database file with the changes to add")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deleted")
+                .about("Print the elements deleted by the wiki, which can still be restored"),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Restore a deleted element, keeping a backup")
+                .arg(Arg::with_name("index").help("Index of the element").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("purge")
+                .about("Forget deleted elements for good, keeping a backup"),
+        )
         .get_matches();
 
-    // TODO useful tooling: merge of files
-
-    let database_filepath = Path::new(matches.value_of_os("database_file").unwrap());
+    let mut config = match matches.value_of_os("config") {
+        Some(path) => config::Config::read_from_file(Path::new(path))?,
+        None => config::Config::default(),
+    };
+    if let Some(path) = matches.value_of_os("database_file") {
+        config.database_file = Some(PathBuf::from(path))
+    }
+    let database_filepath: &Path = match config.database_file {
+        Some(ref path) => path,
+        None => return Err("Missing database file".into()),
+    };
 
     match matches.subcommand() {
         ("wiki", Some(args)) => {
-            let addr = {
-                let addr = args.value_of("addr").unwrap();
-                match addr.parse() {
+            // Command line options override the configuration file.
+            let mut wiki_config = config.wiki;
+            if let Some(addr) = args.value_of("addr") {
+                wiki_config.addr = match addr.parse() {
                     Ok(addr) => addr,
                     _ => return Err(format!("Unable to parse address: {}", addr)),
                 }
-            };
-            let backup_filepath = match matches.value_of_os("backup_file") {
-                Some(path) => Cow::Borrowed(Path::new(path)),
-                None => {
-                    let mut path = database_filepath.as_os_str().to_owned();
-                    path.push(".bak");
-                    Cow::Owned(PathBuf::from(path))
-                }
-            };
-            let autosave_duration = {
-                let minutes_text = args.value_of("autosave").unwrap();
+            }
+            if let Some(path) = args.value_of_os("backup_file") {
+                wiki_config.backup_file = Some(PathBuf::from(path))
+            }
+            if let Some(minutes_text) = args.value_of("autosave") {
                 let minutes: u64 = match minutes_text.parse() {
                     Ok(minutes) if minutes > 0 => minutes,
                     _ => {
@@ -87,18 +291,473 @@ fn main() -> Result<(), String> {
                         ))
                     }
                 };
-                Duration::from_secs(minutes * 60)
-            };
-            eprintln!("[addr] {}", addr);
+                wiki_config.autosave_interval = Duration::from_secs(minutes * 60)
+            }
+            if let Some(collation) = args.value_of("collation") {
+                wiki_config.collation = collation.parse()?
+            }
+            if args.is_present("read_only") {
+                wiki_config.read_only = true
+            }
+            if let Some(path) = args.value_of_os("password_file") {
+                wiki_config.password_file = Some(PathBuf::from(path))
+            }
+            if let Some(names) = args.values_of("plugin") {
+                wiki_config.plugins = names.map(String::from).collect()
+            }
+            let plugins = enabled_plugins(&wiki_config)?;
+            eprintln!("[addr] {}", wiki_config.addr);
             eprintln!("[database file] {}", database_filepath.display());
+            eprintln!(
+                "[backup file] {}",
+                wiki_config.backup_file(database_filepath).display()
+            );
+            for (name, path) in wiki_config.databases.iter() {
+                eprintln!("[database file /db/{}] {}", name, path.display());
+            }
+            if wiki_config.read_only {
+                eprintln!("[read only]");
+            }
+            if let Some(ref path) = wiki_config.password_file {
+                eprintln!("[password file] {}", path.display());
+            }
+            for plugin in plugins.iter() {
+                eprintln!("[plugin] {}", plugin.name());
+            }
+            for constraint in wiki_config.schema.constraints.iter() {
+                eprintln!("[schema] {}", constraint);
+            }
+            wiki::run(database_filepath, &wiki_config, plugins)
+        }
+        ("query", Some(args)) => {
+            let pattern = relations::query::parse(args.value_of("query").unwrap())
+                .map_err(|e| format!("Invalid query: {}", e))?;
+            let rules = rules_with_arguments(&config.wiki.rules, args)?;
+            let database = relations::read_database_from_file(database_filepath)?;
+            let results = database.query_with_rules(&pattern, &rules);
+            match args.value_of("format").unwrap() {
+                "table" => print!(
+                    "{}",
+                    commands::query_table(&database, &pattern.variables(), &results)
+                ),
+                "json" => print_query_json(&pattern.variables(), &results),
+                _ => {
+                    let elements = database.matched_subgraph(&pattern, &results);
+                    let options = relations::dot::Options {
+                        max_label_len: None,
+                        ..relations::dot::Options::default()
+                    };
+                    let stdout = io::stdout();
+                    relations::dot::write_subgraph(&database, &elements, &options, stdout.lock())
+                        .map_err(|e| e.to_string())?
+                }
+            }
+            Ok(())
+        }
+        ("materialize", Some(args)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let rules = rules_with_arguments(&config.wiki.rules, args)?;
+            let mut database = relations::read_database_from_file(database_filepath)?;
+            let inserted = database
+                .materialize_rules(&rules)
+                .map_err(|e| format!("Cannot insert implied relations: {}", e))?;
+            eprintln!("[materialize] {} relations inserted", inserted.len());
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
+            )
+        }
+        ("repl", Some(_)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let mut database = if database_filepath.exists() {
+                relations::read_database_from_file(database_filepath)?
+            } else {
+                relations::Database::new()
+            };
+            let backup_filepath = config.wiki.backup_file(database_filepath);
+            let mut editor = rustyline::Editor::<()>::new();
+            let mut session = commands::Session::new();
+            loop {
+                let line = match editor.readline("rett> ") {
+                    Ok(line) => line,
+                    Err(rustyline::error::ReadlineError::Interrupted) => continue,
+                    Err(rustyline::error::ReadlineError::Eof) if session.modified => {
+                        // No way to ask again: fail, so that scripts piped to stdin notice it
+                        return Err("Unsaved changes discarded at end of input".into());
+                    }
+                    Err(rustyline::error::ReadlineError::Eof) => return Ok(()),
+                    Err(e) => return Err(e.to_string()),
+                };
+                editor.add_history_entry(line.as_str());
+                match line.trim() {
+                    "help" => println!("{}\nsave\nquit", commands::HELP),
+                    "save" => {
+                        relations::write_database_to_file_with_backup(
+                            database_filepath,
+                            &backup_filepath,
+                            &database,
+                        )?;
+                        session.modified = false
+                    }
+                    "quit" if session.modified => {
+                        eprintln!("Unsaved changes: save, or quit again to discard them");
+                        session.modified = false
+                    }
+                    "quit" => return Ok(()),
+                    line => match session.run_line(&mut database, line) {
+                        Ok(output) => print!("{}", output),
+                        Err(e) => eprintln!("{}", e),
+                    },
+                }
+            }
+        }
+        ("run", Some(args)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let script_filepath = Path::new(args.value_of_os("script").unwrap());
+            let script = fs::read_to_string(script_filepath)
+                .map_err(|e| format!("Cannot read script {}: {}", script_filepath.display(), e))?;
+            let mut database = if database_filepath.exists() {
+                relations::read_database_from_file(database_filepath)?
+            } else {
+                relations::Database::new()
+            };
+            // The file is only written if the whole script succeeds.
+            let output = commands::run_script(&mut database, &script)
+                .map_err(|e| format!("{}: {}", script_filepath.display(), e))?;
+            print!("{}", output);
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
+            )
+        }
+        ("export", Some(args)) => {
+            let database = relations::read_database_from_file(database_filepath)?;
+            let options = relations::dot::Options {
+                namespace: args.value_of("namespace").map(String::from),
+                clusters: args
+                    .values_of("cluster")
+                    .into_iter()
+                    .flat_map(|tags| tags.map(relations::dot::Cluster::new))
+                    .collect(),
+                ..relations::dot::Options::default()
+            };
+            let text = match args.value_of("format").unwrap() {
+                "dot" => relations::dot::render(&database, &options),
+                "cytoscape" => relations::cytoscape::render(&database, &options),
+                format @ "jsonld" | format @ "ntriples" => {
+                    let mut context = relations::jsonld::Context::default();
+                    for term in args.values_of("term").into_iter().flatten() {
+                        let equal = term
+                            .find('=')
+                            .ok_or_else(|| format!("Expected descriptor=iri: {}", term))?;
+                        context
+                            .terms
+                            .push((term[..equal].into(), term[equal + 1..].into()))
+                    }
+                    if format == "jsonld" {
+                        relations::jsonld::render(&database, &options, &context)
+                    } else {
+                        relations::ntriples::render(&database, &options, &context)
+                    }
+                }
+                "cypher" => relations::cypher::render(&database, &options),
+                format => {
+                    let plugins = enabled_plugins(&config.wiki)?;
+                    let exporter = plugins
+                        .iter()
+                        .flat_map(|plugin| plugin.exporters())
+                        .find(|exporter| exporter.extension == format)
+                        .ok_or_else(|| format!("Unknown export format: {}", format))?;
+                    let stdout = io::stdout();
+                    return (exporter.write)(&database, &mut stdout.lock())
+                        .map_err(|e| e.to_string());
+                }
+            };
+            print!("{}", text.map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        ("pages", Some(args)) => {
+            let database = relations::read_database_from_file(database_filepath)?;
+            let options = relations::markdown::Options {
+                named: wiki::NAMED_ATOM.into(),
+                ..relations::markdown::Options::default()
+            };
+            let directory = Path::new(args.value_of_os("directory").unwrap());
+            let nb_pages = relations::markdown::write_pages(&database, directory, &options)?;
+            eprintln!("[pages] {}", nb_pages);
+            Ok(())
+        }
+        ("watch", Some(args)) => {
+            use notify::{DebouncedEvent, RecursiveMode, Watcher};
+            use std::sync::mpsc;
+
+            let dot = args.value_of_os("dot").map(Path::new);
+            let svg = args.value_of_os("svg").map(Path::new);
+            let options = relations::dot::Options {
+                namespace: args.value_of("namespace").map(String::from),
+                ..relations::dot::Options::default()
+            };
+            let database_filepath = fs::canonicalize(database_filepath)
+                .map_err(|e| format!("Cannot watch {}: {}", database_filepath.display(), e))?;
+            let render = || match render_graph_files(&database_filepath, &options, dot, svg) {
+                Ok(()) => eprintln!("[watch] Rendered"),
+                Err(e) => eprintln!("[watch] {}", e),
+            };
+            // Saves replace the file by a rename, so watch the directory and filter events.
+            let directory = database_filepath.parent().unwrap();
+            let (sender, receiver) = mpsc::channel();
+            let mut watcher = notify::watcher(sender, Duration::from_millis(200))
+                .map_err(|e| format!("Cannot watch {}: {}", directory.display(), e))?;
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("Cannot watch {}: {}", directory.display(), e))?;
+            render();
+            for event in receiver {
+                match event {
+                    DebouncedEvent::Create(ref path)
+                    | DebouncedEvent::Write(ref path)
+                    | DebouncedEvent::Rename(_, ref path)
+                        if *path == database_filepath =>
+                    {
+                        render()
+                    }
+                    DebouncedEvent::Remove(ref path) if *path == database_filepath => {
+                        eprintln!("[watch] {} removed", database_filepath.display())
+                    }
+                    DebouncedEvent::Error(e, _) => eprintln!("[watch] {}", e),
+                    _ => {}
+                }
+            }
+            Err("Watcher stopped".to_string())
+        }
+        ("duplicates", Some(args)) => {
+            let min_similarity_text = args.value_of("min_similarity").unwrap();
+            let min_similarity: f64 = match min_similarity_text.parse() {
+                Ok(f) if f >= 0. && f <= 1. => f,
+                _ => {
+                    return Err(format!(
+                        "Unable to parse similarity between 0 and 1: {}",
+                        min_similarity_text
+                    ))
+                }
+            };
+            let database = relations::read_database_from_file(database_filepath)?;
+            for candidate in database.duplicate_candidates(min_similarity) {
+                println!(
+                    "#{}\t#{}\t{:.2}",
+                    candidate.first, candidate.second, candidate.similarity
+                );
+            }
+            Ok(())
+        }
+        ("stats", Some(_)) => {
+            let database = relations::read_database_from_file(database_filepath)?;
+            let count = |kind| database.iter().filter(|e| e.value().kind() == kind).count();
+            println!("abstracts\t{}", count(relations::ElementKind::Abstract));
+            println!("atoms\t{}", count(relations::ElementKind::Atom));
+            println!("relations\t{}", count(relations::ElementKind::Relation));
+            let memory = database.memory_usage();
+            println!("memory_elements\t{}", memory.elements);
+            println!("memory_texts\t{}", memory.texts);
+            println!("memory_indexes\t{}", memory.indexes);
+            println!("memory_total\t{}", memory.total());
+            Ok(())
+        }
+        ("import", Some(args)) => {
+            let format = args.value_of("format").unwrap();
+            let plugins = enabled_plugins(&config.wiki)?;
+            let importer = plugins
+                .iter()
+                .flat_map(|plugin| plugin.importers())
+                .find(|importer| importer.name == format)
+                .ok_or_else(|| format!("Unknown import format: {}", format))?;
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let mut database = if database_filepath.exists() {
+                relations::read_database_from_file(database_filepath)?
+            } else {
+                relations::Database::new()
+            };
+            let file = Path::new(args.value_of_os("file").unwrap());
+            let nb_elements = (importer.read)(&mut database, file)?;
+            eprintln!("[import] {} elements", nb_elements);
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
+            )
+        }
+        ("repair", Some(args)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let policy = if args.is_present("keep_duplicates") {
+                relations::RepairPolicy::DuplicatesToAbstract
+            } else {
+                relations::RepairPolicy::MergeDuplicates
+            };
+            let (database, repairs) =
+                relations::read_database_from_file_with_repair(database_filepath, policy)?;
+            if repairs.is_empty() {
+                eprintln!("[repair] Database is consistent");
+                return Ok(());
+            }
+            for repair in repairs.iter() {
+                eprintln!("[repair] {}", repair);
+            }
+            let backup_filepath = config.wiki.backup_file(database_filepath);
             eprintln!("[backup file] {}", backup_filepath.display());
-            wiki::run(
-                &addr,
+            relations::write_database_to_file_with_backup(
                 database_filepath,
                 &backup_filepath,
-                autosave_duration,
+                &database,
+            )
+        }
+        ("import-vault", Some(args)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let mut database = if database_filepath.exists() {
+                relations::read_database_from_file(database_filepath)?
+            } else {
+                relations::Database::new()
+            };
+            let descriptors = relations::VaultDescriptors {
+                named: wiki::NAMED_ATOM.into(),
+                ..relations::VaultDescriptors::default()
+            };
+            let directory = Path::new(args.value_of_os("directory").unwrap());
+            let nb_notes = relations::import_vault(&mut database, directory, &descriptors)?;
+            eprintln!("[import] {} notes", nb_notes);
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
+            )
+        }
+        ("merge", Some(args)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let read = |arg: &str| {
+                relations::read_database_from_file(Path::new(args.value_of_os(arg).unwrap()))
+            };
+            let mine = relations::read_database_from_file(database_filepath)?;
+            let (merged, conflicts) = relations::merge3(&read("base")?, &mine, &read("theirs")?)?;
+            for conflict in conflicts.iter() {
+                eprintln!("[conflict] {}", conflict);
+            }
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &merged,
+            )
+        }
+        ("deleted", Some(_)) => {
+            let database = relations::read_database_from_file(database_filepath)?;
+            for index in database.deleted_elements() {
+                match database.deleted_element(index) {
+                    Some(relations::Element::Abstract) => println!("{}\tabstract", index),
+                    Some(relations::Element::Atom(atom)) => println!("{}\tatom\t{}", index, atom),
+                    Some(relations::Element::Relation(relation)) => match relation.complement {
+                        Some(complement) => println!(
+                            "{}\trelation\t{} {} {}",
+                            index, relation.subject, relation.descriptor, complement
+                        ),
+                        None => println!(
+                            "{}\trelation\t{} {}",
+                            index, relation.subject, relation.descriptor
+                        ),
+                    },
+                    None => (),
+                }
+            }
+            Ok(())
+        }
+        ("restore", Some(args)) => {
+            let index_text = args.value_of("index").unwrap();
+            let index: relations::Index = match index_text.parse() {
+                Ok(index) => index,
+                _ => return Err(format!("Unable to parse index: {}", index_text)),
+            };
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let mut database = relations::read_database_from_file(database_filepath)?;
+            database
+                .restore_element(index)
+                .map_err(|e| format!("Cannot restore element {}: {}", index, e))?;
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
+            )
+        }
+        ("purge", Some(_)) => {
+            let _lock = relations::lock_database_file(database_filepath)?;
+            let mut database = relations::read_database_from_file(database_filepath)?;
+            let purged = database.purge_deleted();
+            eprintln!("[purge] {} elements", purged.len());
+            relations::write_database_to_file_with_backup(
+                database_filepath,
+                &config.wiki.backup_file(database_filepath),
+                &database,
             )
         }
         _ => Err("Missing subcommand".into()),
     }
 }
+
+/// All plugins available in this binary.
+/// Plugin crates are registered here.
+fn compiled_in_plugins() -> Vec<Box<dyn wiki::plugin::Plugin>> {
+    Vec::new()
+}
+
+/// Plugins enabled by the configuration, or by --plugin arguments for the wiki.
+fn enabled_plugins(config: &config::Wiki) -> Result<Vec<Box<dyn wiki::plugin::Plugin>>, String> {
+    let names = config.plugins.iter().map(|name| name.as_str());
+    wiki::plugin::enabled(compiled_in_plugins(), names)
+}
+
+/// Rules of the configuration, with the inverses given by --inverse arguments.
+fn rules_with_arguments(
+    rules: &relations::Rules,
+    args: &clap::ArgMatches,
+) -> Result<relations::Rules, String> {
+    let mut rules = rules.clone();
+    for inverse in args.values_of("inverse").into_iter().flatten() {
+        rules.inverses.push(inverse.parse()?)
+    }
+    Ok(rules)
+}
+
+/// Write the graph of a database file to the requested outputs: dot text, and svg laid out by graphviz.
+fn render_graph_files(
+    database_filepath: &Path,
+    options: &relations::dot::Options,
+    dot: Option<&Path>,
+    svg: Option<&Path>,
+) -> Result<(), String> {
+    let write = |output: &Path, content: &[u8]| {
+        fs::write(output, content).map_err(|e| format!("Cannot write {}: {}", output.display(), e))
+    };
+    let database = relations::read_database_from_file(database_filepath)?;
+    let text = relations::dot::render(&database, options).map_err(|e| e.to_string())?;
+    if let Some(dot) = dot {
+        write(dot, text.as_bytes())?
+    }
+    if let Some(svg) = svg {
+        write(svg, &relations::dot::layout_to_svg(text.as_bytes())?)?
+    }
+    Ok(())
+}
+
+/// Array of objects mapping variable names to element indexes.
+fn print_query_json(variables: &[&str], results: &[Vec<usize>]) {
+    let rows: Vec<String> = results
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = variables
+                .iter()
+                .zip(row)
+                .map(|(name, index)| format!("\"{}\":{}", name, index))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    println!("[{}]", rows.join(","));
+}
@@ -0,0 +1,386 @@
+use super::schema::Schema;
+use super::{Atom, Database, Error, Index, Relation};
+
+/// Text of the atom used as descriptor of element descriptions, see Operation::SetDescription.
+pub const DESCRIPTION_DESCRIPTOR: &'static str = "description";
+
+/// Element used by a relation of a batch: an element of the database, or an earlier item of the batch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BatchRef {
+    /// Element of the database.
+    Existing(Index),
+    /// Element created or used by the item at this position in the batch.
+    Item(usize),
+}
+
+/// Description of an element to insert in a batch.
+#[derive(Clone, Debug)]
+pub enum ElementSpec {
+    /// New abstract element.
+    Abstract,
+    /// Atom, reused if it exists.
+    Atom(Atom),
+    /// Relation between elements of the database or of the batch.
+    Relation {
+        /// Subject of the relation.
+        subject: BatchRef,
+        /// Descriptor of the relation.
+        descriptor: BatchRef,
+        /// Complement, if any.
+        complement: Option<BatchRef>,
+    },
+}
+
+/// Modification of the database, applied in a list by apply_operations.
+#[derive(Clone, Debug)]
+pub enum Operation {
+    /// Insert an atom, or reuse it if present.
+    InsertAtom(Atom),
+    /// Insert a relation, or reuse it if present.
+    UseLink {
+        /// Subject of the relation.
+        subject: BatchRef,
+        /// Descriptor of the relation.
+        descriptor: BatchRef,
+        /// Complement, if any.
+        complement: Option<BatchRef>,
+    },
+    /// Replace the description relations of an element by one with this text.
+    SetDescription {
+        /// Described element.
+        element: BatchRef,
+        /// Text of the description.
+        text: String,
+    },
+    /// Remove an unreferenced element.
+    Remove(BatchRef),
+}
+
+/// Change made by an operation, reverted if a later operation of the list fails.
+enum Undo {
+    /// Element created, to remove.
+    Created(Index),
+    /// Element removed with delete_element, to restore.
+    Deleted(Index),
+}
+
+impl Database {
+    /// Insert elements in order, returning the index of each item.
+    /// Atoms and relations already present are reused, like with insert_atom and insert_relation.
+    /// On error, elements created by the batch are removed again.
+    pub fn insert_batch<I>(&mut self, specs: I) -> Result<Vec<Index>, Error>
+    where
+        I: IntoIterator<Item = ElementSpec>,
+    {
+        let specs: Vec<ElementSpec> = specs.into_iter().collect();
+        let nb_relations = specs
+            .iter()
+            .filter(|spec| match spec {
+                ElementSpec::Relation { .. } => true,
+                _ => false,
+            })
+            .count();
+        self.elements.reserve(specs.len());
+        self.index_of_uuids.reserve(specs.len());
+        self.index_of_relations.reserve(nb_relations);
+        let mut indexes = Vec::with_capacity(specs.len());
+        let mut created = Vec::new();
+        let mut result = Ok(());
+        for spec in specs {
+            match self.insert_spec(spec, &indexes) {
+                Ok((index, is_new)) => {
+                    indexes.push(index);
+                    if is_new {
+                        created.push(index)
+                    }
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if let Err(e) = result {
+            // Relations are created after their components, so reverse order is always removable.
+            self.with_protection_override(|db| {
+                for &index in created.iter().rev() {
+                    db.remove_element(index).unwrap();
+                }
+            });
+            return Err(e);
+        }
+        Ok(indexes)
+    }
+
+    /// Insert one item, returning its index and whether it was created.
+    fn insert_spec(
+        &mut self,
+        spec: ElementSpec,
+        indexes: &[Index],
+    ) -> Result<(Index, bool), Error> {
+        match spec {
+            ElementSpec::Abstract => Ok((self.create_abstract_element(), true)),
+            ElementSpec::Atom(atom) => Ok(self.insert_atom_if_new(atom)),
+            ElementSpec::Relation {
+                subject,
+                descriptor,
+                complement,
+            } => {
+                let relation = Relation {
+                    subject: resolve(subject, indexes)?,
+                    descriptor: resolve(descriptor, indexes)?,
+                    complement: complement.map(|c| resolve(c, indexes)).transpose()?,
+                };
+                self.insert_relation_if_new(relation)
+            }
+        }
+    }
+
+    /// Apply operations in order, returning the index of the element of each operation.
+    /// Relations are created only if they follow the schema.
+    /// Either all operations succeed, or the database is left unchanged and the position of the
+    /// failing operation is returned with the error.
+    pub fn apply_operations(
+        &mut self,
+        operations: &[Operation],
+        schema: &Schema,
+    ) -> Result<Vec<Index>, (usize, Error)> {
+        let mut undo_log = Vec::new();
+        let mut indexes = Vec::with_capacity(operations.len());
+        for (position, operation) in operations.iter().enumerate() {
+            match self.apply_operation(operation, schema, &indexes, &mut undo_log) {
+                Ok(index) => indexes.push(index),
+                Err(e) => {
+                    self.undo(undo_log);
+                    return Err((position, e));
+                }
+            }
+        }
+        // Removed elements were only kept to be restored.
+        for undo in undo_log {
+            if let Undo::Deleted(index) = undo {
+                self.purge_deleted_element(index)
+            }
+        }
+        Ok(indexes)
+    }
+
+    fn apply_operation(
+        &mut self,
+        operation: &Operation,
+        schema: &Schema,
+        indexes: &[Index],
+        undo_log: &mut Vec<Undo>,
+    ) -> Result<Index, Error> {
+        match operation {
+            Operation::InsertAtom(atom) => Ok(self.insert_atom_logged(atom.clone(), undo_log)),
+            Operation::UseLink {
+                subject,
+                descriptor,
+                complement,
+            } => {
+                let relation = Relation {
+                    subject: resolve(*subject, indexes)?,
+                    descriptor: resolve(*descriptor, indexes)?,
+                    complement: complement.map(|c| resolve(c, indexes)).transpose()?,
+                };
+                self.insert_relation_logged(relation, schema, undo_log)
+            }
+            Operation::SetDescription { element, text } => {
+                let element = self.element(resolve(*element, indexes)?)?.index();
+                let descriptor = Atom::from(DESCRIPTION_DESCRIPTOR);
+                let descriptor = self.insert_atom_logged(descriptor, undo_log);
+                let text = self.insert_atom_logged(Atom::from(text.as_str()), undo_log);
+                let previous: Vec<Index> = self
+                    .element(element)?
+                    .subject_of()
+                    .iter()
+                    .filter(|r| r.value().descriptor == descriptor)
+                    .filter(|r| r.value().complement != Some(text))
+                    .map(|r| r.index())
+                    .collect();
+                for index in previous {
+                    self.delete_element(index)?;
+                    undo_log.push(Undo::Deleted(index))
+                }
+                let relation = Relation {
+                    subject: element,
+                    descriptor,
+                    complement: Some(text),
+                };
+                self.insert_relation_logged(relation, schema, undo_log)
+            }
+            Operation::Remove(element) => {
+                let index = resolve(*element, indexes)?;
+                self.delete_element(index)?;
+                undo_log.push(Undo::Deleted(index));
+                Ok(index)
+            }
+        }
+    }
+
+    fn insert_atom_logged(&mut self, atom: Atom, undo_log: &mut Vec<Undo>) -> Index {
+        let (index, is_new) = self.insert_atom_if_new(atom);
+        if is_new {
+            undo_log.push(Undo::Created(index))
+        }
+        index
+    }
+
+    fn insert_relation_logged(
+        &mut self,
+        relation: Relation,
+        schema: &Schema,
+        undo_log: &mut Vec<Undo>,
+    ) -> Result<Index, Error> {
+        if let Some(index) = self.index_of_relation(&relation) {
+            return Ok(index);
+        }
+        let index = self.insert_relation_with_schema(relation, schema)?;
+        undo_log.push(Undo::Created(index));
+        Ok(index)
+    }
+
+    /// Revert changes, most recent first: relations are undone before their components.
+    fn undo(&mut self, undo_log: Vec<Undo>) {
+        self.with_protection_override(|db| {
+            for undo in undo_log.into_iter().rev() {
+                match undo {
+                    Undo::Created(index) => {
+                        db.remove_element(index).unwrap();
+                    }
+                    Undo::Deleted(index) => db.restore_element(index).unwrap(),
+                }
+            }
+        })
+    }
+}
+
+/// Index of a batch reference, given the indexes of the previous items.
+fn resolve(r: BatchRef, indexes: &[Index]) -> Result<Index, Error> {
+    match r {
+        BatchRef::Existing(index) => Ok(index),
+        BatchRef::Item(i) => indexes.get(i).cloned().ok_or(Error::InvalidIndex),
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_batch() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let batch = vec![
+            ElementSpec::Abstract,
+            ElementSpec::Atom(Atom::from("Joe")),
+            ElementSpec::Relation {
+                subject: BatchRef::Item(0),
+                descriptor: BatchRef::Existing(name_i),
+                complement: Some(BatchRef::Item(1)),
+            },
+            ElementSpec::Atom(Atom::from("Name")),
+        ];
+        let indexes = db.insert_batch(batch).unwrap();
+        assert_eq!(indexes[3], name_i); // Deduplicated
+        let relation = Relation {
+            subject: indexes[0],
+            descriptor: name_i,
+            complement: Some(indexes[1]),
+        };
+        assert_eq!(db.index_of_relation(&relation), Some(indexes[2]));
+        assert!(db.element(indexes[0]).unwrap().is_referenced());
+
+        let nb_elements = db.iter().count();
+        let invalid_batch = vec![
+            ElementSpec::Atom(Atom::from("Jack")),
+            ElementSpec::Relation {
+                subject: BatchRef::Item(0),
+                descriptor: BatchRef::Item(2), // Not inserted yet
+                complement: None,
+            },
+            ElementSpec::Abstract,
+        ];
+        assert_eq!(db.insert_batch(invalid_batch), Err(Error::InvalidIndex));
+        assert_eq!(db.iter().count(), nb_elements);
+        assert_eq!(db.index_of_text_atom("Jack"), None);
+    }
+    #[test]
+    fn apply_operations() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let old_i = db.insert_atom(Atom::from("Old"));
+        let no_schema = Schema::default();
+        let operations = vec![
+            Operation::InsertAtom(Atom::from("Joe")),
+            Operation::UseLink {
+                subject: BatchRef::Existing(object_i),
+                descriptor: BatchRef::Existing(name_i),
+                complement: Some(BatchRef::Item(0)),
+            },
+            Operation::SetDescription {
+                element: BatchRef::Existing(object_i),
+                text: "A person.".into(),
+            },
+            Operation::SetDescription {
+                element: BatchRef::Existing(object_i),
+                text: "Joe, a person.".into(),
+            },
+            Operation::Remove(BatchRef::Existing(old_i)),
+        ];
+        let indexes = db.apply_operations(&operations, &no_schema).unwrap();
+        assert_eq!(indexes[4], old_i);
+        assert_eq!(db.index_of_text_atom("Joe"), Some(indexes[0]));
+        assert!(db.element(indexes[1]).is_ok());
+        assert!(db.element(indexes[2]).is_err()); // Replaced by the second description
+        let description = Relation {
+            subject: object_i,
+            descriptor: db.index_of_text_atom(DESCRIPTION_DESCRIPTOR).unwrap(),
+            complement: db.index_of_text_atom("Joe, a person."),
+        };
+        assert_eq!(db.index_of_relation(&description), Some(indexes[3]));
+        assert!(db.element(old_i).is_err());
+        assert!(db.deleted_elements().is_empty());
+
+        let nb_elements = db.iter().count();
+        let uuid = db.element(indexes[3]).unwrap().uuid();
+        let failing = vec![
+            Operation::InsertAtom(Atom::from("Jim")),
+            Operation::SetDescription {
+                element: BatchRef::Existing(object_i),
+                text: "Jim.".into(),
+            },
+            Operation::Remove(BatchRef::Existing(name_i)), // Referenced by the relation
+        ];
+        assert_eq!(
+            db.apply_operations(&failing, &no_schema),
+            Err((2, Error::RemoveReferenced))
+        );
+        assert_eq!(db.iter().count(), nb_elements);
+        assert_eq!(db.index_of_text_atom("Jim"), None);
+        assert_eq!(db.element(indexes[3]).unwrap().uuid(), uuid);
+        assert!(db.deleted_elements().is_empty());
+
+        let schema = Schema {
+            constraints: vec!["Name: integer".parse().unwrap()],
+        };
+        let invalid = vec![
+            Operation::InsertAtom(Atom::from("Jack")),
+            Operation::UseLink {
+                subject: BatchRef::Existing(db.create_abstract_element()),
+                descriptor: BatchRef::Existing(name_i),
+                complement: Some(BatchRef::Item(0)),
+            },
+        ];
+        assert_eq!(
+            db.apply_operations(&invalid, &schema),
+            Err((1, Error::SchemaViolation))
+        );
+        assert_eq!(db.index_of_text_atom("Jack"), None);
+    }
+}
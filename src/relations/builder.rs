@@ -0,0 +1,117 @@
+use super::{Atom, BatchRef, Database, ElementSpec, Error, Index};
+
+/// Fluent construction of an element with relations describing it, inserted as a batch.
+/// Descriptors and complements given as values are atoms, reused if already present.
+///
+/// ```ignore
+/// let joe = ElementBuilder::abstract_()
+///     .related("named", "Joe")
+///     .tagged("person")
+///     .linked_to("works at", company)
+///     .insert(&mut database)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct ElementBuilder {
+    /// The built element is the first item.
+    specs: Vec<ElementSpec>,
+}
+
+impl ElementBuilder {
+    /// Builder of a new abstract element.
+    pub fn abstract_() -> Self {
+        ElementBuilder {
+            specs: vec![ElementSpec::Abstract],
+        }
+    }
+    /// Builder of an atom, reused if it exists.
+    pub fn atom<A: Into<Atom>>(atom: A) -> Self {
+        ElementBuilder {
+            specs: vec![ElementSpec::Atom(atom.into())],
+        }
+    }
+
+    /// Relation "element tag" without complement.
+    pub fn tagged<A: Into<Atom>>(self, tag: A) -> Self {
+        self.relation(tag.into(), None)
+    }
+    /// Relation "element descriptor value", with an atom complement.
+    pub fn related<D: Into<Atom>, V: Into<Atom>>(self, descriptor: D, value: V) -> Self {
+        self.relation(descriptor.into(), Some(ElementSpec::Atom(value.into())))
+    }
+    /// Relation "element descriptor target", with an existing element as complement.
+    pub fn linked_to<D: Into<Atom>>(mut self, descriptor: D, target: Index) -> Self {
+        self.specs.push(ElementSpec::Atom(descriptor.into()));
+        let descriptor = BatchRef::Item(self.specs.len() - 1);
+        self.specs.push(ElementSpec::Relation {
+            subject: BatchRef::Item(0),
+            descriptor,
+            complement: Some(BatchRef::Existing(target)),
+        });
+        self
+    }
+
+    fn relation(mut self, descriptor: Atom, complement: Option<ElementSpec>) -> Self {
+        self.specs.push(ElementSpec::Atom(descriptor));
+        let descriptor = BatchRef::Item(self.specs.len() - 1);
+        let complement = complement.map(|complement| {
+            self.specs.push(complement);
+            BatchRef::Item(self.specs.len() - 1)
+        });
+        self.specs.push(ElementSpec::Relation {
+            subject: BatchRef::Item(0),
+            descriptor,
+            complement,
+        });
+        self
+    }
+
+    /// Batch to give to Database::insert_batch. The built element is the first item.
+    pub fn into_specs(self) -> Vec<ElementSpec> {
+        self.specs
+    }
+    /// Insert the element and its relations, returning the index of the element.
+    pub fn insert(self, database: &mut Database) -> Result<Index, Error> {
+        database.insert_batch(self.specs).map(|indexes| indexes[0])
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn element_builder() {
+        let mut db = Database::new();
+        let company_i = ElementBuilder::abstract_()
+            .related("named", "Acme")
+            .insert(&mut db)
+            .unwrap();
+        let joe_i = ElementBuilder::abstract_()
+            .related("named", "Joe")
+            .tagged("person")
+            .linked_to("works at", company_i)
+            .insert(&mut db)
+            .unwrap();
+        let index = |text: &str| db.index_of_text_atom(text).unwrap();
+        let has_relation = |subject, descriptor, complement| {
+            db.index_of_relation(&Relation {
+                subject,
+                descriptor,
+                complement,
+            })
+            .is_some()
+        };
+        assert!(has_relation(company_i, index("named"), Some(index("Acme"))));
+        assert!(has_relation(joe_i, index("named"), Some(index("Joe"))));
+        assert!(has_relation(joe_i, index("person"), None));
+        assert!(has_relation(joe_i, index("works at"), Some(company_i)));
+        assert_eq!(db.iter().count(), 11);
+
+        let atom_i = ElementBuilder::atom(42i64).tagged("answer").insert(&mut db).unwrap();
+        assert_eq!(db.index_of_atom(&Atom::from(42i64)), Some(atom_i));
+    }
+}
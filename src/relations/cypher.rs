@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Write};
+
+use super::dot::{select, Options};
+use super::{Atom, Database, Element, Error, Index, Relation};
+
+/// Render the part of the database selected by options as Cypher statements, to load it in Neo4j.
+/// Abstract elements are nodes with the `Element` and `Abstract` labels, their index as `id` and their uuid.
+/// Relations with a text descriptor are attached to their subject node:
+/// - atom complements are properties named by the descriptor (a list if there are several values);
+/// - element complements are relationships typed by the descriptor, with the relation index as `id`;
+/// - relations without complement are tags, rendered as labels.
+///
+/// Relations which cannot be attached this way, or are used as components of other relations,
+/// are also nodes with the `Relation` label:
+/// these have a non text or reserved (`id`, `uuid`) descriptor, or an atom subject.
+/// They have `SUBJECT`, `DESCRIPTOR` and `COMPLEMENT` relationships to node components,
+/// and `subject`, `descriptor` and `complement` properties for atom components.
+///
+/// Statements use `MERGE` so loading twice does not duplicate the graph.
+/// Components outside of the selection are omitted.
+pub fn render(database: &Database, options: &Options) -> Result<String, Error> {
+    let elements = select(database, options)?;
+    let value = |index: Index| -> Option<&Element> {
+        if elements.contains(&index) {
+            Some(&database.elements[index].value)
+        } else {
+            None
+        }
+    };
+    let relations = || {
+        elements
+            .iter()
+            .filter_map(move |&index| match database.elements[index].value {
+                Element::Relation(ref r) => Some((index, r)),
+                _ => None,
+            })
+    };
+    let descriptor_name = |r: &Relation| match database.elements[r.descriptor].value {
+        Element::Atom(Atom::Text(ref name)) if name != "id" && name != "uuid" => {
+            Some(name.as_str())
+        }
+        _ => None,
+    };
+
+    let mut reified = HashSet::new();
+    for (index, r) in relations() {
+        for component in components(r) {
+            if let Some(&Element::Relation(_)) = value(component) {
+                reified.insert(component);
+            }
+        }
+        let subject_is_atom = match value(r.subject) {
+            Some(&Element::Atom(_)) => true,
+            _ => false,
+        };
+        if subject_is_atom || descriptor_name(r).is_none() {
+            reified.insert(index);
+        }
+    }
+    let is_node = |index: Index| match value(index) {
+        Some(&Element::Abstract) => true,
+        Some(&Element::Relation(_)) => reified.contains(&index),
+        _ => false,
+    };
+
+    let mut nodes: BTreeMap<Index, Node> = elements
+        .iter()
+        .filter(|&&index| is_node(index))
+        .map(|&index| (index, Node::default()))
+        .collect();
+    let mut links = String::new();
+    for (index, r) in relations() {
+        let name = descriptor_name(r);
+        match (name, nodes.get_mut(&r.subject), r.complement) {
+            (Some(name), Some(subject), None) => subject.labels.push(CypherName(name).to_string()),
+            (Some(name), Some(subject), Some(complement)) => match value(complement) {
+                Some(&Element::Atom(ref atom)) => subject.add_property(name, atom),
+                Some(_) => write!(
+                    links,
+                    "MATCH (s:Element {{id: {}}}), (c:Element {{id: {}}}) MERGE (s)-[:{} {{id: {}}}]->(c);\n",
+                    r.subject,
+                    complement,
+                    CypherName(name),
+                    index
+                )
+                .unwrap(),
+                None => {}
+            },
+            _ => {}
+        }
+        if !reified.contains(&index) {
+            continue;
+        }
+        let roles = [
+            ("SUBJECT", "subject", Some(r.subject)),
+            ("DESCRIPTOR", "descriptor", Some(r.descriptor)),
+            ("COMPLEMENT", "complement", r.complement),
+        ];
+        for &(link, property, component) in roles.iter() {
+            let component = match component {
+                Some(component) => component,
+                None => continue,
+            };
+            match value(component) {
+                Some(&Element::Atom(ref atom)) => {
+                    nodes.get_mut(&index).unwrap().add_property(property, atom)
+                }
+                Some(_) => write!(
+                    links,
+                    "MATCH (r:Element {{id: {}}}), (c:Element {{id: {}}}) MERGE (r)-[:{}]->(c);\n",
+                    index, component, link
+                )
+                .unwrap(),
+                None => {}
+            }
+        }
+    }
+
+    // Index on ids, so that matching nodes when merging is fast.
+    let mut out =
+        String::from("CREATE INDEX element_id IF NOT EXISTS FOR (n:Element) ON (n.id);\n");
+    for (&index, node) in nodes.iter() {
+        let element = database.element(index)?;
+        let kind = match element.value() {
+            Element::Relation(_) => "Relation",
+            _ => "Abstract",
+        };
+        write!(out, "MERGE (n:Element {{id: {}}}) SET n:{}", index, kind).unwrap();
+        for label in node.labels.iter() {
+            write!(out, ":{}", label).unwrap()
+        }
+        if let Some(uuid) = element.uuid() {
+            write!(out, ", n.uuid = \"{}\"", uuid).unwrap()
+        }
+        for (name, values) in node.properties.iter() {
+            match values.len() {
+                1 => write!(out, ", n.{} = {}", name, values[0]).unwrap(),
+                _ => write!(out, ", n.{} = [{}]", name, values.join(", ")).unwrap(),
+            }
+        }
+        out.push_str(";\n")
+    }
+    out.push_str(&links);
+    Ok(out)
+}
+
+fn components(r: &Relation) -> impl Iterator<Item = Index> {
+    vec![r.subject, r.descriptor]
+        .into_iter()
+        .chain(r.complement)
+}
+
+/// Labels and properties of a node, already rendered as Cypher.
+#[derive(Default)]
+struct Node {
+    labels: Vec<String>,
+    properties: BTreeMap<String, Vec<String>>,
+}
+impl Node {
+    fn add_property(&mut self, name: &str, atom: &Atom) {
+        self.properties
+            .entry(CypherName(name).to_string())
+            .or_insert_with(Vec::new)
+            .push(CypherValue(atom).to_string())
+    }
+}
+
+/// Label, relationship type or property name, quoted so that any text is accepted.
+struct CypherName<'a>(&'a str);
+impl<'a> fmt::Display for CypherName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.0.replace('`', "``"))
+    }
+}
+
+/// Atom as a Cypher literal.
+struct CypherValue<'a>(&'a Atom);
+impl<'a> fmt::Display for CypherValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Atom::Text(s) => write!(f, "\"{}\"", CypherEscaped(s)),
+            Atom::Integer(n) => n.fmt(f),
+            Atom::Float(x) => {
+                // Cypher has no literals for infinities and NaN, but float division produces them.
+                let x = x.value();
+                if x.is_nan() {
+                    write!(f, "0.0/0.0")
+                } else if x.is_infinite() {
+                    write!(f, "{}1.0/0.0", if x < 0.0 { "-" } else { "" })
+                } else {
+                    // Debug format always has a decimal point, so it is not read as an integer.
+                    write!(f, "{:?}", x)
+                }
+            }
+            Atom::Date(d) => write!(
+                f,
+                "date({{year: {}, month: {}, day: {}}})",
+                d.year(),
+                d.month(),
+                d.day()
+            ),
+        }
+    }
+}
+
+/// Content of a Cypher string literal.
+struct CypherEscaped<'a>(&'a str);
+impl<'a> fmt::Display for CypherEscaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => "\\\"".fmt(f)?,
+                '\\' => "\\\\".fmt(f)?,
+                '\n' => "\\n".fmt(f)?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Date, Relation};
+    use super::*;
+
+    #[test]
+    fn cypher() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Na\"me"));
+        let object_i = db.create_abstract_element();
+        let other_i = db.create_abstract_element();
+        let person_i = db.insert_atom(Atom::from("person"));
+        let knows_i = db.insert_atom(Atom::from("knows"));
+        let mut insert = |subject, descriptor, complement| {
+            db.insert_relation(Relation {
+                subject: subject,
+                descriptor: descriptor,
+                complement: complement,
+            })
+            .unwrap()
+        };
+        let named_i = insert(object_i, name_i, Some(knows_i));
+        let tag_i = insert(object_i, person_i, None);
+        let link_i = insert(object_i, knows_i, Some(other_i));
+        let since_i = insert(link_i, name_i, Some(person_i));
+        let uuid = |i| db.element(i).unwrap().uuid().unwrap();
+        let expected = [
+            "CREATE INDEX element_id IF NOT EXISTS FOR (n:Element) ON (n.id);\n".to_string(),
+            format!(
+                "MERGE (n:Element {{id: {}}}) SET n:Abstract:`person`, n.uuid = \"{}\", n.`Na\"me` = \"knows\";\n",
+                object_i,
+                uuid(object_i)
+            ),
+            format!(
+                "MERGE (n:Element {{id: {}}}) SET n:Abstract, n.uuid = \"{}\";\n",
+                other_i,
+                uuid(other_i)
+            ),
+            format!(
+                "MERGE (n:Element {{id: {}}}) SET n:Relation, n.uuid = \"{}\", n.`Na\"me` = \"person\", n.`descriptor` = \"knows\";\n",
+                link_i,
+                uuid(link_i)
+            ),
+            "MATCH (s:Element {id: 1}), (c:Element {id: 2}) MERGE (s)-[:`knows` {id: 7}]->(c);\n".to_string(),
+            "MATCH (r:Element {id: 7}), (c:Element {id: 1}) MERGE (r)-[:SUBJECT]->(c);\n".to_string(),
+            "MATCH (r:Element {id: 7}), (c:Element {id: 2}) MERGE (r)-[:COMPLEMENT]->(c);\n".to_string(),
+        ]
+        .concat();
+        assert_eq!((named_i, tag_i, since_i), (5, 6, 8));
+        assert_eq!(render(&db, &Options::default()).unwrap(), expected);
+    }
+
+    #[test]
+    fn cypher_values() {
+        let value = |atom: Atom| CypherValue(&atom).to_string();
+        assert_eq!(value(Atom::from(-3i64)), "-3");
+        assert_eq!(value(Atom::from(2.0)), "2.0");
+        assert_eq!(value(Atom::from(std::f64::NAN)), "0.0/0.0");
+        assert_eq!(value(Atom::from(std::f64::NEG_INFINITY)), "-1.0/0.0");
+        assert_eq!(
+            value(Atom::from(Date::new(-44, 3, 15).unwrap())),
+            "date({year: -44, month: 3, day: 15})"
+        );
+    }
+}
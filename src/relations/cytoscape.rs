@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io;
+
+use super::dot::{select, Options};
+use super::{Database, Element, Error, Index};
+use utils::Set;
+
+/// Render the part of the database selected by options as a cytoscape.js elements JSON object.
+pub fn render(database: &Database, options: &Options) -> Result<String, Error> {
+    let elements = select(database, options)?;
+    let mut output = Vec::new();
+    write_subgraph(database, &elements, &mut output).unwrap(); // Vec<u8> writes cannot fail
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// Write a subset of the database as a cytoscape.js elements JSON object.
+/// All elements are nodes, with a kind and label in their data.
+/// Relations are linked to their components by edges with a role in their data.
+/// Edges to components outside of the subset are omitted.
+pub fn write_subgraph<W: io::Write>(
+    database: &Database,
+    elements: &Set<Index>,
+    mut w: W,
+) -> io::Result<()> {
+    write!(w, "{{\"nodes\":[")?;
+    let mut separator = "";
+    for &index in elements.iter() {
+        let element = match database.element(index) {
+            Ok(element) => element,
+            Err(_) => continue,
+        };
+        let (kind, label) = match element.value() {
+            Element::Abstract => ("abstract", format!("#{}", index)),
+            Element::Atom(atom) => ("atom", atom.to_string()),
+            Element::Relation(_) => ("relation", String::new()),
+        };
+        write!(
+            w,
+            "{}{{\"data\":{{\"id\":\"{}\",\"kind\":\"{}\",\"label\":\"{}\"}}}}",
+            separator,
+            index,
+            kind,
+            JsonEscaped(&label)
+        )?;
+        separator = ","
+    }
+    // Edges are written in a second pass, so that nothing is buffered.
+    write!(w, "],\"edges\":[")?;
+    let mut separator = "";
+    for &index in elements.iter() {
+        let element = match database.element(index) {
+            Ok(element) => element,
+            Err(_) => continue,
+        };
+        let r = match element.value() {
+            Element::Relation(r) => r,
+            _ => continue,
+        };
+        let mut edge = |role: &str, source, target| {
+            if !(elements.contains(&source) && elements.contains(&target)) {
+                return Ok(());
+            }
+            let result = write!(
+                w,
+                "{}{{\"data\":{{\"id\":\"{}-{}\",\"source\":\"{}\",\"target\":\"{}\",\"role\":\"{}\"}}}}",
+                separator, index, role, source, target, role
+            );
+            separator = ",";
+            result
+        };
+        edge("subject", r.subject, index)?;
+        edge("descriptor", index, r.descriptor)?;
+        if let Some(complement) = r.complement {
+            edge("complement", index, complement)?;
+        }
+    }
+    write!(w, "]}}")
+}
+
+/// Content of a JSON string.
+pub struct JsonEscaped<'a>(pub &'a str);
+impl<'a> fmt::Display for JsonEscaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => "\\\"".fmt(f)?,
+                '\\' => "\\\\".fmt(f)?,
+                '\n' => "\\n".fmt(f)?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::dot::Direction;
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn cytoscape() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Na\"me\t"));
+        let object_i = db.create_abstract_element();
+        let relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: name_i,
+                complement: None,
+            })
+            .unwrap();
+        let options = Options {
+            root: Some(relation_i),
+            depth: 1,
+            direction: Direction::Both,
+            max_label_len: None,
+            ..Options::default()
+        };
+        let expected = concat!(
+            "{\"nodes\":[",
+            "{\"data\":{\"id\":\"0\",\"kind\":\"atom\",\"label\":\"Na\\\"me\\u0009\"}},",
+            "{\"data\":{\"id\":\"1\",\"kind\":\"abstract\",\"label\":\"#1\"}},",
+            "{\"data\":{\"id\":\"2\",\"kind\":\"relation\",\"label\":\"\"}}",
+            "],\"edges\":[",
+            "{\"data\":{\"id\":\"2-subject\",\"source\":\"1\",\"target\":\"2\",\"role\":\"subject\"}},",
+            "{\"data\":{\"id\":\"2-descriptor\",\"source\":\"2\",\"target\":\"0\",\"role\":\"descriptor\"}}",
+            "]}"
+        );
+        assert_eq!(render(&db, &options).unwrap(), expected);
+
+        let options = Options {
+            root: Some(object_i),
+            depth: 0,
+            ..Options::default()
+        };
+        let expected = concat!(
+            "{\"nodes\":[",
+            "{\"data\":{\"id\":\"1\",\"kind\":\"abstract\",\"label\":\"#1\"}}",
+            "],\"edges\":[]}"
+        );
+        assert_eq!(render(&db, &options).unwrap(), expected);
+    }
+}
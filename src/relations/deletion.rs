@@ -0,0 +1,146 @@
+use super::events::Change;
+use super::{Database, Element, Error, Index};
+use utils::Map;
+
+impl Database {
+    /// Remove an unreferenced element like remove_element, but keep it so that it can be restored.
+    /// Its index is not reused until deleted elements are purged.
+    /// Deleted elements are written to files, so they can still be restored after reading them back.
+    pub fn delete_element(&mut self, index: Index) -> Result<(), Error> {
+        if self.element(index)?.is_referenced() {
+            return Err(Error::RemoveReferenced);
+        }
+        self.check_not_protected(index)?;
+        let element_data = self.elements.remove_reserved(index).unwrap();
+        self.unregister_element_data(index, &element_data);
+        self.deleted.insert(index, element_data);
+        self.notify(Change::Removed(index));
+        Ok(())
+    }
+
+    /// Put a deleted element back at its index.
+    /// Fails if a component of a relation is missing, or if an identical element was inserted since.
+    pub fn restore_element(&mut self, index: Index) -> Result<(), Error> {
+        let element_data = self.deleted.remove(&index).ok_or(Error::InvalidIndex)?;
+        let restorable = match element_data.value {
+            Element::Abstract => Ok(()),
+            Element::Atom(ref atom) => match self.index_of_atom(atom) {
+                Some(_) => Err(Error::DuplicatedElement),
+                None => Ok(()),
+            },
+            Element::Relation(ref relation) => {
+                let components_valid = self.elements.valid(relation.subject)
+                    && self.elements.valid(relation.descriptor)
+                    && relation.complement.map_or(true, |c| self.elements.valid(c));
+                if !components_valid {
+                    Err(Error::InvalidIndex)
+                } else if self.index_of_relation(relation).is_some() {
+                    Err(Error::DuplicatedElement)
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        if let Err(e) = restorable {
+            self.deleted.insert(index, element_data);
+            return Err(e);
+        }
+        let (value, uuid) = (element_data.value.clone(), element_data.uuid);
+        self.elements.restore(index, element_data);
+        match value {
+            Element::Abstract => (),
+            Element::Atom(atom) => self.register_atom(index, atom).unwrap(),
+            Element::Relation(relation) => self.register_relation(index, relation).unwrap(),
+        }
+        if let Some(uuid) = uuid {
+            self.register_uuid(index, uuid).unwrap() // Uuids of new elements are fresh
+        }
+        self.touch(index);
+        self.notify(Change::Inserted(index));
+        Ok(())
+    }
+
+    /// Forget one deleted element, making its index available again.
+    pub(super) fn purge_deleted_element(&mut self, index: Index) {
+        if self.deleted.remove(&index).is_some() {
+            self.elements.release(index)
+        }
+    }
+
+    /// Indexes of deleted elements, sorted.
+    pub fn deleted_elements(&self) -> Vec<Index> {
+        self.deleted.as_ref().iter().map(|&(index, _)| index).collect()
+    }
+
+    /// Value of a deleted element.
+    pub fn deleted_element(&self, index: Index) -> Option<&Element> {
+        self.deleted.get(&index).map(|element_data| &element_data.value)
+    }
+
+    /// Forget deleted elements, making their indexes available again. Return them.
+    pub fn purge_deleted(&mut self) -> Vec<Index> {
+        let purged = self.deleted_elements();
+        for &index in purged.iter() {
+            self.elements.release(index)
+        }
+        self.deleted = Map::new();
+        purged
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn delete_and_restore() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let relation = Relation {
+            subject: object_i,
+            descriptor: name_i,
+            complement: None,
+        };
+        let relation_i = db.insert_relation(relation.clone()).unwrap();
+
+        assert_eq!(db.delete_element(name_i), Err(Error::RemoveReferenced));
+        db.delete_element(relation_i).unwrap();
+        db.delete_element(name_i).unwrap();
+        assert!(db.element(name_i).is_err());
+        assert_eq!(db.index_of_text_atom("Name"), None);
+        assert_eq!(db.iter().count(), 1);
+        assert_eq!(db.deleted_elements(), vec![name_i, relation_i]);
+        // Relation needs its descriptor
+        assert_eq!(db.restore_element(relation_i), Err(Error::InvalidIndex));
+        let new_i = db.create_abstract_element();
+        assert!(new_i != name_i && new_i != relation_i); // Deleted indexes are not reused
+
+        db.restore_element(name_i).unwrap();
+        db.restore_element(relation_i).unwrap();
+        assert_eq!(db.index_of_relation(&relation), Some(relation_i));
+        assert_eq!(db.index_of_text_atom("Name"), Some(name_i));
+        assert!(db.element(name_i).unwrap().is_referenced());
+        assert!(db.deleted_elements().is_empty());
+
+        db.delete_element(relation_i).unwrap();
+        assert_eq!(db.purge_deleted(), vec![relation_i]);
+        assert_eq!(db.restore_element(relation_i), Err(Error::InvalidIndex));
+        assert_eq!(db.create_abstract_element(), relation_i);
+    }
+
+    #[test]
+    fn restore_duplicate() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        db.delete_element(name_i).unwrap();
+        let other_i = db.insert_atom(Atom::from("Name"));
+        assert_eq!(db.restore_element(name_i), Err(Error::DuplicatedElement));
+        assert_eq!(db.index_of_text_atom("Name"), Some(other_i));
+        assert_eq!(db.deleted_elements(), vec![name_i]);
+    }
+}
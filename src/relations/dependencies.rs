@@ -0,0 +1,157 @@
+use std::cmp::{min, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+use super::{Database, Error, Index};
+
+/// Elements linked by relations with a descriptor, to their dependencies: "subject descriptor complement".
+type DependencyGraph = BTreeMap<Index, Vec<Index>>;
+
+impl Database {
+    fn dependency_graph(&self, descriptor: Index) -> Result<DependencyGraph, Error> {
+        let mut graph = DependencyGraph::new();
+        for relation in self.element(descriptor)?.descriptor_of().iter() {
+            let relation = relation.value();
+            if let Some(complement) = relation.complement {
+                graph.entry(relation.subject).or_insert_with(Vec::new).push(complement);
+                graph.entry(complement).or_insert_with(Vec::new);
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Elements linked by relations with the descriptor, each one after its complements.
+    /// Ties are broken by increasing index. Fails with Error::Cycle if some elements depend on themselves.
+    pub fn topological_order(&self, descriptor: Index) -> Result<Vec<Index>, Error> {
+        let graph = self.dependency_graph(descriptor)?;
+        let mut nb_dependencies: HashMap<Index, usize> = HashMap::new();
+        let mut dependents: HashMap<Index, Vec<Index>> = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for (&element, dependencies) in graph.iter() {
+            nb_dependencies.insert(element, dependencies.len());
+            for &dependency in dependencies.iter() {
+                dependents.entry(dependency).or_insert_with(Vec::new).push(element)
+            }
+            if dependencies.is_empty() {
+                ready.push(Reverse(element))
+            }
+        }
+        let mut order = Vec::with_capacity(graph.len());
+        while let Some(Reverse(element)) = ready.pop() {
+            order.push(element);
+            for dependent in dependents.get(&element).into_iter().flat_map(|d| d.iter()) {
+                let remaining = nb_dependencies.get_mut(dependent).unwrap();
+                *remaining -= 1;
+                if *remaining == 0 {
+                    ready.push(Reverse(*dependent))
+                }
+            }
+        }
+        if order.len() == graph.len() {
+            Ok(order)
+        } else {
+            Err(Error::Cycle)
+        }
+    }
+
+    /// Groups of elements depending on each other through relations with the descriptor.
+    /// These are the strongly connected components with a cycle, each sorted, ordered by first element.
+    pub fn find_cycles(&self, descriptor: Index) -> Result<Vec<Vec<Index>>, Error> {
+        let graph = self.dependency_graph(descriptor)?;
+        // Iterative Tarjan algorithm, as dependency chains can be long.
+        let mut discovery: HashMap<Index, usize> = HashMap::new();
+        let mut lowlink: HashMap<Index, usize> = HashMap::new();
+        let mut stack = Vec::new();
+        let mut on_stack = HashSet::new();
+        let mut cycles = Vec::new();
+        for &root in graph.keys() {
+            if discovery.contains_key(&root) {
+                continue;
+            }
+            // Elements being visited, with the position of the next dependency to explore.
+            let mut visits = vec![(root, 0)];
+            while let Some((element, next)) = visits.pop() {
+                if next == 0 {
+                    let n = discovery.len();
+                    discovery.insert(element, n);
+                    lowlink.insert(element, n);
+                    stack.push(element);
+                    on_stack.insert(element);
+                }
+                let dependencies = &graph[&element];
+                if next < dependencies.len() {
+                    visits.push((element, next + 1));
+                    let dependency = dependencies[next];
+                    if !discovery.contains_key(&dependency) {
+                        visits.push((dependency, 0))
+                    } else if on_stack.contains(&dependency) {
+                        let low = min(lowlink[&element], discovery[&dependency]);
+                        lowlink.insert(element, low);
+                    }
+                    continue;
+                }
+                if let Some(&(parent, _)) = visits.last() {
+                    let low = min(lowlink[&parent], lowlink[&element]);
+                    lowlink.insert(parent, low);
+                }
+                if lowlink[&element] == discovery[&element] {
+                    let mut component = Vec::new();
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack.remove(&member);
+                        component.push(member);
+                        if member == element {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || dependencies.contains(&element) {
+                        component.sort();
+                        cycles.push(component)
+                    }
+                }
+            }
+        }
+        cycles.sort();
+        Ok(cycles)
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn dependencies() {
+        let (mut db, e) = fixture! {
+            depends = atom("depends on") {};
+            likes = atom("likes") {};
+            a = abstract_() {};
+            b = abstract_() { linked_to("depends on", a) };
+            c = abstract_() { linked_to("depends on", b), linked_to("depends on", a) };
+            d = abstract_() { linked_to("likes", a) };
+        };
+        assert_eq!(db.topological_order(e.depends), Ok(vec![e.a, e.b, e.c]));
+        assert_eq!(db.find_cycles(e.depends), Ok(Vec::new()));
+        assert_eq!(db.topological_order(e.likes), Ok(vec![e.a, e.d]));
+
+        {
+            let mut likes = |subject, complement| {
+                db.insert_relation(Relation {
+                    subject,
+                    descriptor: e.likes,
+                    complement: Some(complement),
+                })
+                .unwrap()
+            };
+            likes(e.a, e.b);
+            likes(e.b, e.d);
+            likes(e.c, e.c);
+        }
+        assert_eq!(db.topological_order(e.likes), Err(Error::Cycle));
+        assert_eq!(db.find_cycles(e.likes), Ok(vec![vec![e.a, e.b, e.d], vec![e.c]]));
+        assert_eq!(db.find_cycles(1000), Err(Error::InvalidIndex));
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use super::{Database, Element, Error, Index, Ref, Relation};
+use utils::Set;
+
+/// Which links are followed when exploring the neighborhood of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From a relation to its components.
+    Components,
+    /// From an element to relations using it.
+    Referencing,
+    /// Both directions.
+    Both,
+}
+
+/// Group of elements drawn in a box: those tagged by the text atom, with relations "e tag".
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    /// Text of the tag atom.
+    pub tag: String,
+    /// Defaults to the tag.
+    pub label: Option<String>,
+    /// Graphviz color name or "#rrggbb" value.
+    pub color: Option<String>,
+}
+impl Cluster {
+    /// Cluster of elements with the tag, with default label and color.
+    pub fn new<S: Into<String>>(tag: S) -> Self {
+        Cluster {
+            tag: tag.into(),
+            label: None,
+            color: None,
+        }
+    }
+}
+
+/// Graphviz attributes of nodes. Unset attributes use the graphviz defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeStyle {
+    /// Graphviz shape name, like "box".
+    pub shape: Option<String>,
+    /// Graphviz color name or "#rrggbb" value.
+    pub color: Option<String>,
+    /// Font name, like "Helvetica".
+    pub font: Option<String>,
+}
+impl NodeStyle {
+    /// Style with only a shape.
+    pub fn with_shape<S: Into<String>>(shape: S) -> Self {
+        NodeStyle {
+            shape: Some(shape.into()),
+            ..NodeStyle::default()
+        }
+    }
+    /// Attributes of self, completed by those of base.
+    fn over(&self, base: &NodeStyle) -> NodeStyle {
+        NodeStyle {
+            shape: self.shape.clone().or_else(|| base.shape.clone()),
+            color: self.color.clone().or_else(|| base.color.clone()),
+            font: self.font.clone().or_else(|| base.font.clone()),
+        }
+    }
+}
+
+/// Appearance of nodes by element kind, with overrides for tagged elements.
+#[derive(Debug, Clone)]
+pub struct DotStyle {
+    /// Style of abstract elements.
+    pub abstract_element: NodeStyle,
+    /// Style of atoms.
+    pub atom: NodeStyle,
+    /// Style of relations.
+    pub relation: NodeStyle,
+    /// Elements with relations "e tag" use the style of the first of their tags in this list.
+    /// Its attributes replace those of the kind style.
+    pub tags: Vec<(String, NodeStyle)>,
+}
+impl Default for DotStyle {
+    fn default() -> Self {
+        DotStyle {
+            abstract_element: NodeStyle::with_shape("box"),
+            atom: NodeStyle::with_shape("none"),
+            relation: NodeStyle::with_shape("point"),
+            tags: Vec::new(),
+        }
+    }
+}
+impl DotStyle {
+    /// Style of an element, tag_indexes being the atoms of style tags if they exist.
+    fn node_style(
+        &self,
+        database: &Database,
+        tag_indexes: &[Option<Index>],
+        element: &Ref<Element>,
+    ) -> NodeStyle {
+        let kind_style = match element.value() {
+            Element::Abstract => &self.abstract_element,
+            Element::Atom(_) => &self.atom,
+            Element::Relation(_) => &self.relation,
+        };
+        let has_tag = |tag: Index| {
+            let relation = Relation {
+                subject: element.index(),
+                descriptor: tag,
+                complement: None,
+            };
+            database.index_of_relation(&relation).is_some()
+        };
+        let tag_style = self
+            .tags
+            .iter()
+            .zip(tag_indexes.iter())
+            .find(|&(_, tag)| tag.map_or(false, &has_tag));
+        match tag_style {
+            Some((&(_, ref style), _)) => style.over(kind_style),
+            None => kind_style.clone(),
+        }
+    }
+}
+
+/// Selection of the part of the database to render, and how.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Render the neighborhood of this element, or the whole database if None.
+    pub root: Option<Index>,
+    /// Number of links followed from the root.
+    pub depth: usize,
+    /// Direction of the links followed from the root.
+    pub direction: Direction,
+    /// Only render elements of this namespace.
+    pub namespace: Option<String>,
+    /// Atom labels longer than this number of chars are truncated.
+    pub max_label_len: Option<usize>,
+    /// Elements are drawn in the first cluster with a tag they have.
+    pub clusters: Vec<Cluster>,
+    /// Appearance of nodes.
+    pub style: DotStyle,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            root: None,
+            depth: 1,
+            direction: Direction::Both,
+            namespace: None,
+            max_label_len: Some(50),
+            clusters: Vec::new(),
+            style: DotStyle::default(),
+        }
+    }
+}
+
+/// Render the part of the database selected by options as a graphviz dot graph.
+pub fn render(database: &Database, options: &Options) -> Result<String, Error> {
+    let elements = select(database, options)?;
+    let mut output = Vec::new();
+    write_subgraph(database, &elements, options, &mut output).unwrap(); // Vec<u8> writes cannot fail
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// Elements selected by options: the neighborhood of the root, or all elements.
+/// Then restricted to a namespace if requested.
+pub fn select(database: &Database, options: &Options) -> Result<Set<Index>, Error> {
+    let elements = match options.root {
+        Some(root) => neighborhood(database, root, options.depth, options.direction)?,
+        None => database.iter().map(|e| e.index()).collect(),
+    };
+    Ok(match options.namespace {
+        Some(ref name) => {
+            let members = database.namespace_members(name);
+            elements.intersection(&members).cloned().collect()
+        }
+        None => elements,
+    })
+}
+
+/// Elements reachable from root in at most depth steps following links in direction.
+pub fn neighborhood(
+    database: &Database,
+    root: Index,
+    depth: usize,
+    direction: Direction,
+) -> Result<Set<Index>, Error> {
+    database.element(root)?;
+    let mut reached = HashSet::new();
+    reached.insert(root);
+    let mut frontier = vec![root];
+    for _ in 0..depth {
+        let mut next_frontier = Vec::new();
+        for index in frontier {
+            let element = database.element(index).unwrap();
+            let mut neighbors: Vec<Index> = Vec::new();
+            if direction != Direction::Components {
+                neighbors.extend(
+                    element
+                        .subject_of()
+                        .iter()
+                        .chain(element.descriptor_of().iter())
+                        .chain(element.complement_of().iter())
+                        .map(|r| r.index()),
+                )
+            }
+            if direction != Direction::Referencing {
+                if let Element::Relation(r) = element.value() {
+                    neighbors.extend(
+                        [Some(r.subject), Some(r.descriptor), r.complement]
+                            .iter()
+                            .filter_map(|&c| c),
+                    )
+                }
+            }
+            for neighbor in neighbors {
+                if reached.insert(neighbor) {
+                    next_frontier.push(neighbor)
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    Ok(reached.into_iter().collect())
+}
+
+/// Elements of the subset in each cluster, an element being only in the first matching one.
+fn cluster_members(
+    database: &Database,
+    elements: &Set<Index>,
+    clusters: &[Cluster],
+) -> Vec<Vec<Index>> {
+    let mut clustered = HashSet::new();
+    let mut members = Vec::with_capacity(clusters.len());
+    for cluster in clusters.iter() {
+        let mut tagged: Vec<Index> = match database.get_text_atom(cluster.tag.as_str()) {
+            Some(tag) => tag
+                .descriptor_of()
+                .iter()
+                .map(|r| r.value())
+                .filter(|r| r.complement.is_none() && elements.contains(&r.subject))
+                .map(|r| r.subject)
+                .filter(|&subject| clustered.insert(subject))
+                .collect(),
+            None => Vec::new(),
+        };
+        tagged.sort();
+        members.push(tagged)
+    }
+    members
+}
+
+/// Write a subset of the database as a graphviz dot graph.
+/// Relations are drawn as points linked to their components.
+/// Links to components outside of the subset are not drawn.
+/// Only the label length, clusters and style of options are used.
+pub fn write_subgraph<W: io::Write>(
+    database: &Database,
+    elements: &Set<Index>,
+    options: &Options,
+    mut w: W,
+) -> io::Result<()> {
+    write!(w, "digraph {{\n")?;
+    let tag_indexes: Vec<Option<Index>> = options
+        .style
+        .tags
+        .iter()
+        .map(|(tag, _)| database.index_of_text_atom(tag.as_str()))
+        .collect();
+    let mut clustered = HashSet::new();
+    let members = cluster_members(database, elements, &options.clusters);
+    for (i, (cluster, members)) in options.clusters.iter().zip(members).enumerate() {
+        if members.is_empty() {
+            continue;
+        }
+        let label = cluster.label.as_ref().unwrap_or(&cluster.tag);
+        write!(w, "\tsubgraph cluster_{} {{\n", i)?;
+        write!(w, "\t\tlabel=\"{}\";\n", EscapedLabel::new(label))?;
+        if let Some(ref color) = cluster.color {
+            write!(w, "\t\tcolor=\"{}\";\n", EscapedLabel::new(color))?;
+        }
+        for index in members {
+            if let Ok(element) = database.element(index) {
+                let style = options.style.node_style(database, &tag_indexes, &element);
+                write!(w, "\t")?;
+                write_node(&mut w, &element, &style, options.max_label_len)?;
+            }
+            clustered.insert(index);
+        }
+        write!(w, "\t}}\n")?;
+    }
+    for &index in elements.iter() {
+        let element = match database.element(index) {
+            Ok(element) => element,
+            Err(_) => continue,
+        };
+        if !clustered.contains(&index) {
+            let style = options.style.node_style(database, &tag_indexes, &element);
+            write_node(&mut w, &element, &style, options.max_label_len)?;
+        }
+        if let Element::Relation(r) = element.value() {
+            if elements.contains(&r.subject) {
+                write!(w, "\t{} -> {} [arrowhead=none];\n", r.subject, index)?;
+            }
+            if elements.contains(&r.descriptor) {
+                write!(
+                    w,
+                    "\t{} -> {} [style=dashed,arrowhead=none];\n",
+                    index, r.descriptor
+                )?;
+            }
+            if let Some(complement) = r.complement.filter(|c| elements.contains(c)) {
+                write!(w, "\t{} -> {};\n", index, complement)?;
+            }
+        }
+    }
+    write!(w, "}}\n")
+}
+
+fn write_node<W: io::Write>(
+    w: &mut W,
+    element: &Ref<Element>,
+    style: &NodeStyle,
+    max_label_len: Option<usize>,
+) -> io::Result<()> {
+    let mut attributes = Vec::new();
+    if let Some(ref shape) = style.shape {
+        attributes.push(format!("shape={}", DotId(shape)))
+    }
+    if let Some(ref color) = style.color {
+        attributes.push(format!("color={}", DotId(color)))
+    }
+    if let Some(ref font) = style.font {
+        attributes.push(format!("fontname={}", DotId(font)))
+    }
+    match element.value() {
+        Element::Abstract => attributes.push(format!("label=\"#{}\"", element.index())),
+        Element::Atom(atom) => attributes.push(format!(
+            "label=\"{}\"",
+            EscapedLabel {
+                text: &atom.to_string(),
+                max_len: max_label_len,
+            }
+        )),
+        Element::Relation(_) => (),
+    }
+    write!(w, "\t{} [{}];\n", element.index(), attributes.join(","))
+}
+
+/// Attribute value, quoted unless it is a plain word.
+struct DotId<'a>(&'a str);
+impl<'a> fmt::Display for DotId<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let is_word_char = |c: char| c.is_ascii_alphabetic() || c == '_';
+        if !self.0.is_empty() && self.0.chars().all(is_word_char) {
+            self.0.fmt(f)
+        } else {
+            write!(f, "\"{}\"", EscapedLabel::new(self.0))
+        }
+    }
+}
+
+/// Layout a dot graph to svg using the graphviz dot program, if available.
+pub fn layout_to_svg(dot_text: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run dot: {}", e))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(dot_text)
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!("dot failed: {}", output.status))
+    }
+}
+
+/// Text for a quoted dot label, optionally truncated to max_len chars.
+/// Backslashes start escape sequences in labels, so they must be escaped like quotes.
+/// Line breaks are kept as centered line breaks, other control chars are dropped.
+struct EscapedLabel<'a> {
+    text: &'a str,
+    max_len: Option<usize>,
+}
+impl<'a> EscapedLabel<'a> {
+    fn new(text: &'a str) -> Self {
+        EscapedLabel {
+            text,
+            max_len: None,
+        }
+    }
+}
+impl<'a> fmt::Display for EscapedLabel<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let max_len = self.max_len.unwrap_or(usize::max_value());
+        for (i, c) in self.text.chars().enumerate() {
+            if i >= max_len {
+                return "…".fmt(f);
+            }
+            match c {
+                '"' => "\\\"".fmt(f)?,
+                '\\' => "\\\\".fmt(f)?,
+                '\n' => "\\n".fmt(f)?,
+                c if c.is_control() => (),
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn dot() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let is_named_i = db.insert_atom(Atom::from("is \"named\""));
+        let relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: is_named_i,
+                complement: Some(name_i),
+            })
+            .unwrap();
+        let _other_i = db.create_abstract_element();
+
+        let both = Direction::Both;
+        let elements = neighborhood(&db, object_i, 0, both).unwrap();
+        assert_eq!(elements.as_ref(), &[object_i]);
+        let elements = neighborhood(&db, object_i, 1, both).unwrap();
+        assert_eq!(elements.as_ref(), &[object_i, relation_i]);
+        let elements = neighborhood(&db, object_i, 2, Direction::Components).unwrap();
+        assert_eq!(elements.as_ref(), &[object_i]);
+        let elements = neighborhood(&db, name_i, 2, Direction::Referencing).unwrap();
+        assert_eq!(elements.as_ref(), &[name_i, relation_i]);
+        let elements = neighborhood(&db, object_i, 2, both).unwrap();
+        assert_eq!(
+            elements.as_ref(),
+            &[name_i, object_i, is_named_i, relation_i]
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        let options = Options {
+            max_label_len: None,
+            ..Options::default()
+        };
+        write_subgraph(&db, &elements, &options, &mut output).unwrap();
+        let expected = "digraph {
+\t0 [shape=none,label=\"Name\"];
+\t1 [shape=box,label=\"#1\"];
+\t2 [shape=none,label=\"is \\\"named\\\"\"];
+\t3 [shape=point];
+\t1 -> 3 [arrowhead=none];
+\t3 -> 2 [style=dashed,arrowhead=none];
+\t3 -> 0;
+}
+";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        // Whole database includes the isolated element
+        let whole = render(&db, &Options::default()).unwrap();
+        assert!(whole.contains("\t4 [shape=box,label=\"#4\"];\n"));
+        let options = Options {
+            root: Some(object_i),
+            depth: 2,
+            direction: both,
+            max_label_len: None,
+            ..Options::default()
+        };
+        assert_eq!(render(&db, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn clusters() {
+        let (db, e) = fixture! {
+            joe = abstract_() { tagged("person") };
+            acme = abstract_() { tagged("company") };
+            jack = abstract_() { tagged("person"), tagged("company") };
+            other = abstract_() {};
+        };
+        let person = Cluster {
+            color: Some("blue".into()),
+            ..Cluster::new("person")
+        };
+        let company = Cluster {
+            label: Some("Company".into()),
+            ..Cluster::new("company")
+        };
+        let options = Options {
+            clusters: vec![person, company, Cluster::new("unused")],
+            ..Options::default()
+        };
+        let elements: Set<Index> = vec![e.joe, e.acme, e.jack, e.other].into();
+        let mut output: Vec<u8> = Vec::new();
+        write_subgraph(&db, &elements, &options, &mut output).unwrap();
+        let expected = format!(
+            "digraph {{
+\tsubgraph cluster_0 {{
+\t\tlabel=\"person\";
+\t\tcolor=\"blue\";
+\t\t{joe} [shape=box,label=\"#{joe}\"];
+\t\t{jack} [shape=box,label=\"#{jack}\"];
+\t}}
+\tsubgraph cluster_1 {{
+\t\tlabel=\"Company\";
+\t\t{acme} [shape=box,label=\"#{acme}\"];
+\t}}
+\t{other} [shape=box,label=\"#{other}\"];
+}}
+",
+            joe = e.joe,
+            acme = e.acme,
+            jack = e.jack,
+            other = e.other
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn style() {
+        let (db, e) = fixture! {
+            named = atom("named") {};
+            joe = abstract_() { related("named", "Joe"), tagged("person") };
+            acme = abstract_() { tagged("company") };
+        };
+        let person = NodeStyle {
+            color: Some("#ff0000".into()),
+            font: Some("Helvetica".into()),
+            ..NodeStyle::default()
+        };
+        let options = Options {
+            style: DotStyle {
+                atom: NodeStyle::default(),
+                relation: NodeStyle::with_shape("circle"),
+                tags: vec![
+                    ("company".into(), NodeStyle::with_shape("house")),
+                    ("person".into(), person),
+                ],
+                ..DotStyle::default()
+            },
+            ..Options::default()
+        };
+        let elements: Set<Index> = vec![e.named, e.joe, e.acme].into();
+        let mut output: Vec<u8> = Vec::new();
+        write_subgraph(&db, &elements, &options, &mut output).unwrap();
+        let expected = format!(
+            "digraph {{
+\t{named} [label=\"named\"];
+\t{joe} [shape=box,color=\"#ff0000\",fontname=Helvetica,label=\"#{joe}\"];
+\t{acme} [shape=house,label=\"#{acme}\"];
+}}
+",
+            named = e.named,
+            joe = e.joe,
+            acme = e.acme
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(DotId("a_b").to_string(), "a_b");
+        assert_eq!(DotId("light blue").to_string(), "\"light blue\"");
+        assert_eq!(DotId("").to_string(), "\"\"");
+    }
+
+    #[test]
+    fn label_escaping() {
+        let escaped =
+            |text: &str, max_len: Option<usize>| EscapedLabel { text, max_len }.to_string();
+        assert_eq!(escaped("plain", None), "plain");
+        assert_eq!(escaped("\"", None), "\\\"");
+        assert_eq!(escaped("a\\\"b", None), "a\\\\\\\"b");
+        assert_eq!(escaped("\\", None), "\\\\");
+        assert_eq!(escaped("line\nbreak", None), "line\\nbreak");
+        assert_eq!(escaped("a\r\tb\u{0}", None), "ab");
+        assert_eq!(escaped("}\"; x -> y; \"{", None), "}\\\"; x -> y; \\\"{");
+        // Truncation counts chars, not bytes
+        assert_eq!(escaped("éléphant", Some(3)), "élé…");
+        assert_eq!(escaped("abc", Some(3)), "abc");
+        assert_eq!(escaped("abc", Some(0)), "…");
+    }
+}
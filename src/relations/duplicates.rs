@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::{Database, Element, Index, Ref};
+
+/// Pair of abstract elements with similar links, which may represent the same thing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateCandidate {
+    /// Lowest index of the pair.
+    pub first: Index,
+    /// Highest index of the pair.
+    pub second: Index,
+    /// Jaccard index of their links: number of shared links divided by number of links of either.
+    pub similarity: f64,
+}
+
+/// Use of an element in a relation, identified by the other components.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+enum Link {
+    Subject(Index, Option<Index>),
+    Descriptor(Index, Option<Index>),
+    Complement(Index, Index),
+}
+
+/// Links shared by more elements than this are too common to indicate duplicates.
+/// They are ignored, which also prevents generating a quadratic number of pairs.
+const MAX_SHARING: usize = 50;
+
+fn links(element: Ref<Element>) -> Vec<Link> {
+    let subject_of = element.subject_of();
+    let descriptor_of = element.descriptor_of();
+    let complement_of = element.complement_of();
+    let as_subject = subject_of.iter().map(|r| {
+        let r = r.value();
+        Link::Subject(r.descriptor, r.complement)
+    });
+    let as_descriptor = descriptor_of.iter().map(|r| {
+        let r = r.value();
+        Link::Descriptor(r.subject, r.complement)
+    });
+    let as_complement = complement_of.iter().map(|r| {
+        let r = r.value();
+        Link::Complement(r.subject, r.descriptor)
+    });
+    as_subject.chain(as_descriptor).chain(as_complement).collect()
+}
+
+impl Database {
+    /// Pairs of abstract elements sharing links, with a similarity of at least min_similarity.
+    /// Sorted by decreasing similarity, then by indexes.
+    pub fn duplicate_candidates(&self, min_similarity: f64) -> Vec<DuplicateCandidate> {
+        let mut nb_links: HashMap<Index, usize> = HashMap::new();
+        let mut elements_with_link: HashMap<Link, Vec<Index>> = HashMap::new();
+        for element in self.iter() {
+            if let Element::Abstract = element.value() {
+                let index = element.index();
+                let links = links(element);
+                nb_links.insert(index, links.len());
+                for link in links {
+                    // Elements are iterated by increasing index, so vectors are sorted.
+                    elements_with_link.entry(link).or_insert_with(Vec::new).push(index)
+                }
+            }
+        }
+        let mut nb_shared_links: HashMap<(Index, Index), usize> = HashMap::new();
+        for elements in elements_with_link.values() {
+            if elements.len() > MAX_SHARING {
+                continue;
+            }
+            for (i, &first) in elements.iter().enumerate() {
+                for &second in elements[i + 1..].iter() {
+                    *nb_shared_links.entry((first, second)).or_insert(0) += 1
+                }
+            }
+        }
+        let mut candidates: Vec<DuplicateCandidate> = nb_shared_links
+            .into_iter()
+            .map(|((first, second), shared)| {
+                let union = nb_links[&first] + nb_links[&second] - shared;
+                DuplicateCandidate {
+                    first,
+                    second,
+                    similarity: shared as f64 / union as f64,
+                }
+            })
+            .filter(|candidate| candidate.similarity >= min_similarity)
+            .collect();
+        candidates.sort_by(|lhs, rhs| {
+            let by_similarity = rhs.similarity.partial_cmp(&lhs.similarity);
+            by_similarity
+                .unwrap_or(Ordering::Equal)
+                .then((lhs.first, lhs.second).cmp(&(rhs.first, rhs.second)))
+        });
+        candidates
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn duplicate_candidates() {
+        let mut db = Database::new();
+        let named_i = db.insert_atom(Atom::from("named"));
+        let joe_i = db.insert_atom(Atom::from("Joe"));
+        let person_i = db.insert_atom(Atom::from("person"));
+        let a_i = db.create_abstract_element();
+        let b_i = db.create_abstract_element();
+        let c_i = db.create_abstract_element();
+        {
+            let mut relate = |subject, descriptor, complement| {
+                db.insert_relation(Relation {
+                    subject,
+                    descriptor,
+                    complement,
+                })
+                .unwrap();
+            };
+            relate(a_i, named_i, Some(joe_i));
+            relate(b_i, named_i, Some(joe_i));
+            relate(a_i, person_i, None);
+            relate(b_i, person_i, None);
+            relate(c_i, person_i, None);
+        }
+
+        let candidate = |first, second, similarity| DuplicateCandidate {
+            first,
+            second,
+            similarity,
+        };
+        assert_eq!(db.duplicate_candidates(0.6), vec![candidate(a_i, b_i, 1.0)]);
+        assert_eq!(
+            db.duplicate_candidates(0.),
+            vec![
+                candidate(a_i, b_i, 1.0),
+                candidate(a_i, c_i, 0.5),
+                candidate(b_i, c_i, 0.5)
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use super::{Database, Index};
+
+/// Modification of the database, notified to observers after it is applied.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Change {
+    /// Element inserted at this index.
+    Inserted(Index),
+    /// Element removed from this index.
+    Removed(Index),
+    /// Atom value changed, or atom replaced by an abstract.
+    Modified(Index),
+    /// Whole content replaced, like by Database::restore: any element may have changed.
+    Reset,
+}
+
+/// Callback receiving the database after each change.
+pub type Observer = Arc<dyn Fn(&Database, Change) + Send + Sync>;
+
+impl Database {
+    /// Register a callback called after each modification of the database.
+    /// Loading a database from a file does not generate changes, restoring a version does.
+    /// Observers are not copied to clones of the database.
+    pub fn on_change<F>(&mut self, observer: F)
+    where
+        F: Fn(&Database, Change) + Send + Sync + 'static,
+    {
+        self.observers.push(Arc::new(observer))
+    }
+
+    /// Number of changes since the database was created or read, incremented by each change.
+    /// Cached data computed from the database is still valid while it is the same.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub(super) fn notify(&mut self, change: Change) {
+        self.revision += 1;
+        for observer in self.observers.iter() {
+            observer(self, change)
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn on_change() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut db = Database::new();
+        {
+            let changes = changes.clone();
+            db.on_change(move |db, change| {
+                assert!(match change {
+                    Change::Removed(i) => db.element(i).is_err(),
+                    Change::Inserted(i) | Change::Modified(i) => db.element(i).is_ok(),
+                    Change::Reset => true,
+                });
+                changes.lock().unwrap().push(change)
+            });
+        }
+        let name_i = db.insert_atom(Atom::from("Name"));
+        assert_eq!(db.insert_atom(Atom::from("Name")), name_i); // Already present
+        let object_i = db.create_abstract_element();
+        let relation = Relation {
+            subject: object_i,
+            descriptor: name_i,
+            complement: None,
+        };
+        let relation_i = db.insert_relation(relation.clone()).unwrap();
+        assert_eq!(db.insert_relation(relation).unwrap(), relation_i); // Already present
+        db.replace_atom_value(name_i, Atom::from("Named")).unwrap();
+        db.remove_element(relation_i).unwrap();
+        db.replace_atom_with_abstract(name_i).unwrap();
+        assert!(db.remove_element(relation_i).is_err());
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                Change::Inserted(name_i),
+                Change::Inserted(object_i),
+                Change::Inserted(relation_i),
+                Change::Modified(name_i),
+                Change::Removed(relation_i),
+                Change::Modified(name_i),
+            ]
+        );
+        assert_eq!(db.revision(), 6);
+        let mut copy = db.clone();
+        assert_eq!(copy.revision(), 6);
+        copy.create_abstract_element();
+        assert_eq!(changes.lock().unwrap().len(), 6);
+    }
+}
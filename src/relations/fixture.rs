@@ -0,0 +1,61 @@
+/// Build a database from a list of named elements, for tests and examples.
+/// Each statement creates an element with an ElementBuilder constructor and methods.
+/// Elements can refer to earlier ones by name.
+/// Returns the database and a struct with the index of each named element.
+/// Panics if an insertion fails.
+///
+/// ```
+/// # #[macro_use] extern crate rett;
+/// # fn main() {
+/// let (db, e) = fixture! {
+///     acme = abstract_() { related("named", "Acme") };
+///     joe = abstract_() { related("named", "Joe"), linked_to("works at", acme) };
+///     answer = atom(42i64) {};
+/// };
+/// assert!(db.element(e.joe).is_ok());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fixture {
+    ($($name:ident = $constructor:ident($($arg:expr),*) {
+        $($method:ident($($method_arg:expr),*)),*
+    };)*) => {{
+        #[allow(dead_code)]
+        struct Fixture {
+            $($name: $crate::relations::Index,)*
+        }
+        let mut database = $crate::relations::Database::new();
+        $(
+            let $name = $crate::relations::ElementBuilder::$constructor($($arg),*)
+                $(.$method($($method_arg),*))*
+                .insert(&mut database)
+                .expect(concat!("fixture: cannot insert ", stringify!($name)));
+        )*
+        (database, Fixture { $($name,)* })
+    }};
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+
+    #[test]
+    fn fixture() {
+        let (db, e) = fixture! {
+            acme = abstract_() { related("named", "Acme") };
+            joe = abstract_() { related("named", "Joe"), linked_to("works at", acme) };
+            named = atom("named") {};
+        };
+        let works_at = db.index_of_text_atom("works at").unwrap();
+        let relation = Relation {
+            subject: e.joe,
+            descriptor: works_at,
+            complement: Some(e.acme),
+        };
+        assert!(db.index_of_relation(&relation).is_some());
+        assert_eq!(db.index_of_atom(&Atom::from("named")), Some(e.named));
+    }
+}
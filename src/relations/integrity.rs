@@ -0,0 +1,535 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::{Atom, Database, Element, ElementData, Index, Relation};
+use utils::Set;
+
+/// Broken invariant of the database, usually from a hand-edited or corrupted file.
+#[derive(Debug, Eq, PartialEq)]
+pub struct IntegrityError {
+    /// Element breaking the invariant.
+    pub index: Index,
+    /// Invariant broken.
+    pub kind: IntegrityErrorKind,
+}
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Kind of broken invariant.
+pub enum IntegrityErrorKind {
+    /// Relation component is not an element.
+    DanglingReference {
+        /// Missing component.
+        component: Index,
+    },
+    /// Relation uses itself as a component.
+    SelfReference,
+    /// Relation component leads back to the relation through other relations.
+    ReferenceCycle {
+        /// Component on the cycle.
+        component: Index,
+    },
+    /// Same atom or relation value as another element.
+    Duplicate {
+        /// Element with the same value.
+        of: Index,
+    },
+    /// Atom or relation missing from the lookup tables.
+    NotIndexed,
+    /// Lookup table entry pointing to an element with another value.
+    StaleIndexEntry,
+    /// Relation component does not list the relation in its back-links.
+    MissingBackLink {
+        /// Component without the back-link.
+        component: Index,
+    },
+    /// Element back-links contain a relation which does not use the element.
+    StaleBackLink {
+        /// Relation in the back-links.
+        relation: Index,
+    },
+}
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "element {}: {}", self.index, self.kind)
+    }
+}
+impl fmt::Display for IntegrityErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntegrityErrorKind::DanglingReference { component } => {
+                write!(f, "component {} does not exist", component)
+            }
+            IntegrityErrorKind::SelfReference => "relation references itself".fmt(f),
+            IntegrityErrorKind::ReferenceCycle { component } => {
+                write!(f, "component {} is part of a reference cycle", component)
+            }
+            IntegrityErrorKind::Duplicate { of } => write!(f, "duplicate of element {}", of),
+            IntegrityErrorKind::NotIndexed => "missing from lookup tables".fmt(f),
+            IntegrityErrorKind::StaleIndexEntry => "lookup table entry has another value".fmt(f),
+            IntegrityErrorKind::MissingBackLink { component } => {
+                write!(f, "not in back-links of component {}", component)
+            }
+            IntegrityErrorKind::StaleBackLink { relation } => {
+                write!(f, "back-links contain unrelated relation {}", relation)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Subject,
+    Descriptor,
+    Complement,
+}
+fn components(relation: &Relation) -> [(Role, Option<Index>); 3] {
+    [
+        (Role::Subject, Some(relation.subject)),
+        (Role::Descriptor, Some(relation.descriptor)),
+        (Role::Complement, relation.complement),
+    ]
+}
+fn back_links(data: &ElementData, role: Role) -> &Set<Index> {
+    match role {
+        Role::Subject => &data.subject_of,
+        Role::Descriptor => &data.descriptor_of,
+        Role::Complement => &data.complement_of,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+impl Database {
+    /// Check all structural invariants. Errors are sorted by element index.
+    /// A database only modified through its methods always validates.
+    pub fn validate(&self) -> Vec<IntegrityError> {
+        let mut errors = Vec::new();
+        let mut visits = HashMap::new();
+        let mut cycles = Vec::new();
+        {
+            let mut error = |index, kind| errors.push(IntegrityError { index, kind });
+            for (index, data) in self.elements.iter() {
+                match data.value {
+                    Element::Abstract => (),
+                    Element::Atom(ref atom) => match self.index_of_atom(atom) {
+                        Some(i) if i == index => (),
+                        Some(i) => error(index, IntegrityErrorKind::Duplicate { of: i }),
+                        None => error(index, IntegrityErrorKind::NotIndexed),
+                    },
+                    Element::Relation(ref relation) => {
+                        match self.index_of_relations.get(relation) {
+                            Some(&i) if i == index => (),
+                            Some(&i) => error(index, IntegrityErrorKind::Duplicate { of: i }),
+                            None => error(index, IntegrityErrorKind::NotIndexed),
+                        }
+                        for &(role, component) in components(relation).iter() {
+                            let component = match component {
+                                Some(component) => component,
+                                None => continue,
+                            };
+                            match self.elements.get(component) {
+                                _ if component == index => {
+                                    error(index, IntegrityErrorKind::SelfReference)
+                                }
+                                None => error(
+                                    index,
+                                    IntegrityErrorKind::DanglingReference { component },
+                                ),
+                                Some(data) => {
+                                    if !back_links(data, role).contains(&index) {
+                                        error(
+                                            index,
+                                            IntegrityErrorKind::MissingBackLink { component },
+                                        )
+                                    }
+                                }
+                            }
+                        }
+                        let element_at = |i: Index| self.elements.get(i).map(|d| &d.value);
+                        find_reference_cycles(&element_at, index, &mut visits, &mut cycles);
+                    }
+                }
+                for &role in [Role::Subject, Role::Descriptor, Role::Complement].iter() {
+                    for &relation in back_links(data, role).iter() {
+                        let uses_element = match self.elements.get(relation).map(|d| &d.value) {
+                            Some(Element::Relation(r)) => components(r)
+                                .iter()
+                                .any(|&(r_role, c)| r_role == role && c == Some(index)),
+                            _ => false,
+                        };
+                        if !uses_element {
+                            error(index, IntegrityErrorKind::StaleBackLink { relation })
+                        }
+                    }
+                }
+            }
+            for &(index, component) in cycles.iter() {
+                error(index, IntegrityErrorKind::ReferenceCycle { component })
+            }
+            let text_index = &self.index_of_text_atoms;
+            for (hash, index) in text_index.entries() {
+                match self.elements.get(index).map(|d| &d.value) {
+                    Some(Element::Atom(Atom::Text(s))) if text_index.hash(s) == hash => (),
+                    _ => error(index, IntegrityErrorKind::StaleIndexEntry),
+                }
+            }
+            for (atom, &index) in self.index_of_value_atoms.iter() {
+                match self.elements.get(index).map(|d| &d.value) {
+                    Some(Element::Atom(a)) if a == atom => (),
+                    _ => error(index, IntegrityErrorKind::StaleIndexEntry),
+                }
+            }
+            for (relation, &index) in self.index_of_relations.iter() {
+                match self.elements.get(index).map(|d| &d.value) {
+                    Some(Element::Relation(r)) if r == relation => (),
+                    _ => error(index, IntegrityErrorKind::StaleIndexEntry),
+                }
+            }
+        }
+        errors.sort_by_key(|e| e.index);
+        errors
+    }
+}
+
+/// Depth first search through relation components, with elements given by element_at.
+/// Adds (relation, component) to cycles if the component is a relation being explored.
+/// Self references are excluded, they are reported separately.
+fn find_reference_cycles<'e, F>(
+    element_at: &F,
+    index: Index,
+    visits: &mut HashMap<Index, Visit>,
+    cycles: &mut Vec<(Index, Index)>,
+) where
+    F: Fn(Index) -> Option<&'e Element>,
+{
+    if visits.contains_key(&index) {
+        return;
+    }
+    let relation = match element_at(index) {
+        Some(Element::Relation(relation)) => relation.clone(),
+        _ => return,
+    };
+    visits.insert(index, Visit::InProgress);
+    for &(_, component) in components(&relation).iter() {
+        match component {
+            Some(component) if component != index => match visits.get(&component) {
+                Some(&Visit::InProgress) => cycles.push((index, component)),
+                Some(&Visit::Done) => (),
+                None => find_reference_cycles(element_at, component, visits, cycles),
+            },
+            _ => (),
+        }
+    }
+    visits.insert(index, Visit::Done);
+}
+
+/// How to repair duplicated atoms or relations, which cannot coexist in lookup tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairPolicy {
+    /// Redirect references to the duplicate towards the first occurrence, and remove it.
+    MergeDuplicates,
+    /// Keep the duplicate and its references, as an abstract element.
+    DuplicatesToAbstract,
+}
+
+/// Change made by a repair.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Repair {
+    /// Relation removed as it could not be fixed.
+    Removed {
+        /// Relation removed.
+        index: Index,
+        /// Problem that could not be fixed.
+        reason: IntegrityErrorKind,
+    },
+    /// Duplicate element merged into the first element with its value.
+    Merged {
+        /// Duplicate element, now unused.
+        index: Index,
+        /// Element which replaces it.
+        into: Index,
+    },
+    /// Atom or relation replaced by an abstract element, as its value was already used.
+    MadeAbstract {
+        /// Element which lost its value.
+        index: Index,
+    },
+    /// Uuid already used by a previous element.
+    UuidCleared {
+        /// Element which lost its uuid.
+        index: Index,
+    },
+}
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repair::Removed { index, ref reason } => {
+                write!(f, "removed relation {}: {}", index, reason)
+            }
+            Repair::Merged { index, into } => write!(f, "merged element {} into {}", index, into),
+            Repair::MadeAbstract { index } => {
+                write!(f, "duplicate element {} made abstract", index)
+            }
+            Repair::UuidCleared { index } => write!(f, "cleared duplicate uuid of {}", index),
+        }
+    }
+}
+
+/// Fix element slots read from a corrupted file so that they can be loaded.
+/// Back-links and lookup tables are not stored, they are rebuilt when loading the slots.
+pub(super) fn repair_slots(
+    mut slots: Vec<Option<ElementData>>,
+    policy: RepairPolicy,
+) -> (Vec<Option<ElementData>>, Vec<Repair>) {
+    let mut repairs = Vec::new();
+    let mut redirects: HashMap<Index, Index> = HashMap::new();
+
+    let mut uuids = HashSet::new();
+    for (index, slot) in slots.iter_mut().enumerate() {
+        if let Some(data) = slot.as_mut() {
+            if let Some(uuid) = data.uuid {
+                if !uuids.insert(uuid) {
+                    data.uuid = None;
+                    repairs.push(Repair::UuidCleared { index })
+                }
+            }
+        }
+    }
+
+    let mut atoms: HashMap<Atom, Index> = HashMap::new();
+    for index in 0..slots.len() {
+        let atom = match slots[index].as_ref().map(|data| &data.value) {
+            Some(Element::Atom(atom)) => atom.clone(),
+            _ => continue,
+        };
+        match atoms.get(&atom).cloned() {
+            Some(first) => resolve_duplicate(
+                &mut slots,
+                &mut redirects,
+                &mut repairs,
+                policy,
+                index,
+                first,
+            ),
+            None => {
+                atoms.insert(atom, index);
+            }
+        }
+    }
+
+    // Removing or merging a relation can break or duplicate relations using it: iterate.
+    loop {
+        let mut changed = false;
+        let mut relations: HashMap<Relation, Index> = HashMap::new();
+        for index in 0..slots.len() {
+            let relation = match slots[index].as_ref().map(|data| &data.value) {
+                Some(Element::Relation(r)) => Relation {
+                    subject: follow(&redirects, r.subject),
+                    descriptor: follow(&redirects, r.descriptor),
+                    complement: r.complement.map(|c| follow(&redirects, c)),
+                },
+                _ => continue,
+            };
+            let broken = components(&relation)
+                .iter()
+                .filter_map(|&(_, component)| component)
+                .filter_map(|component| {
+                    if component == index {
+                        Some(IntegrityErrorKind::SelfReference)
+                    } else if slots.get(component).map_or(true, |slot| slot.is_none()) {
+                        Some(IntegrityErrorKind::DanglingReference { component })
+                    } else {
+                        None
+                    }
+                })
+                .next();
+            if let Some(reason) = broken {
+                slots[index] = None;
+                repairs.push(Repair::Removed { index, reason });
+                changed = true;
+                continue;
+            }
+            match relations.get(&relation).cloned() {
+                Some(first) => {
+                    resolve_duplicate(
+                        &mut slots,
+                        &mut redirects,
+                        &mut repairs,
+                        policy,
+                        index,
+                        first,
+                    );
+                    changed = true;
+                }
+                None => {
+                    relations.insert(relation.clone(), index);
+                    slots[index].as_mut().unwrap().value = Element::Relation(relation);
+                }
+            }
+        }
+        if !changed {
+            let mut visits = HashMap::new();
+            let mut cycles = Vec::new();
+            {
+                let element_at = |i: Index| slots.get(i).and_then(|s| s.as_ref()).map(|d| &d.value);
+                for index in 0..slots.len() {
+                    find_reference_cycles(&element_at, index, &mut visits, &mut cycles);
+                }
+            }
+            for (index, component) in cycles {
+                slots[index] = None;
+                repairs.push(Repair::Removed {
+                    index,
+                    reason: IntegrityErrorKind::ReferenceCycle { component },
+                });
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    (slots, repairs)
+}
+
+/// Final target of redirections.
+fn follow(redirects: &HashMap<Index, Index>, mut index: Index) -> Index {
+    while let Some(&target) = redirects.get(&index) {
+        index = target
+    }
+    index
+}
+
+fn resolve_duplicate(
+    slots: &mut Vec<Option<ElementData>>,
+    redirects: &mut HashMap<Index, Index>,
+    repairs: &mut Vec<Repair>,
+    policy: RepairPolicy,
+    index: Index,
+    first: Index,
+) {
+    match policy {
+        RepairPolicy::MergeDuplicates => {
+            slots[index] = None;
+            redirects.insert(index, first);
+            repairs.push(Repair::Merged { index, into: first })
+        }
+        RepairPolicy::DuplicatesToAbstract => {
+            slots[index].as_mut().unwrap().value = Element::Abstract;
+            repairs.push(Repair::MadeAbstract { index })
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: name_i,
+                complement: None,
+            })
+            .unwrap();
+        assert!(db.validate().is_empty());
+
+        let corrupted = {
+            let mut db = db.clone();
+            db.elements[name_i].descriptor_of = Set::new();
+            db.elements[object_i].complement_of.insert(relation_i);
+            db.index_of_text_atoms.remove("Name", name_i);
+            db.validate()
+        };
+        assert_eq!(
+            corrupted,
+            vec![
+                IntegrityError {
+                    index: name_i,
+                    kind: IntegrityErrorKind::NotIndexed
+                },
+                IntegrityError {
+                    index: object_i,
+                    kind: IntegrityErrorKind::StaleBackLink {
+                        relation: relation_i
+                    }
+                },
+                IntegrityError {
+                    index: relation_i,
+                    kind: IntegrityErrorKind::MissingBackLink { component: name_i }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_references() {
+        // Relations referencing themselves or each other cannot be built by Database methods.
+        let read = |text: &[u8]| Database::read_from(text).err().unwrap().to_string();
+        assert!(read(b"A\nR 1 0\n").contains("relation references itself"));
+        assert!(read(b"A\nR 2 0\nR 1 0\n").contains("reference cycle"));
+        assert!(read(b"A\nR 0 0 5\n").contains("Bad Element at index 1"));
+    }
+
+    #[test]
+    fn repair() {
+        let corrupted = b"T a\nA\nT a\nR 1 2\nR 1 0\nR 5 0\nR 9 0\nR 8 0\nR 7 0\n";
+        assert!(Database::read_from(&corrupted[..]).is_err());
+
+        let (db, repairs) =
+            Database::read_and_repair_from(&corrupted[..], RepairPolicy::MergeDuplicates).unwrap();
+        assert_eq!(
+            repairs,
+            vec![
+                Repair::Merged { index: 2, into: 0 },
+                Repair::Merged { index: 4, into: 3 },
+                Repair::Removed {
+                    index: 5,
+                    reason: IntegrityErrorKind::SelfReference
+                },
+                Repair::Removed {
+                    index: 6,
+                    reason: IntegrityErrorKind::DanglingReference { component: 9 }
+                },
+                Repair::Removed {
+                    index: 8,
+                    reason: IntegrityErrorKind::ReferenceCycle { component: 7 }
+                },
+                Repair::Removed {
+                    index: 7,
+                    reason: IntegrityErrorKind::DanglingReference { component: 8 }
+                },
+            ]
+        );
+        assert!(db.validate().is_empty());
+        assert_eq!(db.iter().count(), 3);
+        let relation = Relation {
+            subject: 1,
+            descriptor: 0,
+            complement: None,
+        };
+        assert_eq!(db.index_of_relation(&relation), Some(3));
+
+        let (db, repairs) =
+            Database::read_and_repair_from(&corrupted[..], RepairPolicy::DuplicatesToAbstract)
+                .unwrap();
+        assert_eq!(repairs[0], Repair::MadeAbstract { index: 2 });
+        assert!(db.validate().is_empty());
+        match db.element(2).unwrap().value() {
+            Element::Abstract => (),
+            _ => panic!("duplicate atom should be abstract"),
+        }
+        assert_eq!(db.index_of_relation(&relation), Some(4));
+    }
+}
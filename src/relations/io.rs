@@ -1,56 +1,188 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use fs2::FileExt;
+use percent_encoding::percent_decode;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
-use super::{Atom, Database, Element, ElementData, Relation};
+use super::integrity::{repair_slots, Repair, RepairPolicy};
+use super::migrations;
+use super::{Atom, Database, Element, ElementData, Index, Provenance, Relation};
 use utils::SlotVec;
 
+/// Files with a ".gz" extension are transparently compressed.
+fn is_gzip_file(filename: &Path) -> bool {
+    filename.extension().map_or(false, |e| e == "gz")
+}
+
+fn open_database_file(filename: &Path) -> Result<io::BufReader<Box<dyn io::Read>>, String> {
+    let file = File::open(filename)
+        .map_err(|e| format!("Cannot read file {}: {}", filename.display(), e))?;
+    let reader: Box<dyn io::Read> = if is_gzip_file(filename) {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(io::BufReader::new(reader))
+}
+
+/// Prevent two processes from using the same database file and overwriting each other's saves.
+/// The advisory lock is taken on a separate file, as saving replaces the database file.
+/// It is released when the returned file is dropped.
+pub fn lock_database_file(database_file: &Path) -> Result<File, String> {
+    let mut lock_path = database_file.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    let lock_file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Cannot open lock file {}: {}", lock_path.display(), e))?;
+    lock_file.try_lock_exclusive().map_err(|_| {
+        format!(
+            "Database file {} is already used by another process",
+            database_file.display()
+        )
+    })?;
+    Ok(lock_file)
+}
+
 /// Read the database from file.
 pub fn read_database_from_file(filename: &Path) -> Result<Database, String> {
-    File::open(filename)
-        .map_err(|e| format!("Cannot read file {}: {}", filename.display(), e))
-        .and_then(|file| {
-            Database::read_from(io::BufReader::new(file)).map_err(|e| {
-                format!(
-                    "Invalid database format in file {}: {}",
-                    filename.display(),
-                    e
-                )
-            })
-        })
+    Database::read_from(open_database_file(filename)?).map_err(|e| {
+        format!(
+            "Invalid database format in file {}: {}",
+            filename.display(),
+            e
+        )
+    })
+}
+
+/// Read the database from file, fixing inconsistencies instead of failing on them.
+/// Lines that cannot be parsed are still errors.
+pub fn read_database_from_file_with_repair(
+    filename: &Path,
+    policy: RepairPolicy,
+) -> Result<(Database, Vec<Repair>), String> {
+    Database::read_and_repair_from(open_database_file(filename)?, policy).map_err(|e| {
+        format!(
+            "Invalid database format in file {}: {}",
+            filename.display(),
+            e
+        )
+    })
 }
 
 /// Write database to a file.
 pub fn write_database_to_file(filename: &Path, database: &Database) -> Result<(), String> {
-    File::create(filename)
-        .and_then(|f| database.write_to(io::BufWriter::new(f)))
-        .map_err(|e| format!("Cannot write database to {}: {}", filename.display(), e))
+    write_database_atomically(filename, None, database)
+}
+
+/// Write database to a file, moving the previous version of the file to backup.
+pub fn write_database_to_file_with_backup(
+    filename: &Path,
+    backup: &Path,
+    database: &Database,
+) -> Result<(), String> {
+    write_database_atomically(filename, Some(backup), database)
+}
+
+/// The database is written to a temporary file in the same directory, then renamed over the target.
+/// A crash during serialization leaves the previous file intact instead of a truncated one.
+/// The backup is a link to (or a copy of) the previous file, so the target always exists.
+fn write_database_atomically(
+    filename: &Path,
+    backup: Option<&Path>,
+    database: &Database,
+) -> Result<(), String> {
+    let mut tmp_filename = filename.as_os_str().to_owned();
+    tmp_filename.push(".tmp");
+    let tmp_filename = PathBuf::from(tmp_filename);
+    let written = File::create(&tmp_filename).and_then(|f| {
+        let f = if is_gzip_file(filename) {
+            let mut encoder = GzEncoder::new(io::BufWriter::new(f), Compression::default());
+            database.write_to(&mut encoder)?;
+            encoder.finish()?
+        } else {
+            let mut writer = io::BufWriter::new(f);
+            database.write_to(&mut writer)?;
+            writer
+        };
+        f.into_inner()?.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_filename);
+        return Err(format!(
+            "Cannot write database to {}: {}",
+            tmp_filename.display(),
+            e
+        ));
+    }
+    if let Some(backup) = backup {
+        keep_backup(filename, backup).map_err(|e| format!("Cannot write backup: {}", e))?
+    }
+    fs::rename(&tmp_filename, filename)
+        .map_err(|e| format!("Cannot write database to {}: {}", filename.display(), e))?;
+    sync_parent_directory(filename)
+        .map_err(|e| format!("Cannot sync directory of {}: {}", filename.display(), e))
+}
+
+/// Make backup a hard link to the current file, or a copy if links are not supported.
+/// Nothing is done if the file does not exist yet.
+fn keep_backup(filename: &Path, backup: &Path) -> io::Result<()> {
+    if !filename.exists() {
+        return Ok(());
+    }
+    match fs::remove_file(backup) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    fs::hard_link(filename, backup).or_else(|_| fs::copy(filename, backup).map(|_| ()))
+}
+
+/// Make the rename of the file durable, by syncing the directory entry.
+#[cfg(unix)]
+fn sync_parent_directory(filename: &Path) -> io::Result<()> {
+    let directory = match filename.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    File::open(directory)?.sync_all()
+}
+/// Directories cannot be opened as files on other systems.
+#[cfg(not(unix))]
+fn sync_parent_directory(_filename: &Path) -> io::Result<()> {
+    Ok(())
 }
 
 /******************************************************************************
  * IO using a simple text format.
  *
+ * The first line is a header with the format version, see migrations.
  * The slot-vector of elements is printed with one line per slot, in order.
  * The first char of the line indicates which type of element the line represents.
+ * It is optionally followed by '@' and the element uuid,
+ * then by '!' and the provenance: creation and modification timestamps, and escaped author.
  * Empty lines are empty slots.
+ * Lines of deleted elements start with '~', so that their slots stay reserved and they can be restored.
  */
 impl Database {
     /// Write database in a simple text format to any io.
     pub fn write_to<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        for element_slot in self.elements.as_ref().iter() {
-            match element_slot {
-                Some(element) => match element.value {
-                    Element::Abstract => write!(w, "A\n"),
-                    Element::Atom(ref atom) => match atom {
-                        Atom::Text(ref s) => write!(w, "T {}\n", EscapedAtomText(s)),
-                    },
-                    Element::Relation(ref rel) => match rel.complement {
-                        Some(c) => write!(w, "R {} {} {}\n", rel.subject, rel.descriptor, c),
-                        None => write!(w, "R {} {}\n", rel.subject, rel.descriptor),
-                    },
-                },
-                None => write!(w, "\n"),
+        write!(w, "{}\n", migrations::header())?;
+        for (index, element_slot) in self.elements.as_ref().iter().enumerate() {
+            match (element_slot, self.deleted.get(&index)) {
+                (Some(element), _) => write_element_line(&mut w, element),
+                (None, Some(deleted)) => {
+                    write!(w, "~")?;
+                    write_element_line(&mut w, deleted)
+                }
+                (None, None) => write!(w, "\n"),
             }?
         }
         Ok(())
@@ -58,90 +190,189 @@ impl Database {
 
     /// Read database in a simple text format from any io.
     pub fn read_from<R: io::BufRead>(reader: R) -> io::Result<Database> {
-        // Read list of elements
-        let element_for = |line: &str| -> Result<Element, &str> {
-            let (type_char, tail) = split_first(line).unwrap();
-            match type_char {
-                'A' => match tail {
-                    "" => Ok(Element::Abstract),
-                    _ => Err("Abstract: trailing text"),
-                },
-                'T' => match split_first(tail) {
-                    Some((' ', text)) => Ok(Element::Atom(Atom::from(text))),
-                    _ => Err("Text: missing space"),
-                },
-                'R' => match split_first(tail) {
-                    Some((' ', text)) => {
-                        let mut it = text.split(' ').map(|s| s.parse::<usize>());
-                        let fields = [it.next(), it.next(), it.next(), it.next()];
-                        match fields {
-                            [Some(Ok(s)), Some(Ok(d)), Some(Ok(c)), None] => {
-                                Ok(Element::Relation(Relation {
-                                    subject: s,
-                                    descriptor: d,
-                                    complement: Some(c),
-                                }))
-                            }
-                            [Some(Ok(s)), Some(Ok(d)), None, None] => {
-                                Ok(Element::Relation(Relation {
-                                    subject: s,
-                                    descriptor: d,
-                                    complement: None,
-                                }))
-                            }
-                            _ => Err("Relation: bad field format or count"),
-                        }
-                    }
-                    _ => Err("Relation: missing space"),
-                },
-                _ => Err("Unrecognized type char"),
-            }
-        };
-        let parsed_elements: io::Result<Vec<Option<ElementData>>> = reader
-            .lines()
-            .map(|maybe_line| {
-                maybe_line.and_then(|line| {
-                    if line.is_empty() {
-                        Ok(None)
-                    } else {
-                        match element_for(&line) {
-                            Ok(e) => Ok(Some(ElementData::new(e))),
-                            Err(reason) => Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("Cannot parse line '{}': {}", line, reason),
-                            )),
-                        }
-                    }
-                })
-            })
-            .collect();
-        Database::new_from(parsed_elements?).map_err(|s| io::Error::new(io::ErrorKind::Other, s))
+        let (slots, deleted) = read_element_slots(reader)?;
+        Database::new_from(slots, deleted).map_err(|s| io::Error::new(io::ErrorKind::Other, s))
+    }
+
+    /// Read database, fixing broken relations and duplicates. Also returns the list of fixes.
+    pub fn read_and_repair_from<R: io::BufRead>(
+        reader: R,
+        policy: RepairPolicy,
+    ) -> io::Result<(Database, Vec<Repair>)> {
+        let (slots, deleted) = read_element_slots(reader)?;
+        let (slots, repairs) = repair_slots(slots, policy);
+        let database =
+            Database::new_from(slots, deleted).expect("repaired database must be consistent");
+        Ok((database, repairs))
     }
 
-    fn new_from(elements: Vec<Option<ElementData>>) -> Result<Database, String> {
+    /// Database of the element slots, with deleted elements reserving their empty slots.
+    /// Deleted elements are only checked when restored.
+    pub(super) fn new_from(
+        mut elements: Vec<Option<ElementData>>,
+        deleted: Vec<(Index, ElementData)>,
+    ) -> Result<Database, String> {
+        let deleted_indexes: Vec<Index> = deleted.iter().map(|&(index, _)| index).collect();
+        for (index, element_data) in deleted {
+            match elements.get_mut(index) {
+                Some(slot) if slot.is_none() => *slot = Some(element_data),
+                _ => return Err(format!("Deleted element at used index {}", index)),
+            }
+        }
         let mut db = Database {
             elements: SlotVec::from(elements),
             ..Database::new()
         };
+        for index in deleted_indexes {
+            let element_data = db.elements.remove_reserved(index).unwrap();
+            db.deleted.insert(index, element_data);
+        }
         // Check and register elements
-        let nb_slots = db.elements.capacity();
-        for index in 0..nb_slots {
-            if let Some(element) = db.elements.as_ref()[index]
-                .as_ref()
-                .map(|ed| ed.value.clone())
-            {
-                match element {
-                    Element::Abstract => Ok(()),
-                    Element::Atom(atom) => db.register_atom(index, atom),
-                    Element::Relation(relation) => db.register_relation(index, relation),
-                }
-                .map_err(|s| format!("Bad Element at index {}: {}", index, s))?;
+        let elements: Vec<_> = db
+            .elements
+            .iter()
+            .map(|(index, ed)| (index, ed.value.clone(), ed.uuid))
+            .collect();
+        for (index, element, uuid) in elements {
+            match element {
+                Element::Abstract => Ok(()),
+                Element::Atom(atom) => db.register_atom(index, atom),
+                Element::Relation(relation) => db.register_relation(index, relation),
             }
+            .and_then(|()| match uuid {
+                Some(uuid) => db.register_uuid(index, uuid),
+                None => Ok(()),
+            })
+            .map_err(|s| format!("Bad Element at index {}: {}", index, s))?;
+        }
+        // Registration does not catch relations referencing themselves, directly or not.
+        let errors: Vec<String> = db.validate().iter().map(|e| e.to_string()).collect();
+        if !errors.is_empty() {
+            return Err(format!("Inconsistent database: {}", errors.join(", ")));
         }
         Ok(db)
     }
 }
 
+fn write_element_line<W: io::Write>(w: &mut W, element: &ElementData) -> io::Result<()> {
+    let metadata = MetadataSuffix(element);
+    match element.value {
+        Element::Abstract => write!(w, "A{}\n", metadata),
+        Element::Atom(ref atom) => match atom {
+            Atom::Text(ref s) => write!(w, "T{} {}\n", metadata, EscapedAtomText(s)),
+            Atom::Integer(n) => write!(w, "I{} {}\n", metadata, n),
+            Atom::Float(x) => write!(w, "F{} {}\n", metadata, x),
+            Atom::Date(d) => write!(w, "D{} {}\n", metadata, d),
+        },
+        Element::Relation(ref rel) => match rel.complement {
+            Some(c) => write!(
+                w,
+                "R{} {} {} {}\n",
+                metadata, rel.subject, rel.descriptor, c
+            ),
+            None => write!(w, "R{} {} {}\n", metadata, rel.subject, rel.descriptor),
+        },
+    }
+}
+
+/// Element slots from the text format, and deleted elements by index.
+/// There is no consistency check. Lines of older format versions are migrated before parsing.
+fn read_element_slots<R: io::BufRead>(
+    reader: R,
+) -> io::Result<(Vec<Option<ElementData>>, Vec<(Index, ElementData)>)> {
+    let format_error = |message| io::Error::new(io::ErrorKind::Other, message);
+    let element_for = |line: &str| -> Result<ElementData, &str> {
+        let (type_char, tail) = split_first(line).unwrap();
+        let (uuid, tail) = split_uuid(tail)?;
+        let (provenance, tail) = split_provenance(tail)?;
+        let element = match type_char {
+            'A' => match tail {
+                "" => Ok(Element::Abstract),
+                _ => Err("Abstract: trailing text"),
+            },
+            'T' => match split_first(tail) {
+                Some((' ', text)) => Ok(Element::Atom(Atom::from(text))),
+                _ => Err("Text: missing space"),
+            },
+            'I' => match split_first(tail) {
+                Some((' ', text)) => text
+                    .parse()
+                    .map(|n| Element::Atom(Atom::Integer(n)))
+                    .map_err(|_| "Integer: invalid value"),
+                _ => Err("Integer: missing space"),
+            },
+            'F' => match split_first(tail) {
+                Some((' ', text)) => text.parse().map(|x| Element::Atom(Atom::Float(x))),
+                _ => Err("Float: missing space"),
+            },
+            'D' => match split_first(tail) {
+                Some((' ', text)) => text.parse().map(|d| Element::Atom(Atom::Date(d))),
+                _ => Err("Date: missing space"),
+            },
+            'R' => match split_first(tail) {
+                Some((' ', text)) => {
+                    let mut it = text.split(' ').map(|s| s.parse::<usize>());
+                    let fields = [it.next(), it.next(), it.next(), it.next()];
+                    match fields {
+                        [Some(Ok(s)), Some(Ok(d)), Some(Ok(c)), None] => {
+                            Ok(Element::Relation(Relation {
+                                subject: s,
+                                descriptor: d,
+                                complement: Some(c),
+                            }))
+                        }
+                        [Some(Ok(s)), Some(Ok(d)), None, None] => Ok(Element::Relation(Relation {
+                            subject: s,
+                            descriptor: d,
+                            complement: None,
+                        })),
+                        _ => Err("Relation: bad field format or count"),
+                    }
+                }
+                _ => Err("Relation: missing space"),
+            },
+            _ => Err("Unrecognized type char"),
+        }?;
+        Ok(ElementData {
+            provenance,
+            ..ElementData::new(element, uuid)
+        })
+    };
+    let mut lines = reader.lines();
+    let (version, first_line) = match lines.next() {
+        Some(line) => {
+            let line = line?;
+            match migrations::parse_header(&line).map_err(format_error)? {
+                Some(version) => (version, None),
+                None => (0, Some(line)),
+            }
+        }
+        None => (migrations::CURRENT_VERSION, None),
+    };
+    let mut slots = Vec::new();
+    let mut deleted = Vec::new();
+    for line in first_line.map(Ok).into_iter().chain(lines) {
+        let line = migrations::migrate_line(version, line?).map_err(format_error)?;
+        if line.is_empty() {
+            slots.push(None);
+            continue;
+        }
+        let (is_deleted, element_line) = match split_first(&line) {
+            Some(('~', element_line)) => (true, element_line),
+            _ => (false, line.as_str()),
+        };
+        let element = element_for(element_line)
+            .map_err(|reason| format_error(format!("Cannot parse line '{}': {}", line, reason)))?;
+        if is_deleted {
+            deleted.push((slots.len(), element));
+            slots.push(None)
+        } else {
+            slots.push(Some(element))
+        }
+    }
+    Ok((slots, deleted))
+}
+
 struct EscapedAtomText<'a>(&'a str);
 impl<'a> fmt::Display for EscapedAtomText<'a> {
     // Remove all \n. TODO replace with ' ' or something else ?
@@ -153,6 +384,96 @@ impl<'a> fmt::Display for EscapedAtomText<'a> {
     }
 }
 
+struct MetadataSuffix<'a>(&'a ElementData);
+impl<'a> fmt::Display for MetadataSuffix<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref uuid) = self.0.uuid {
+            write!(f, "@{}", uuid)?
+        }
+        if let Some(ref provenance) = self.0.provenance {
+            write!(f, "!{},{}", provenance.created, provenance.modified)?;
+            if let Some(ref author) = provenance.author {
+                write!(f, ",{}", EscapedAuthor(author))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Percent encoding of characters ending the provenance part.
+struct EscapedAuthor<'a>(&'a str);
+impl<'a> fmt::Display for EscapedAuthor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                ' ' | ',' | '%' => write!(f, "%{:02X}", c as u32)?,
+                c if c.is_control() => {
+                    let mut buffer = [0; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        write!(f, "%{:02X}", byte)?
+                    }
+                }
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the optional "@uuid" part after the type char.
+fn split_uuid(s: &str) -> Result<(Option<Uuid>, &str), &'static str> {
+    match split_first(s) {
+        Some(('@', tail)) => {
+            let end = tail.find(|c| c == ' ' || c == '!').unwrap_or(tail.len());
+            let (uuid_text, tail) = tail.split_at(end);
+            match Uuid::parse_str(uuid_text) {
+                Ok(uuid) => Ok((Some(uuid), tail)),
+                Err(_) => Err("bad uuid"),
+            }
+        }
+        _ => Ok((None, s)),
+    }
+}
+
+/// Parse the optional "!created,modified[,author]" part after the uuid.
+fn split_provenance(s: &str) -> Result<(Option<Provenance>, &str), &'static str> {
+    match split_first(s) {
+        Some(('!', tail)) => {
+            let end = tail.find(' ').unwrap_or(tail.len());
+            let (provenance_text, tail) = tail.split_at(end);
+            let mut fields = provenance_text.split(',');
+            let (created, modified) = {
+                let mut timestamp = || {
+                    fields
+                        .next()
+                        .and_then(|field| field.parse().ok())
+                        .ok_or("bad provenance timestamp")
+                };
+                (timestamp()?, timestamp()?)
+            };
+            let author = match fields.next() {
+                Some(author) => Some(
+                    percent_decode(author.as_bytes())
+                        .decode_utf8()
+                        .map_err(|_| "bad provenance author")?
+                        .into_owned(),
+                ),
+                None => None,
+            };
+            if fields.next().is_some() {
+                return Err("provenance: trailing fields");
+            }
+            let provenance = Provenance {
+                created,
+                modified,
+                author,
+            };
+            Ok((Some(provenance), tail))
+        }
+        _ => Ok((None, s)),
+    }
+}
+
 fn split_first(s: &str) -> Option<(char, &str)> {
     s.chars().next().map(|first: char| {
         let (_, tail) = s.split_at(first.len_utf8());
@@ -165,6 +486,7 @@ fn split_first(s: &str) -> Option<(char, &str)> {
  */
 #[cfg(test)]
 mod tests {
+    use super::super::Date;
     use super::*;
 
     #[test]
@@ -181,13 +503,26 @@ mod tests {
             descriptor: is_named_i,
             complement: Some(name_i),
         };
-        let _relation_i = db.insert_relation(relation.clone()).unwrap();
+        let relation_i = db.insert_relation(relation.clone()).unwrap();
 
         // Serialization
         let mut serialized: Vec<u8> = Vec::new();
         db.write_to(&mut serialized).expect("serialization failure");
-        let expected_serialized = b"T Name\nA\nT is named\nR 1 2 0\n";
-        assert_eq!(serialized, expected_serialized);
+        let metadata = |i| {
+            let element = db.element(i).unwrap();
+            let provenance = element.provenance().unwrap();
+            let (created, modified) = (provenance.created, provenance.modified);
+            format!("@{}!{},{}", element.uuid().unwrap(), created, modified)
+        };
+        let expected_serialized = format!(
+            "{}\nT{} Name\nA{}\nT{} is named\nR{} 1 2 0\n",
+            migrations::header(),
+            metadata(name_i),
+            metadata(object_i),
+            metadata(is_named_i),
+            metadata(relation_i)
+        );
+        assert_eq!(serialized, expected_serialized.as_bytes());
 
         // Deserialization
         let db_clone = Database::read_from(serialized.as_slice()).expect("deserialization failure");
@@ -203,6 +538,8 @@ mod tests {
                         _ => false,
                     };
                     element_match
+                        && dbo.uuid == dbc.uuid
+                        && dbo.provenance == dbc.provenance
                         && dbo.subject_of == dbc.subject_of
                         && dbo.descriptor_of == dbc.descriptor_of
                         && dbo.complement_of == dbc.complement_of
@@ -211,7 +548,161 @@ mod tests {
             };
             assert!(both_slots_match);
         }
-        assert_eq!(db.index_of_text_atoms, db_clone.index_of_text_atoms);
+        // Text hashes are keyed per database, so only compare indexed atoms.
+        let text_atoms = |db: &Database| {
+            let mut atoms: Vec<usize> = db.index_of_text_atoms.entries().map(|e| e.1).collect();
+            atoms.sort();
+            atoms
+        };
+        assert_eq!(text_atoms(&db), text_atoms(&db_clone));
+        assert_eq!(db.index_of_value_atoms, db_clone.index_of_value_atoms);
         assert_eq!(db.index_of_relations, db_clone.index_of_relations);
+        assert_eq!(db.index_of_uuids, db_clone.index_of_uuids);
+    }
+
+    #[test]
+    fn io_without_uuids() {
+        // Files written before uuids were introduced must still load, and round trip.
+        let elements = "T Name\nA\nT is named\nR 1 2 0\n";
+        let db = Database::read_from(elements.as_bytes()).expect("deserialization failure");
+        assert!(db.iter().all(|e| e.uuid().is_none()));
+        let mut reserialized: Vec<u8> = Vec::new();
+        db.write_to(&mut reserialized).expect("serialization failure");
+        let expected = format!("{}\n{}", migrations::header(), elements);
+        assert_eq!(&reserialized[..], expected.as_bytes());
+    }
+
+    #[test]
+    fn io_v1_file() {
+        // Version 1 files have uuids but no provenance.
+        let uuid = "a0b1c2d3-e4f5-4a6b-8c7d-9e0f1a2b3c4d";
+        let serialized = format!("# rett database v1\nT@{} Name\nA\n", uuid);
+        let db = Database::read_from(serialized.as_bytes()).expect("deserialization failure");
+        assert_eq!(db.element(0).unwrap().uuid(), Uuid::parse_str(uuid).ok());
+        assert!(db.iter().all(|e| e.provenance().is_none()));
+        let mut reserialized: Vec<u8> = Vec::new();
+        db.write_to(&mut reserialized).expect("serialization failure");
+        let expected = format!("{}\nT@{} Name\nA\n", migrations::header(), uuid);
+        assert_eq!(&reserialized[..], expected.as_bytes());
+    }
+
+    #[test]
+    fn io_value_atoms() {
+        let serialized = format!("{}\nI -42\nF 1.5\nD 2019-07-14\n", migrations::header());
+        let db = Database::read_from(serialized.as_bytes()).expect("deserialization failure");
+        assert_eq!(db.index_of_atom(&Atom::from(-42)), Some(0));
+        assert_eq!(db.index_of_atom(&Atom::from(1.5)), Some(1));
+        let date = "2019-07-14".parse::<Date>().unwrap();
+        assert_eq!(db.index_of_atom(&Atom::from(date)), Some(2));
+        let mut reserialized: Vec<u8> = Vec::new();
+        db.write_to(&mut reserialized).expect("serialization failure");
+        assert_eq!(&reserialized[..], serialized.as_bytes());
+
+        assert!(Database::read_from(&b"I 4.2\n"[..]).is_err());
+        assert!(Database::read_from(&b"D 2019-02-30\n"[..]).is_err());
+        // Same value
+        assert!(Database::read_from(&b"F 0.0\nF -0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn gzip_file() {
+        let serialized = format!("{}\nT Name\nA\nT is named\nR 1 2 0\n", migrations::header());
+        let db = Database::read_from(serialized.as_bytes()).unwrap();
+        let filename = std::env::temp_dir().join(format!("rett-test-{}.gz", Uuid::new_v4()));
+        write_database_to_file(&filename, &db).unwrap();
+        let compressed = std::fs::read(&filename).unwrap();
+        assert_eq!(&compressed[..2], &[0x1f, 0x8b]); // Gzip magic number
+        let reread = read_database_from_file(&filename);
+        std::fs::remove_file(&filename).unwrap();
+        let mut reserialized: Vec<u8> = Vec::new();
+        reread.unwrap().write_to(&mut reserialized).unwrap();
+        assert_eq!(&reserialized[..], serialized.as_bytes());
+    }
+
+    #[test]
+    fn io_deleted_elements() {
+        let header = migrations::header();
+        let text = format!("{}\nT Name\nA\nR 1 0\n\nT Other\n", header);
+        let mut db = Database::read_from(text.as_bytes()).unwrap();
+        db.delete_element(2).unwrap();
+        db.delete_element(0).unwrap();
+        let filename = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        write_database_to_file(&filename, &db).unwrap();
+        let written = std::fs::read(&filename);
+        let mut reread = read_database_from_file(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        let expected = format!("{}\n~T Name\nA\n~R 1 0\n\nT Other\n", header);
+        assert_eq!(written.unwrap(), expected.as_bytes());
+
+        // Slots stay reserved after reading, and elements can be restored
+        assert_eq!(reread.deleted_elements(), vec![0, 2]);
+        assert_eq!(reread.index_of_text_atom("Name"), None);
+        assert_eq!(reread.create_abstract_element(), 3);
+        assert_eq!(reread.create_abstract_element(), 5);
+        reread.restore_element(0).unwrap();
+        reread.restore_element(2).unwrap();
+        assert_eq!(reread.index_of_text_atom("Name"), Some(0));
+        assert!(reread.element(0).unwrap().is_referenced());
+
+        let used_slot = format!("{}\nA\n", header);
+        let mut db = Database::read_from(used_slot.as_bytes()).unwrap();
+        db.create_abstract_element();
+        db.delete_element(1).unwrap();
+        db.purge_deleted();
+        let mut purged: Vec<u8> = Vec::new();
+        db.write_to(&mut purged).unwrap();
+        assert_eq!(purged, format!("{}\nA\n\n", header).as_bytes()); // Purged slot is empty
+    }
+
+    #[test]
+    fn atomic_write_with_backup() {
+        let old = Database::read_from(&b"T old\n"[..]).unwrap();
+        let new = Database::read_from(&b"T new\n"[..]).unwrap();
+        let dir = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let filename = dir.join("db");
+        let backup = dir.join("db.bak");
+        write_database_to_file_with_backup(&filename, &backup, &old).unwrap();
+        assert!(!backup.exists()); // Nothing to backup yet
+        write_database_to_file_with_backup(&filename, &backup, &new).unwrap();
+        let content = std::fs::read(&filename);
+        let backup_content = std::fs::read(&backup);
+        let nb_files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let header = migrations::header();
+        assert_eq!(content.unwrap(), format!("{}\nT new\n", header).as_bytes());
+        assert_eq!(backup_content.unwrap(), format!("{}\nT old\n", header).as_bytes());
+        assert_eq!(nb_files, 2); // No temporary file left
+    }
+
+    #[test]
+    fn io_provenance() {
+        let serialized = format!(
+            "{}\nT!10,20 Name\nA@{}!10,10,J%20D%2C%25é\n",
+            migrations::header(),
+            "a0b1c2d3-e4f5-4a6b-8c7d-9e0f1a2b3c4d"
+        );
+        let db = Database::read_from(serialized.as_bytes()).expect("deserialization failure");
+        let provenance = |i| db.element(i).unwrap().provenance().cloned().unwrap();
+        assert_eq!((provenance(0).created, provenance(0).modified), (10, 20));
+        assert_eq!(provenance(0).author, None);
+        assert_eq!(provenance(1).author, Some("J D,%é".into()));
+        let mut reserialized: Vec<u8> = Vec::new();
+        db.write_to(&mut reserialized).expect("serialization failure");
+        assert_eq!(&reserialized[..], serialized.as_bytes());
+        assert!(Database::read_from(&b"A!10\n"[..]).is_err());
+        assert!(Database::read_from(&b"A!10,x\n"[..]).is_err());
+    }
+
+    #[test]
+    fn unversioned_file() {
+        // Files written before the format header still load, and are written with it.
+        let serialized = b"T Name\n\nA\n";
+        let db = Database::read_from(&serialized[..]).expect("deserialization failure");
+        let mut reserialized: Vec<u8> = Vec::new();
+        db.write_to(&mut reserialized).expect("serialization failure");
+        let expected = format!("{}\nT Name\n\nA\n", migrations::header());
+        assert_eq!(&reserialized[..], expected.as_bytes());
+        assert!(Database::read_from(&b"# rett database v99\nA\n"[..]).is_err());
     }
 }
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use super::cytoscape::JsonEscaped;
+use super::dot::{select, Options};
+use super::{Atom, Database, Element, Error, Ref};
+
+/// IRIs given to descriptors in the JSON-LD context.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Descriptors not in terms get this prefix followed by their percent encoded text.
+    pub vocabulary: String,
+    /// IRI of descriptors designated by their text, like ("named", "http://schema.org/name").
+    pub terms: Vec<(String, String)>,
+}
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            vocabulary: "urn:rett:vocabulary:".into(),
+            terms: Vec::new(),
+        }
+    }
+}
+impl Context {
+    /// IRI of a descriptor designated by its text.
+    pub(super) fn iri(&self, descriptor: &str) -> String {
+        match self.terms.iter().find(|(text, _)| text == descriptor) {
+            Some((_, iri)) => iri.clone(),
+            None => format!("{}{}", self.vocabulary, PercentEncoded(descriptor)),
+        }
+    }
+}
+
+pub(super) const XSD: &'static str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Render the abstract elements selected by options as a JSON-LD document.
+/// Elements are nodes identified by their uuid, or blank nodes if they have none.
+/// Relations "e d" become types of e, and relations "e d c" properties of e, d being a text atom.
+/// Complements are literals for atoms, and references for selected abstract elements.
+/// Other relations have no linked data equivalent and are omitted.
+pub fn render(database: &Database, options: &Options, context: &Context) -> Result<String, Error> {
+    let elements = select(database, options)?;
+    let mut used_terms = BTreeMap::new();
+    let mut nodes = Vec::new();
+    for &index in elements.iter() {
+        let element = database.element(index)?;
+        match element.value() {
+            Element::Abstract => (),
+            _ => continue,
+        }
+        let mut types = Vec::new();
+        let mut properties: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for relation in element.subject_of().iter() {
+            let r = relation.value();
+            if !elements.contains(&relation.index()) {
+                continue;
+            }
+            let descriptor = match database.element(r.descriptor)?.value() {
+                Element::Atom(Atom::Text(text)) => text.as_str(),
+                _ => continue,
+            };
+            let value = match r.complement {
+                None => {
+                    types.push(format!("\"{}\"", JsonEscaped(descriptor)));
+                    used_terms.insert(descriptor, context.iri(descriptor));
+                    continue;
+                }
+                Some(complement) if elements.contains(&complement) => {
+                    let complement = database.element(complement)?;
+                    match complement.value() {
+                        Element::Atom(atom) => JsonLdValue(atom).to_string(),
+                        Element::Abstract => format!("{{\"@id\":\"{}\"}}", NodeId(&complement)),
+                        Element::Relation(_) => continue,
+                    }
+                }
+                Some(_) => continue,
+            };
+            used_terms.insert(descriptor, context.iri(descriptor));
+            properties.entry(descriptor).or_insert_with(Vec::new).push(value)
+        }
+        let mut node = format!("{{\"@id\":\"{}\"", NodeId(&element));
+        if !types.is_empty() {
+            write!(node, ",\"@type\":[{}]", types.join(",")).unwrap()
+        }
+        for (descriptor, values) in properties {
+            let values = values.join(",");
+            write!(node, ",\"{}\":[{}]", JsonEscaped(descriptor), values).unwrap()
+        }
+        node.push('}');
+        nodes.push(node)
+    }
+    let terms: Vec<String> = used_terms
+        .into_iter()
+        .map(|(term, iri)| format!("\"{}\":\"{}\"", JsonEscaped(term), JsonEscaped(&iri)))
+        .collect();
+    Ok(format!(
+        "{{\"@context\":{{{}}},\"@graph\":[{}]}}",
+        terms.join(","),
+        nodes.join(",")
+    ))
+}
+
+/// Identifier of an element node.
+struct NodeId<'a, 'e: 'a>(&'a Ref<'e, Element>);
+impl<'a, 'e> fmt::Display for NodeId<'a, 'e> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.uuid() {
+            Some(uuid) => write!(f, "urn:uuid:{}", uuid),
+            None => write!(f, "_:e{}", self.0.index()),
+        }
+    }
+}
+
+/// Atom as a JSON-LD value: native JSON when possible, typed literal otherwise.
+struct JsonLdValue<'a>(&'a Atom);
+impl<'a> fmt::Display for JsonLdValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let typed = |f: &mut fmt::Formatter, value: &dyn fmt::Display, datatype: &str| {
+            write!(f, "{{\"@value\":\"{}\",\"@type\":\"{}{}\"}}", value, XSD, datatype)
+        };
+        match self.0 {
+            Atom::Text(s) => write!(f, "\"{}\"", JsonEscaped(s)),
+            Atom::Integer(n) => n.fmt(f),
+            Atom::Float(x) => {
+                // JSON has no infinities and NaN, they are written with the xsd:double lexical form.
+                let x = x.value();
+                if x.is_nan() {
+                    typed(f, &"NaN", "double")
+                } else if x.is_infinite() {
+                    typed(f, &if x < 0.0 { "-INF" } else { "INF" }, "double")
+                } else {
+                    write!(f, "{:?}", x)
+                }
+            }
+            Atom::Date(d) => typed(f, d, "date"),
+        }
+    }
+}
+
+/// Text percent encoded to be part of an IRI.
+struct PercentEncoded<'a>(&'a str);
+impl<'a> fmt::Display for PercentEncoded<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &b in self.0.as_bytes() {
+            let c = b as char;
+            if c.is_ascii_alphanumeric() || "-._~".contains(c) {
+                c.fmt(f)?
+            } else {
+                write!(f, "%{:02X}", b)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Date;
+    use super::*;
+
+    #[test]
+    fn json_ld() {
+        let (mut db, e) = fixture! {
+            acme = abstract_() { related("named", "Acme") };
+            joe = abstract_() {
+                tagged("person"),
+                related("named", "Joe \"Jr\""),
+                related("age", 42i64),
+                linked_to("works at", acme)
+            };
+        };
+        let anonymous_i = db.create_abstract_element();
+        let context = Context {
+            terms: vec![("named".into(), "http://schema.org/name".into())],
+            ..Context::default()
+        };
+        let id = |index| {
+            let uuid = db.element(index).unwrap().uuid().unwrap();
+            format!("urn:uuid:{}", uuid)
+        };
+        let expected = concat!(
+            "{\"@context\":{",
+            "\"age\":\"urn:rett:vocabulary:age\",",
+            "\"named\":\"http://schema.org/name\",",
+            "\"person\":\"urn:rett:vocabulary:person\",",
+            "\"works at\":\"urn:rett:vocabulary:works%20at\"",
+            "},\"@graph\":[",
+            "{\"@id\":\"ACME\",\"named\":[\"Acme\"]},",
+            "{\"@id\":\"JOE\",\"@type\":[\"person\"],\"age\":[42],",
+            "\"named\":[\"Joe \\\"Jr\\\"\"],\"works at\":[{\"@id\":\"ACME\"}]},",
+            "{\"@id\":\"ANONYMOUS\"}",
+            "]}"
+        )
+        .replace("ACME", &id(e.acme))
+        .replace("JOE", &id(e.joe))
+        .replace("ANONYMOUS", &id(anonymous_i));
+        let output = render(&db, &Options::default(), &context).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn json_ld_values() {
+        let value = |atom: Atom| JsonLdValue(&atom).to_string();
+        assert_eq!(value(Atom::from(-3i64)), "-3");
+        assert_eq!(value(Atom::from(2.0)), "2.0");
+        assert_eq!(
+            value(Atom::from(std::f64::NEG_INFINITY)),
+            "{\"@value\":\"-INF\",\"@type\":\"http://www.w3.org/2001/XMLSchema#double\"}"
+        );
+        assert_eq!(
+            value(Atom::from(Date::new(2019, 7, 14).unwrap())),
+            "{\"@value\":\"2019-07-14\",\"@type\":\"http://www.w3.org/2001/XMLSchema#date\"}"
+        );
+        assert_eq!(PercentEncoded("a b/é").to_string(), "a%20b%2F%C3%A9");
+    }
+}
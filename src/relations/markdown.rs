@@ -0,0 +1,202 @@
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::{Atom, Database, Element, Error, Index, Ref, Relation};
+
+/// Descriptors with a special place in pages, designated by their text.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Relations "e named name" give the page title and link texts.
+    pub named: String,
+    /// Text complements of relations "e description text" are paragraphs below the title.
+    pub description: String,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            named: "named".into(),
+            description: "description".into(),
+        }
+    }
+}
+
+/// Write a Markdown page per abstract element in the directory, named by index like 12.md.
+/// Pages list relations of the element, with links to pages of other abstract elements.
+/// An index.md page links to all pages, sorted by name.
+/// Returns the number of element pages.
+pub fn write_pages(
+    database: &Database,
+    directory: &Path,
+    options: &Options,
+) -> Result<usize, String> {
+    let error = |e: io::Error| format!("Cannot write pages in {}: {}", directory.display(), e);
+    fs::create_dir_all(directory).map_err(error)?;
+    let mut entries = Vec::new();
+    for element in database.iter() {
+        if let Element::Abstract = element.value() {
+            let page = render_page(database, element.index(), options).unwrap();
+            let filename = directory.join(format!("{}.md", element.index()));
+            fs::write(filename, page).map_err(error)?;
+            entries.push((name(database, &element, options), element.index()))
+        }
+    }
+    entries.sort();
+    let mut index = String::from("# Index\n\n");
+    for (name, element) in entries.iter() {
+        write!(index, "- [{}]({}.md)\n", MarkdownEscaped(name), element).unwrap()
+    }
+    fs::write(directory.join("index.md"), index).map_err(error)?;
+    Ok(entries.len())
+}
+
+/// Markdown page of an abstract element.
+pub fn render_page(database: &Database, index: Index, options: &Options) -> Result<String, Error> {
+    let element = database.element(index)?;
+    match element.value() {
+        Element::Abstract => (),
+        _ => return Err(Error::InvalidIndex),
+    }
+    let named = database.index_of_text_atom(options.named.as_str());
+    let description = database.index_of_text_atom(options.description.as_str());
+    let mut page = format!("# {}\n", MarkdownEscaped(&name(database, &element, options)));
+    let mut relations = String::new();
+    for relation in element.subject_of().iter() {
+        let r = relation.value();
+        if Some(r.descriptor) == named {
+            continue; // In the title
+        }
+        if Some(r.descriptor) == description {
+            if let Some(complement) = relation.complement() {
+                if let Element::Atom(Atom::Text(text)) = complement.value() {
+                    write!(page, "\n{}\n", text).unwrap()
+                }
+            }
+            continue;
+        }
+        write!(relations, "- {}\n", RelationText(database, r, index, options)).unwrap()
+    }
+    if !relations.is_empty() {
+        write!(page, "\n## Relations\n\n{}", relations).unwrap()
+    }
+    let complement_of = element.complement_of();
+    if !complement_of.is_empty() {
+        page.push_str("\n## Referenced by\n\n");
+        for relation in complement_of.iter() {
+            let text = RelationText(database, relation.value(), index, options);
+            write!(page, "- {}\n", text).unwrap()
+        }
+    }
+    Ok(page)
+}
+
+/// Text of the first naming atom, or #index.
+fn name(database: &Database, element: &Ref<Element>, options: &Options) -> String {
+    let named = database.index_of_text_atom(options.named.as_str());
+    let naming_atom = element
+        .subject_of_where(|r| Some(r.value().descriptor) == named)
+        .find_map(|r| match r.complement()?.value() {
+            Element::Atom(atom) => Some(atom.to_string()),
+            _ => None,
+        });
+    naming_atom.unwrap_or_else(|| format!("#{}", element.index()))
+}
+
+/// Relation as a Markdown list item, omitting the page element.
+struct RelationText<'a>(&'a Database, &'a Relation, Index, &'a Options);
+impl<'a> fmt::Display for RelationText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let RelationText(database, relation, page, options) = *self;
+        let mut components = vec![relation.subject, relation.descriptor];
+        components.extend(relation.complement);
+        let mut first = true;
+        for component in components.into_iter().filter(|&c| c != page) {
+            if !first {
+                " ".fmt(f)?
+            }
+            first = false;
+            ElementText(database, component, options, 1).fmt(f)?
+        }
+        Ok(())
+    }
+}
+
+/// Element as Markdown: link for abstract elements, text for atoms, components for relations.
+struct ElementText<'a>(&'a Database, Index, &'a Options, usize);
+impl<'a> fmt::Display for ElementText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ElementText(database, index, options, depth) = *self;
+        let element = database.element(index).unwrap();
+        match element.value() {
+            Element::Abstract => write!(
+                f,
+                "[{}]({}.md)",
+                MarkdownEscaped(&name(database, &element, options)),
+                index
+            ),
+            Element::Atom(atom) => MarkdownEscaped(&atom.to_string()).fmt(f),
+            Element::Relation(r) if depth > 0 => {
+                write!(f, "(")?;
+                ElementText(database, r.subject, options, depth - 1).fmt(f)?;
+                write!(f, " ")?;
+                ElementText(database, r.descriptor, options, depth - 1).fmt(f)?;
+                if let Some(complement) = r.complement {
+                    write!(f, " ")?;
+                    ElementText(database, complement, options, depth - 1).fmt(f)?;
+                }
+                write!(f, ")")
+            }
+            Element::Relation(_) => write!(f, "relation \\#{}", index),
+        }
+    }
+}
+
+/// Inline text without Markdown formatting.
+struct MarkdownEscaped<'a>(&'a str);
+impl<'a> fmt::Display for MarkdownEscaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '<' | '>' | '|' => write!(f, "\\{}", c)?,
+                '\n' => " ".fmt(f)?,
+                c if c.is_control() => (),
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_page() {
+        let (db, e) = fixture! {
+            acme = abstract_() { related("named", "Acme") };
+            joe = abstract_() {
+                related("named", "Joe *Jr*"),
+                related("description", "A person."),
+                tagged("person"),
+                linked_to("works at", acme)
+            };
+            unnamed = abstract_() { linked_to("knows", joe) };
+        };
+        let options = Options::default();
+        let expected = format!(
+            "# Joe \\*Jr\\*\n\nA person.\n\n## Relations\n\n- person\n- works at [Acme]({acme}.md)\n\n\
+             ## Referenced by\n\n- [\\#{unnamed}]({unnamed}.md) knows\n",
+            acme = e.acme,
+            unnamed = e.unnamed
+        );
+        assert_eq!(render_page(&db, e.joe, &options), Ok(expected));
+        let named = db.index_of_text_atom("named").unwrap();
+        assert_eq!(render_page(&db, named, &options), Err(Error::InvalidIndex));
+        assert_eq!(MarkdownEscaped("a_b\n[c]").to_string(), "a\\_b \\[c\\]");
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+use super::{Atom, Database, Element, ElementData, Index, Relation};
+
+/// Element with relation components designated by uuid, to compare elements across databases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Content {
+    Abstract,
+    Atom(Atom),
+    Relation(Uuid, Uuid, Option<Uuid>),
+}
+
+/// Elements of a database by uuid, with their index.
+struct Contents<'a> {
+    database: &'a Database,
+    elements: HashMap<Uuid, (Content, Index)>,
+}
+impl<'a> Contents<'a> {
+    fn new(database: &'a Database) -> Result<Self, String> {
+        let uuid_of = |index: Index| {
+            database
+                .element(index)
+                .ok()
+                .and_then(|e| e.uuid())
+                .ok_or_else(|| format!("Element without uuid at index {}", index))
+        };
+        let mut elements = HashMap::new();
+        for element in database.iter() {
+            let content = match element.value() {
+                Element::Abstract => Content::Abstract,
+                Element::Atom(atom) => Content::Atom(atom.clone()),
+                Element::Relation(r) => Content::Relation(
+                    uuid_of(r.subject)?,
+                    uuid_of(r.descriptor)?,
+                    match r.complement {
+                        Some(complement) => Some(uuid_of(complement)?),
+                        None => None,
+                    },
+                ),
+            };
+            elements.insert(uuid_of(element.index())?, (content, element.index()));
+        }
+        Ok(Contents { database, elements })
+    }
+    fn get(&self, uuid: &Uuid) -> Option<&Content> {
+        self.elements.get(uuid).map(|entry| &entry.0)
+    }
+    /// Uuids in index order.
+    fn uuids(&self) -> Vec<Uuid> {
+        let mut uuids: Vec<(Index, Uuid)> = self
+            .elements
+            .iter()
+            .map(|(uuid, entry)| (entry.1, *uuid))
+            .collect();
+        uuids.sort();
+        uuids.into_iter().map(|(_, uuid)| uuid).collect()
+    }
+    fn data(&self, uuid: &Uuid) -> &'a ElementData {
+        &self.database.elements[self.elements[uuid].1]
+    }
+}
+
+/// Element of the merged database that could not be merged automatically.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    /// Uuid of the element in both databases.
+    pub uuid: Uuid,
+    /// Reason of the conflict.
+    pub kind: ConflictKind,
+}
+/// Reason of a conflict, and how it was resolved.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConflictKind {
+    /// Changed differently in both databases, or changed in one and removed in the other.
+    /// The change of mine is kept, or the change of theirs if mine removed the element.
+    BothChanged,
+    /// Removed in one database, but used by a relation kept from the other, so it is kept.
+    RemovedButUsed,
+}
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::BothChanged => "changed in both databases",
+            ConflictKind::RemovedButUsed => "removed but still used",
+        };
+        write!(f, "element {}: {}", self.uuid, kind)
+    }
+}
+
+/// Combine the changes made since base in two copies of a database, elements being matched by uuid.
+/// Changes made in only one copy are applied, and other differences are reported as conflicts.
+/// Atoms or relations with the same value in both copies are merged, keeping the uuid of mine.
+/// Fails if an element has no uuid, as with old database files.
+pub fn merge3(
+    base: &Database,
+    mine: &Database,
+    theirs: &Database,
+) -> Result<(Database, Vec<Conflict>), String> {
+    let (base, mine, theirs) = (
+        Contents::new(base)?,
+        Contents::new(mine)?,
+        Contents::new(theirs)?,
+    );
+    // Elements of mine first, to keep its indexes where possible.
+    let mut order = mine.uuids();
+    order.extend(theirs.uuids().into_iter().filter(|u| mine.get(u).is_none()));
+    let mut conflicts = Vec::new();
+    // Chosen content, with the database it is taken from.
+    let mut merged: HashMap<Uuid, &Contents> = HashMap::new();
+    for uuid in order.iter() {
+        let (b, m, t) = (base.get(uuid), mine.get(uuid), theirs.get(uuid));
+        let source = if m == t || t == b {
+            m.map(|_| &mine)
+        } else if m == b {
+            t.map(|_| &theirs)
+        } else {
+            conflicts.push(Conflict {
+                uuid: *uuid,
+                kind: ConflictKind::BothChanged,
+            });
+            m.map(|_| &mine).or_else(|| t.map(|_| &theirs))
+        };
+        if let Some(source) = source {
+            merged.insert(*uuid, source);
+        }
+    }
+    // Restore removed components of kept relations, which may be relations themselves.
+    let mut unchecked: Vec<Uuid> = order.clone();
+    while let Some(uuid) = unchecked.pop() {
+        let components = match merged.get(&uuid).and_then(|source| source.get(&uuid)) {
+            Some(&Content::Relation(subject, descriptor, complement)) => {
+                let mut components = vec![subject, descriptor];
+                components.extend(complement);
+                components
+            }
+            _ => continue,
+        };
+        for component in components {
+            if merged.contains_key(&component) {
+                continue;
+            }
+            let source = [&mine, &theirs, &base]
+                .iter()
+                .cloned()
+                .find(|source| source.get(&component).is_some());
+            if let Some(source) = source {
+                merged.insert(component, source);
+                order.push(component);
+                unchecked.push(component);
+                conflicts.push(Conflict {
+                    uuid: component,
+                    kind: ConflictKind::RemovedButUsed,
+                });
+            }
+        }
+    }
+
+    let mut builder = Builder {
+        merged: &merged,
+        slots: Vec::new(),
+        indexes: HashMap::new(),
+        atoms: HashMap::new(),
+        relations: HashMap::new(),
+    };
+    for uuid in order.iter() {
+        if merged.contains_key(uuid) {
+            builder.index_of(uuid);
+        }
+    }
+    let database = Database::new_from(builder.slots, Vec::new())?;
+    Ok((database, conflicts))
+}
+
+/// Creates the element slots of the merged database, components of relations first.
+struct Builder<'m, 'a: 'm> {
+    merged: &'m HashMap<Uuid, &'m Contents<'a>>,
+    slots: Vec<Option<ElementData>>,
+    indexes: HashMap<Uuid, Index>,
+    atoms: HashMap<Atom, Index>,
+    relations: HashMap<Relation, Index>,
+}
+impl<'m, 'a> Builder<'m, 'a> {
+    fn index_of(&mut self, uuid: &Uuid) -> Index {
+        if let Some(&index) = self.indexes.get(uuid) {
+            return index;
+        }
+        let source = self.merged[uuid];
+        let value = match source.get(uuid).unwrap() {
+            Content::Abstract => Element::Abstract,
+            Content::Atom(atom) => {
+                let existing = self.atoms.get(atom).cloned();
+                match existing {
+                    Some(index) => return self.same_as(uuid, index),
+                    None => Element::Atom(atom.clone()),
+                }
+            }
+            Content::Relation(subject, descriptor, complement) => {
+                let relation = Relation {
+                    subject: self.index_of(subject),
+                    descriptor: self.index_of(descriptor),
+                    complement: complement.map(|complement| self.index_of(&complement)),
+                };
+                let existing = self.relations.get(&relation).cloned();
+                match existing {
+                    Some(index) => return self.same_as(uuid, index),
+                    None => Element::Relation(relation),
+                }
+            }
+        };
+        let index = self.slots.len();
+        match value {
+            Element::Atom(ref atom) => {
+                self.atoms.insert(atom.clone(), index);
+            }
+            Element::Relation(ref relation) => {
+                self.relations.insert(relation.clone(), index);
+            }
+            Element::Abstract => (),
+        }
+        self.slots.push(Some(ElementData {
+            provenance: source.data(uuid).provenance.clone(),
+            ..ElementData::new(value, Some(*uuid))
+        }));
+        self.indexes.insert(*uuid, index);
+        index
+    }
+    /// Element with the same value as an already created one.
+    fn same_as(&mut self, uuid: &Uuid, index: Index) -> Index {
+        self.indexes.insert(*uuid, index);
+        index
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uuid(db: &Database, index: Index) -> Uuid {
+        db.element(index).unwrap().uuid().unwrap()
+    }
+    fn text_atom(db: &Database, uuid: &Uuid) -> String {
+        let index = db.index_of_uuid(uuid).unwrap();
+        match db.element(index).unwrap().value() {
+            Element::Atom(Atom::Text(text)) => text.clone(),
+            _ => panic!("not a text atom"),
+        }
+    }
+
+    #[test]
+    fn merge_changes() {
+        let (base, e) = fixture! {
+            joe = abstract_() { related("named", "Joe") };
+            jack = abstract_() { related("named", "Jack") };
+            old = abstract_() {};
+        };
+        let mut mine = base.clone();
+        let mut theirs = base.clone();
+        let named = base.index_of_text_atom("named").unwrap();
+        let person = mine.insert_atom(Atom::from("person"));
+        let mine_relation = Relation {
+            subject: e.joe,
+            descriptor: person,
+            complement: None,
+        };
+        mine.insert_relation(mine_relation).unwrap();
+        mine.remove_element(e.old).unwrap();
+        let jack_name = base.index_of_text_atom("Jack").unwrap();
+        theirs
+            .replace_atom_value(jack_name, Atom::from("Jacky"))
+            .unwrap();
+        let person_theirs = theirs.insert_atom(Atom::from("person"));
+        let theirs_relation = Relation {
+            subject: e.jack,
+            descriptor: person_theirs,
+            complement: None,
+        };
+        theirs.insert_relation(theirs_relation).unwrap();
+
+        let (merged, conflicts) = merge3(&base, &mine, &theirs).unwrap();
+        assert_eq!(conflicts, Vec::new());
+        assert!(merged.index_of_uuid(&uuid(&base, e.old)).is_none());
+        assert_eq!(text_atom(&merged, &uuid(&base, jack_name)), "Jacky");
+        // Both "person" atoms are merged, keeping the uuid of mine.
+        let person_i = merged.index_of_text_atom("person").unwrap();
+        assert_eq!(
+            merged.element(person_i).unwrap().uuid(),
+            Some(uuid(&mine, person))
+        );
+        assert_eq!(
+            merged
+                .element(person_i)
+                .unwrap()
+                .descriptor_of()
+                .iter()
+                .count(),
+            2
+        );
+        assert_eq!(
+            merged
+                .element(named)
+                .unwrap()
+                .descriptor_of()
+                .iter()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn merge_conflicts() {
+        let (base, e) = fixture! {
+            joe = abstract_() { related("named", "Joe") };
+            old = abstract_() {};
+        };
+        let joe_name = base.index_of_text_atom("Joe").unwrap();
+        let mut mine = base.clone();
+        let mut theirs = base.clone();
+        mine.replace_atom_value(joe_name, Atom::from("Joseph"))
+            .unwrap();
+        theirs
+            .replace_atom_value(joe_name, Atom::from("Jo"))
+            .unwrap();
+        mine.remove_element(e.old).unwrap();
+        let knows = theirs.insert_atom(Atom::from("knows"));
+        let relation = Relation {
+            subject: e.joe,
+            descriptor: knows,
+            complement: Some(e.old),
+        };
+        theirs.insert_relation(relation).unwrap();
+
+        let (merged, conflicts) = merge3(&base, &mine, &theirs).unwrap();
+        let conflict = |index, kind| Conflict {
+            uuid: uuid(&base, index),
+            kind,
+        };
+        assert_eq!(
+            conflicts,
+            vec![
+                conflict(joe_name, ConflictKind::BothChanged),
+                conflict(e.old, ConflictKind::RemovedButUsed)
+            ]
+        );
+        assert_eq!(text_atom(&merged, &uuid(&base, joe_name)), "Joseph");
+        assert!(merged.index_of_uuid(&uuid(&base, e.old)).is_some());
+
+        let unversioned = Database::read_from(&b"T a\n"[..]).unwrap();
+        assert!(merge3(&unversioned, &base, &base).is_err());
+        assert!(merge3(&base, &base, &unversioned).is_err());
+    }
+}
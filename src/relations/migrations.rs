@@ -0,0 +1,72 @@
+/// Version of the text format written by this program.
+/// Increment it and add a migration when the element line syntax changes.
+pub const CURRENT_VERSION: u32 = 3;
+
+const HEADER_PREFIX: &'static str = "# rett database v";
+
+/// First line of written files.
+pub fn header() -> String {
+    format!("{}{}", HEADER_PREFIX, CURRENT_VERSION)
+}
+
+/// Version declared by a header line, or None if the line is not a header.
+/// Files without header are version 0.
+pub fn parse_header(line: &str) -> Result<Option<u32>, String> {
+    if !line.starts_with(HEADER_PREFIX) {
+        return Ok(None);
+    }
+    let version = line[HEADER_PREFIX.len()..]
+        .parse()
+        .map_err(|_| format!("Bad format header '{}'", line))?;
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "Format version {} is newer than supported version {}",
+            version, CURRENT_VERSION
+        ));
+    }
+    Ok(Some(version))
+}
+
+/// Upgrade of an element line (or empty slot line) from version i to i + 1, stored at position i.
+type Migration = fn(String) -> Result<String, String>;
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [from_unversioned, from_v1, from_v2];
+
+/// Files from before the header have the same element lines as version 1.
+fn from_unversioned(line: String) -> Result<String, String> {
+    Ok(line)
+}
+
+/// Version 2 adds the optional provenance part, so version 1 lines are valid.
+fn from_v1(line: String) -> Result<String, String> {
+    Ok(line)
+}
+
+/// Version 3 adds lines of deleted elements, so version 2 lines are valid.
+fn from_v2(line: String) -> Result<String, String> {
+    Ok(line)
+}
+
+/// Rewrite a line from a file of the given version to the current version.
+pub fn migrate_line(version: u32, line: String) -> Result<String, String> {
+    MIGRATIONS[version as usize..]
+        .iter()
+        .try_fold(line, |line, migration| migration(line))
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        assert_eq!(parse_header(&header()), Ok(Some(CURRENT_VERSION)));
+        assert_eq!(parse_header("# rett database v0"), Ok(Some(0)));
+        assert_eq!(parse_header("T Name"), Ok(None));
+        assert!(parse_header("# rett database vX").is_err());
+        assert!(parse_header(&format!("{}{}", HEADER_PREFIX, CURRENT_VERSION + 1)).is_err());
+        assert_eq!(migrate_line(0, "A".into()), Ok("A".into()));
+    }
+}
@@ -1,22 +1,159 @@
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::hash::Hash;
 use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{self, Deref};
+use std::slice;
+use std::sync::{Arc, Weak};
+use uuid::Uuid;
 
-use utils::{FuzzySearcher, Set, SlotVec};
+use utils::{FuzzySearcher, Map, Set, SlotVec, SlotVecIter};
+
+/// Declaration of test databases.
+#[macro_use]
+mod fixture;
+
+/// Index of text atoms not duplicating texts.
+mod text_index;
+use self::text_index::TextIndex;
+
+/// Search of words in text atoms.
+mod word_index;
+pub use self::word_index::words;
+use self::word_index::WordIndex;
+
+/// Lookup of elements by name, maintained from changes.
+mod name_index;
+pub use self::name_index::NameIndex;
 
 /// Database write/read to files.
 mod io;
-pub use self::io::{read_database_from_file, write_database_to_file};
+pub use self::io::lock_database_file;
+pub use self::io::{read_database_from_file, read_database_from_file_with_repair};
+pub use self::io::{write_database_to_file, write_database_to_file_with_backup};
+
+/// Upgrade of files written in older versions of the text format.
+mod migrations;
+
+/// Detection of degenerate structures.
+mod warnings;
+pub use self::warnings::Warning;
+
+/// Graphviz output.
+pub mod dot;
+
+/// Export to the cytoscape.js graph JSON format.
+pub mod cytoscape;
+
+/// Export to Neo4j Cypher statements.
+pub mod cypher;
+
+/// Export to JSON-LD linked data documents.
+pub mod jsonld;
+
+/// Export to N-Triples, with the linked data mapping of JSON-LD.
+pub mod ntriples;
+
+/// Export to Markdown pages, as a static snapshot of the wiki.
+pub mod markdown;
+
+/// Import of Markdown notes linked by [[wiki-links]].
+mod vault;
+pub use self::vault::{import_vault, VaultDescriptors};
+
+/// Pattern matching queries with a textual syntax.
+pub mod query;
+
+/// Relations implied by other relations.
+mod rules;
+pub use self::rules::{Inverse, Rules};
+
+/// Insertion of many elements at once.
+mod batch;
+pub use self::batch::{BatchRef, ElementSpec, Operation, DESCRIPTION_DESCRIPTOR};
+
+/// Fluent construction of elements with their relations.
+mod builder;
+pub use self::builder::ElementBuilder;
+
+/// Navigation along relations with fixed descriptors.
+mod path;
+pub use self::path::Step;
+
+/// Classification of elements in type hierarchies.
+mod ontology;
+pub use self::ontology::{IS_A_DESCRIPTOR, SUBTYPE_OF_DESCRIPTOR};
+
+/// Facts with more than two arguments, reified with chained relations.
+mod nary;
+pub use self::nary::{argument_descriptor, NaryFact, FROM_ROLE, TO_ROLE};
+
+/// Ordering and cycles of elements linked by a descriptor.
+mod dependencies;
+
+/// Detection of abstract elements representing the same thing.
+mod duplicates;
+pub use self::duplicates::DuplicateCandidate;
+
+/// Checks of structural invariants.
+mod integrity;
+pub use self::integrity::{IntegrityError, IntegrityErrorKind, Repair, RepairPolicy};
+
+/// Non text atom values.
+mod value;
+pub use self::value::{Date, Float};
+
+/// Notification of modifications.
+mod events;
+pub use self::events::{Change, Observer};
+
+/// Partition of elements in named namespaces.
+mod namespace;
+pub use self::namespace::NAMESPACE_DESCRIPTOR;
+
+/// Removal of elements that can be undone.
+mod deletion;
+
+/// Creation and modification times of elements.
+mod provenance;
+pub use self::provenance::{Provenance, Timestamp};
+
+/// User defined constraints on relations.
+pub mod schema;
+
+/// Structural comparison of elements.
+mod signature;
+pub use self::signature::Signature;
+
+/// Named copies of the database, for point in time recovery.
+mod versions;
+pub use self::versions::{list_versions, versions_directory, Version};
+
+/// Reconciliation of concurrently edited copies of a database.
+mod merge;
+pub use self::merge::{merge3, Conflict, ConflictKind};
 
 /// Error type for graph operations
 #[derive(Debug, Eq, PartialEq)]
 pub enum Error {
-    InvalidIndex,      // Index does not exists or element
-    DuplicatedElement, // Trying to insert an already existing element
-    RemoveReferenced,  // Trying to remove a referenced element
-    WouldMerge,        // An operation would force an merge of elements
+    /// Index does not exists or element has the wrong kind.
+    InvalidIndex,
+    /// Trying to insert an already existing element.
+    DuplicatedElement,
+    /// Trying to remove a referenced element.
+    RemoveReferenced,
+    /// An operation would force a merge of elements.
+    WouldMerge,
+    /// Subject already has a relation with this unique descriptor.
+    NotUnique,
+    /// Trying to remove or change a protected element.
+    Protected,
+    /// Inserted element does not follow the schema.
+    SchemaViolation,
+    /// Elements depend on themselves.
+    Cycle,
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -25,11 +162,41 @@ impl fmt::Display for Error {
             Error::DuplicatedElement => "duplicated element".fmt(f),
             Error::RemoveReferenced => "trying to remove referenced element".fmt(f),
             Error::WouldMerge => "elements would be merged with this operation".fmt(f),
+            Error::NotUnique => "subject already has a relation with this descriptor".fmt(f),
+            Error::Protected => "element is protected".fmt(f),
+            Error::SchemaViolation => "element does not follow the schema".fmt(f),
+            Error::Cycle => "elements depend on themselves".fmt(f),
         }
     }
 }
 impl std::error::Error for Error {}
 
+/// Text of the atom declaring descriptors unique per subject.
+/// A descriptor d is unique if the relation "d _unique" exists.
+pub const UNIQUE_DESCRIPTOR_TAG: &'static str = "_unique";
+
+/// Text of the atom marking elements as protected against removal and change.
+/// An element e is protected if the relation "e _protected" exists.
+/// The protecting relation and the tag atom are protected too.
+pub const PROTECTED_TAG: &'static str = "_protected";
+
+/// Approximate memory used by a database, in bytes, without allocator overhead.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryUsage {
+    /// Element slots and back-links.
+    pub elements: usize,
+    /// Texts of atoms, stored once in the elements.
+    pub texts: usize,
+    /// Lookup tables by value, text, words and uuid, and the fuzzy search index.
+    pub indexes: usize,
+}
+impl MemoryUsage {
+    /// Sum of all categories.
+    pub fn total(&self) -> usize {
+        self.elements + self.texts + self.indexes
+    }
+}
+
 /// All database elements are referenced by an index, and share the same index space.
 pub type Index = usize;
 
@@ -41,19 +208,28 @@ type RelationIndex = usize;
 pub struct Abstract;
 
 /// Atom of data that is known, self contained, indexable.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Atom {
+    /// Text, like names and descriptions.
     Text(String),
-    // TODO integers ?
-    // TODO tuple of atoms ? (for dates, etc)
+    /// Integer number.
+    Integer(i64),
+    /// Floating point number.
+    Float(Float),
+    /// Calendar date.
+    Date(Date),
+    // TODO tuple of atoms ?
 }
 
 /// Binary relation between any two elements, tagged by a third one.
 /// If the second entity is omitted, this is a simple description.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Relation {
+    /// Element described by the relation.
     pub subject: Index,
+    /// Element naming the relation, usually a text atom.
     pub descriptor: Index,
+    /// Second element of binary relations.
     pub complement: Option<Index>,
 }
 
@@ -67,23 +243,78 @@ impl From<&str> for Atom {
         Atom::Text(s.into())
     }
 }
+impl From<i64> for Atom {
+    fn from(n: i64) -> Atom {
+        Atom::Integer(n)
+    }
+}
+impl From<f64> for Atom {
+    fn from(f: f64) -> Atom {
+        Atom::Float(Float::new(f))
+    }
+}
+impl From<Date> for Atom {
+    fn from(d: Date) -> Atom {
+        Atom::Date(d)
+    }
+}
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atom::Text(s) => s.fmt(f),
+            Atom::Integer(n) => n.fmt(f),
+            Atom::Float(x) => x.fmt(f),
+            Atom::Date(d) => d.fmt(f),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
+/// Value of an element: abstract, atom or relation.
 pub enum Element {
+    /// Element without value, described by its relations.
     Abstract,
+    /// Data value.
     Atom(Atom),
+    /// Relation between other elements.
     Relation(Relation),
 }
+impl Element {
+    /// Kind of the element, without the value.
+    pub fn kind(&self) -> ElementKind {
+        match *self {
+            Element::Abstract => ElementKind::Abstract,
+            Element::Atom(_) => ElementKind::Atom,
+            Element::Relation(_) => ElementKind::Relation,
+        }
+    }
+}
+
+/// Type of element, without the value.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ElementKind {
+    /// Abstract element.
+    Abstract,
+    /// Atom.
+    Atom,
+    /// Relation.
+    Relation,
+}
+#[derive(Clone)]
 struct ElementData {
     value: Element,
+    uuid: Option<Uuid>,
+    provenance: Option<Provenance>,
     subject_of: Set<RelationIndex>,
     descriptor_of: Set<RelationIndex>,
     complement_of: Set<RelationIndex>,
 }
 impl ElementData {
-    fn new(e: Element) -> Self {
+    fn new(e: Element, uuid: Option<Uuid>) -> Self {
         Self {
             value: e,
+            uuid: uuid,
+            provenance: None,
             subject_of: Set::new(),
             descriptor_of: Set::new(),
             complement_of: Set::new(),
@@ -91,37 +322,116 @@ impl ElementData {
     }
 }
 
+/// Elements, with lookup tables to find atoms and relations by value.
 pub struct Database {
     elements: SlotVec<ElementData>,
-    index_of_text_atoms: HashMap<String, AtomIndex>,
+    index_of_text_atoms: TextIndex,
+    index_of_value_atoms: HashMap<Atom, AtomIndex>, // Non text atoms
     index_of_relations: HashMap<Relation, RelationIndex>,
+    index_of_uuids: HashMap<Uuid, Index>,
     text_atom_fuzzy_searcher: FuzzySearcher<Index>,
+    text_atom_word_index: WordIndex,
+    /// Lowercase texts of text atoms, ordered for prefix completion.
+    text_atom_lowercase: BTreeSet<(String, Index)>,
+    protection_override: bool,
+    observers: Vec<Observer>,
+    /// Number of changes since creation or loading.
+    revision: u64,
+    /// Recorded in the provenance of created elements.
+    author: Option<String>,
+    /// Elements removed with delete_element, whose slots are kept reserved.
+    deleted: Map<Index, ElementData>,
+}
+
+/// Copies are independent databases: observers of the original are not notified of their changes.
+impl Clone for Database {
+    fn clone(&self) -> Self {
+        Database {
+            elements: self.elements.clone(),
+            index_of_text_atoms: self.index_of_text_atoms.clone(),
+            index_of_value_atoms: self.index_of_value_atoms.clone(),
+            index_of_relations: self.index_of_relations.clone(),
+            index_of_uuids: self.index_of_uuids.clone(),
+            text_atom_fuzzy_searcher: self.text_atom_fuzzy_searcher.clone(),
+            text_atom_word_index: self.text_atom_word_index.clone(),
+            text_atom_lowercase: self.text_atom_lowercase.clone(),
+            protection_override: self.protection_override,
+            observers: Vec::new(),
+            revision: self.revision,
+            author: self.author.clone(),
+            deleted: self.deleted.clone(),
+        }
+    }
+}
+
+/// `database[i]` is the value of the element, like `database.element(i).unwrap().value()`.
+/// Panics if the index is not a valid element.
+impl ops::Index<Index> for Database {
+    type Output = Element;
+    fn index(&self, i: Index) -> &Element {
+        &self.elements[i].value
+    }
 }
 
 impl Database {
+    /// Empty database.
     pub fn new() -> Database {
         Database {
             elements: SlotVec::new(),
-            index_of_text_atoms: HashMap::new(),
+            index_of_text_atoms: TextIndex::default(),
+            index_of_value_atoms: HashMap::new(),
             index_of_relations: HashMap::new(),
+            index_of_uuids: HashMap::new(),
             text_atom_fuzzy_searcher: FuzzySearcher::new(),
+            text_atom_word_index: WordIndex::default(),
+            text_atom_lowercase: BTreeSet::new(),
+            protection_override: false,
+            observers: Vec::new(),
+            revision: 0,
+            author: None,
+            deleted: Map::new(),
         }
     }
 
     /// Add a new abstract element.
     pub fn create_abstract_element(&mut self) -> Index {
-        self.elements.insert(ElementData::new(Element::Abstract))
+        let index = self.insert_element_data(Element::Abstract);
+        self.notify(Change::Inserted(index));
+        index
+    }
+    /// Store a new element with a fresh uuid. Type specific tables are not updated.
+    fn insert_element_data(&mut self, e: Element) -> Index {
+        let uuid = Uuid::new_v4();
+        let data = ElementData {
+            provenance: Some(self.new_provenance()),
+            ..ElementData::new(e, Some(uuid))
+        };
+        let index = self.elements.insert(data);
+        self.register_uuid(index, uuid).unwrap(); // Fresh v4 uuids do not collide
+        index
+    }
+    /// Add the uuid of a newly inserted element to tables. No-op on error.
+    fn register_uuid(&mut self, index: Index, uuid: Uuid) -> Result<(), Error> {
+        if self.index_of_uuids.contains_key(&uuid) {
+            return Err(Error::DuplicatedElement);
+        }
+        self.index_of_uuids.insert(uuid, index);
+        Ok(())
     }
 
     /// Add an atom, or return index if already present.
     pub fn insert_atom(&mut self, atom: Atom) -> Index {
+        self.insert_atom_if_new(atom).0
+    }
+    /// Like insert_atom, also telling if the atom was created.
+    fn insert_atom_if_new(&mut self, atom: Atom) -> (Index, bool) {
         match self.index_of_atom(&atom) {
-            Some(index) => index,
+            Some(index) => (index, false),
             None => {
-                let data = ElementData::new(Element::Atom(atom.clone()));
-                let index = self.elements.insert(data);
+                let index = self.insert_element_data(Element::Atom(atom.clone()));
                 self.register_atom(index, atom).unwrap();
-                index
+                self.notify(Change::Inserted(index));
+                (index, true)
             }
         }
     }
@@ -129,13 +439,19 @@ impl Database {
     fn register_atom(&mut self, index: Index, atom: Atom) -> Result<(), Error> {
         match atom {
             Atom::Text(s) => {
-                let insert = self.index_of_text_atoms.insert(s.clone(), index);
-                if insert.is_some() {
+                if self.index_of_text_atom(s.as_str()).is_some() {
                     return Err(Error::DuplicatedElement);
                 }
+                self.index_of_text_atoms.insert(&s, index);
                 self.text_atom_fuzzy_searcher.insert(&s, index);
+                self.text_atom_word_index.insert(&s, index);
+                self.text_atom_lowercase.insert((s.to_lowercase(), index));
                 Ok(())
             }
+            atom => match self.index_of_value_atoms.insert(atom, index) {
+                Some(_) => Err(Error::DuplicatedElement),
+                None => Ok(()),
+            },
         }
     }
     /// Removes an existing atom from tables. Panics if atom does not exist.
@@ -143,22 +459,42 @@ impl Database {
         match atom {
             Atom::Text(s) => {
                 self.text_atom_fuzzy_searcher.remove(s, &index);
-                self.index_of_text_atoms.remove(s).unwrap(); // Must be filled
+                self.text_atom_word_index.remove(s, index);
+                self.text_atom_lowercase.remove(&(s.to_lowercase(), index));
+                assert!(self.index_of_text_atoms.remove(s, index)); // Must be filled
+            }
+            atom => {
+                self.index_of_value_atoms.remove(atom).unwrap();
             }
         }
     }
 
     /// Add a relation, or return index if already present.
+    /// Fails if the descriptor is unique and already used by the subject.
     pub fn insert_relation(&mut self, relation: Relation) -> Result<Index, Error> {
+        self.insert_relation_if_new(relation).map(|(index, _)| index)
+    }
+    /// Like insert_relation, also telling if the relation was created.
+    fn insert_relation_if_new(&mut self, relation: Relation) -> Result<(Index, bool), Error> {
         match self.index_of_relation(&relation) {
-            Some(index) => Ok(index),
+            Some(index) => Ok((index, false)),
             None => {
-                let data = ElementData::new(Element::Relation(relation.clone()));
-                let index = self.elements.insert(data);
+                if self.is_unique_descriptor(relation.descriptor)
+                    && self.subject_has_descriptor(relation.subject, relation.descriptor)
+                {
+                    return Err(Error::NotUnique);
+                }
+                let index = self.insert_element_data(Element::Relation(relation.clone()));
                 match self.register_relation(index, relation) {
-                    Ok(()) => Ok(index),
+                    Ok(()) => {
+                        self.notify(Change::Inserted(index));
+                        Ok((index, true))
+                    }
                     Err(e) => {
-                        self.elements.remove(index); // Revert insertion.
+                        // Revert insertion.
+                        if let Some(uuid) = self.elements.remove(index).unwrap().uuid {
+                            self.index_of_uuids.remove(&uuid);
+                        }
                         Err(e)
                     }
                 }
@@ -185,6 +521,7 @@ impl Database {
     }
     /// Removes a relation from tables. Panics if relation does not exist.
     fn unregister_relation(&mut self, index: Index, rel: &Relation) {
+        self.index_of_relations.remove(rel).unwrap();
         self.elements[rel.subject]
             .subject_of
             .remove(&index)
@@ -201,6 +538,70 @@ impl Database {
         }
     }
 
+    /// Test if the descriptor is declared unique per subject, see UNIQUE_DESCRIPTOR_TAG.
+    pub fn is_unique_descriptor(&self, descriptor: Index) -> bool {
+        match self.index_of_text_atom(UNIQUE_DESCRIPTOR_TAG) {
+            Some(unique) => self
+                .index_of_relation(&Relation {
+                    subject: descriptor,
+                    descriptor: unique,
+                    complement: None,
+                })
+                .is_some(),
+            None => false,
+        }
+    }
+    fn subject_has_descriptor(&self, subject: Index, descriptor: Index) -> bool {
+        match self.elements.get(subject) {
+            Some(data) => data.subject_of.as_ref().iter().any(|&r| {
+                match self.elements[r].value {
+                    Element::Relation(ref relation) => relation.descriptor == descriptor,
+                    _ => false,
+                }
+            }),
+            None => false,
+        }
+    }
+
+    /// Test if an element is protected, see PROTECTED_TAG.
+    pub fn is_protected(&self, index: Index) -> bool {
+        match self.index_of_text_atom(PROTECTED_TAG) {
+            Some(protected) => {
+                let is_tagged = self
+                    .index_of_relation(&Relation {
+                        subject: index,
+                        descriptor: protected,
+                        complement: None,
+                    })
+                    .is_some();
+                let is_tagging = match self.elements.get(index).map(|data| &data.value) {
+                    Some(Element::Relation(r)) => r.descriptor == protected && r.complement.is_none(),
+                    _ => false,
+                };
+                index == protected || is_tagged || is_tagging
+            }
+            None => false,
+        }
+    }
+    fn check_not_protected(&self, index: Index) -> Result<(), Error> {
+        if !self.protection_override && self.is_protected(index) {
+            Err(Error::Protected)
+        } else {
+            Ok(())
+        }
+    }
+    /// Run operations on the database ignoring protections.
+    /// This is an explicit administrator action.
+    pub fn with_protection_override<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Database) -> R,
+    {
+        self.protection_override = true;
+        let r = f(self);
+        self.protection_override = false;
+        r
+    }
+
     /// Access element by index.
     pub fn element(&self, i: Index) -> Result<Ref<Element>, Error> {
         if self.elements.valid(i) {
@@ -211,21 +612,34 @@ impl Database {
     }
 
     // Retrieve index of indexable entities.
+    /// Index of the atom with this value.
     pub fn index_of_atom(&self, atom: &Atom) -> Option<Index> {
         match atom {
             Atom::Text(s) => self.index_of_text_atom(s),
+            atom => self.index_of_value_atoms.get(atom).cloned(),
         }
     }
+    /// Index of the text atom with this text, without building an atom.
     pub fn index_of_text_atom<Q>(&self, text: &Q) -> Option<Index>
     where
         String: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        self.index_of_text_atoms.get(text).cloned()
+        self.index_of_text_atoms.get(text, |index| {
+            match self.elements.get(index).map(|data| &data.value) {
+                Some(Element::Atom(Atom::Text(s))) => Some(s),
+                _ => None,
+            }
+        })
     }
+    /// Index of the relation with these components.
     pub fn index_of_relation(&self, relation: &Relation) -> Option<Index> {
         self.index_of_relations.get(relation).cloned()
     }
+    /// Uuids are stable across removals and databases, unlike indexes.
+    pub fn index_of_uuid(&self, uuid: &Uuid) -> Option<Index> {
+        self.index_of_uuids.get(uuid).cloned()
+    }
 
     /// Get atom ref if it exists, by name.
     pub fn get_text_atom<'a, Q>(&'a self, text: &Q) -> Option<Ref<'a, Atom>>
@@ -237,10 +651,86 @@ impl Database {
             .map(|index| Ref::new(self, index))
     }
 
+    /// Frozen copy of the current state, that can be shared between threads.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            database: Arc::new(self.clone()),
+        }
+    }
+
     /// Iterate on all elements.
     pub fn iter<'a>(&'a self) -> ElementIterator<'a> {
         ElementIterator::new(self)
     }
+    /// Iterate on all relations.
+    pub fn relations<'a>(&'a self) -> impl Iterator<Item = Ref<'a, Relation>> + 'a {
+        self.iter().filter_map(|e| match e.cases() {
+            ElementRef::Relation(r) => Some(r),
+            _ => None,
+        })
+    }
+    /// Relations using the element as subject, from back-links.
+    pub fn relations_with_subject<'a>(
+        &'a self,
+        subject: Index,
+    ) -> Result<impl Iterator<Item = Ref<'a, Relation>>, Error> {
+        Ok(self.element(subject)?.subject_of().iter())
+    }
+    /// Relations using the element as descriptor, from back-links.
+    pub fn relations_with_descriptor<'a>(
+        &'a self,
+        descriptor: Index,
+    ) -> Result<impl Iterator<Item = Ref<'a, Relation>>, Error> {
+        Ok(self.element(descriptor)?.descriptor_of().iter())
+    }
+    /// Relations using the element as complement, from back-links.
+    pub fn relations_with_complement<'a>(
+        &'a self,
+        complement: Index,
+    ) -> Result<impl Iterator<Item = Ref<'a, Relation>>, Error> {
+        Ok(self.element(complement)?.complement_of().iter())
+    }
+
+    /// Approximate memory used by the database.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        fn table<K, V>(map: &HashMap<K, V>) -> usize
+        where
+            K: Eq + Hash,
+        {
+            map.capacity() * (size_of::<K>() + size_of::<V>())
+        }
+        let back_links = |data: &ElementData| {
+            let sets = [&data.subject_of, &data.descriptor_of, &data.complement_of];
+            sets.iter().map(|set| set.len() * size_of::<Index>()).sum::<usize>()
+        };
+        let elements = self.elements.capacity() * size_of::<Option<ElementData>>()
+            + self.elements.iter().map(|(_, data)| back_links(data)).sum::<usize>();
+        let texts = self
+            .elements
+            .iter()
+            .map(|(_, data)| match data.value {
+                Element::Atom(Atom::Text(ref s)) => s.capacity(),
+                _ => 0,
+            })
+            .sum();
+        let lowercase_texts = self
+            .text_atom_lowercase
+            .iter()
+            .map(|&(ref text, _)| size_of::<(String, Index)>() + text.capacity())
+            .sum::<usize>();
+        let indexes = self.index_of_text_atoms.memory_usage()
+            + self.text_atom_word_index.memory_usage()
+            + self.text_atom_fuzzy_searcher.memory_usage()
+            + lowercase_texts
+            + table(&self.index_of_value_atoms)
+            + table(&self.index_of_relations)
+            + table(&self.index_of_uuids);
+        MemoryUsage {
+            elements,
+            texts,
+            indexes,
+        }
+    }
 
     /// Perform a fuzzy search for text atoms.
     pub fn text_atom_fuzzy_matches<'a>(&'a self, pattern: &str) -> TextAtomFuzzyMatches<'a> {
@@ -250,18 +740,115 @@ impl Database {
         }
     }
 
+    /// Descriptions containing all words of the query, by increasing index.
+    /// Descriptions are text atoms used as complement with the description descriptor.
+    pub fn find_in_descriptions<'a>(&'a self, query: &str) -> Vec<Ref<'a, Atom>> {
+        let descriptor = match self.index_of_text_atom(DESCRIPTION_DESCRIPTOR) {
+            Some(descriptor) => descriptor,
+            None => return Vec::new(),
+        };
+        let is_description = |atom: &Ref<'a, Atom>| {
+            atom.complement_of()
+                .iter()
+                .any(|r| r.value().descriptor == descriptor)
+        };
+        let indexes = self.text_atom_word_index.find(query);
+        indexes
+            .into_iter()
+            .map(|index| Ref::new(self, index))
+            .filter(is_description)
+            .collect()
+    }
+
+    /// Text atoms starting with the prefix, ignoring case, in order of lowercase text.
+    /// Texts extending another match come after it. Only matching atoms are visited.
+    pub fn text_atoms_with_prefix<'a>(
+        &'a self,
+        prefix: &str,
+    ) -> impl Iterator<Item = Ref<'a, Atom>> + 'a {
+        let prefix = prefix.to_lowercase();
+        self.text_atom_lowercase
+            .range((prefix.clone(), 0)..)
+            .take_while(move |&&(ref text, _)| text.starts_with(prefix.as_str()))
+            .map(move |&(_, index)| Ref::new(self, index))
+    }
+
     /// Remove an existing unreference element. Return the element value.
     pub fn remove_element(&mut self, index: Index) -> Result<Element, Error> {
         if self.element(index)?.is_referenced() {
             return Err(Error::RemoveReferenced);
         }
+        self.check_not_protected(index)?;
         let element_data = self.elements.remove(index).unwrap();
+        self.unregister_element_data(index, &element_data);
+        self.notify(Change::Removed(index));
+        Ok(element_data.value)
+    }
+    /// Remove an element and all relations referencing it, recursively.
+    /// Nothing is removed if one of them is protected. Return indexes in removal order.
+    pub fn remove_element_and_relations(&mut self, index: Index) -> Result<Vec<Index>, Error> {
+        let removal_order = {
+            // Post-order traversal: referencing relations are removed before their components.
+            let mut visited = HashSet::new();
+            let mut order = Vec::new();
+            let mut stack = vec![(self.element(index)?, false)];
+            while let Some((element, components_done)) = stack.pop() {
+                if components_done {
+                    order.push(element.index());
+                } else if visited.insert(element.index()) {
+                    stack.push((element.clone(), true));
+                    let referencing = element
+                        .subject_of()
+                        .iter()
+                        .chain(element.descriptor_of().iter())
+                        .chain(element.complement_of().iter());
+                    stack.extend(referencing.map(|r| (self.element(r.index()).unwrap(), false)))
+                }
+            }
+            order
+        };
+        for &i in removal_order.iter() {
+            self.check_not_protected(i)?
+        }
+        for &i in removal_order.iter() {
+            self.remove_element(i).unwrap();
+        }
+        Ok(removal_order)
+    }
+    /// Remove a removed element from tables.
+    fn unregister_element_data(&mut self, index: Index, element_data: &ElementData) {
+        if let Some(uuid) = element_data.uuid {
+            self.index_of_uuids.remove(&uuid);
+        }
         match &element_data.value {
             Element::Abstract => (),
             Element::Atom(a) => self.unregister_atom(index, a),
             Element::Relation(r) => self.unregister_relation(index, r),
         }
-        Ok(element_data.value)
+    }
+
+    /// Atoms and abstracts not used by any relation, usually left by aborted edits.
+    pub fn orphans(&self) -> Vec<Index> {
+        self.iter()
+            .filter(|e| match e.value() {
+                Element::Relation(_) => false,
+                _ => !e.is_referenced(),
+            })
+            .map(|e| e.index())
+            .collect()
+    }
+
+    /// Remove all orphans that are not protected. Return their indexes.
+    pub fn remove_orphans(&mut self) -> Vec<Index> {
+        let removable: Vec<Index> = self
+            .orphans()
+            .into_iter()
+            .filter(|&i| self.check_not_protected(i).is_ok())
+            .collect();
+        for &index in removable.iter() {
+            self.remove_element(index).unwrap();
+        }
+        removable
     }
 
     /// Replace the value of an existing atom with another. Relations are preserved.
@@ -270,6 +857,7 @@ impl Database {
         if self.index_of_atom(&new_atom).is_some() {
             return Err(Error::WouldMerge);
         }
+        self.check_not_protected(index)?;
         let old_atom = match &mut self
             .elements
             .get_mut(index)
@@ -281,11 +869,14 @@ impl Database {
         };
         self.unregister_atom(index, &old_atom);
         self.register_atom(index, new_atom).unwrap();
+        self.touch(index);
+        self.notify(Change::Modified(index));
         Ok(())
     }
 
     /// Replace an atom by an abstract. Relations are preserved.
     pub fn replace_atom_with_abstract(&mut self, index: Index) -> Result<Atom, Error> {
+        self.check_not_protected(index)?;
         let element_value = &mut self
             .elements
             .get_mut(index)
@@ -299,6 +890,8 @@ impl Database {
         match old_value {
             Element::Atom(a) => {
                 self.unregister_atom(index, &a);
+                self.touch(index);
+                self.notify(Change::Modified(index));
                 Ok(a)
             }
             _ => unreachable!(),
@@ -306,6 +899,37 @@ impl Database {
     }
 }
 
+/// Immutable database state, cheap to clone.
+/// Accessing the database is done through Deref.
+#[derive(Clone)]
+pub struct Snapshot {
+    database: Arc<Database>,
+}
+impl Snapshot {
+    /// Reference that does not keep the copy alive.
+    pub fn downgrade(&self) -> WeakSnapshot {
+        WeakSnapshot {
+            database: Arc::downgrade(&self.database),
+        }
+    }
+}
+impl Deref for Snapshot {
+    type Target = Database;
+    fn deref(&self) -> &Database {
+        &self.database
+    }
+}
+/// Snapshot which may have been dropped, to share it while it is still used.
+pub struct WeakSnapshot {
+    database: Weak<Database>,
+}
+impl WeakSnapshot {
+    /// Snapshot, if it is still used elsewhere.
+    pub fn upgrade(&self) -> Option<Snapshot> {
+        self.database.upgrade().map(|database| Snapshot { database })
+    }
+}
+
 /// A Ref<'a, E> is a valid index into the database to an "element of type E".
 /// If E is Atom/Object/Relation, this is a ref to the specific variant.
 /// If E is Element, this is a ref to any type (but still valid index).
@@ -322,21 +946,50 @@ impl<'a, E> Ref<'a, E> {
             marker: PhantomData,
         }
     }
-    pub fn database(&self) -> &Database {
+    /// Database containing the element.
+    pub fn database(&self) -> &'a Database {
         self.database
     }
+    /// Index of the element.
     pub fn index(&self) -> Index {
         self.index
     }
+    /// Stable identifier. Elements from old database files may not have one.
+    pub fn uuid(&self) -> Option<Uuid> {
+        self.data().uuid
+    }
+    /// Creation and modification times of the element, if recorded.
+    pub fn provenance(&self) -> Option<&'a Provenance> {
+        self.data().provenance.as_ref()
+    }
+    /// Relations with this element as subject.
     pub fn subject_of(&self) -> RelationRefSet<'a> {
         RelationRefSet::new(self.database, &self.data().subject_of)
     }
+    /// Relations with this element as descriptor.
     pub fn descriptor_of(&self) -> RelationRefSet<'a> {
         RelationRefSet::new(self.database, &self.data().descriptor_of)
     }
+    /// Relations with this element as complement.
     pub fn complement_of(&self) -> RelationRefSet<'a> {
         RelationRefSet::new(self.database, &self.data().complement_of)
     }
+    /// Relations with this element as subject, restricted to those matching the predicate.
+    pub fn subject_of_where<F>(&self, predicate: F) -> impl Iterator<Item = Ref<'a, Relation>>
+    where
+        F: Fn(&Ref<'a, Relation>) -> bool,
+    {
+        self.subject_of().iter().filter(move |r| predicate(r))
+    }
+    /// Relations with this element as subject and the atom as descriptor, like names.
+    pub fn subject_of_with_descriptor(
+        &self,
+        descriptor: &Atom,
+    ) -> impl Iterator<Item = Ref<'a, Relation>> {
+        let descriptor = self.database.index_of_atom(descriptor);
+        self.subject_of_where(move |r| Some(r.value().descriptor) == descriptor)
+    }
+    /// True if the element is a component of a relation, so it cannot be removed.
     pub fn is_referenced(&self) -> bool {
         let data = self.data();
         data.subject_of.as_ref().len() > 0
@@ -355,9 +1008,11 @@ impl<'a, E> Clone for Ref<'a, E> {
 }
 impl<'a, E> Copy for Ref<'a, E> {}
 impl<'a> Ref<'a, Element> {
-    pub fn value(&self) -> &Element {
+    /// Value of the element.
+    pub fn value(&self) -> &'a Element {
         &self.data().value
     }
+    /// Ref to the specific kind of element.
     pub fn cases(&self) -> ElementRef<'a> {
         match self.data().value {
             Element::Abstract => ElementRef::Abstract(Ref::new(self.database, self.index)),
@@ -367,7 +1022,8 @@ impl<'a> Ref<'a, Element> {
     }
 }
 impl<'a> Ref<'a, Atom> {
-    pub fn value(&self) -> &Atom {
+    /// Value of the atom.
+    pub fn value(&self) -> &'a Atom {
         match self.data().value {
             Element::Atom(ref atom) => atom,
             _ => panic!("Ref<Atom> must be an atom"),
@@ -375,18 +1031,22 @@ impl<'a> Ref<'a, Atom> {
     }
 }
 impl<'a> Ref<'a, Relation> {
-    pub fn value(&self) -> &Relation {
+    /// Components of the relation.
+    pub fn value(&self) -> &'a Relation {
         match self.data().value {
             Element::Relation(ref rel) => rel,
             _ => panic!("Ref<Relation> must be a relation"),
         }
     }
+    /// Subject of the relation.
     pub fn subject(&self) -> Ref<'a, Element> {
         Ref::new(self.database, self.value().subject)
     }
+    /// Descriptor of the relation.
     pub fn descriptor(&self) -> Ref<'a, Element> {
         Ref::new(self.database, self.value().descriptor)
     }
+    /// Complement of the relation, if any.
     pub fn complement(&self) -> Option<Ref<'a, Element>> {
         self.value().complement.map(|i| Ref::new(self.database, i))
     }
@@ -405,58 +1065,96 @@ impl<'a> RelationRefSet<'a> {
             set: set,
         }
     }
+    /// Number of relations.
     pub fn len(&self) -> usize {
         self.set.as_ref().len()
     }
-    pub fn get(&self, i: usize) -> Ref<'a, Relation> {
-        Ref::new(self.database, self.set.as_ref()[i])
+    /// True if there are no relations.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
     }
-    pub fn iter(&self) -> impl Iterator<Item = Ref<'a, Relation>> {
-        let database = self.database; // Explicitely clone ref
-        self.set
-            .as_ref()
-            .iter()
-            .map(move |i| Ref::new(database, *i))
+    /// Relation at position i, relations being sorted by index.
+    pub fn get(&self, i: usize) -> Option<Ref<'a, Relation>> {
+        let database = self.database;
+        self.set.as_ref().get(i).map(|&index| Ref::new(database, index))
+    }
+    /// Iterate on relations, by increasing index.
+    pub fn iter(&self) -> RelationRefIter<'a> {
+        RelationRefIter {
+            database: self.database,
+            indexes: self.set.as_ref().iter(),
+        }
+    }
+}
+impl<'a> IntoIterator for RelationRefSet<'a> {
+    type Item = Ref<'a, Relation>;
+    type IntoIter = RelationRefIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
+impl<'a, 's> IntoIterator for &'s RelationRefSet<'a> {
+    type Item = Ref<'a, Relation>;
+    type IntoIter = RelationRefIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator on a RelationRefSet, by increasing index.
+pub struct RelationRefIter<'a> {
+    database: &'a Database,
+    indexes: slice::Iter<'a, Index>,
+}
+impl<'a> Iterator for RelationRefIter<'a> {
+    type Item = Ref<'a, Relation>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let database = self.database;
+        self.indexes.next().map(|&index| Ref::new(database, index))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indexes.size_hint()
+    }
+}
+impl<'a> DoubleEndedIterator for RelationRefIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let database = self.database;
+        self.indexes.next_back().map(|&index| Ref::new(database, index))
+    }
+}
+impl<'a> ExactSizeIterator for RelationRefIter<'a> {}
 
 /// Enum of ref structs, to perform exploration.
 pub enum ElementRef<'a> {
+    /// Abstract element.
     Abstract(Ref<'a, Abstract>),
+    /// Atom.
     Atom(Ref<'a, Atom>),
+    /// Relation.
     Relation(Ref<'a, Relation>),
 }
 
 /// Iterator on elements in the database, by increasing ids.
 pub struct ElementIterator<'a> {
     database: &'a Database,
-    index: Index,
+    elements: SlotVecIter<'a, ElementData>,
 }
 impl<'a> ElementIterator<'a> {
     fn new(database: &'a Database) -> Self {
         ElementIterator {
             database: database,
-            index: 0,
+            elements: database.elements.iter(),
         }
     }
 }
 impl<'a> Iterator for ElementIterator<'a> {
     type Item = Ref<'a, Element>;
     fn next(&mut self) -> Option<Self::Item> {
-        let end_index = self.database.elements.capacity();
-        loop {
-            if self.index == end_index {
-                return None;
-            }
-            let current_index = self.index;
-            self.index += 1;
-            if self.database.elements.valid(current_index) {
-                return Some(Ref::new(self.database, current_index));
-            }
-        }
+        let database = self.database;
+        self.elements.next().map(|(i, _)| Ref::new(database, i))
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(self.database.elements.capacity() - self.index))
+        self.elements.size_hint()
     }
 }
 
@@ -493,6 +1191,258 @@ mod tests {
         let _copy3 = copy;
     }
 
+    #[test]
+    fn index_operator() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        let joe_i = db.insert_atom(Atom::from("Joe"));
+        assert_eq!(db[object_i].kind(), ElementKind::Abstract);
+        match db[joe_i] {
+            Element::Atom(Atom::Text(ref text)) => assert_eq!(text, "Joe"),
+            _ => panic!("expected the Joe text atom"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_operator_invalid() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        db.remove_element(object_i).unwrap();
+        let _ = &db[object_i];
+    }
+
+    #[test]
+    fn unique_descriptor() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        let born_i = db.insert_atom(Atom::from("born in"));
+        let first_i = db.insert_atom(Atom::from("1900"));
+        let second_i = db.insert_atom(Atom::from("1901"));
+        let born = |complement| Relation {
+            subject: object_i,
+            descriptor: born_i,
+            complement: Some(complement),
+        };
+        let first_relation_i = db.insert_relation(born(first_i)).unwrap();
+        assert!(!db.is_unique_descriptor(born_i));
+
+        let unique_i = db.insert_atom(Atom::from(UNIQUE_DESCRIPTOR_TAG));
+        db.insert_relation(Relation {
+            subject: born_i,
+            descriptor: unique_i,
+            complement: None,
+        })
+        .unwrap();
+        assert!(db.is_unique_descriptor(born_i));
+        assert_eq!(db.insert_relation(born(first_i)), Ok(first_relation_i));
+        assert_eq!(db.insert_relation(born(second_i)), Err(Error::NotUnique));
+        // Failed insertion leaves no trace
+        assert_eq!(db.index_of_relation(&born(second_i)), None);
+        assert_eq!(db.element(second_i).unwrap().complement_of().len(), 0);
+    }
+
+    #[test]
+    fn protection() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let protected_i = db.insert_atom(Atom::from(PROTECTED_TAG));
+        let tag_i = db
+            .insert_relation(Relation {
+                subject: name_i,
+                descriptor: protected_i,
+                complement: None,
+            })
+            .unwrap();
+        assert!(db.is_protected(name_i));
+        assert!(db.is_protected(protected_i));
+        assert!(db.is_protected(tag_i));
+        assert_eq!(db.remove_element(tag_i).err(), Some(Error::Protected));
+        assert_eq!(
+            db.replace_atom_value(name_i, Atom::from("Other")),
+            Err(Error::Protected)
+        );
+        assert_eq!(
+            db.replace_atom_with_abstract(name_i).err(),
+            Some(Error::Protected)
+        );
+
+        // Remove protection explicitly
+        db.with_protection_override(|db| db.remove_element(tag_i))
+            .unwrap();
+        assert!(!db.is_protected(name_i));
+        assert_eq!(db.replace_atom_value(name_i, Atom::from("Other")), Ok(()));
+    }
+
+    #[test]
+    fn snapshot() {
+        fn shareable<T: Send + Sync>(_: &T) {}
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let snapshot = db.snapshot();
+        shareable(&snapshot);
+
+        // Snapshot does not see later changes
+        db.remove_element(name_i).unwrap();
+        let other_i = db.insert_atom(Atom::from("Other"));
+        assert_eq!(other_i, name_i);
+        assert_eq!(snapshot.index_of_text_atom("Name"), Some(name_i));
+        assert_eq!(snapshot.index_of_text_atom("Other"), None);
+        let copy = snapshot.clone();
+        assert_eq!(copy.iter().count(), 1);
+
+        let weak = snapshot.downgrade();
+        assert!(weak.upgrade().is_some());
+        drop((snapshot, copy));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn uuids() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        let atom_i = db.insert_atom(Atom::from("Name"));
+        let object_uuid = db.element(object_i).unwrap().uuid().unwrap();
+        let atom_uuid = db.element(atom_i).unwrap().uuid().unwrap();
+        assert_ne!(object_uuid, atom_uuid);
+        assert_eq!(Some(object_i), db.index_of_uuid(&object_uuid));
+        assert_eq!(Some(atom_i), db.index_of_uuid(&atom_uuid));
+
+        // Uuid is not reused with the slot
+        db.remove_element(object_i).unwrap();
+        assert_eq!(None, db.index_of_uuid(&object_uuid));
+        let new_object_i = db.create_abstract_element();
+        assert_eq!(object_i, new_object_i);
+        assert_ne!(Some(object_uuid), db.element(new_object_i).unwrap().uuid());
+    }
+
+    #[test]
+    fn relation_iterators() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let is_named_i = db.insert_atom(Atom::from("is named"));
+        let relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: is_named_i,
+                complement: Some(name_i),
+            })
+            .unwrap();
+        let meta_i = db
+            .insert_relation(Relation {
+                subject: relation_i,
+                descriptor: name_i,
+                complement: None,
+            })
+            .unwrap();
+
+        fn indexes<'a, I: Iterator<Item = Ref<'a, Relation>>>(it: I) -> Vec<Index> {
+            it.map(|r| r.index()).collect()
+        }
+        assert_eq!(indexes(db.relations()), vec![relation_i, meta_i]);
+        let with_subject = |i| indexes(db.relations_with_subject(i).unwrap());
+        assert_eq!(with_subject(object_i), vec![relation_i]);
+        assert_eq!(with_subject(relation_i), vec![meta_i]);
+        let with_descriptor = indexes(db.relations_with_descriptor(name_i).unwrap());
+        assert_eq!(with_descriptor, vec![meta_i]);
+        let with_complement = indexes(db.relations_with_complement(name_i).unwrap());
+        assert_eq!(with_complement, vec![relation_i]);
+        assert!(indexes(db.relations_with_complement(object_i).unwrap()).is_empty());
+        assert!(db.relations_with_subject(42).is_err());
+
+        let other_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: name_i,
+                complement: None,
+            })
+            .unwrap();
+        let object = db.element(object_i).unwrap();
+        let subject_of = object.subject_of();
+        assert_eq!(subject_of.iter().len(), 2);
+        assert_eq!(indexes(subject_of.iter().rev()), vec![other_i, relation_i]);
+        assert_eq!(subject_of.get(1).map(|r| r.index()), Some(other_i));
+        assert!(subject_of.get(2).is_none());
+        assert_eq!(indexes((&subject_of).into_iter()), vec![relation_i, other_i]);
+        assert!(object.complement_of().is_empty());
+
+        let named = indexes(object.subject_of_with_descriptor(&Atom::from("Name")));
+        assert_eq!(named, vec![other_i]);
+        let unknown = indexes(object.subject_of_with_descriptor(&Atom::from("Unknown")));
+        assert!(unknown.is_empty());
+        let with_complement = indexes(object.subject_of_where(|r| r.value().complement.is_some()));
+        assert_eq!(with_complement, vec![relation_i]);
+    }
+
+    #[test]
+    fn orphans() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let _relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: name_i,
+                complement: None,
+            })
+            .unwrap();
+        let orphan_atom_i = db.insert_atom(Atom::from("Orphan"));
+        let orphan_abstract_i = db.create_abstract_element();
+        let protected_i = db.insert_atom(Atom::from(PROTECTED_TAG));
+        assert_eq!(db.orphans(), vec![orphan_atom_i, orphan_abstract_i, protected_i]);
+        assert_eq!(db.remove_orphans(), vec![orphan_atom_i, orphan_abstract_i]);
+        assert_eq!(db.orphans(), vec![protected_i]);
+        assert!(db.element(name_i).is_ok());
+        assert!(db.element(orphan_atom_i).is_err());
+    }
+
+    #[test]
+    fn remove_element_and_relations() {
+        let mut db = Database::new();
+        let name_i = db.insert_atom(Atom::from("Name"));
+        let object_i = db.create_abstract_element();
+        let other_i = db.create_abstract_element();
+        let relate = |db: &mut Database, subject, descriptor, complement| {
+            db.insert_relation(Relation {
+                subject,
+                descriptor,
+                complement,
+            })
+            .unwrap()
+        };
+        let named_i = relate(&mut db, object_i, name_i, None);
+        let link_i = relate(&mut db, other_i, named_i, Some(object_i));
+        let kept_i = relate(&mut db, other_i, name_i, None);
+
+        assert_eq!(db.remove_element_and_relations(object_i), Ok(vec![link_i, named_i, object_i]));
+        assert!(db.element(named_i).is_err());
+        assert!(db.element(kept_i).is_ok());
+        assert!(db.remove_element_and_relations(object_i).is_err());
+
+        let protected_i = db.insert_atom(Atom::from(PROTECTED_TAG));
+        relate(&mut db, kept_i, protected_i, None);
+        assert_eq!(db.remove_element_and_relations(name_i), Err(Error::Protected));
+        assert!(db.element(name_i).is_ok());
+    }
+
+    #[test]
+    fn text_atoms_with_prefix() {
+        let mut db = Database::new();
+        let long_i = db.insert_atom(Atom::from("Élan vital"));
+        let short_i = db.insert_atom(Atom::from("élan"));
+        let _other_i = db.insert_atom(Atom::from("Elan"));
+        let _number_i = db.insert_atom(Atom::from(3i64));
+        let found = |prefix| -> Vec<Index> {
+            let atoms = db.text_atoms_with_prefix(prefix);
+            atoms.map(|a| a.index()).collect()
+        };
+        assert_eq!(found("ÉLA"), vec![short_i, long_i]);
+        assert_eq!(found("élan v"), vec![long_i]);
+        assert_eq!(found("x"), Vec::<Index>::new());
+        assert_eq!(found("").len(), 3);
+    }
+
     #[test]
     fn basic() {
         // Create a very small database
@@ -551,7 +1501,8 @@ mod tests {
         };
         assert_eq!(r_name.value(), &Atom::from("Name"));
 
-        let complement = r_name.complement_of().get(0);
+        let complement = r_name.complement_of().get(0).unwrap();
         assert_eq!(complement.index(), relation_i);
+        assert!(r_name.complement_of().get(1).is_none());
     }
 }
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::{Atom, Change, Database, Element, Index};
+
+/// Elements by the text of their names, a name being the text atom complement of a relation
+/// with the naming descriptor. Updated on each change of the database, so lookups do not scan
+/// all the relations of the name atom.
+pub struct NameIndex {
+    descriptor: String,
+    names: RwLock<Names>,
+}
+#[derive(Default)]
+struct Names {
+    /// Naming descriptor atom, if it exists.
+    descriptor: Option<Index>,
+    /// Named elements by increasing index. Text atoms are unique: one relation per name.
+    elements_named: HashMap<String, Vec<Index>>,
+    /// Naming relations, with name and named element.
+    relations: HashMap<Index, (String, Index)>,
+}
+
+impl NameIndex {
+    /// Index of the names given by the descriptor, kept up to date by observing the database.
+    pub fn attach(database: &mut Database, descriptor: &str) -> Arc<NameIndex> {
+        let index = Arc::new(NameIndex {
+            descriptor: descriptor.to_string(),
+            names: RwLock::new(Names::default()),
+        });
+        index.rebuild(database);
+        {
+            let index = index.clone();
+            database.on_change(move |database, change| index.update(database, change));
+        }
+        index
+    }
+
+    /// Elements named with this text, by increasing index.
+    pub fn find_by_name(&self, name: &str) -> Vec<Index> {
+        let names = self.names.read().unwrap();
+        names
+            .elements_named
+            .get(name)
+            .cloned()
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Index the database from scratch.
+    fn rebuild(&self, database: &Database) {
+        let descriptor = database.index_of_text_atom(self.descriptor.as_str());
+        let mut names = Names {
+            descriptor,
+            ..Names::default()
+        };
+        if let Some(descriptor) = descriptor {
+            for relation in database.element(descriptor).unwrap().descriptor_of().iter() {
+                names.insert(database, relation.index())
+            }
+        }
+        *self.names.write().unwrap() = names;
+    }
+
+    fn update(&self, database: &Database, change: Change) {
+        let descriptor = database.index_of_text_atom(self.descriptor.as_str());
+        if change == Change::Reset || descriptor != self.descriptor_index() {
+            return self.rebuild(database); // New content, or descriptor created, renamed or removed
+        }
+        let mut names = self.names.write().unwrap();
+        match change {
+            Change::Inserted(index) => names.insert(database, index),
+            Change::Removed(index) => names.remove(index),
+            Change::Reset => {} // Rebuilt above
+            Change::Modified(index) => {
+                // Relation replaced by an abstract element, or new name for the naming relations.
+                names.remove(index);
+                if let Ok(element) = database.element(index) {
+                    for relation in element.complement_of().iter() {
+                        if names.relations.contains_key(&relation.index()) {
+                            names.remove(relation.index());
+                            names.insert(database, relation.index())
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn descriptor_index(&self) -> Option<Index> {
+        self.names.read().unwrap().descriptor
+    }
+}
+
+impl Names {
+    /// Add the relation if it is a naming relation.
+    fn insert(&mut self, database: &Database, relation: Index) {
+        let value = |index: Index| database.elements.get(index).map(|data| &data.value);
+        let r = match value(relation) {
+            Some(Element::Relation(r)) if Some(r.descriptor) == self.descriptor => r,
+            _ => return,
+        };
+        let name = match r.complement.and_then(value) {
+            Some(Element::Atom(Atom::Text(name))) => name.clone(),
+            _ => return,
+        };
+        let elements = self
+            .elements_named
+            .entry(name.clone())
+            .or_insert_with(Vec::new);
+        if let Err(position) = elements.binary_search(&r.subject) {
+            elements.insert(position, r.subject)
+        }
+        self.relations.insert(relation, (name, r.subject));
+    }
+
+    /// Remove the relation if it is an indexed naming relation.
+    fn remove(&mut self, relation: Index) {
+        let (name, subject) = match self.relations.remove(&relation) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let now_empty = match self.elements_named.get_mut(&name) {
+            Some(elements) => {
+                elements.retain(|&e| e != subject);
+                elements.is_empty()
+            }
+            None => false,
+        };
+        if now_empty {
+            self.elements_named.remove(&name);
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn name_index() {
+        let (mut db, e) = fixture! {
+            joe = abstract_() { related("named", "Joe") };
+            other_joe = abstract_() { related("named", "Joe"), related("nickname", "Jack") };
+        };
+        let names = NameIndex::attach(&mut db, "named");
+        assert_eq!(names.find_by_name("Joe"), vec![e.joe, e.other_joe]);
+        assert!(names.find_by_name("Jack").is_empty());
+
+        let named = db.index_of_text_atom("named").unwrap();
+        let jack = db.index_of_text_atom("Jack").unwrap();
+        let jack_name = Relation {
+            subject: e.joe,
+            descriptor: named,
+            complement: Some(jack),
+        };
+        let jack_name_i = db.insert_relation(jack_name).unwrap();
+        assert_eq!(names.find_by_name("Jack"), vec![e.joe]);
+        db.replace_atom_value(jack, Atom::from("Jacky")).unwrap();
+        assert!(names.find_by_name("Jack").is_empty());
+        assert_eq!(names.find_by_name("Jacky"), vec![e.joe]);
+        db.remove_element(jack_name_i).unwrap();
+        assert!(names.find_by_name("Jacky").is_empty());
+
+        db.replace_atom_value(named, Atom::from("called")).unwrap();
+        assert!(names.find_by_name("Joe").is_empty());
+        db.replace_atom_value(named, Atom::from("named")).unwrap();
+        assert_eq!(names.find_by_name("Joe"), vec![e.joe, e.other_joe]);
+
+        let (restored, restored_e) = fixture! { jack = abstract_() { related("named", "Jack") }; };
+        db.restore(restored);
+        assert!(names.find_by_name("Joe").is_empty());
+        assert_eq!(names.find_by_name("Jack"), vec![restored_e.jack]);
+    }
+}
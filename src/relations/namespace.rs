@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use super::{Atom, Database, Element, Index, Ref};
+use utils::Set;
+
+/// Text of the atom used as descriptor to put elements in namespaces.
+/// An element e belongs to namespace "n" if the relation "e _namespace n" exists, "n" being a text atom.
+/// Relations with a member as subject are members too, so descriptions follow their subject.
+/// Descriptors and complements of member relations are included, so that members form a subgraph.
+pub const NAMESPACE_DESCRIPTOR: &'static str = "_namespace";
+
+impl Database {
+    /// Names of all namespaces used, sorted.
+    pub fn namespaces(&self) -> Vec<&str> {
+        let descriptor = match self.get_text_atom(NAMESPACE_DESCRIPTOR) {
+            Some(descriptor) => descriptor,
+            None => return Vec::new(),
+        };
+        let mut names: Vec<&str> = descriptor
+            .descriptor_of()
+            .iter()
+            .filter_map(|r| r.value().complement)
+            .filter_map(|c| match self.elements[c].value {
+                Element::Atom(Atom::Text(ref name)) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Elements of a namespace: elements tagged with it, relations describing members,
+    /// and the components of these relations.
+    pub fn namespace_members(&self, name: &str) -> Set<Index> {
+        let descriptor = self.index_of_text_atom(NAMESPACE_DESCRIPTOR);
+        let namespace = self.get_text_atom(name);
+        let (descriptor, namespace) = match (descriptor, namespace) {
+            (Some(descriptor), Some(namespace)) => (descriptor, namespace),
+            _ => return Set::new(),
+        };
+        let mut members = HashSet::new();
+        let mut to_visit: Vec<Index> = namespace
+            .complement_of()
+            .iter()
+            .filter(|r| r.value().descriptor == descriptor)
+            .map(|r| r.value().subject)
+            .collect();
+        while let Some(index) = to_visit.pop() {
+            if members.insert(index) {
+                let element = self.element(index).unwrap();
+                to_visit.extend(element.subject_of().iter().map(|r| r.index()))
+            }
+        }
+        // Components are not followed further: their own relations are not members.
+        let mut components = Vec::new();
+        for &index in members.iter() {
+            if let Element::Relation(ref r) = self.elements[index].value {
+                components.push(r.descriptor);
+                components.extend(r.complement)
+            }
+        }
+        members.extend(components);
+        members.into_iter().collect()
+    }
+
+    /// Iterate on elements of a namespace, by increasing index.
+    pub fn iter_namespace<'a>(&'a self, name: &str) -> impl Iterator<Item = Ref<'a, Element>> + 'a {
+        let members = self.namespace_members(name);
+        members
+            .into_iter()
+            .map(move |index| self.element(index).unwrap())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn namespaces() {
+        let mut db = Database::new();
+        assert!(db.namespaces().is_empty());
+        let namespace_i = db.insert_atom(Atom::from(NAMESPACE_DESCRIPTOR));
+        let work_i = db.insert_atom(Atom::from("work"));
+        let home_i = db.insert_atom(Atom::from("home"));
+        let named_i = db.insert_atom(Atom::from("named"));
+        let project_i = db.create_abstract_element();
+        let garden_i = db.create_abstract_element();
+        let relate = |db: &mut Database, subject, descriptor, complement| {
+            db.insert_relation(Relation {
+                subject,
+                descriptor,
+                complement,
+            })
+            .unwrap()
+        };
+        let tag_i = relate(&mut db, project_i, namespace_i, Some(work_i));
+        let _ = relate(&mut db, garden_i, namespace_i, Some(home_i));
+        let name_i = relate(&mut db, project_i, named_i, Some(work_i));
+        let meta_i = relate(&mut db, name_i, named_i, None);
+
+        assert_eq!(db.namespaces(), vec!["home", "work"]);
+        let members = db.namespace_members("work");
+        let expected = [
+            namespace_i,
+            work_i,
+            named_i,
+            project_i,
+            tag_i,
+            name_i,
+            meta_i,
+        ];
+        assert_eq!(members.as_ref(), &expected);
+        let iterated: Vec<Index> = db.iter_namespace("work").map(|e| e.index()).collect();
+        assert_eq!(iterated, expected.to_vec());
+        assert!(db.namespace_members("unknown").is_empty());
+        assert!(db.namespace_members("named").is_empty());
+    }
+}
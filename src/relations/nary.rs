@@ -0,0 +1,267 @@
+use super::{Atom, Change, Database, Element, Error, Index, Relation};
+
+/// Fact with an ordered list of arguments, like "transfer(from, to, amount, date)".
+/// Relations only have a subject and a complement, so facts are reified by chained relations:
+/// an abstract element f stands for the fact, with the relation "f d" for its descriptor d,
+/// and the relation "f _i a" for its argument a at position i, counted from 1 like rdf:_1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NaryFact {
+    /// Descriptor of the fact.
+    pub descriptor: Index,
+    /// Arguments, in order.
+    pub arguments: Vec<Index>,
+}
+
+/// Text of the atom used as descriptor for the subject of a reified relation.
+pub const FROM_ROLE: &'static str = "from";
+/// Text of the atom used as descriptor for the complement of a reified relation.
+pub const TO_ROLE: &'static str = "to";
+
+/// Text of the atom used as descriptor for the argument at position (from 0).
+pub fn argument_descriptor(position: usize) -> String {
+    format!("_{}", position + 1)
+}
+
+/// Position (from 0) of the argument for an argument descriptor text.
+fn argument_position(descriptor: &str) -> Option<usize> {
+    if !descriptor.starts_with('_') {
+        return None;
+    }
+    match descriptor[1..].parse::<usize>() {
+        Ok(n) if n > 0 && descriptor[1..] == n.to_string() => Some(n - 1),
+        _ => None,
+    }
+}
+
+impl Database {
+    /// Create the abstract element and relations representing a fact.
+    /// Nothing is inserted if an index is invalid.
+    pub fn insert_nary_fact(&mut self, fact: &NaryFact) -> Result<Index, Error> {
+        self.element(fact.descriptor)?;
+        for &argument in fact.arguments.iter() {
+            self.element(argument)?;
+        }
+        let subject = self.create_abstract_element();
+        self.insert_fact_relations(subject, fact)?;
+        Ok(subject)
+    }
+
+    /// Replace the relation "s d c" by an abstract element f with relations "f d", "f from s" and
+    /// "f to c" (no "to" without complement), using the FROM_ROLE and TO_ROLE atoms.
+    /// Used when a relation needs more attributes, or descriptions of the roles of its components.
+    /// The relation element becomes f, so relations referencing it are kept.
+    /// Insertions are checked first: on error the relation is left unchanged.
+    pub fn reify_relation(&mut self, index: Index) -> Result<(), Error> {
+        let relation = match self.element(index)?.value() {
+            Element::Relation(r) => r.clone(),
+            _ => return Err(Error::InvalidIndex),
+        };
+        self.check_not_protected(index)?;
+        // Relations of f may conflict with those of the element, or with each other.
+        // Role atoms created here are not unique descriptors, so they cannot conflict.
+        let mut descriptors = vec![
+            Some(relation.descriptor),
+            self.index_of_text_atom(FROM_ROLE),
+        ];
+        if relation.complement.is_some() {
+            descriptors.push(self.index_of_text_atom(TO_ROLE))
+        }
+        for (position, &descriptor) in descriptors.iter().enumerate() {
+            let descriptor = match descriptor {
+                Some(descriptor) => descriptor,
+                None => continue,
+            };
+            let used = self.subject_has_descriptor(index, descriptor)
+                || descriptors[..position].contains(&Some(descriptor));
+            if used && self.is_unique_descriptor(descriptor) {
+                return Err(Error::NotUnique);
+            }
+        }
+        self.unregister_relation(index, &relation);
+        self.elements.get_mut(index).unwrap().value = Element::Abstract;
+        self.touch(index);
+        self.notify(Change::Modified(index));
+        let from = self.insert_atom(Atom::from(FROM_ROLE));
+        let mut roles = vec![(relation.descriptor, None), (from, Some(relation.subject))];
+        if let Some(complement) = relation.complement {
+            roles.push((self.insert_atom(Atom::from(TO_ROLE)), Some(complement)))
+        }
+        for (descriptor, complement) in roles {
+            let role = Relation {
+                subject: index,
+                descriptor,
+                complement,
+            };
+            self.insert_relation(role).unwrap(); // Checked above
+        }
+        Ok(())
+    }
+
+    fn insert_fact_relations(&mut self, subject: Index, fact: &NaryFact) -> Result<(), Error> {
+        self.insert_relation(Relation {
+            subject,
+            descriptor: fact.descriptor,
+            complement: None,
+        })?;
+        for (position, &argument) in fact.arguments.iter().enumerate() {
+            let descriptor = self.insert_atom(Atom::from(argument_descriptor(position)));
+            self.insert_relation(Relation {
+                subject,
+                descriptor,
+                complement: Some(argument),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Fact represented by an abstract element, if it has a descriptor and contiguous arguments.
+    /// The descriptor is given by its first relation without complement.
+    pub fn nary_fact(&self, index: Index) -> Result<Option<NaryFact>, Error> {
+        let element = self.element(index)?;
+        match element.value() {
+            Element::Abstract => (),
+            _ => return Ok(None),
+        }
+        let mut descriptor = None;
+        let mut arguments: Vec<Option<Index>> = Vec::new();
+        for relation in element.subject_of().iter() {
+            let r = relation.value();
+            let complement = match r.complement {
+                None => {
+                    descriptor = descriptor.or(Some(r.descriptor));
+                    continue;
+                }
+                Some(complement) => complement,
+            };
+            let position = match self.element(r.descriptor)?.value() {
+                Element::Atom(Atom::Text(text)) => argument_position(text),
+                _ => None,
+            };
+            if let Some(position) = position {
+                if position >= arguments.len() {
+                    arguments.resize(position + 1, None)
+                }
+                if arguments[position].is_some() {
+                    return Ok(None); // Ambiguous argument
+                }
+                arguments[position] = Some(complement)
+            }
+        }
+        let arguments: Option<Vec<Index>> = arguments.into_iter().collect();
+        Ok(match (descriptor, arguments) {
+            (Some(descriptor), Some(arguments)) => Some(NaryFact {
+                descriptor,
+                arguments,
+            }),
+            _ => None,
+        })
+    }
+
+    /// Elements of the facts having this argument at position (from 0).
+    pub fn nary_facts_with_argument(&self, argument: Index, position: usize) -> Vec<Index> {
+        let descriptor = self.index_of_text_atom(&argument_descriptor(position));
+        let (argument, descriptor) = match (self.element(argument), descriptor) {
+            (Ok(argument), Some(descriptor)) => (argument, descriptor),
+            _ => return Vec::new(),
+        };
+        argument
+            .complement_of()
+            .iter()
+            .map(|r| r.value())
+            .filter(|r| r.descriptor == descriptor)
+            .map(|r| r.subject)
+            .collect()
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::UNIQUE_DESCRIPTOR_TAG;
+    use super::*;
+
+    #[test]
+    fn nary_facts() {
+        let (mut db, e) = fixture! {
+            transfer = atom("transfer") {};
+            alice = abstract_() { related("named", "Alice") };
+            bob = abstract_() { related("named", "Bob") };
+            amount = atom(100i64) {};
+        };
+        let fact = NaryFact {
+            descriptor: e.transfer,
+            arguments: vec![e.alice, e.bob, e.amount],
+        };
+        let fact_i = db.insert_nary_fact(&fact).unwrap();
+        assert_eq!(db.nary_fact(fact_i), Ok(Some(fact.clone())));
+        assert_eq!(db.nary_fact(e.alice), Ok(None));
+        assert_eq!(db.nary_fact(e.amount), Ok(None));
+        assert_eq!(db.nary_facts_with_argument(e.bob, 1), vec![fact_i]);
+        assert!(db.nary_facts_with_argument(e.bob, 0).is_empty());
+        assert!(db.nary_facts_with_argument(e.bob, 5).is_empty());
+
+        let nb_elements = db.iter().count();
+        let invalid = NaryFact {
+            descriptor: e.transfer,
+            arguments: vec![e.alice, 1000],
+        };
+        assert_eq!(db.insert_nary_fact(&invalid), Err(Error::InvalidIndex));
+        assert_eq!(db.iter().count(), nb_elements);
+
+        assert_eq!(argument_position("_1"), Some(0));
+        assert_eq!(argument_position("_01"), None);
+        assert_eq!(argument_position("_0"), None);
+        assert_eq!(argument_position("named"), None);
+    }
+
+    #[test]
+    fn reify_relation() {
+        let (mut db, e) = fixture! {
+            alice = abstract_() {};
+            bob = abstract_() {};
+            knows = atom("knows") {};
+            since = atom("since") {};
+            year = atom(2010i64) {};
+        };
+        let relation = |subject, descriptor, complement| Relation {
+            subject,
+            descriptor,
+            complement,
+        };
+        let acquaintance = relation(e.alice, e.knows, Some(e.bob));
+        let acquaintance_i = db.insert_relation(acquaintance.clone()).unwrap();
+        let note = relation(acquaintance_i, e.since, Some(e.year));
+        let note_i = db.insert_relation(note.clone()).unwrap();
+        let uuid = db.element(acquaintance_i).unwrap().uuid();
+
+        assert_eq!(db.reify_relation(acquaintance_i), Ok(()));
+        let reified = db.element(acquaintance_i).unwrap();
+        assert!(match reified.value() {
+            Element::Abstract => true,
+            _ => false,
+        });
+        assert_eq!(reified.uuid(), uuid);
+        assert_eq!(db.index_of_relation(&acquaintance), None);
+        assert_eq!(db.index_of_relation(&note), Some(note_i));
+        let from = db.index_of_text_atom(FROM_ROLE).unwrap();
+        let to = db.index_of_text_atom(TO_ROLE).unwrap();
+        let roles = [(e.knows, None), (from, Some(e.alice)), (to, Some(e.bob))];
+        for &(descriptor, complement) in roles.iter() {
+            let role = relation(acquaintance_i, descriptor, complement);
+            assert!(db.index_of_relation(&role).is_some());
+        }
+
+        // A unique descriptor already used by the relation element: nothing is changed.
+        let unique = db.insert_atom(Atom::from(UNIQUE_DESCRIPTOR_TAG));
+        db.insert_relation(relation(e.since, unique, None)).unwrap();
+        let met = relation(e.alice, e.since, Some(e.bob));
+        let met_i = db.insert_relation(met.clone()).unwrap();
+        db.insert_relation(relation(met_i, e.since, Some(e.year)))
+            .unwrap();
+        assert_eq!(db.reify_relation(met_i), Err(Error::NotUnique));
+        assert_eq!(db.index_of_relation(&met), Some(met_i));
+        assert_eq!(db.reify_relation(e.alice), Err(Error::InvalidIndex));
+    }
+}
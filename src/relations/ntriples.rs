@@ -0,0 +1,170 @@
+use std::fmt;
+use std::io;
+
+use super::dot::{select, Options};
+use super::jsonld::{Context, XSD};
+use super::{Atom, Database, Element, Error, Index, Ref};
+use utils::Set;
+
+const RDF_TYPE: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// Render the abstract elements selected by options as N-Triples.
+pub fn render(database: &Database, options: &Options, context: &Context) -> Result<String, Error> {
+    let elements = select(database, options)?;
+    let mut output = Vec::new();
+    write_subgraph(database, &elements, context, &mut output).unwrap(); // Vec<u8> writes cannot fail
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// Write the abstract elements of a subset as N-Triples, one line per triple.
+/// The mapping is the one of JSON-LD documents: "e d" is a type of e, and "e d c" a property.
+pub fn write_subgraph<W: io::Write>(
+    database: &Database,
+    elements: &Set<Index>,
+    context: &Context,
+    mut w: W,
+) -> io::Result<()> {
+    for &index in elements.iter() {
+        let element = match database.element(index) {
+            Ok(element) => element,
+            Err(_) => continue,
+        };
+        match element.value() {
+            Element::Abstract => (),
+            _ => continue,
+        }
+        for relation in element.subject_of().iter() {
+            let r = relation.value();
+            if !elements.contains(&relation.index()) {
+                continue;
+            }
+            let descriptor = match database.element(r.descriptor) {
+                Ok(descriptor) => descriptor,
+                Err(_) => continue,
+            };
+            let descriptor = match descriptor.value() {
+                Element::Atom(Atom::Text(text)) => text.as_str(),
+                _ => continue,
+            };
+            let complement = match r.complement {
+                None => {
+                    let iri = context.iri(descriptor);
+                    write!(w, "{} <{}> <{}> .\n", Node(&element), RDF_TYPE, iri)?;
+                    continue;
+                }
+                Some(complement) if elements.contains(&complement) => {
+                    match database.element(complement) {
+                        Ok(complement) => complement,
+                        Err(_) => continue,
+                    }
+                }
+                Some(_) => continue,
+            };
+            let iri = context.iri(descriptor);
+            match complement.value() {
+                Element::Atom(atom) => {
+                    write!(w, "{} <{}> {} .\n", Node(&element), iri, Literal(atom))?
+                }
+                Element::Abstract => {
+                    write!(w, "{} <{}> {} .\n", Node(&element), iri, Node(&complement))?
+                }
+                Element::Relation(_) => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Element as an IRI from its uuid, or a blank node if it has none.
+struct Node<'a, 'e: 'a>(&'a Ref<'e, Element>);
+impl<'a, 'e> fmt::Display for Node<'a, 'e> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.uuid() {
+            Some(uuid) => write!(f, "<urn:uuid:{}>", uuid),
+            None => write!(f, "_:e{}", self.0.index()),
+        }
+    }
+}
+
+/// Atom as a literal, typed with xsd datatypes except for text.
+struct Literal<'a>(&'a Atom);
+impl<'a> fmt::Display for Literal<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let typed = |f: &mut fmt::Formatter, value: &dyn fmt::Display, datatype: &str| {
+            write!(f, "\"{}\"^^<{}{}>", value, XSD, datatype)
+        };
+        match self.0 {
+            Atom::Text(s) => write!(f, "\"{}\"", Escaped(s)),
+            Atom::Integer(n) => typed(f, n, "integer"),
+            Atom::Float(x) => {
+                let x = x.value();
+                if x.is_nan() {
+                    typed(f, &"NaN", "double")
+                } else if x.is_infinite() {
+                    typed(f, &if x < 0.0 { "-INF" } else { "INF" }, "double")
+                } else {
+                    typed(f, &format!("{:?}", x), "double")
+                }
+            }
+            Atom::Date(d) => typed(f, d, "date"),
+        }
+    }
+}
+
+/// Content of an N-Triples string literal.
+struct Escaped<'a>(&'a str);
+impl<'a> fmt::Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => "\\\"".fmt(f)?,
+                '\\' => "\\\\".fmt(f)?,
+                '\n' => "\\n".fmt(f)?,
+                '\r' => "\\r".fmt(f)?,
+                c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+                c => c.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn n_triples() {
+        let (db, e) = fixture! {
+            acme = abstract_() { related("named", "Acme\n") };
+            joe = abstract_() {
+                tagged("person"),
+                related("age", 42i64),
+                linked_to("works at", acme)
+            };
+        };
+        let context = Context {
+            terms: vec![("named".into(), "http://schema.org/name".into())],
+            ..Context::default()
+        };
+        let id = |index| {
+            let uuid = db.element(index).unwrap().uuid().unwrap();
+            format!("<urn:uuid:{}>", uuid)
+        };
+        let expected = concat!(
+            "ACME <http://schema.org/name> \"Acme\\n\" .\n",
+            "JOE <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> ",
+            "<urn:rett:vocabulary:person> .\n",
+            "JOE <urn:rett:vocabulary:age> ",
+            "\"42\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n",
+            "JOE <urn:rett:vocabulary:works%20at> ACME .\n"
+        )
+        .replace("ACME", &id(e.acme))
+        .replace("JOE", &id(e.joe));
+        let output = render(&db, &Options::default(), &context).unwrap();
+        assert_eq!(output, expected);
+    }
+}
@@ -0,0 +1,103 @@
+use super::{Database, Error, Index, Step};
+use utils::Set;
+
+/// Text of the atom used as descriptor for classification.
+/// "e is_a t" means that e is an instance of type t.
+pub const IS_A_DESCRIPTOR: &'static str = "is_a";
+
+/// Text of the atom used as descriptor between types.
+/// "s subtype_of t" means that instances of s are also instances of t.
+/// Subtyping is transitive, and a type without instances is still a type.
+pub const SUBTYPE_OF_DESCRIPTOR: &'static str = "subtype_of";
+
+impl Database {
+    /// Types of an element, directly or through supertypes.
+    pub fn types_of(&self, element: Index) -> Result<Set<Index>, Error> {
+        let element = self.element(element)?.index();
+        let direct_types = match self.index_of_text_atom(IS_A_DESCRIPTOR) {
+            Some(is_a) => self.follow(element, &[Step::Out(is_a)])?,
+            None => return Ok(Set::new()),
+        };
+        let mut types: Vec<Index> = direct_types.iter().cloned().collect();
+        if let Some(subtype_of) = self.index_of_text_atom(SUBTYPE_OF_DESCRIPTOR) {
+            for &type_ in direct_types.iter() {
+                let supertypes = self.transitive_closure(type_, Step::Out(subtype_of), None)?;
+                types.extend(supertypes.iter().cloned())
+            }
+        }
+        // Types reached through several paths are kept once.
+        types.sort_unstable();
+        types.dedup();
+        Ok(Set::from(types))
+    }
+
+    /// Subtypes of a type, directly or through other subtypes.
+    pub fn subtypes(&self, type_: Index) -> Result<Set<Index>, Error> {
+        let type_ = self.element(type_)?.index();
+        match self.index_of_text_atom(SUBTYPE_OF_DESCRIPTOR) {
+            Some(subtype_of) => self.transitive_closure(type_, Step::In(subtype_of), None),
+            None => Ok(Set::new()),
+        }
+    }
+
+    /// Instances of a type or of its subtypes.
+    pub fn instances_of(&self, type_: Index) -> Result<Set<Index>, Error> {
+        let subtypes = self.subtypes(type_)?;
+        let is_a = match self.index_of_text_atom(IS_A_DESCRIPTOR) {
+            Some(is_a) => is_a,
+            None => return Ok(Set::new()),
+        };
+        let mut instances = Vec::new();
+        for &type_ in Some(type_).iter().chain(subtypes.iter()) {
+            instances.extend(self.follow(type_, &[Step::In(is_a)])?.iter().cloned())
+        }
+        instances.sort_unstable();
+        instances.dedup();
+        Ok(Set::from(instances))
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ontology() {
+        let (db, e) = fixture! {
+            animal = abstract_() {};
+            mammal = abstract_() { linked_to(SUBTYPE_OF_DESCRIPTOR, animal) };
+            cat = abstract_() { linked_to(SUBTYPE_OF_DESCRIPTOR, mammal) };
+            bird = abstract_() { linked_to(SUBTYPE_OF_DESCRIPTOR, animal) };
+            fish = abstract_() { linked_to(SUBTYPE_OF_DESCRIPTOR, animal) };
+            felix = abstract_() { linked_to(IS_A_DESCRIPTOR, cat) };
+            tweety = abstract_() { linked_to(IS_A_DESCRIPTOR, bird) };
+            rex = abstract_() { linked_to(IS_A_DESCRIPTOR, animal) };
+        };
+        assert_eq!(
+            db.types_of(e.felix).unwrap().as_ref(),
+            &[e.animal, e.mammal, e.cat]
+        );
+        assert_eq!(db.types_of(e.rex).unwrap().as_ref(), &[e.animal]);
+        assert!(db.types_of(e.animal).unwrap().is_empty());
+        // A type without instances is still a subtype.
+        assert_eq!(
+            db.subtypes(e.animal).unwrap().as_ref(),
+            &[e.mammal, e.cat, e.bird, e.fish]
+        );
+        assert!(db.subtypes(e.cat).unwrap().is_empty());
+        assert_eq!(
+            db.instances_of(e.animal).unwrap().as_ref(),
+            &[e.felix, e.tweety, e.rex]
+        );
+        assert_eq!(db.instances_of(e.bird).unwrap().as_ref(), &[e.tweety]);
+        assert!(db.instances_of(e.fish).unwrap().is_empty());
+        assert_eq!(db.instances_of(1000), Err(Error::InvalidIndex));
+
+        let (db, e) = fixture! { alone = abstract_() {}; };
+        assert!(db.instances_of(e.alone).unwrap().is_empty());
+        assert!(db.subtypes(e.alone).unwrap().is_empty());
+    }
+}
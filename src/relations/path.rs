@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use super::{Database, Error, Index};
+use utils::Set;
+
+/// Hop along relations with a given descriptor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+    /// From the subject of relations to their complement.
+    Out(Index),
+    /// From the complement of relations to their subject.
+    In(Index),
+}
+
+impl Database {
+    /// Elements reached from start by following each step in turn.
+    /// Lighter than a query for fixed navigation, like "complement of the named relation".
+    pub fn follow(&self, start: Index, steps: &[Step]) -> Result<Set<Index>, Error> {
+        let mut reached = vec![self.element(start)?.index()];
+        for &step in steps.iter() {
+            let mut next = Vec::new();
+            for &index in reached.iter() {
+                next.extend(self.take_step(index, step)?)
+            }
+            next.sort_unstable();
+            next.dedup();
+            reached = next;
+        }
+        Ok(Set::from(reached))
+    }
+
+    /// Elements reached from start by repeating the step, like all ancestors in an "is a" hierarchy.
+    /// Each element is visited once, so cycles terminate. At most max_depth steps are taken if set.
+    /// Start is only part of the result if a cycle leads back to it.
+    pub fn transitive_closure(
+        &self,
+        start: Index,
+        step: Step,
+        max_depth: Option<usize>,
+    ) -> Result<Set<Index>, Error> {
+        let mut reached = HashSet::new();
+        let mut frontier = vec![self.element(start)?.index()];
+        let mut depth = 0;
+        while !frontier.is_empty() && max_depth.map_or(true, |max_depth| depth < max_depth) {
+            let mut next = Vec::new();
+            for &index in frontier.iter() {
+                for reached_index in self.take_step(index, step)? {
+                    if reached.insert(reached_index) {
+                        next.push(reached_index)
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        Ok(reached.into_iter().collect())
+    }
+
+    /// Elements reached from an element by one step, with repetitions.
+    fn take_step(&self, index: Index, step: Step) -> Result<Vec<Index>, Error> {
+        let element = self.element(index)?;
+        Ok(match step {
+            Step::Out(descriptor) => element
+                .subject_of()
+                .iter()
+                .map(|r| r.value())
+                .filter(|r| r.descriptor == descriptor)
+                .filter_map(|r| r.complement)
+                .collect(),
+            Step::In(descriptor) => element
+                .complement_of()
+                .iter()
+                .map(|r| r.value())
+                .filter(|r| r.descriptor == descriptor)
+                .map(|r| r.subject)
+                .collect(),
+        })
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn follow() {
+        let (db, e) = fixture! {
+            named = atom("named") {};
+            member = atom("member of") {};
+            guild = abstract_() { related("named", "Guild") };
+            joe = abstract_() { related("named", "Joe"), linked_to("member of", guild) };
+            jack = abstract_() { related("named", "Jack"), linked_to("member of", guild) };
+        };
+        let name = |text: &str| db.index_of_text_atom(text).unwrap();
+        let names_of_members = db
+            .follow(e.joe, &[Step::Out(e.member), Step::In(e.member), Step::Out(e.named)])
+            .unwrap();
+        assert_eq!(names_of_members.as_ref(), &[name("Joe"), name("Jack")]);
+        let none = db.follow(e.guild, &[Step::Out(e.member)]).unwrap();
+        assert!(none.is_empty());
+        assert_eq!(db.follow(e.jack, &[]).unwrap().as_ref(), &[e.jack]);
+        assert_eq!(db.follow(1000, &[]), Err(Error::InvalidIndex));
+    }
+
+    #[test]
+    fn transitive_closure() {
+        let (mut db, e) = fixture! {
+            animal = abstract_() {};
+            mammal = abstract_() { linked_to("is a", animal) };
+            cat = abstract_() { linked_to("is a", mammal) };
+            tiger = abstract_() { linked_to("is a", cat) };
+        };
+        let is_a = db.index_of_text_atom("is a").unwrap();
+        let closure = |db: &Database, start, step, max_depth| {
+            db.transitive_closure(start, step, max_depth).unwrap()
+        };
+        let ancestors = closure(&db, e.tiger, Step::Out(is_a), None);
+        assert_eq!(ancestors.as_ref(), &[e.animal, e.mammal, e.cat]);
+        let parents = closure(&db, e.tiger, Step::Out(is_a), Some(1));
+        assert_eq!(parents.as_ref(), &[e.cat]);
+        let descendants = closure(&db, e.mammal, Step::In(is_a), None);
+        assert_eq!(descendants.as_ref(), &[e.cat, e.tiger]);
+        assert!(closure(&db, e.animal, Step::Out(is_a), None).is_empty());
+
+        db.insert_relation(Relation {
+            subject: e.animal,
+            descriptor: is_a,
+            complement: Some(e.tiger),
+        })
+        .unwrap();
+        let cycle = closure(&db, e.cat, Step::Out(is_a), None);
+        assert_eq!(cycle.as_ref(), &[e.animal, e.mammal, e.cat, e.tiger]);
+        assert_eq!(
+            db.transitive_closure(1000, Step::Out(is_a), None),
+            Err(Error::InvalidIndex)
+        );
+    }
+}
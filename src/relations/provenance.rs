@@ -0,0 +1,137 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Database, Element, Index, Ref};
+
+/// Seconds since the unix epoch.
+pub type Timestamp = u64;
+
+/// Creation and last modification of an element.
+/// Elements from database files written before provenance was recorded have none.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    /// Time of creation.
+    pub created: Timestamp,
+    /// Time of the last change of the value or relations.
+    pub modified: Timestamp,
+    /// Author of the creation, if known.
+    pub author: Option<String>,
+}
+
+pub fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Database {
+    /// Author recorded in the provenance of elements created from now on.
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author
+    }
+
+    /// Provenance of a new element.
+    pub(super) fn new_provenance(&self) -> Provenance {
+        let now = now();
+        Provenance {
+            created: now,
+            modified: now,
+            author: self.author.clone(),
+        }
+    }
+
+    /// Record a modification of an element. Elements without provenance stay without.
+    pub(super) fn touch(&mut self, index: Index) {
+        if let Some(data) = self.elements.get_mut(index) {
+            if let Some(ref mut provenance) = data.provenance {
+                provenance.modified = now()
+            }
+        }
+    }
+
+    /// Elements created in [start, end), by increasing index.
+    pub fn iter_created_between<'a>(
+        &'a self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> impl Iterator<Item = Ref<'a, Element>> + 'a {
+        self.iter().filter(move |element| match element.provenance() {
+            Some(p) => start <= p.created && p.created < end,
+            None => false,
+        })
+    }
+
+    /// Elements created or modified since a time, by increasing index.
+    pub fn iter_modified_since<'a>(
+        &'a self,
+        since: Timestamp,
+    ) -> impl Iterator<Item = Ref<'a, Element>> + 'a {
+        self.iter().filter(move |element| match element.provenance() {
+            Some(p) => since <= p.modified,
+            None => false,
+        })
+    }
+
+    /// Elements most recently created or modified, latest first.
+    pub fn recently_modified<'a>(&'a self, count: usize) -> Vec<Ref<'a, Element>> {
+        // Min-heap of the latest elements seen, so memory is bounded by count.
+        // Elements modified in the same second are likely created in index order.
+        let mut latest: BinaryHeap<Reverse<(Timestamp, Index)>> = BinaryHeap::new();
+        for element in self.iter() {
+            if let Some(provenance) = element.provenance() {
+                latest.push(Reverse((provenance.modified, element.index())));
+                if latest.len() > count {
+                    latest.pop();
+                }
+            }
+        }
+        latest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, index))| self.element(index).unwrap())
+            .collect()
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Atom;
+    use super::*;
+
+    #[test]
+    fn provenance() {
+        let mut db = Database::new();
+        let before = now();
+        let anonymous_i = db.insert_atom(Atom::from("anonymous"));
+        db.set_author(Some("Joe".into()));
+        let object_i = db.create_abstract_element();
+        let after = now() + 1;
+
+        let provenance = |i| db.element(i).unwrap().provenance().cloned().unwrap();
+        assert_eq!(provenance(anonymous_i).author, None);
+        assert_eq!(provenance(object_i).author, Some("Joe".into()));
+        let created = provenance(object_i).created;
+        assert!(before <= created && created < after);
+        assert_eq!(provenance(object_i).modified, created);
+
+        let created: Vec<Index> = db
+            .iter_created_between(before, after)
+            .map(|e| e.index())
+            .collect();
+        assert_eq!(created, vec![anonymous_i, object_i]);
+        assert_eq!(db.iter_created_between(0, before).count(), 0);
+        assert_eq!(db.iter_modified_since(after).count(), 0);
+        let recent = |count| -> Vec<Index> {
+            let elements = db.recently_modified(count);
+            elements.iter().map(|e| e.index()).collect()
+        };
+        assert_eq!(recent(0), vec![]);
+        assert_eq!(recent(1), vec![object_i]);
+        assert_eq!(recent(3), vec![object_i, anonymous_i]);
+    }
+}
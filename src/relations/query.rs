@@ -0,0 +1,515 @@
+use std::fmt;
+
+use super::rules::Rules;
+use super::{Database, Element, Index, Relation};
+use utils::Set;
+
+/// Part of a clause.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    /// `?name`: any element, the same one for all uses of the name.
+    Variable(String),
+    /// `#index`: a specific element.
+    Index(Index),
+    /// `"text"` or a bare word: the text atom with this text.
+    Text(String),
+    /// `_`: any element.
+    Any,
+}
+
+/// `subject -[descriptor]-> complement`, or `subject -[descriptor]` for relations without complement.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Clause {
+    /// Subject of matching relations.
+    pub subject: Term,
+    /// Descriptor of matching relations.
+    pub descriptor: Term,
+    /// Complement of matching relations, None for relations without complement.
+    pub complement: Option<Term>,
+}
+
+/// Clauses separated by commas, which must all match with consistent variables.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    /// Clauses, in order of the query text.
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// Syntax error in a query.
+pub struct ParseError {
+    /// Byte offset in the query text.
+    pub position: usize,
+    /// What was expected.
+    pub message: &'static str,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+impl std::error::Error for ParseError {}
+
+/// Parse a textual query, like `?x -[named]-> "Joe", ?x -[lives in]-> ?city`.
+pub fn parse(text: &str) -> Result<Pattern, ParseError> {
+    let mut parser = Parser { text, position: 0 };
+    let mut clauses = vec![parser.clause()?];
+    while parser.try_consume(",") {
+        clauses.push(parser.clause()?)
+    }
+    parser.skip_whitespace();
+    if parser.position != text.len() {
+        return Err(parser.error("unexpected text"));
+    }
+    Ok(Pattern { clauses })
+}
+
+/// Parse a single term, like `#3` or `"Joe"`.
+pub fn parse_term(text: &str) -> Result<Term, ParseError> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_whitespace();
+    let term = parser.term()?;
+    parser.skip_whitespace();
+    if parser.position != text.len() {
+        return Err(parser.error("unexpected text"));
+    }
+    Ok(term)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            position: self.position,
+            message,
+        }
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len()
+    }
+    fn try_consume(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len()
+        }
+        found
+    }
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), ParseError> {
+        if self.try_consume(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+    /// Longest prefix of chars matching the predicate.
+    fn take_while<P: Fn(char) -> bool>(&mut self, predicate: P) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !predicate(c)).unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    fn clause(&mut self) -> Result<Clause, ParseError> {
+        let subject = self.term()?;
+        self.expect("-[", "expected \"-[\"")?;
+        let descriptor = self.term()?;
+        self.expect("]", "expected \"]\"")?;
+        let complement = if self.try_consume("->") {
+            Some(self.term()?)
+        } else {
+            None
+        };
+        Ok(Clause {
+            subject,
+            descriptor,
+            complement,
+        })
+    }
+
+    fn term(&mut self) -> Result<Term, ParseError> {
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        if self.try_consume("?") {
+            match self.take_while(is_word_char) {
+                "" => Err(self.error("expected variable name")),
+                name => Ok(Term::Variable(name.into())),
+            }
+        } else if self.try_consume("#") {
+            match self.take_while(|c| c.is_ascii_digit()).parse() {
+                Ok(index) => Ok(Term::Index(index)),
+                Err(_) => Err(self.error("expected element index")),
+            }
+        } else if self.try_consume("\"") {
+            self.quoted_text().map(Term::Text)
+        } else {
+            // Bare words may contain inner spaces: "-[lives in]->"
+            let start = self.position;
+            let words = self.take_while(|c| is_word_char(c) || c == ' ').trim_end();
+            self.position = start + words.len();
+            match words {
+                "" => Err(self.error("expected term")),
+                "_" => Ok(Term::Any),
+                words => Ok(Term::Text(words.into())),
+            }
+        }
+    }
+
+    /// Text after an opening quote, with \" and \\ escapes.
+    fn quoted_text(&mut self) -> Result<String, ParseError> {
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(text);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) if c == '"' || c == '\\' => text.push(c),
+                    _ => {
+                        self.position += i;
+                        return Err(self.error("invalid escape sequence"));
+                    }
+                },
+                c => text.push(c),
+            }
+        }
+        self.position = self.text.len();
+        Err(self.error("unterminated text"))
+    }
+}
+
+impl Pattern {
+    /// Names of variables, in order of first appearance.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables: Vec<&str> = Vec::new();
+        for clause in self.clauses.iter() {
+            let terms = [
+                Some(&clause.subject),
+                Some(&clause.descriptor),
+                clause.complement.as_ref(),
+            ];
+            for term in terms.iter().filter_map(|t| *t) {
+                if let Term::Variable(name) = term {
+                    if !variables.contains(&name.as_str()) {
+                        variables.push(name)
+                    }
+                }
+            }
+        }
+        variables
+    }
+}
+
+/// How a term constrains a relation component, given current variable values.
+#[derive(Clone, Copy)]
+enum Constraint {
+    Element(Index),
+    FreeVariable(usize),
+    Any,
+}
+
+impl Database {
+    /// All assignments of the pattern variables such that every clause matches a relation.
+    /// Each result row holds the variable values in the order of `Pattern::variables`.
+    pub fn query(&self, pattern: &Pattern) -> Vec<Vec<Index>> {
+        self.query_with_rules(pattern, &Rules::default())
+    }
+
+    /// Query where clauses also match the relations implied by rules, without inserting them.
+    pub fn query_with_rules(&self, pattern: &Pattern, rules: &Rules) -> Vec<Vec<Index>> {
+        let inverses = rules.inverse_descriptors(self);
+        let variables = pattern.variables();
+        let mut solutions: Vec<Vec<Option<Index>>> = vec![vec![None; variables.len()]];
+        for clause in pattern.clauses.iter() {
+            let mut extended_solutions = Vec::new();
+            for solution in solutions.iter() {
+                self.match_clause(clause, &variables, &inverses, solution, &mut extended_solutions)
+            }
+            solutions = extended_solutions;
+        }
+        // All variables appear in a clause, so they are all bound in the solutions.
+        solutions
+            .into_iter()
+            .map(|solution| solution.into_iter().map(|v| v.unwrap()).collect())
+            .collect()
+    }
+
+    /// Elements of query results, elements named in the pattern, and relations between them.
+    pub fn matched_subgraph(&self, pattern: &Pattern, results: &[Vec<Index>]) -> Set<Index> {
+        let mut elements: Vec<Index> = results.iter().flat_map(|row| row.iter().cloned()).collect();
+        for clause in pattern.clauses.iter() {
+            let terms = [
+                Some(&clause.subject),
+                Some(&clause.descriptor),
+                clause.complement.as_ref(),
+            ];
+            for term in terms.iter().filter_map(|t| *t) {
+                match term {
+                    Term::Index(index) if self.element(*index).is_ok() => elements.push(*index),
+                    Term::Text(text) => elements.extend(self.index_of_text_atom(text.as_str())),
+                    _ => (),
+                }
+            }
+        }
+        let elements: Set<Index> = elements.into_iter().collect();
+        let relations: Vec<Index> = elements
+            .iter()
+            .flat_map(|&i| self.element(i).unwrap().subject_of().iter())
+            .filter(|r| {
+                let relation = r.value();
+                elements.contains(&relation.descriptor)
+                    && relation.complement.map_or(true, |c| elements.contains(&c))
+            })
+            .map(|r| r.index())
+            .collect();
+        elements.iter().cloned().chain(relations).collect()
+    }
+
+    /// Add extensions of solution matching the clause to out.
+    /// Inverses are pairs of descriptors (d, i) such that "s d c" implies "c i s".
+    fn match_clause(
+        &self,
+        clause: &Clause,
+        variables: &[&str],
+        inverses: &[(Index, Index)],
+        solution: &[Option<Index>],
+        out: &mut Vec<Vec<Option<Index>>>,
+    ) {
+        let constraint = |term: &Term| -> Option<Constraint> {
+            match term {
+                Term::Variable(name) => {
+                    let slot = variables.iter().position(|v| *v == name.as_str()).unwrap();
+                    Some(match solution[slot] {
+                        Some(index) => Constraint::Element(index),
+                        None => Constraint::FreeVariable(slot),
+                    })
+                }
+                Term::Index(index) => self
+                    .element(*index)
+                    .ok()
+                    .map(|_| Constraint::Element(*index)),
+                Term::Text(text) => self
+                    .index_of_text_atom(text.as_str())
+                    .map(Constraint::Element),
+                Term::Any => Some(Constraint::Any),
+            }
+        };
+        // A missing element in the database makes the clause impossible.
+        let (subject, descriptor) =
+            match (constraint(&clause.subject), constraint(&clause.descriptor)) {
+                (Some(subject), Some(descriptor)) => (subject, descriptor),
+                _ => return,
+            };
+        let complement = match clause.complement.as_ref().map(|term| constraint(term)) {
+            Some(None) => return,
+            Some(Some(complement)) => Some(complement),
+            None => None,
+        };
+
+        // Use back-links of a fixed component to restrict candidate relations.
+        let candidates: Vec<Index> = match (subject, descriptor, complement) {
+            (Constraint::Element(i), _, _) => self
+                .relations_with_subject(i)
+                .unwrap()
+                .map(|r| r.index())
+                .collect(),
+            (_, Constraint::Element(i), _) => self
+                .relations_with_descriptor(i)
+                .unwrap()
+                .map(|r| r.index())
+                .collect(),
+            (_, _, Some(Constraint::Element(i))) => self
+                .relations_with_complement(i)
+                .unwrap()
+                .map(|r| r.index())
+                .collect(),
+            _ => self.relations().map(|r| r.index()).collect(),
+        };
+        let mut relations: Vec<Relation> = candidates
+            .into_iter()
+            .filter_map(|candidate| match self.element(candidate).unwrap().value() {
+                Element::Relation(relation) => Some(relation.clone()),
+                _ => None,
+            })
+            .collect();
+        for &(source, implied) in inverses.iter() {
+            match descriptor {
+                Constraint::Element(i) if i != implied => continue,
+                _ => (),
+            }
+            for r in self.relations_with_descriptor(source).unwrap() {
+                let r = r.value();
+                let complement = match r.complement {
+                    Some(complement) => complement,
+                    None => continue,
+                };
+                let relation = Relation {
+                    subject: complement,
+                    descriptor: implied,
+                    complement: Some(r.subject),
+                };
+                // Materialized inverses are already candidates.
+                if self.index_of_relation(&relation).is_none() {
+                    relations.push(relation)
+                }
+            }
+        }
+        for relation in relations {
+            let mut extended = solution.to_vec();
+            let matches = bind(subject, Some(relation.subject), &mut extended)
+                && bind(descriptor, Some(relation.descriptor), &mut extended)
+                && match complement {
+                    Some(complement) => bind(complement, relation.complement, &mut extended),
+                    None => relation.complement.is_none(),
+                };
+            if matches {
+                out.push(extended)
+            }
+        }
+    }
+}
+
+/// Check that the relation component value satisfies the constraint, binding free variables.
+fn bind(constraint: Constraint, value: Option<Index>, solution: &mut [Option<Index>]) -> bool {
+    match (constraint, value) {
+        (_, None) => false,
+        (Constraint::Element(index), Some(value)) => index == value,
+        (Constraint::Any, Some(_)) => true,
+        // The same variable may appear twice in a clause, and be bound by the first occurrence.
+        (Constraint::FreeVariable(slot), Some(value)) => match solution[slot] {
+            Some(bound) => bound == value,
+            None => {
+                solution[slot] = Some(value);
+                true
+            }
+        },
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let variable = |name: &str| Term::Variable(name.into());
+        let text = |text: &str| Term::Text(text.into());
+        assert_eq!(
+            parse("?x -[name]-> \"joe\""),
+            Ok(Pattern {
+                clauses: vec![Clause {
+                    subject: variable("x"),
+                    descriptor: text("name"),
+                    complement: Some(text("joe")),
+                }]
+            })
+        );
+        assert_eq!(
+            parse(" #3-[ lives in ]->?city , _ -[?d], \"a \\\"b\\\\\" -[_unique]"),
+            Ok(Pattern {
+                clauses: vec![
+                    Clause {
+                        subject: Term::Index(3),
+                        descriptor: text("lives in"),
+                        complement: Some(variable("city")),
+                    },
+                    Clause {
+                        subject: Term::Any,
+                        descriptor: variable("d"),
+                        complement: None,
+                    },
+                    Clause {
+                        subject: text("a \"b\\"),
+                        descriptor: text("_unique"),
+                        complement: None,
+                    }
+                ]
+            })
+        );
+
+        let error_position = |query: &str| parse(query).unwrap_err().position;
+        assert_eq!(error_position(""), 0);
+        assert_eq!(error_position("?x"), 2);
+        assert_eq!(error_position("?x -[?]"), 6);
+        assert_eq!(error_position("?x -[name"), 9);
+        assert_eq!(error_position("?x -[name] ?y"), 11);
+        assert_eq!(error_position("\"abc"), 4);
+        assert_eq!(error_position("\"a\\bc\" -[name]"), 2);
+        assert_eq!(error_position("#x -[name]"), 1);
+
+        assert_eq!(parse_term(" lives in "), Ok(text("lives in")));
+        assert_eq!(parse_term("#3"), Ok(Term::Index(3)));
+        assert_eq!(parse_term("#3 #4").unwrap_err().position, 3);
+    }
+
+    #[test]
+    fn matching() {
+        let mut db = Database::new();
+        let named_i = db.insert_atom(Atom::from("named"));
+        let lives_i = db.insert_atom(Atom::from("lives in"));
+        let city_i = db.create_abstract_element();
+        let (joe_i, jack_i, averell_i) = {
+            let mut person = |name: &str, with_city: bool| {
+                let person_i = db.create_abstract_element();
+                let name_i = db.insert_atom(Atom::from(name));
+                db.insert_relation(Relation {
+                    subject: person_i,
+                    descriptor: named_i,
+                    complement: Some(name_i),
+                })
+                .unwrap();
+                if with_city {
+                    db.insert_relation(Relation {
+                        subject: person_i,
+                        descriptor: lives_i,
+                        complement: Some(city_i),
+                    })
+                    .unwrap();
+                }
+                person_i
+            };
+            (
+                person("Joe", true),
+                person("Jack", true),
+                person("Averell", false),
+            )
+        };
+        let query = |text: &str| db.query(&parse(text).unwrap());
+
+        assert_eq!(query("?x -[named]-> Joe"), vec![vec![joe_i]]);
+        assert_eq!(query("?x -[lives in]-> _"), vec![vec![joe_i], vec![jack_i]]);
+        assert_eq!(
+            query("?x -[lives in]-> ?c, ?y -[lives in]-> ?c, ?y -[named]-> Jack"),
+            vec![vec![joe_i, city_i, jack_i], vec![jack_i, city_i, jack_i]]
+        );
+        assert_eq!(
+            query(format!("#{} -[?d]-> ?n", averell_i).as_str()).len(),
+            1
+        );
+        assert!(query("?x -[named]-> William").is_empty());
+        assert!(query("?x -[named]").is_empty());
+        assert!(query("?x -[named]-> ?x").is_empty());
+        assert!(query("#1000 -[named]-> _").is_empty());
+
+        let pattern = parse("?x -[named]-> Joe").unwrap();
+        let results = db.query(&pattern);
+        let joe_name_i = db.index_of_text_atom("Joe").unwrap();
+        let joe_named_i = db.element(joe_i).unwrap().subject_of().get(0).unwrap().index();
+        assert_eq!(
+            db.matched_subgraph(&pattern, &results).as_ref(),
+            &[named_i, joe_i, joe_name_i, joe_named_i]
+        );
+    }
+}
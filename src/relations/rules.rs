@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use super::{Atom, Database, Error, Index, Relation};
+
+/// Relations "s d c" imply relations "c i s", and conversely: "parent of" and "child of".
+/// Descriptors are designated by their text atom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inverse {
+    /// Text of the descriptor.
+    pub descriptor: String,
+    /// Text of the inverse descriptor.
+    pub inverse: String,
+}
+
+/// Parse "descriptor=inverse", as in configuration files and on the command line.
+impl FromStr for Inverse {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.find('=') {
+            Some(equal) if equal > 0 && equal + 1 < text.len() => Ok(Inverse {
+                descriptor: text[..equal].into(),
+                inverse: text[equal + 1..].into(),
+            }),
+            _ => Err(format!("Expected descriptor=inverse: {}", text)),
+        }
+    }
+}
+
+/// Inference rules, applied by queries or materialized as relations.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    /// Pairs of inverse descriptors.
+    pub inverses: Vec<Inverse>,
+}
+
+impl Rules {
+    /// Pairs of descriptors (d, i) such that "s d c" implies "c i s", in both directions.
+    /// Rules are ignored while one of their descriptors has no atom, as no relation uses it.
+    pub(super) fn inverse_descriptors(&self, database: &Database) -> Vec<(Index, Index)> {
+        let mut pairs = Vec::new();
+        for rule in self.inverses.iter() {
+            let descriptor = database.index_of_text_atom(rule.descriptor.as_str());
+            let inverse = database.index_of_text_atom(rule.inverse.as_str());
+            if let (Some(descriptor), Some(inverse)) = (descriptor, inverse) {
+                pairs.push((descriptor, inverse));
+                if inverse != descriptor {
+                    pairs.push((inverse, descriptor))
+                }
+            }
+        }
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+}
+
+impl Database {
+    /// Relations implied by rules for relations of the element, that are not in the database.
+    /// Used to navigate from an element as if the relations had been materialized.
+    pub fn implied_relations(&self, index: Index, rules: &Rules) -> Result<Vec<Relation>, Error> {
+        let element = self.element(index)?;
+        let inverses = rules.inverse_descriptors(self);
+        let mut implied = Vec::new();
+        for r in Iterator::chain(element.subject_of().iter(), element.complement_of().iter()) {
+            let r = r.value();
+            let complement = match r.complement {
+                Some(complement) => complement,
+                None => continue,
+            };
+            for &(_, inverse) in inverses.iter().filter(|pair| pair.0 == r.descriptor) {
+                let relation = Relation {
+                    subject: complement,
+                    descriptor: inverse,
+                    complement: Some(r.subject),
+                };
+                if self.index_of_relation(&relation).is_none() && !implied.contains(&relation) {
+                    implied.push(relation)
+                }
+            }
+        }
+        Ok(implied)
+    }
+
+    /// Insert the relations implied by rules that are missing, so that they can be navigated.
+    /// Returns the indexes of inserted relations.
+    pub fn materialize_rules(&mut self, rules: &Rules) -> Result<Vec<Index>, Error> {
+        for rule in rules.inverses.iter() {
+            self.insert_atom(Atom::from(rule.descriptor.as_str()));
+            self.insert_atom(Atom::from(rule.inverse.as_str()));
+        }
+        let mut implied = Vec::new();
+        for (source, inverse) in rules.inverse_descriptors(self) {
+            for r in self.relations_with_descriptor(source)? {
+                let r = r.value();
+                if let Some(complement) = r.complement {
+                    implied.push(Relation {
+                        subject: complement,
+                        descriptor: inverse,
+                        complement: Some(r.subject),
+                    })
+                }
+            }
+        }
+        let mut inserted = Vec::new();
+        for relation in implied {
+            if self.index_of_relation(&relation).is_none() {
+                inserted.push(self.insert_relation(relation)?)
+            }
+        }
+        Ok(inserted)
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::query::parse;
+    use super::*;
+
+    #[test]
+    fn inverses() {
+        let (mut db, e) = fixture! {
+            child = abstract_() { related("named", "Kid") };
+            parent = abstract_() { linked_to("parent of", child) };
+        };
+        let rules = Rules {
+            inverses: vec![Inverse {
+                descriptor: "parent of".into(),
+                inverse: "child of".into(),
+            }],
+        };
+        assert_eq!("parent of=child of".parse(), Ok(rules.inverses[0].clone()));
+        assert!("parent of".parse::<Inverse>().is_err());
+        assert!("=child of".parse::<Inverse>().is_err());
+        let pattern = parse("?c -[child of]-> ?p").unwrap();
+        assert!(db.query_with_rules(&pattern, &rules).is_empty()); // No "child of" atom yet
+        assert_eq!(db.implied_relations(e.child, &rules), Ok(Vec::new()));
+        let child_of_i = db.insert_atom(Atom::from("child of"));
+        assert!(db.query(&pattern).is_empty());
+        let results = db.query_with_rules(&pattern, &rules);
+        assert_eq!(results, vec![vec![e.child, e.parent]]);
+        let implied = Relation {
+            subject: e.child,
+            descriptor: child_of_i,
+            complement: Some(e.parent),
+        };
+        assert_eq!(
+            db.implied_relations(e.child, &rules),
+            Ok(vec![implied.clone()])
+        );
+        assert_eq!(db.implied_relations(e.parent, &rules), Ok(vec![implied]));
+
+        let inserted = db.materialize_rules(&rules).unwrap();
+        assert_eq!(inserted.len(), 1);
+        let relation = Relation {
+            subject: e.child,
+            descriptor: child_of_i,
+            complement: Some(e.parent),
+        };
+        assert_eq!(db.index_of_relation(&relation), Some(inserted[0]));
+        assert_eq!(db.query(&pattern), results);
+        assert_eq!(db.query_with_rules(&pattern, &rules), results);
+        let pattern = parse("?p -[parent of]-> ?c").unwrap();
+        assert_eq!(db.query_with_rules(&pattern, &rules).len(), 1);
+        assert!(db.materialize_rules(&rules).unwrap().is_empty());
+        assert_eq!(db.implied_relations(e.child, &rules), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,463 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Atom, Database, Element, ElementKind, Error, Index, Relation};
+
+/// Kind of element, with the type of value for atoms.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValueKind {
+    /// Abstract element.
+    Abstract,
+    /// Relation.
+    Relation,
+    /// Text atom.
+    Text,
+    /// Integer atom.
+    Integer,
+    /// Float atom.
+    Float,
+    /// Date atom.
+    Date,
+}
+impl<'e> From<&'e Element> for ValueKind {
+    fn from(element: &Element) -> ValueKind {
+        match element {
+            Element::Abstract => ValueKind::Abstract,
+            Element::Relation(_) => ValueKind::Relation,
+            Element::Atom(Atom::Text(_)) => ValueKind::Text,
+            Element::Atom(Atom::Integer(_)) => ValueKind::Integer,
+            Element::Atom(Atom::Float(_)) => ValueKind::Float,
+            Element::Atom(Atom::Date(_)) => ValueKind::Date,
+        }
+    }
+}
+impl ValueKind {
+    fn name(self) -> &'static str {
+        match self {
+            ValueKind::Abstract => "abstract",
+            ValueKind::Relation => "relation",
+            ValueKind::Text => "text",
+            ValueKind::Integer => "integer",
+            ValueKind::Float => "float",
+            ValueKind::Date => "date",
+        }
+    }
+}
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name().fmt(f)
+    }
+}
+impl FromStr for ValueKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kinds = [
+            ValueKind::Abstract,
+            ValueKind::Relation,
+            ValueKind::Text,
+            ValueKind::Integer,
+            ValueKind::Float,
+            ValueKind::Date,
+        ];
+        kinds
+            .iter()
+            .cloned()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("Unknown value kind: {}", s))
+    }
+}
+
+/// Rule that a database should follow. Descriptors are designated by their text atom.
+#[derive(Clone, Debug)]
+pub enum Constraint {
+    /// Relations with this descriptor must have a complement of one of these kinds.
+    ComplementKind {
+        /// Text of the descriptor.
+        descriptor: String,
+        /// Allowed kinds of complement.
+        kinds: Vec<ValueKind>,
+    },
+    /// Relations with this descriptor must have a subject of one of these kinds.
+    SubjectKind {
+        /// Text of the descriptor.
+        descriptor: String,
+        /// Allowed kinds of subject.
+        kinds: Vec<ValueKind>,
+    },
+    /// Elements of this kind must be the subject of exactly one relation with this descriptor.
+    ExactlyOne {
+        /// Kind of the constrained elements.
+        subject_kind: ElementKind,
+        /// Text of the descriptor.
+        descriptor: String,
+    },
+}
+
+/// Textual syntax used in configuration files: "born: date|integer" for ComplementKind,
+/// "subject of born: abstract" for SubjectKind, "abstract has one named" for ExactlyOne.
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_kinds = |f: &mut fmt::Formatter, kinds: &[ValueKind]| {
+            for (i, kind) in kinds.iter().enumerate() {
+                write!(f, "{}{}", if i == 0 { " " } else { "|" }, kind)?
+            }
+            Ok(())
+        };
+        match self {
+            Constraint::ComplementKind { descriptor, kinds } => {
+                write!(f, "{}:", descriptor)?;
+                write_kinds(f, kinds)
+            }
+            Constraint::SubjectKind { descriptor, kinds } => {
+                write!(f, "subject of {}:", descriptor)?;
+                write_kinds(f, kinds)
+            }
+            Constraint::ExactlyOne {
+                subject_kind,
+                descriptor,
+            } => {
+                let kind = match subject_kind {
+                    ElementKind::Abstract => "abstract",
+                    ElementKind::Atom => "atom",
+                    ElementKind::Relation => "relation",
+                };
+                write!(f, "{} has one {}", kind, descriptor)
+            }
+        }
+    }
+}
+impl FromStr for Constraint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let subject_kinds = [
+            ("abstract", ElementKind::Abstract),
+            ("atom", ElementKind::Atom),
+            ("relation", ElementKind::Relation),
+        ];
+        for &(name, subject_kind) in subject_kinds.iter() {
+            let prefix = format!("{} has one ", name);
+            if s.starts_with(&prefix) {
+                let descriptor = s[prefix.len()..].trim();
+                if descriptor.is_empty() {
+                    break;
+                }
+                return Ok(Constraint::ExactlyOne {
+                    subject_kind,
+                    descriptor: descriptor.to_string(),
+                });
+            }
+        }
+        let subject_prefix = "subject of ";
+        let descriptor_kinds = if s.starts_with(subject_prefix) {
+            &s[subject_prefix.len()..]
+        } else {
+            s
+        };
+        // Descriptors may contain ':', but kinds do not.
+        let colon = descriptor_kinds
+            .rfind(':')
+            .ok_or_else(|| format!("Invalid constraint: {}", s))?;
+        let descriptor = descriptor_kinds[..colon].trim().to_string();
+        if descriptor.is_empty() {
+            return Err(format!("Invalid constraint: {}", s));
+        }
+        let kinds = descriptor_kinds[colon + 1..]
+            .split('|')
+            .map(|kind| kind.trim().parse())
+            .collect::<Result<Vec<ValueKind>, String>>()?;
+        if s.starts_with(subject_prefix) {
+            Ok(Constraint::SubjectKind { descriptor, kinds })
+        } else {
+            Ok(Constraint::ComplementKind { descriptor, kinds })
+        }
+    }
+}
+
+/// Set of constraints, checked on demand or enforced when inserting relations.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    /// Constraints, designated by their position in violations.
+    pub constraints: Vec<Constraint>,
+}
+
+/// Element not following a constraint, designated by its position in the schema.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    /// Position of the constraint in the schema.
+    pub constraint: usize,
+    /// Element not following it.
+    pub element: Index,
+}
+
+impl Database {
+    /// List elements violating the constraints, by constraint then element index.
+    pub fn check_schema(&self, schema: &Schema) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (constraint_i, constraint) in schema.constraints.iter().enumerate() {
+            let mut violated_by = |element| {
+                violations.push(Violation {
+                    constraint: constraint_i,
+                    element,
+                })
+            };
+            match constraint {
+                Constraint::ComplementKind { descriptor, kinds } => {
+                    let descriptor = match self.get_text_atom(descriptor.as_str()) {
+                        Some(descriptor) => descriptor,
+                        None => continue, // No relation can use it
+                    };
+                    for relation in descriptor.descriptor_of().iter() {
+                        if !self.has_complement_of_kind(relation.value(), kinds) {
+                            violated_by(relation.index())
+                        }
+                    }
+                }
+                Constraint::SubjectKind { descriptor, kinds } => {
+                    let descriptor = match self.get_text_atom(descriptor.as_str()) {
+                        Some(descriptor) => descriptor,
+                        None => continue,
+                    };
+                    for relation in descriptor.descriptor_of().iter() {
+                        let subject = self.element(relation.value().subject).unwrap();
+                        if !kinds.contains(&ValueKind::from(subject.value())) {
+                            violated_by(relation.index())
+                        }
+                    }
+                }
+                Constraint::ExactlyOne {
+                    subject_kind,
+                    descriptor,
+                } => {
+                    let descriptor = self.index_of_text_atom(descriptor.as_str());
+                    for element in self.iter() {
+                        if element.value().kind() != *subject_kind {
+                            continue;
+                        }
+                        let nb_relations = element
+                            .subject_of()
+                            .iter()
+                            .filter(|r| Some(r.value().descriptor) == descriptor)
+                            .count();
+                        if nb_relations != 1 {
+                            violated_by(element.index())
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    /// Insert a relation only if it follows the schema.
+    pub fn insert_relation_with_schema(
+        &mut self,
+        relation: Relation,
+        schema: &Schema,
+    ) -> Result<Index, Error> {
+        if !self.relation_violations(&relation, schema)?.is_empty() {
+            return Err(Error::SchemaViolation);
+        }
+        self.insert_relation(relation)
+    }
+
+    /// Positions of the constraints that inserting the relation would violate.
+    /// ExactlyOne constraints cannot be satisfied by one insertion, so they only forbid a second relation.
+    pub fn relation_violations(
+        &self,
+        relation: &Relation,
+        schema: &Schema,
+    ) -> Result<Vec<usize>, Error> {
+        let descriptor_text = match self.element(relation.descriptor)?.value() {
+            Element::Atom(Atom::Text(text)) => Some(text.as_str()),
+            _ => None,
+        };
+        let subject = self.element(relation.subject)?;
+        let follows = |constraint: &Constraint| match constraint {
+            Constraint::ComplementKind { descriptor, kinds } => {
+                descriptor_text != Some(descriptor.as_str())
+                    || self.has_complement_of_kind(relation, kinds)
+            }
+            Constraint::SubjectKind { descriptor, kinds } => {
+                descriptor_text != Some(descriptor.as_str())
+                    || kinds.contains(&ValueKind::from(subject.value()))
+            }
+            Constraint::ExactlyOne {
+                subject_kind,
+                descriptor,
+            } => {
+                descriptor_text != Some(descriptor.as_str())
+                    || subject.value().kind() != *subject_kind
+                    || !subject
+                        .subject_of()
+                        .iter()
+                        .any(|r| r.value().descriptor == relation.descriptor)
+            }
+        };
+        Ok(schema
+            .constraints
+            .iter()
+            .enumerate()
+            .filter(|&(_, constraint)| !follows(constraint))
+            .map(|(constraint_i, _)| constraint_i)
+            .collect())
+    }
+
+    /// Descriptors named by the schema that a new relation of the subject could use.
+    /// Without complement, descriptors are only excluded by ExactlyOne constraints.
+    pub fn schema_descriptors(
+        &self,
+        subject: Index,
+        complement: Option<Index>,
+        schema: &Schema,
+    ) -> Result<Vec<Index>, Error> {
+        let mut descriptors: Vec<Index> = schema
+            .constraints
+            .iter()
+            .map(|constraint| match constraint {
+                Constraint::ComplementKind { descriptor, .. }
+                | Constraint::SubjectKind { descriptor, .. }
+                | Constraint::ExactlyOne { descriptor, .. } => descriptor,
+            })
+            .filter_map(|descriptor| self.index_of_text_atom(descriptor.as_str()))
+            .collect();
+        descriptors.sort();
+        descriptors.dedup();
+        let mut valid = Vec::new();
+        for descriptor in descriptors {
+            let relation = Relation {
+                subject,
+                descriptor,
+                complement,
+            };
+            let violations = self.relation_violations(&relation, schema)?;
+            let is_valid = violations.iter().all(|&i| match schema.constraints[i] {
+                Constraint::ComplementKind { .. } => complement.is_none(),
+                Constraint::SubjectKind { .. } | Constraint::ExactlyOne { .. } => false,
+            });
+            if is_valid {
+                valid.push(descriptor)
+            }
+        }
+        Ok(valid)
+    }
+
+    fn has_complement_of_kind(&self, relation: &Relation, kinds: &[ValueKind]) -> bool {
+        match relation.complement.map(|c| self.element(c)) {
+            Some(Ok(complement)) => kinds.contains(&ValueKind::from(complement.value())),
+            _ => false,
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Date;
+    use super::*;
+
+    #[test]
+    fn schema() {
+        let schema = Schema {
+            constraints: vec![
+                Constraint::ComplementKind {
+                    descriptor: "born".into(),
+                    kinds: vec![ValueKind::Date],
+                },
+                Constraint::ExactlyOne {
+                    subject_kind: ElementKind::Abstract,
+                    descriptor: "named".into(),
+                },
+                Constraint::SubjectKind {
+                    descriptor: "born".into(),
+                    kinds: vec![ValueKind::Abstract],
+                },
+            ],
+        };
+        let mut db = Database::new();
+        let born_i = db.insert_atom(Atom::from("born"));
+        let named_i = db.insert_atom(Atom::from("named"));
+        let joe_i = db.insert_atom(Atom::from("Joe"));
+        let date_i = db.insert_atom(Atom::from(Date::new(1990, 1, 1).unwrap()));
+        let person_i = db.create_abstract_element();
+        let unnamed_i = db.create_abstract_element();
+        let relation = |subject, descriptor, complement| Relation {
+            subject,
+            descriptor,
+            complement: Some(complement),
+        };
+
+        let name_i = db
+            .insert_relation_with_schema(relation(person_i, named_i, joe_i), &schema)
+            .unwrap();
+        let second_name = relation(person_i, named_i, date_i);
+        let result = db.insert_relation_with_schema(second_name.clone(), &schema);
+        assert_eq!(result, Err(Error::SchemaViolation));
+        let bad_birth = relation(person_i, born_i, joe_i);
+        let result = db.insert_relation_with_schema(bad_birth.clone(), &schema);
+        assert_eq!(result, Err(Error::SchemaViolation));
+        assert_eq!(db.relation_violations(&second_name, &schema), Ok(vec![1]));
+        assert_eq!(db.relation_violations(&bad_birth, &schema), Ok(vec![0]));
+        let atom_birth = relation(joe_i, born_i, date_i);
+        assert_eq!(db.relation_violations(&atom_birth, &schema), Ok(vec![2]));
+        {
+            let descriptors =
+                |subject, complement| db.schema_descriptors(subject, complement, &schema);
+            assert_eq!(descriptors(person_i, None), Ok(vec![born_i]));
+            assert_eq!(descriptors(unnamed_i, None), Ok(vec![born_i, named_i]));
+            assert_eq!(descriptors(unnamed_i, Some(joe_i)), Ok(vec![named_i]));
+            assert_eq!(descriptors(joe_i, None), Ok(vec![named_i]));
+        }
+        let birth = relation(person_i, born_i, date_i);
+        assert!(db.insert_relation_with_schema(birth, &schema).is_ok());
+
+        let violation = |constraint, element| Violation {
+            constraint,
+            element,
+        };
+        assert_eq!(db.check_schema(&schema), vec![violation(1, unnamed_i)]);
+        let bad_birth_i = db.insert_relation(bad_birth).unwrap();
+        let second_name_i = db.insert_relation(second_name).unwrap();
+        db.remove_element(name_i).unwrap();
+        assert_eq!(
+            db.check_schema(&schema),
+            vec![violation(0, bad_birth_i), violation(1, unnamed_i)]
+        );
+        db.remove_element(second_name_i).unwrap();
+        let atom_birth_i = db.insert_relation(atom_birth).unwrap();
+        assert_eq!(
+            db.check_schema(&schema),
+            vec![
+                violation(0, bad_birth_i),
+                violation(1, person_i),
+                violation(1, unnamed_i),
+                violation(2, atom_birth_i)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_constraints() {
+        let constraint = |s: &str| s.parse::<Constraint>().map(|c| c.to_string());
+        assert_eq!(constraint("born : date"), Ok("born: date".to_string()));
+        assert_eq!(
+            constraint("at time: integer|date"),
+            Ok("at time: integer|date".to_string())
+        );
+        assert_eq!(constraint("a:b: text"), Ok("a:b: text".to_string()));
+        assert_eq!(
+            constraint("subject of at time:relation|abstract"),
+            Ok("subject of at time: relation|abstract".to_string())
+        );
+        assert_eq!(
+            constraint("abstract has one named"),
+            Ok("abstract has one named".to_string())
+        );
+        assert!(constraint("born: time").is_err());
+        assert!(constraint(": date").is_err());
+        assert!(constraint("named").is_err());
+        assert!(constraint("abstract has one ").is_err());
+        assert!(constraint("subject of : date").is_err());
+    }
+}
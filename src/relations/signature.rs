@@ -0,0 +1,111 @@
+use super::{Database, ElementKind, Error, Index};
+use utils::Set;
+
+/// Shape of an element: its kind, and the descriptors of relations it is the subject of,
+/// with the kind of their complement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+    /// Kind of the element.
+    pub kind: ElementKind,
+    /// Descriptor and complement kind of relations with the element as subject.
+    pub descriptions: Set<(Index, Option<ElementKind>)>,
+}
+
+impl Database {
+    /// Compute the signature of an element.
+    pub fn signature(&self, index: Index) -> Result<Signature, Error> {
+        let element = self.element(index)?;
+        Ok(Signature {
+            kind: element.value().kind(),
+            descriptions: element
+                .subject_of()
+                .iter()
+                .map(|r| {
+                    let complement_kind = r.complement().map(|c| c.value().kind());
+                    (r.descriptor().index(), complement_kind)
+                })
+                .collect(),
+        })
+    }
+
+    /// Elements of the same kind that have at least all the descriptions of the signature.
+    /// Results are sorted by index.
+    pub fn matching_signature(&self, signature: &Signature) -> Vec<Index> {
+        let mut candidates: Option<Set<Index>> = None;
+        for &(descriptor, complement_kind) in signature.descriptions.iter() {
+            let subjects: Set<Index> = match self.element(descriptor) {
+                Ok(descriptor) => descriptor
+                    .descriptor_of()
+                    .iter()
+                    .filter(|r| r.complement().map(|c| c.value().kind()) == complement_kind)
+                    .map(|r| r.subject().index())
+                    .collect(),
+                Err(_) => return Vec::new(),
+            };
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.intersection(&subjects).cloned().collect(),
+                None => subjects,
+            });
+        }
+        let has_kind = |i: &Index| self.element(*i).unwrap().value().kind() == signature.kind;
+        match candidates {
+            Some(candidates) => candidates.iter().cloned().filter(has_kind).collect(),
+            None => self.iter().map(|e| e.index()).filter(has_kind).collect(),
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Relation};
+    use super::*;
+
+    #[test]
+    fn similar() {
+        let mut db = Database::new();
+        let named_i = db.insert_atom(Atom::from("named"));
+        let lives_i = db.insert_atom(Atom::from("lives in"));
+        let city_i = db.create_abstract_element();
+        let (joe_i, jack_i) = {
+            let mut person = |name: &str, with_city: bool| {
+                let person_i = db.create_abstract_element();
+                let name_i = db.insert_atom(Atom::from(name));
+                db.insert_relation(Relation {
+                    subject: person_i,
+                    descriptor: named_i,
+                    complement: Some(name_i),
+                })
+                .unwrap();
+                if with_city {
+                    db.insert_relation(Relation {
+                        subject: person_i,
+                        descriptor: lives_i,
+                        complement: Some(city_i),
+                    })
+                    .unwrap();
+                }
+                person_i
+            };
+            let joe_i = person("Joe", true);
+            let jack_i = person("Jack", true);
+            person("Averell", false);
+            (joe_i, jack_i)
+        };
+
+        let signature = db.signature(joe_i).unwrap();
+        assert_eq!(signature.kind, ElementKind::Abstract);
+        assert_eq!(signature.descriptions.len(), 2);
+        assert_eq!(db.matching_signature(&signature), vec![joe_i, jack_i]);
+
+        let atom_signature = db.signature(named_i).unwrap();
+        assert_eq!(
+            db.matching_signature(&atom_signature).len(),
+            db.iter()
+                .filter(|e| e.value().kind() == ElementKind::Atom)
+                .count()
+        );
+    }
+}
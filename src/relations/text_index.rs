@@ -0,0 +1,162 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem::size_of;
+use std::slice;
+
+use super::Index;
+
+/// Index of text atoms by text, without a copy of the texts.
+/// Texts are stored in elements only. The index maps their hash to candidate atoms,
+/// and candidates are compared to the searched text using the text of the element.
+/// Hashes are keyed per index like HashMap keys, so colliding texts cannot be chosen in advance.
+#[derive(Clone, Debug, Default)]
+pub struct TextIndex {
+    hasher: RandomState,
+    by_hash: HashMap<u64, Candidates>,
+}
+
+/// Atoms with the same text hash. Collisions are rare, so a single atom is stored inline.
+#[derive(Clone, Debug)]
+enum Candidates {
+    One(Index),
+    Many(Vec<Index>),
+}
+
+impl TextIndex {
+    /// Hash equal for a String and its borrowed forms, like HashMap keys.
+    pub fn hash<Q: Hash + ?Sized>(&self, text: &Q) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Index of the atom with this text. text_of gives the text of candidate atoms.
+    pub fn get<'a, Q, F>(&self, text: &Q, text_of: F) -> Option<Index>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: Fn(Index) -> Option<&'a String>,
+    {
+        let candidates = self.by_hash.get(&self.hash(text))?;
+        candidates
+            .as_slice()
+            .iter()
+            .cloned()
+            .find(|&index| text_of(index).map_or(false, |s| s.borrow() == text))
+    }
+    /// Add an atom with a text not already present.
+    pub fn insert(&mut self, text: &str, index: Index) {
+        let hash = self.hash(text);
+        let candidates = match self.by_hash.remove(&hash) {
+            None => Candidates::One(index),
+            Some(Candidates::One(other)) => Candidates::Many(vec![other, index]),
+            Some(Candidates::Many(mut others)) => {
+                others.push(index);
+                Candidates::Many(others)
+            }
+        };
+        self.by_hash.insert(hash, candidates);
+    }
+    /// Remove an atom. Returns false if it was not indexed with this text.
+    pub fn remove(&mut self, text: &str, index: Index) -> bool {
+        let hash = self.hash(text);
+        let (removed, remaining) = match self.by_hash.remove(&hash) {
+            None => (false, None),
+            Some(Candidates::One(i)) if i == index => (true, None),
+            Some(Candidates::One(i)) => (false, Some(Candidates::One(i))),
+            Some(Candidates::Many(mut candidates)) => {
+                let len = candidates.len();
+                candidates.retain(|&i| i != index);
+                let removed = candidates.len() < len;
+                match candidates.len() {
+                    1 => (removed, Some(Candidates::One(candidates[0]))),
+                    _ => (removed, Some(Candidates::Many(candidates))),
+                }
+            }
+        };
+        if let Some(remaining) = remaining {
+            self.by_hash.insert(hash, remaining);
+        }
+        removed
+    }
+
+    /// (hash, index) of all indexed atoms.
+    pub fn entries<'a>(&'a self) -> impl Iterator<Item = (u64, Index)> + 'a {
+        self.by_hash.iter().flat_map(|(&hash, candidates)| {
+            candidates
+                .as_slice()
+                .iter()
+                .map(move |&index| (hash, index))
+        })
+    }
+    /// Approximate size of the index in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let entry = size_of::<u64>() + size_of::<Candidates>();
+        let collisions = self
+            .by_hash
+            .values()
+            .map(|candidates| match *candidates {
+                Candidates::One(_) => 0,
+                Candidates::Many(ref v) => v.capacity() * size_of::<Index>(),
+            })
+            .sum::<usize>();
+        self.by_hash.capacity() * entry + collisions
+    }
+}
+
+impl Candidates {
+    fn as_slice(&self) -> &[Index] {
+        match *self {
+            Candidates::One(ref index) => slice::from_ref(index),
+            Candidates::Many(ref indexes) => indexes,
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Database};
+    use super::*;
+
+    #[test]
+    fn text_index() {
+        let texts = vec!["a".to_string(), "b".to_string()];
+        let text_of = |index: Index| texts.get(index);
+        let mut text_index = TextIndex::default();
+        text_index.insert("a", 0);
+        text_index.insert("b", 1);
+        assert_eq!(text_index.get("a", &text_of), Some(0));
+        assert_eq!(text_index.get(&"b".to_string(), &text_of), Some(1));
+        assert_eq!(text_index.get("c", &text_of), None);
+        assert!(text_index.remove("a", 0));
+        assert!(!text_index.remove("a", 0));
+        assert_eq!(text_index.get("a", &text_of), None);
+        assert_eq!(text_index.entries().count(), 1);
+
+        // Collisions are only possible with the same hash, so test them with the inner table.
+        let hash = text_index.hash("b");
+        let collision = Candidates::Many(vec![3, 1]);
+        text_index.by_hash.insert(hash, collision);
+        assert_eq!(text_index.get("b", &text_of), Some(1));
+        assert!(text_index.remove("b", 3));
+        assert_eq!(text_index.entries().collect::<Vec<_>>(), vec![(hash, 1)]);
+    }
+
+    #[test]
+    fn memory_usage() {
+        let mut db = Database::new();
+        let empty = db.memory_usage();
+        db.insert_atom(Atom::from("Some text"));
+        let usage = db.memory_usage();
+        assert!(usage.texts >= "Some text".len());
+        assert!(usage.elements > empty.elements);
+        assert!(usage.indexes > empty.indexes);
+        assert!(db.text_atom_fuzzy_searcher.memory_usage() > 0);
+        assert_eq!(usage.total(), usage.elements + usage.texts + usage.indexes);
+    }
+}
@@ -0,0 +1,178 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Floating point number usable as an atom: with a total order, and hashable.
+/// -0.0 is stored as 0.0, and all NaNs as the same NaN, so equality is bitwise.
+#[derive(Clone, Copy, Debug)]
+pub struct Float(f64);
+impl Float {
+    /// Float with a canonical zero and NaN.
+    pub fn new(f: f64) -> Self {
+        if f == 0.0 {
+            Float(0.0)
+        } else if f.is_nan() {
+            Float(std::f64::NAN)
+        } else {
+            Float(f)
+        }
+    }
+    /// Value as a f64.
+    pub fn value(self) -> f64 {
+        self.0
+    }
+    /// Integer with the same order as the float: negative floats have their magnitude bits flipped.
+    fn ordered_bits(self) -> i64 {
+        let bits = self.0.to_bits() as i64;
+        if bits < 0 {
+            bits ^ i64::max_value()
+        } else {
+            bits
+        }
+    }
+}
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.ordered_bits() == other.ordered_bits()
+    }
+}
+impl Eq for Float {}
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ordered_bits().hash(state)
+    }
+}
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ordered_bits().cmp(&other.ordered_bits())
+    }
+}
+impl fmt::Display for Float {
+    /// Shortest representation that parses back to the same value.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+impl FromStr for Float {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Float::new).map_err(|_| "invalid float")
+    }
+}
+
+/// Day of the proleptic gregorian calendar, without time zone.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+}
+impl Date {
+    /// None if the day does not exist.
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Date> {
+        let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        if day >= 1 && day <= days_in_month {
+            Some(Date { year, month, day })
+        } else {
+            None
+        }
+    }
+    /// Year, as in ISO 8601: 0 is 1 BC.
+    pub fn year(self) -> i32 {
+        self.year
+    }
+    /// Month, from 1 to 12.
+    pub fn month(self) -> u8 {
+        self.month
+    }
+    /// Day of the month, from 1.
+    pub fn day(self) -> u8 {
+        self.day
+    }
+}
+impl fmt::Display for Date {
+    /// ISO 8601 format: 2019-07-14.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.year < 0 {
+            write!(f, "-")?
+        }
+        write!(
+            f,
+            "{:04}-{:02}-{:02}",
+            self.year.abs(),
+            self.month,
+            self.day
+        )
+    }
+}
+impl FromStr for Date {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Split from the end, as the year may be negative.
+        let mut fields = s.rsplitn(3, '-');
+        let (day, month, year) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(day), Some(month), Some(year)) => (day, month, year),
+            _ => return Err("date must be year-month-day"),
+        };
+        match (year.parse(), month.parse(), day.parse()) {
+            (Ok(year), Ok(month), Ok(day)) => Date::new(year, month, day).ok_or("invalid day"),
+            _ => Err("invalid date number"),
+        }
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float() {
+        assert_eq!(Float::new(-0.0), Float::new(0.0));
+        assert_eq!(Float::new(std::f64::NAN), Float::new(-std::f64::NAN));
+        let mut floats: Vec<Float> = [2.5, -1.0, 0.0, -1e300, 1e-300, std::f64::INFINITY]
+            .iter()
+            .map(|&f| Float::new(f))
+            .collect();
+        floats.sort();
+        let sorted: Vec<f64> = floats.iter().map(|f| f.value()).collect();
+        assert_eq!(sorted, vec![-1e300, -1.0, 0.0, 1e-300, 2.5, std::f64::INFINITY]);
+        for f in floats {
+            assert_eq!(f.to_string().parse::<Float>(), Ok(f));
+        }
+        assert!("one".parse::<Float>().is_err());
+    }
+
+    #[test]
+    fn date() {
+        assert!(Date::new(2019, 2, 29).is_none());
+        assert!(Date::new(2000, 2, 29).is_some());
+        assert!(Date::new(1900, 2, 29).is_none());
+        assert!(Date::new(2019, 13, 1).is_none());
+        assert!(Date::new(2019, 4, 31).is_none());
+        let date = Date::new(2019, 7, 14).unwrap();
+        assert_eq!(date.to_string(), "2019-07-14");
+        assert_eq!("2019-07-14".parse(), Ok(date));
+        let ides = Date::new(-44, 3, 15).unwrap();
+        assert_eq!(ides.to_string(), "-0044-03-15");
+        assert_eq!("-0044-03-15".parse(), Ok(ides));
+        assert!(ides < date);
+        assert!("2019-07".parse::<Date>().is_err());
+        assert!("2019-02-30".parse::<Date>().is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Atom, Database, Index, Relation};
+
+/// Descriptors of relations created for notes, designated by their text.
+#[derive(Debug, Clone)]
+pub struct VaultDescriptors {
+    /// Relation "note named title", title being the file name without extension.
+    pub named: String,
+    /// Relation "note links_to target" for each [[target]] link.
+    pub links_to: String,
+}
+impl Default for VaultDescriptors {
+    fn default() -> Self {
+        VaultDescriptors {
+            named: "named".into(),
+            links_to: "links to".into(),
+        }
+    }
+}
+
+/// Import a directory of Markdown notes with [[wiki-links]], like an Obsidian vault.
+/// Each note and each link target without a note becomes an abstract element with a name.
+/// Inline #tags become relations "note tag".
+/// Elements are always created, so importing twice duplicates notes.
+/// Links designate notes by file name, so notes in different directories must have different names.
+/// Returns the number of notes, not counting link targets without a note.
+pub fn import_vault(
+    database: &mut Database,
+    directory: &Path,
+    descriptors: &VaultDescriptors,
+) -> Result<usize, String> {
+    let mut files = Vec::new();
+    list_markdown_files(directory, &mut files)
+        .map_err(|e| format!("Cannot list files of {}: {}", directory.display(), e))?;
+    files.sort();
+    // Read everything before modifying the database, so that errors leave it unchanged.
+    let mut texts = Vec::with_capacity(files.len());
+    let mut file_of_title: HashMap<String, &Path> = HashMap::new();
+    for file in files.iter() {
+        let title = file.file_stem().unwrap().to_string_lossy().into_owned();
+        if let Some(other) = file_of_title.insert(title.clone(), file) {
+            return Err(format!(
+                "Notes with the same name: {} and {}",
+                other.display(),
+                file.display()
+            ));
+        }
+        let text = fs::read_to_string(file)
+            .map_err(|e| format!("Cannot read note {}: {}", file.display(), e))?;
+        texts.push((title, text))
+    }
+
+    let named = database.insert_atom(Atom::from(descriptors.named.as_str()));
+    let links_to = database.insert_atom(Atom::from(descriptors.links_to.as_str()));
+    let mut notes: HashMap<String, Index> = HashMap::new();
+    {
+        let mut note_of_title = |database: &mut Database, title: &str| -> Index {
+            *notes.entry(title.to_string()).or_insert_with(|| {
+                let note = database.create_abstract_element();
+                let name = database.insert_atom(Atom::from(title));
+                database.insert_relation(relation(note, named, Some(name))).unwrap();
+                note
+            })
+        };
+        // Create all notes before links, so that links to existing notes are resolved.
+        let note_of_file: Vec<Index> = texts
+            .iter()
+            .map(|(title, _)| note_of_title(database, title))
+            .collect();
+        for (&note, (_, text)) in note_of_file.iter().zip(texts.iter()) {
+            let content = parse_note(text);
+            for tag in content.tags {
+                let tag = database.insert_atom(Atom::from(tag));
+                database.insert_relation(relation(note, tag, None)).unwrap();
+            }
+            for link in content.links {
+                let target = note_of_title(database, &link);
+                database.insert_relation(relation(note, links_to, Some(target))).unwrap();
+            }
+        }
+    }
+    Ok(texts.len())
+}
+
+fn relation(subject: Index, descriptor: Index, complement: Option<Index>) -> Relation {
+    Relation {
+        subject,
+        descriptor,
+        complement,
+    }
+}
+
+fn list_markdown_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_hidden = path.file_name().map_or(false, |n| n.to_string_lossy().starts_with('.'));
+        if is_hidden {
+            // Vault settings (.obsidian), version control, trash
+            continue;
+        }
+        if entry.file_type()?.is_symlink() && path.is_dir() {
+            continue; // May link to a parent directory, and notes would be imported twice anyway
+        }
+        if path.is_dir() {
+            list_markdown_files(&path, files)?
+        } else if path.extension().map_or(false, |e| e == "md") {
+            files.push(path)
+        }
+    }
+    Ok(())
+}
+
+/// Tags and link targets of a note, in order of first appearance.
+#[derive(Debug, Default, PartialEq)]
+struct NoteContent {
+    tags: Vec<String>,
+    links: Vec<String>,
+}
+
+/// Extract #tags and [[target|alias]] links, ignoring fenced code blocks.
+/// Links to headings of a note are links to the note, and path-like targets designate a file name.
+fn parse_note(text: &str) -> NoteContent {
+    let mut content = NoteContent::default();
+    let mut in_code_block = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            let end = match rest[start..].find("]]") {
+                Some(end) => start + end,
+                None => break,
+            };
+            let target = rest[start + 2..end]
+                .split(|c: char| c == '|' || c == '#')
+                .next()
+                .unwrap();
+            let target = target.rsplit('/').next().unwrap().trim();
+            if !target.is_empty() && !content.links.iter().any(|l| l == target) {
+                content.links.push(target.to_string())
+            }
+            rest = &rest[end + 2..];
+        }
+        let is_tag_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '/';
+        let mut previous = ' ';
+        for (i, c) in line.char_indices() {
+            if c == '#' && previous.is_whitespace() {
+                let after = &line[i + 1..];
+                let tag = &after[..after.find(|c: char| !is_tag_char(c)).unwrap_or(after.len())];
+                // Tags must have a non numeric char, "#1" is not a tag
+                let is_tag = tag.chars().any(|c| !c.is_numeric());
+                if is_tag && !content.tags.iter().any(|t| t == tag) {
+                    content.tags.push(tag.to_string())
+                }
+            }
+            previous = c
+        }
+    }
+    content
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn note_parsing() {
+        let text = "# Title\n\
+                    Met [[Joe]] and [[people/Jack|J.]] at #work, see [[Joe#Contact]].\n\
+                    Issue #12 #project/rett\n\
+                    ```\n\
+                    #not-a-tag [[Not a link]]\n\
+                    ```\n\
+                    [[unterminated";
+        let content = parse_note(text);
+        assert_eq!(content.links, vec!["Joe", "Jack"]);
+        assert_eq!(content.tags, vec!["work", "project/rett"]);
+    }
+
+    #[test]
+    fn vault_import() {
+        let dir = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("people")).unwrap();
+        std::fs::create_dir_all(dir.join(".obsidian")).unwrap();
+        std::fs::write(dir.join("Acme.md"), "Company of [[Joe]] #company").unwrap();
+        std::fs::write(dir.join("people/Joe.md"), "Works at [[Acme]], knows [[Jack]]").unwrap();
+        std::fs::write(dir.join(".obsidian/config.md"), "[[Ignored]]").unwrap();
+        std::fs::write(dir.join("notes.txt"), "[[Ignored]]").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("people/loop")).unwrap();
+        let mut db = Database::new();
+        let result = import_vault(&mut db, &dir, &VaultDescriptors::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(2)); // Jack is a link target without note
+
+        let note = |name: &str| {
+            let name = db.index_of_text_atom(name).unwrap();
+            let named = db.index_of_text_atom("named").unwrap();
+            let naming = db.element(name).unwrap();
+            let relation = naming.complement_of().iter().find(|r| r.value().descriptor == named);
+            relation.unwrap().value().subject
+        };
+        let (acme, joe, jack) = (note("Acme"), note("Joe"), note("Jack"));
+        let links_to = db.index_of_text_atom("links to").unwrap();
+        let company = db.index_of_text_atom("company").unwrap();
+        assert!(db.index_of_relation(&relation(acme, links_to, Some(joe))).is_some());
+        assert!(db.index_of_relation(&relation(joe, links_to, Some(acme))).is_some());
+        assert!(db.index_of_relation(&relation(joe, links_to, Some(jack))).is_some());
+        assert!(db.index_of_relation(&relation(acme, company, None)).is_some());
+        assert!(db.index_of_text_atom("Ignored").is_none());
+    }
+
+    #[test]
+    fn vault_duplicate_names() {
+        let dir = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("people")).unwrap();
+        std::fs::write(dir.join("Joe.md"), "[[Acme]]").unwrap();
+        std::fs::write(dir.join("people/Joe.md"), "").unwrap();
+        let mut db = Database::new();
+        let result = import_vault(&mut db, &dir, &VaultDescriptors::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.unwrap_err().starts_with("Notes with the same name"));
+        assert_eq!(db.iter().count(), 0);
+    }
+}
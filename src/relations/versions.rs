@@ -0,0 +1,159 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{read_database_from_file, write_database_to_file, Change, Database};
+
+/// Versions are compressed, as they are rarely read.
+const VERSION_EXTENSION: &'static str = "gz";
+
+/// Named copy of a database, written once and never modified.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Version {
+    /// Name given when the version was created, used as file name.
+    pub name: String,
+    /// Seconds since the unix epoch.
+    pub created: u64,
+}
+
+/// Directory of the versions of a database file: "db.txt" has its versions in "db.txt.versions".
+pub fn versions_directory(database_file: &Path) -> PathBuf {
+    let mut path = database_file.as_os_str().to_owned();
+    path.push(".versions");
+    PathBuf::from(path)
+}
+
+/// Names are file names, so they are restricted to alphanumeric characters, '-' and '_'.
+fn version_file(directory: &Path, name: &str) -> Result<PathBuf, String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(format!("Invalid version name: {}", name));
+    }
+    Ok(directory.join(format!("{}.{}", name, VERSION_EXTENSION)))
+}
+
+/// Versions stored in a directory, most recent first. A missing directory has no versions.
+pub fn list_versions(directory: &Path) -> Result<Vec<Version>, String> {
+    let error = |e: io::Error| format!("Cannot list versions in {}: {}", directory.display(), e);
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(error(e)),
+    };
+    let mut versions = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(error)?.path();
+        if path.extension().map_or(true, |e| e != VERSION_EXTENSION) {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let created = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(error)?;
+        let created = created
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        versions.push(Version { name, created })
+    }
+    versions.sort_by(|a, b| (b.created, &a.name).cmp(&(a.created, &b.name)));
+    Ok(versions)
+}
+
+impl Database {
+    /// Store the current content as a named version in the directory.
+    /// Versions are immutable: tagging with the name of an existing version fails.
+    pub fn tag_version(&self, directory: &Path, name: &str) -> Result<Version, String> {
+        let file = version_file(directory, name)?;
+        if file.exists() {
+            return Err(format!("Version already exists: {}", name));
+        }
+        fs::create_dir_all(directory)
+            .map_err(|e| format!("Cannot create directory {}: {}", directory.display(), e))?;
+        write_database_to_file(&file, self)?;
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(Version {
+            name: name.to_string(),
+            created,
+        })
+    }
+
+    /// Read a named version from the directory.
+    pub fn load_version(directory: &Path, name: &str) -> Result<Database, String> {
+        read_database_from_file(&version_file(directory, name)?)
+    }
+
+    /// Replace the content of the database by the one of another, like a loaded version.
+    /// Observers and author are kept. Observers are notified of a single Change::Reset.
+    pub fn restore(&mut self, other: Database) {
+        let observers = mem::replace(&mut self.observers, Vec::new());
+        let author = self.author.take();
+        let revision = self.revision;
+        *self = other;
+        self.observers = observers;
+        self.author = author;
+        self.revision = revision;
+        self.notify(Change::Reset)
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Atom;
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[test]
+    fn versions() {
+        let (mut db, e) = fixture! {
+            joe = abstract_() { related("named", "Joe") };
+        };
+        let dir = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        let tagged = db.tag_version(&dir, "v1");
+        let retagged = db.tag_version(&dir, "v1");
+        let invalid = db.tag_version(&dir, "../v1");
+        db.insert_atom(Atom::from("Jack"));
+        db.tag_version(&dir, "v2").unwrap();
+        let versions = list_versions(&dir);
+        let loaded = Database::load_version(&dir, "v1");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tagged.map(|v| v.name), Ok("v1".to_string()));
+        assert!(retagged.is_err());
+        assert!(invalid.is_err());
+        let names: Vec<String> = versions.unwrap().into_iter().map(|v| v.name).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"v1".to_string()) && names.contains(&"v2".to_string()));
+        assert_eq!(list_versions(&dir), Ok(Vec::new()));
+
+        let resets = Arc::new(AtomicUsize::new(0));
+        {
+            let resets = resets.clone();
+            db.on_change(move |_, change| {
+                if change == Change::Reset {
+                    resets.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+        let revision = db.revision();
+        db.restore(loaded.unwrap());
+        assert!(db.revision() > revision);
+        assert_eq!(resets.load(Ordering::SeqCst), 1);
+        assert!(db.element(e.joe).is_ok());
+        assert!(db.index_of_text_atom("Joe").is_some());
+        assert!(db.index_of_text_atom("Jack").is_none());
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{Atom, Database, Element, Index, UNIQUE_DESCRIPTOR_TAG};
+
+/// Elements referenced by more relations are hard to display and slow to update.
+pub const MAX_REFERENCES: usize = 10_000;
+/// Text atoms longer than this (in bytes) are probably pasted documents.
+pub const MAX_ATOM_TEXT_LEN: usize = 1 << 20;
+/// Relations nested deeper than this (relation about a relation about...) are unreadable.
+pub const MAX_RELATION_DEPTH: usize = 8;
+
+/// Structure that degrades performance or readability, often a modeling mistake.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Warning {
+    /// Element used by more than MAX_REFERENCES relations.
+    ManyReferences {
+        /// Element.
+        index: Index,
+        /// Number of relations using it.
+        count: usize,
+    },
+    /// Text atom longer than MAX_ATOM_TEXT_LEN.
+    LargeAtom {
+        /// Atom.
+        index: Index,
+        /// Length in bytes.
+        len: usize,
+    },
+    /// Descriptor of a single relation, maybe a typo of another descriptor.
+    DescriptorUsedOnce {
+        /// Descriptor.
+        index: Index,
+    },
+    /// Relation nested deeper than MAX_RELATION_DEPTH.
+    DeepRelation {
+        /// Relation.
+        index: Index,
+        /// Nesting depth.
+        depth: usize,
+    },
+    /// Subject of several relations with a descriptor declared unique.
+    NotUnique {
+        /// Subject.
+        index: Index,
+        /// Unique descriptor.
+        descriptor: Index,
+    },
+}
+impl Warning {
+    /// Element the warning is about.
+    pub fn index(&self) -> Index {
+        match *self {
+            Warning::ManyReferences { index, .. } => index,
+            Warning::LargeAtom { index, .. } => index,
+            Warning::DescriptorUsedOnce { index } => index,
+            Warning::DeepRelation { index, .. } => index,
+            Warning::NotUnique { index, .. } => index,
+        }
+    }
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::ManyReferences { index, count } => {
+                write!(f, "element {} is referenced by {} relations", index, count)
+            }
+            Warning::LargeAtom { index, len } => write!(f, "atom {} has {} bytes", index, len),
+            Warning::DescriptorUsedOnce { index } => {
+                write!(f, "descriptor atom {} is used only once", index)
+            }
+            Warning::DeepRelation { index, depth } => {
+                write!(f, "relation {} is nested {} levels deep", index, depth)
+            }
+            Warning::NotUnique { index, descriptor } => write!(
+                f,
+                "element {} has multiple relations with unique descriptor {}",
+                index, descriptor
+            ),
+        }
+    }
+}
+
+impl Database {
+    /// Detect structures exceeding the soft limits.
+    /// Also reports unique descriptors used multiple times by a subject,
+    /// which can only come from relations created before the uniqueness declaration.
+    /// Warnings are sorted by element index.
+    pub fn soft_limit_warnings(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut depths = HashMap::new();
+        let has_unique_descriptors = self.index_of_text_atom(UNIQUE_DESCRIPTOR_TAG).is_some();
+        for element in self.iter() {
+            let index = element.index();
+            let count = element.subject_of().len()
+                + element.descriptor_of().len()
+                + element.complement_of().len();
+            if count > MAX_REFERENCES {
+                warnings.push(Warning::ManyReferences { index, count });
+            }
+            match element.value() {
+                Element::Abstract => (),
+                Element::Atom(atom) => {
+                    if let Atom::Text(s) = atom {
+                        if s.len() > MAX_ATOM_TEXT_LEN {
+                            warnings.push(Warning::LargeAtom {
+                                index,
+                                len: s.len(),
+                            });
+                        }
+                    }
+                    if element.descriptor_of().len() == 1 {
+                        warnings.push(Warning::DescriptorUsedOnce { index });
+                    }
+                }
+                Element::Relation(_) => {
+                    let depth = self.relation_depth(index, &mut depths);
+                    if depth > MAX_RELATION_DEPTH {
+                        warnings.push(Warning::DeepRelation { index, depth });
+                    }
+                }
+            }
+            if has_unique_descriptors {
+                let mut descriptors: Vec<Index> = element
+                    .subject_of()
+                    .iter()
+                    .map(|r| r.descriptor().index())
+                    .filter(|&d| self.is_unique_descriptor(d))
+                    .collect();
+                descriptors.sort_unstable();
+                let mut duplicated: Vec<Index> = descriptors
+                    .windows(2)
+                    .filter(|pair| pair[0] == pair[1])
+                    .map(|pair| pair[0])
+                    .collect();
+                duplicated.dedup();
+                for descriptor in duplicated {
+                    warnings.push(Warning::NotUnique { index, descriptor });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Nesting depth of relations: 0 for non relations, 1 for relations between non relations.
+    /// Memoized in depths. Cycles (corrupted databases) are cut.
+    fn relation_depth(&self, index: Index, depths: &mut HashMap<Index, usize>) -> usize {
+        if let Some(&depth) = depths.get(&index) {
+            return depth;
+        }
+        let relation = match self.elements.get(index).map(|data| &data.value) {
+            Some(Element::Relation(relation)) => relation.clone(),
+            _ => return 0,
+        };
+        depths.insert(index, 0); // Placeholder stopping cycles
+        let depth = 1 + [
+            Some(relation.subject),
+            Some(relation.descriptor),
+            relation.complement,
+        ]
+        .iter()
+        .filter_map(|&component| component)
+        .map(|component| self.relation_depth(component, depths))
+        .max()
+        .unwrap_or(0);
+        depths.insert(index, depth);
+        depth
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::Relation;
+    use super::*;
+
+    #[test]
+    fn warnings() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        let is_i = db.insert_atom(Atom::from("is"));
+        let mut relation_i = db
+            .insert_relation(Relation {
+                subject: object_i,
+                descriptor: is_i,
+                complement: None,
+            })
+            .unwrap();
+        assert_eq!(
+            db.soft_limit_warnings(),
+            vec![Warning::DescriptorUsedOnce { index: is_i }]
+        );
+
+        // Chain of relations annotating the previous one
+        for _ in 0..MAX_RELATION_DEPTH {
+            relation_i = db
+                .insert_relation(Relation {
+                    subject: relation_i,
+                    descriptor: is_i,
+                    complement: None,
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            db.soft_limit_warnings(),
+            vec![Warning::DeepRelation {
+                index: relation_i,
+                depth: MAX_RELATION_DEPTH + 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn not_unique() {
+        let mut db = Database::new();
+        let object_i = db.create_abstract_element();
+        let is_i = db.insert_atom(Atom::from("is"));
+        for text in ["big", "small"].iter() {
+            let complement_i = db.insert_atom(Atom::from(*text));
+            db.insert_relation(Relation {
+                subject: object_i,
+                descriptor: is_i,
+                complement: Some(complement_i),
+            })
+            .unwrap();
+        }
+        assert!(db.soft_limit_warnings().is_empty());
+
+        // Declared unique after the fact
+        let unique_i = db.insert_atom(Atom::from(UNIQUE_DESCRIPTOR_TAG));
+        db.insert_relation(Relation {
+            subject: is_i,
+            descriptor: unique_i,
+            complement: None,
+        })
+        .unwrap();
+        assert_eq!(
+            db.soft_limit_warnings(),
+            vec![
+                Warning::NotUnique {
+                    index: object_i,
+                    descriptor: is_i,
+                },
+                Warning::DescriptorUsedOnce { index: unique_i },
+            ]
+        );
+    }
+}
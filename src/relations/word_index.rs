@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use super::Index;
+use utils::Set;
+
+/// Words of a text: maximal sequences of alphanumeric chars, in lowercase, sorted and unique.
+pub fn words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Index of text atoms by the words they contain.
+/// The fuzzy searcher finds short texts like names, this finds words in long texts like descriptions.
+#[derive(Clone, Debug, Default)]
+pub struct WordIndex {
+    atoms_with_word: HashMap<String, Set<Index>>,
+}
+
+impl WordIndex {
+    pub fn insert(&mut self, text: &str, index: Index) {
+        for word in words(text) {
+            self.atoms_with_word
+                .entry(word)
+                .or_insert_with(Set::new)
+                .insert(index)
+        }
+    }
+    /// Remove an atom, text being the one used for insertion.
+    pub fn remove(&mut self, text: &str, index: Index) {
+        for word in words(text) {
+            let now_empty = match self.atoms_with_word.get_mut(&word) {
+                Some(atoms) => {
+                    atoms.remove(&index);
+                    atoms.is_empty()
+                }
+                None => false,
+            };
+            if now_empty {
+                self.atoms_with_word.remove(&word);
+            }
+        }
+    }
+
+    /// Atoms containing all words of the query, by increasing index. Empty for a query without words.
+    pub fn find(&self, query: &str) -> Vec<Index> {
+        let mut sets = Vec::new();
+        for word in words(query) {
+            match self.atoms_with_word.get(&word) {
+                Some(atoms) => sets.push(atoms),
+                None => return Vec::new(),
+            }
+        }
+        sets.sort_by_key(|atoms| atoms.len());
+        match sets.split_first() {
+            Some((smallest, others)) => smallest
+                .iter()
+                .cloned()
+                .filter(|index| others.iter().all(|atoms| atoms.contains(index)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Approximate size of the index in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let entry = size_of::<String>() + size_of::<Set<Index>>();
+        self.atoms_with_word
+            .iter()
+            .map(|(word, atoms)| entry + word.capacity() + atoms.len() * size_of::<Index>())
+            .sum()
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{Atom, Database, DESCRIPTION_DESCRIPTOR};
+    use super::*;
+
+    #[test]
+    fn word_splitting() {
+        assert_eq!(
+            words("Écrit en 1984, écrit à Paris."),
+            vec!["1984", "en", "paris", "à", "écrit"]
+        );
+        assert!(words(" -- ").is_empty());
+    }
+
+    #[test]
+    fn find_in_descriptions() {
+        let (mut db, _) = fixture! {
+            walk = abstract_() {
+                related(DESCRIPTION_DESCRIPTOR, "A long walk in the park"),
+                related("named", "Park walk")
+            };
+            park = abstract_() { related(DESCRIPTION_DESCRIPTOR, "Park") };
+            walking = atom("Walking") {};
+        };
+        let text_i = |db: &Database, text: &str| db.index_of_atom(&Atom::from(text)).unwrap();
+        let long_i = text_i(&db, "A long walk in the park");
+        let short_i = text_i(&db, "Park");
+        let indexes = |db: &Database, query: &str| -> Vec<Index> {
+            db.find_in_descriptions(query)
+                .iter()
+                .map(|atom| atom.index())
+                .collect()
+        };
+        assert_eq!(indexes(&db, "park"), vec![long_i, short_i]);
+        assert_eq!(indexes(&db, "WALK, park"), vec![long_i]);
+        assert!(indexes(&db, "walk run").is_empty());
+        assert!(indexes(&db, "").is_empty());
+        db.replace_atom_value(long_i, Atom::from("A short stroll"))
+            .unwrap();
+        assert_eq!(indexes(&db, "park"), vec![short_i]);
+        assert!(indexes(&db, "walk").is_empty());
+        assert_eq!(
+            db.text_atom_word_index.find("walk"),
+            vec![text_i(&db, "Park walk")]
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::iter::FromIterator;
+use std::iter::{Enumerate, FromIterator, Peekable};
+use std::mem::size_of;
 use std::ops;
+use std::slice;
+use std::str::FromStr;
+use std::vec;
 
 /// Remove prefix and return tail of string if successful
 pub fn remove_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
@@ -12,53 +17,194 @@ pub fn remove_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
     }
 }
 
+/// Text ordering used to sort names for display.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Collation {
+    /// Unicode code point order: uppercase first, accented letters at the end.
+    Binary,
+    /// Ignore case and accents first, like in a dictionary. Ties use binary order.
+    Locale,
+}
+impl Collation {
+    /// Order of two texts.
+    pub fn compare(self, lhs: &str, rhs: &str) -> Ordering {
+        match self {
+            Collation::Binary => lhs.cmp(rhs),
+            Collation::Locale => Iterator::cmp(folded_chars(lhs), folded_chars(rhs))
+                .then_with(|| lhs.cmp(rhs)),
+        }
+    }
+}
+impl FromStr for Collation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(Collation::Binary),
+            "locale" => Ok(Collation::Locale),
+            _ => Err(format!("Unknown collation: {}", s)),
+        }
+    }
+}
+/// Lowercase chars without diacritics. Covers latin letters used in western europe.
+fn folded_chars<'a>(s: &'a str) -> impl Iterator<Item = char> + 'a {
+    s.chars().flat_map(|c| c.to_lowercase()).flat_map(|c| {
+        let folded: &'static str = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => "a",
+            'æ' => "ae",
+            'ç' => "c",
+            'è' | 'é' | 'ê' | 'ë' => "e",
+            'ì' | 'í' | 'î' | 'ï' => "i",
+            'ñ' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+            'œ' => "oe",
+            'ß' => "ss",
+            'ù' | 'ú' | 'û' | 'ü' => "u",
+            'ý' | 'ÿ' => "y",
+            _ => "",
+        };
+        // Chars without a folding are kept as is.
+        let keep = if folded.is_empty() { Some(c) } else { None };
+        folded.chars().chain(keep)
+    })
+}
+
 /// Vector where elements never change indexes.
-/// Removal generate holes.
+/// Removal generate holes, which are reused by later insertions.
+#[derive(Clone)]
 pub struct SlotVec<T> {
     inner: Vec<Option<T>>,
+    free: Vec<usize>, // Indexes of holes, next one to fill at the end
 }
 impl<T> SlotVec<T> {
+    /// Empty vector.
     pub fn new() -> Self {
-        SlotVec { inner: Vec::new() }
+        SlotVec {
+            inner: Vec::new(),
+            free: Vec::new(),
+        }
     }
+    /// True if there is an element at this index.
     pub fn valid(&self, i: usize) -> bool {
         i < self.inner.len() && self.inner[i].is_some()
     }
+    /// Element at this index, if any.
     pub fn get(&self, i: usize) -> Option<&T> {
         match self.inner.get(i) {
             Some(&Some(ref e)) => Some(e),
             _ => None,
         }
     }
+    /// Mutable element at this index, if any.
     pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
         match self.inner.get_mut(i) {
             Some(&mut Some(ref mut e)) => Some(e),
             _ => None,
         }
     }
+    /// Insert an element, reusing a hole if possible. Returns its index.
     pub fn insert(&mut self, e: T) -> usize {
-        // Find unused index
-        for index in 0..self.inner.len() {
-            let cell = &mut self.inner[index];
-            if cell.is_none() {
-                *cell = Some(e);
-                return index;
-            }
+        // Reuse a hole
+        if let Some(index) = self.free.pop() {
+            self.inner[index] = Some(e);
+            return index;
         }
         // Or allocate new one
         let index = self.inner.len();
         self.inner.push(Some(e));
         index
     }
+    /// Remove an element, making its slot available to insertions.
     pub fn remove(&mut self, i: usize) -> Option<T> {
-        match self.inner.get_mut(i) {
+        let removed = match self.inner.get_mut(i) {
             Some(slot) => slot.take(),
             None => None,
+        };
+        if removed.is_some() {
+            self.free.push(i);
         }
+        removed
+    }
+    /// Remove an element, keeping its slot reserved: it is not reused until released.
+    pub fn remove_reserved(&mut self, i: usize) -> Option<T> {
+        self.inner.get_mut(i).and_then(|slot| slot.take())
+    }
+    /// Fill a reserved slot again.
+    pub fn restore(&mut self, i: usize, e: T) {
+        assert!(self.inner[i].is_none(), "restoring in a used slot");
+        self.inner[i] = Some(e)
+    }
+    /// Make a reserved slot available to insertions.
+    pub fn release(&mut self, i: usize) {
+        assert!(self.inner[i].is_none(), "releasing a used slot");
+        debug_assert!(!self.free.contains(&i), "releasing a free slot");
+        self.free.push(i)
     }
+    /// Number of slots, used or not: indexes are lower than this.
     pub fn capacity(&self) -> usize {
         self.inner.len()
     }
+    /// Allocate space for additional elements, not counting holes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional.saturating_sub(self.free.len()))
+    }
+    /// Iterate on (index, element) pairs, skipping holes.
+    pub fn iter(&self) -> SlotVecIter<T> {
+        SlotVecIter {
+            inner: self.inner.iter().enumerate(),
+        }
+    }
+    /// Iterate on (index, mutable element) pairs, skipping holes.
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)> + 'a {
+        self.inner
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.as_mut().map(|e| (i, e)))
+    }
+    /// Indexes of valid elements.
+    pub fn indices<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+        self.iter().map(|(i, _)| i)
+    }
+    /// Remove elements for which the predicate is false. Kept elements do not move.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &T) -> bool,
+    {
+        for i in 0..self.inner.len() {
+            let keep = match self.inner[i] {
+                Some(ref e) => f(i, e),
+                None => true,
+            };
+            if !keep {
+                self.remove(i);
+            }
+        }
+    }
+    /// Remove all elements, returning (index, element) pairs.
+    pub fn drain(&mut self) -> impl Iterator<Item = (usize, T)> {
+        self.free.clear();
+        std::mem::replace(&mut self.inner, Vec::new())
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.map(|e| (i, e)))
+    }
+}
+/// Iterator on (index, element) pairs of a SlotVec, skipping holes.
+pub struct SlotVecIter<'a, T: 'a> {
+    inner: Enumerate<slice::Iter<'a, Option<T>>>,
+}
+impl<'a, T> Iterator for SlotVecIter<'a, T> {
+    type Item = (usize, &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((i, slot)) = self.inner.next() {
+            if let Some(ref e) = *slot {
+                return Some((i, e));
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
 }
 impl<T> ops::Index<usize> for SlotVec<T> {
     type Output = T;
@@ -78,7 +224,9 @@ impl<T> AsRef<[Option<T>]> for SlotVec<T> {
 }
 impl<T> From<Vec<Option<T>>> for SlotVec<T> {
     fn from(inner: Vec<Option<T>>) -> Self {
-        SlotVec { inner }
+        // Lowest holes are filled first
+        let free = (0..inner.len()).rev().filter(|&i| inner[i].is_none()).collect();
+        SlotVec { inner, free }
     }
 }
 impl<T> FromIterator<Option<T>> for SlotVec<T> {
@@ -92,17 +240,43 @@ impl<T> FromIterator<Option<T>> for SlotVec<T> {
 
 /// Set based on a sorted vector.
 /// Elements are unique.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Set<T: Ord> {
     inner: Vec<T>,
 }
 impl<T: Ord> Set<T> {
+    /// Empty set.
     pub fn new() -> Self {
         Set { inner: Vec::new() }
     }
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// True if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Iterate in increasing order.
+    pub fn iter(&self) -> slice::Iter<T> {
+        self.inner.iter()
+    }
+    /// True if the element is in the set.
     pub fn contains(&self, e: &T) -> bool {
         self.inner.binary_search(e).is_ok()
     }
+    /// Elements in self or other, in increasing order.
+    pub fn union<'a>(&'a self, other: &'a Set<T>) -> SetOperation<'a, T> {
+        SetOperation::new(self, other, true, true, true)
+    }
+    /// Elements in both self and other, in increasing order.
+    pub fn intersection<'a>(&'a self, other: &'a Set<T>) -> SetOperation<'a, T> {
+        SetOperation::new(self, other, false, false, true)
+    }
+    /// Elements in self but not in other, in increasing order.
+    pub fn difference<'a>(&'a self, other: &'a Set<T>) -> SetOperation<'a, T> {
+        SetOperation::new(self, other, true, false, false)
+    }
     /// Insert element if not present.
     pub fn insert(&mut self, e: T) {
         if let Err(insertion_index) = self.inner.binary_search(&e) {
@@ -129,6 +303,13 @@ impl<T: Ord> From<Vec<T>> for Set<T> {
         Set { inner }
     }
 }
+impl<T: Ord> IntoIterator for Set<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
 impl<T: Ord> FromIterator<T> for Set<T> {
     fn from_iter<I>(iter: I) -> Self
     where
@@ -138,8 +319,68 @@ impl<T: Ord> FromIterator<T> for Set<T> {
     }
 }
 
+/// Iterator on the result of a set operation, computed by merging both sorted vectors.
+pub struct SetOperation<'a, T: 'a> {
+    lhs: Peekable<slice::Iter<'a, T>>,
+    rhs: Peekable<slice::Iter<'a, T>>,
+    keep_lhs_only: bool,
+    keep_rhs_only: bool,
+    keep_both: bool,
+}
+impl<'a, T: Ord> SetOperation<'a, T> {
+    fn new(
+        lhs: &'a Set<T>,
+        rhs: &'a Set<T>,
+        keep_lhs_only: bool,
+        keep_rhs_only: bool,
+        keep_both: bool,
+    ) -> Self {
+        SetOperation {
+            lhs: lhs.inner.iter().peekable(),
+            rhs: rhs.inner.iter().peekable(),
+            keep_lhs_only,
+            keep_rhs_only,
+            keep_both,
+        }
+    }
+}
+impl<'a, T: Ord> Iterator for SetOperation<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.lhs.peek(), self.rhs.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(l), Some(r)) => l.cmp(r),
+            };
+            match order {
+                Ordering::Less => {
+                    let e = self.lhs.next();
+                    if self.keep_lhs_only {
+                        return e;
+                    }
+                }
+                Ordering::Greater => {
+                    let e = self.rhs.next();
+                    if self.keep_rhs_only {
+                        return e;
+                    }
+                }
+                Ordering::Equal => {
+                    self.rhs.next();
+                    let e = self.lhs.next();
+                    if self.keep_both {
+                        return e;
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Map based on a sorted vector.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Map<K: Ord, V> {
     inner: Vec<(K, V)>,
 }
@@ -191,6 +432,10 @@ impl<K: Ord, V> Map<K, V> {
             None
         }
     }
+    /// Keep only the entries for which the predicate is true.
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&mut self, mut f: F) {
+        self.inner.retain(|&(ref k, ref v)| f(k, v))
+    }
 }
 impl<K: Ord, V> AsRef<[(K, V)]> for Map<K, V> {
     fn as_ref(&self) -> &[(K, V)] {
@@ -218,7 +463,7 @@ impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
 /// The search is based on decomposing strings into [char;3] sequences,
 /// and returning the D with the most associated sequences.
 /// Values are converted to lowercase to improve matching probability.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FuzzySearcher<D: Ord + Clone + Hash> {
     kmers: HashMap<[char; 3], Map<D, usize>>,
 }
@@ -268,6 +513,17 @@ impl<D: Ord + Clone + Hash> FuzzySearcher<D> {
         }
     }
 
+    /// Approximate size of the kmer tables in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let entry = size_of::<[char; 3]>() + size_of::<Map<D, usize>>();
+        let maps = self
+            .kmers
+            .values()
+            .map(|map| map.inner.capacity() * size_of::<(D, usize)>())
+            .sum::<usize>();
+        self.kmers.capacity() * entry + maps
+    }
+
     pub fn matches(&self, s: &str) -> Vec<(D, usize)> {
         let chars = to_lowercase_char_vec(s);
         // Accumulate number of matching kmer to each defined D.
@@ -298,6 +554,77 @@ fn to_lowercase_char_vec(s: &str) -> Vec<char> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test::Bencher;
+
+    #[test]
+    fn collation() {
+        let mut names = vec!["Zoé", "élan", "Eric", "abc", "Elan"];
+        names.sort_by(|l, r| Collation::Binary.compare(l, r));
+        assert_eq!(names, vec!["Elan", "Eric", "Zoé", "abc", "élan"]);
+        names.sort_by(|l, r| Collation::Locale.compare(l, r));
+        assert_eq!(names, vec!["abc", "Elan", "élan", "Eric", "Zoé"]);
+    }
+
+    #[test]
+    fn set_operations() {
+        let lhs: Set<_> = vec![5, 1, 3, 7].into_iter().collect();
+        let rhs: Set<_> = vec![3, 4, 5, 6].into_iter().collect();
+        assert_eq!(lhs.len(), 4);
+        assert_eq!(lhs.iter().cloned().collect::<Vec<_>>(), vec![1, 3, 5, 7]);
+        let union: Vec<_> = lhs.union(&rhs).cloned().collect();
+        assert_eq!(union, vec![1, 3, 4, 5, 6, 7]);
+        let intersection: Vec<_> = lhs.intersection(&rhs).cloned().collect();
+        assert_eq!(intersection, vec![3, 5]);
+        let difference: Vec<_> = lhs.difference(&rhs).cloned().collect();
+        assert_eq!(difference, vec![1, 7]);
+        assert_eq!(lhs.intersection(&Set::new()).count(), 0);
+    }
+
+    #[test]
+    fn slot_vec() {
+        let mut v: SlotVec<_> = vec![Some(0), None, Some(2), None].into();
+        assert_eq!(v.insert(1), 1);
+        assert_eq!(v.insert(3), 3);
+        assert_eq!(v.insert(4), 4);
+        assert_eq!(v.remove(2), Some(2));
+        assert_eq!(v.remove(2), None); // Hole is only registered once
+        assert_eq!(v.insert(5), 2);
+        assert_eq!(v.insert(6), 5);
+        assert_eq!(v.capacity(), 6);
+        assert_eq!(v.remove_reserved(0), Some(0));
+        assert_eq!(v.insert(7), 6); // Reserved slot is not reused
+        v.restore(0, 8);
+        assert_eq!(v.get(0), Some(&8));
+        assert_eq!(v.remove_reserved(0), Some(8));
+        v.release(0);
+        assert_eq!(v.insert(9), 0);
+    }
+
+    #[test]
+    fn slot_vec_iterators() {
+        let mut v: SlotVec<_> = vec![Some(0), None, Some(2), Some(3)].into();
+        assert_eq!(v.iter().collect::<Vec<_>>(), vec![(0, &0), (2, &2), (3, &3)]);
+        assert_eq!(v.indices().collect::<Vec<_>>(), vec![0, 2, 3]);
+        for (i, e) in v.iter_mut() {
+            *e += i;
+        }
+        v.retain(|i, _| i != 2);
+        assert_eq!(v.iter().collect::<Vec<_>>(), vec![(0, &0), (3, &6)]);
+        assert_eq!(v.insert(10), 2); // Last hole created is filled first
+        assert_eq!(v.drain().collect::<Vec<_>>(), vec![(0, 0), (2, 10), (3, 6)]);
+        assert_eq!(v.capacity(), 0);
+        assert_eq!(v.insert(11), 0);
+    }
+
+    #[bench]
+    fn slot_vec_insert_after_remove(b: &mut Bencher) {
+        // Worst case for hole search: a single hole at the end of a big vector.
+        let mut v: SlotVec<usize> = (0..100_000).map(Some).collect();
+        b.iter(|| {
+            v.remove(99_999);
+            v.insert(0)
+        });
+    }
 
     #[test]
     fn fuzzy_search() {
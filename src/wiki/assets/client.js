@@ -16,3 +16,125 @@ if (remove_overlay) {
 	add_click_listener_by_id('remove_start', function () { show(remove_overlay); });
 	add_click_listener_by_id('remove_cancel', function () { hide(remove_overlay); });
 }
+
+// Refresh the page when the database changes, unless the user is typing in a form.
+// Element pages only refresh for changes to elements they link to.
+// Paths start with the prefix of the database when the server has several.
+var element_path = (document.body.getAttribute('data-prefix') || '') + '/element/';
+function displayed_elements() {
+	var elements = {};
+	var links = document.querySelectorAll('a[href^="' + element_path + '"]');
+	for (var i = 0; i < links.length; i++) {
+		elements[parseInt(links[i].getAttribute('href').slice(element_path.length), 10)] = true;
+	}
+	return elements;
+}
+function is_editing() {
+	var fields = document.querySelectorAll('input[type="text"], textarea');
+	for (var i = 0; i < fields.length; i++) {
+		if (fields[i].value !== '' || fields[i] === document.activeElement) { return true; }
+	}
+	return false;
+}
+var live_url = document.body.getAttribute('data-live');
+if (live_url && window.WebSocket) {
+	var scheme = window.location.protocol === 'https:' ? 'wss://' : 'ws://';
+	var live = new WebSocket(scheme + window.location.host + live_url);
+	var element_page = window.location.pathname.indexOf(element_path) === 0;
+	live.onmessage = function (event) {
+		var change = JSON.parse(event.data);
+		var elements = displayed_elements();
+		var is_displayed = !change.elements || change.elements.some(function (i) { return elements[i]; });
+		if (element_page && !is_displayed) { return; }
+		if (is_editing()) {
+			document.body.classList.add('outdated');
+		} else {
+			window.location.reload();
+		}
+	};
+}
+
+// Quick open palette: Ctrl+K, type the start of a name, arrows to select, Enter to open.
+var palette = document.getElementById('palette');
+var complete_url = document.body.getAttribute('data-complete');
+if (palette && complete_url) {
+	var palette_input = document.getElementById('palette_input');
+	var palette_results = document.getElementById('palette_results');
+	var selected = 0;
+	var select = function (i) {
+		var items = palette_results.children;
+		if (items.length === 0) { return; }
+		selected = (i + items.length) % items.length;
+		for (var j = 0; j < items.length; j++) { items[j].classList.toggle('selected', j === selected); }
+	};
+	var close_palette = function () {
+		palette.hidden = true;
+		palette_input.value = '';
+		palette_results.innerHTML = '';
+	};
+	palette_input.addEventListener('input', function () {
+		var prefix = palette_input.value;
+		if (prefix === '') { palette_results.innerHTML = ''; return; }
+		// Keep the edit state of the current page in the urls of results.
+		var query = window.location.search;
+		var request = new XMLHttpRequest();
+		request.open('GET', complete_url + (query ? query + '&' : '?') + 'q=' + encodeURIComponent(prefix));
+		request.onload = function () {
+			if (request.status !== 200 || palette_input.value !== prefix) { return; }
+			palette_results.innerHTML = '';
+			JSON.parse(request.responseText).forEach(function (result) {
+				var link = document.createElement('a');
+				link.href = result.url;
+				link.className = result.kind;
+				link.textContent = result.text;
+				var item = document.createElement('li');
+				item.appendChild(link);
+				palette_results.appendChild(item);
+			});
+			select(0);
+		};
+		request.send();
+	});
+	palette_input.addEventListener('keydown', function (event) {
+		if (event.key === 'ArrowDown') { select(selected + 1); event.preventDefault(); }
+		else if (event.key === 'ArrowUp') { select(selected - 1); event.preventDefault(); }
+		else if (event.key === 'Escape') { close_palette(); }
+		else if (event.key === 'Enter') {
+			var item = palette_results.children[selected];
+			if (item) { window.location.href = item.firstChild.href; }
+		}
+	});
+	document.addEventListener('keydown', function (event) {
+		if ((event.ctrlKey || event.metaKey) && event.key === 'k') {
+			event.preventDefault();
+			palette.hidden = false;
+			palette_input.focus();
+		}
+	});
+}
+
+// Interactive graph, when the cytoscape.js library could be loaded. Clicking a node opens its page.
+var graph_container = document.getElementById('cytoscape');
+if (graph_container && window.cytoscape) {
+	var graph_request = new XMLHttpRequest();
+	graph_request.open('GET', graph_container.getAttribute('data-graph'));
+	graph_request.onload = function () {
+		if (graph_request.status !== 200) { return; }
+		var graph = window.cytoscape({
+			container: graph_container,
+			elements: JSON.parse(graph_request.responseText),
+			layout: { name: 'cose', animate: false },
+			style: [
+				{ selector: 'node', style: { 'label': 'data(label)', 'font-size': 10 } },
+				{ selector: 'node[kind = "atom"]', style: { 'background-color': '#390' } },
+				{ selector: 'node[kind = "relation"]', style: { 'background-color': '#068', 'shape': 'diamond' } },
+				{ selector: 'node[kind = "abstract"]', style: { 'background-color': '#c00' } },
+				{ selector: 'edge', style: {
+					'label': 'data(role)', 'font-size': 8, 'curve-style': 'bezier', 'target-arrow-shape': 'triangle'
+				} }
+			]
+		});
+		graph.on('tap', 'node', function (event) { window.location.href = element_path + event.target.id(); });
+	};
+	graph_request.send();
+}
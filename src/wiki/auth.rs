@@ -0,0 +1,110 @@
+use hyper::header::{self, HeaderMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::web;
+use utils::{remove_prefix, Map};
+
+const SESSION_COOKIE: &'static str = "rett_session";
+/// Sessions must log in again after this duration.
+const SESSION_DURATION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Shared secret protecting mutation routes.
+/// Clients either send it as a bearer token, or exchange it for a session cookie on the login page.
+/// Sessions are kept in memory and lost on restart, and expire after SESSION_DURATION.
+/// The author name given at login is recorded in the provenance of created elements.
+pub struct Auth {
+    password: String,
+    sessions: Mutex<Map<String, Session>>,
+}
+struct Session {
+    author: Option<String>,
+    expires: Instant,
+}
+impl Auth {
+    pub fn new(password: String) -> Self {
+        Auth {
+            password,
+            sessions: Mutex::new(Map::new()),
+        }
+    }
+    /// Open a session if the password is correct. Returns the Set-Cookie header value.
+    pub fn login(&self, password: &str, author: Option<String>) -> Option<String> {
+        if !constant_time_eq(password, &self.password) {
+            return None;
+        }
+        let session = Uuid::new_v4().to_simple().to_string();
+        let cookie = session_cookie(&session, SESSION_DURATION);
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires > now);
+        let expires = now + SESSION_DURATION;
+        sessions.insert(session, Session { author, expires });
+        Some(cookie)
+    }
+    /// Close the sessions of the request. Returns the Set-Cookie header value removing the cookie.
+    pub fn logout(&self, headers: &HeaderMap) -> String {
+        let mut sessions = self.sessions.lock().unwrap();
+        for session in session_cookies(headers) {
+            sessions.remove(session);
+        }
+        session_cookie("", Duration::from_secs(0))
+    }
+    /// Request has a valid bearer token or session cookie.
+    pub fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let header_values = |name| {
+            headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+        };
+        let has_token = header_values(header::AUTHORIZATION)
+            .filter_map(|value| remove_prefix(value, "Bearer "))
+            .any(|token| constant_time_eq(token.trim(), &self.password));
+        has_token || {
+            let sessions = self.sessions.lock().unwrap();
+            let now = Instant::now();
+            session_cookies(headers)
+                .filter_map(|session| sessions.get(session))
+                .any(|session| session.expires > now)
+        }
+    }
+    /// Author name of the session of the request, if any.
+    pub fn author(&self, headers: &HeaderMap) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        session_cookies(headers)
+            .filter_map(|session| sessions.get(session))
+            .filter(|session| session.expires > now)
+            .filter_map(|session| session.author.clone())
+            .next()
+    }
+}
+
+/// Set-Cookie header value for a session.
+/// Each database prefix has its own sessions, so cookies are restricted to it.
+fn session_cookie(session: &str, duration: Duration) -> String {
+    format!(
+        "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Strict",
+        SESSION_COOKIE,
+        session,
+        web::prefixed_path("/"),
+        duration.as_secs()
+    )
+}
+
+/// Values of session cookies in request headers.
+fn session_cookies<'h>(headers: &'h HeaderMap) -> impl Iterator<Item = &'h str> {
+    web::cookie_values(headers, SESSION_COOKIE)
+}
+
+/// String comparison whose duration does not depend on the position of the first difference.
+fn constant_time_eq(lhs: &str, rhs: &str) -> bool {
+    lhs.len() == rhs.len()
+        && lhs
+            .bytes()
+            .zip(rhs.bytes())
+            .fold(0, |acc, (l, r)| acc | (l ^ r))
+            == 0
+}
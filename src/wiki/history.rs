@@ -0,0 +1,83 @@
+use hyper::header::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::web;
+use relations::Index;
+
+const VISITOR_COOKIE: &'static str = "rett_visitor";
+
+/// Visits kept for each visitor.
+const MAX_VISITS: usize = 50;
+
+/// Visitors kept in memory, the least recently active being forgotten first.
+const MAX_VISITORS: usize = 1000;
+
+/// Elements whose page was visited by each browser, identified by a cookie.
+/// Histories are kept in memory and lost on restart.
+pub struct VisitHistory {
+    visitors: Mutex<Visitors>,
+}
+struct Visitors {
+    /// Time of last activity and visited elements, most recent first.
+    histories: HashMap<String, (u64, VecDeque<Index>)>,
+    /// Counts visits, to order activity.
+    clock: u64,
+}
+impl VisitHistory {
+    pub fn new() -> Self {
+        VisitHistory {
+            visitors: Mutex::new(Visitors {
+                histories: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+    /// Record a visit of the element by the request sender.
+    /// Returns the Set-Cookie header value identifying new visitors.
+    pub fn record(&self, headers: &HeaderMap, index: Index) -> Option<String> {
+        let (visitor, cookie) = match web::cookie_values(headers, VISITOR_COOKIE).next() {
+            Some(visitor) => (visitor.to_string(), None),
+            None => {
+                let visitor = Uuid::new_v4().to_simple().to_string();
+                let cookie = format!(
+                    "{}={}; Path=/; HttpOnly; SameSite=Strict",
+                    VISITOR_COOKIE, visitor
+                );
+                (visitor, Some(cookie))
+            }
+        };
+        let mut visitors = self.visitors.lock().unwrap();
+        if !visitors.histories.contains_key(&visitor) && visitors.histories.len() >= MAX_VISITORS {
+            let least_active = visitors
+                .histories
+                .iter()
+                .min_by_key(|entry| (entry.1).0)
+                .map(|entry| entry.0.clone());
+            if let Some(least_active) = least_active {
+                visitors.histories.remove(&least_active);
+            }
+        }
+        visitors.clock += 1;
+        let clock = visitors.clock;
+        let history = visitors
+            .histories
+            .entry(visitor)
+            .or_insert_with(|| (0, VecDeque::new()));
+        history.0 = clock;
+        history.1.retain(|&visited| visited != index);
+        history.1.push_front(index);
+        history.1.truncate(MAX_VISITS);
+        cookie
+    }
+    /// Elements visited by the request sender, most recent first.
+    pub fn visits(&self, headers: &HeaderMap) -> Vec<Index> {
+        let visitors = self.visitors.lock().unwrap();
+        web::cookie_values(headers, VISITOR_COOKIE)
+            .filter_map(|visitor| visitors.histories.get(visitor))
+            .map(|history| history.1.iter().cloned().collect())
+            .next()
+            .unwrap_or_else(Vec::new)
+    }
+}
@@ -0,0 +1,226 @@
+use std::char;
+
+/// Parsed JSON value. Objects keep their entries in document order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+impl Value {
+    /// Value of an object entry.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|e| e.0 == key).map(|e| &e.1),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+    /// Number if it is a non negative integer.
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Value::Number(x) if x >= 0. && x.fract() == 0. && x <= usize::max_value() as f64 => {
+                Some(x as usize)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Nesting of arrays and objects is limited, so that requests cannot overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Parse a complete JSON document.
+pub fn parse(text: &str) -> Result<Value, &'static str> {
+    let mut parser = Parser { text, position: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return Err("unexpected text after value");
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len()
+    }
+    /// Consume the token if the text starts with it.
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len()
+        }
+        found
+    }
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.rest().chars().next()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, &'static str> {
+        if depth > MAX_DEPTH {
+            return Err("too many nested values");
+        }
+        self.skip_whitespace();
+        if self.eat("{") {
+            let mut entries = Vec::new();
+            self.skip_whitespace();
+            if self.eat("}") {
+                return Ok(Value::Object(entries));
+            }
+            loop {
+                self.skip_whitespace();
+                let key = self.string()?;
+                self.skip_whitespace();
+                if !self.eat(":") {
+                    return Err("expected : after object key");
+                }
+                entries.push((key, self.value(depth + 1)?));
+                self.skip_whitespace();
+                if self.eat("}") {
+                    return Ok(Value::Object(entries));
+                }
+                if !self.eat(",") {
+                    return Err("expected , or } in object");
+                }
+            }
+        } else if self.eat("[") {
+            let mut values = Vec::new();
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(Value::Array(values));
+            }
+            loop {
+                values.push(self.value(depth + 1)?);
+                self.skip_whitespace();
+                if self.eat("]") {
+                    return Ok(Value::Array(values));
+                }
+                if !self.eat(",") {
+                    return Err("expected , or ] in array");
+                }
+            }
+        } else if self.rest().starts_with('"') {
+            self.string().map(Value::String)
+        } else if self.eat("null") {
+            Ok(Value::Null)
+        } else if self.eat("true") {
+            Ok(Value::Boolean(true))
+        } else if self.eat("false") {
+            Ok(Value::Boolean(false))
+        } else {
+            let rest = self.rest();
+            let is_number_char = |c: char| c.is_ascii_digit() || "+-.eE".contains(c);
+            let end = rest.find(|c: char| !is_number_char(c)).unwrap_or(rest.len());
+            if end == 0 {
+                return Err("invalid value");
+            }
+            let x = rest[..end].parse::<f64>().map_err(|_| "invalid number")?;
+            self.position += end;
+            Ok(Value::Number(x))
+        }
+    }
+
+    /// Parse a double quoted string with escapes.
+    fn string(&mut self) -> Result<String, &'static str> {
+        if !self.eat("\"") {
+            return Err("expected string");
+        }
+        let mut s = String::new();
+        loop {
+            match self.next_char().ok_or("unterminated string")? {
+                '"' => return Ok(s),
+                '\\' => match self.next_char() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.unicode_escape()?),
+                    _ => return Err("invalid escape in string"),
+                },
+                c if c < ' ' => return Err("control character in string"),
+                c => s.push(c),
+            }
+        }
+    }
+    /// Character of a \u escape, with the low half of surrogate pairs in a second escape.
+    fn unicode_escape(&mut self) -> Result<char, &'static str> {
+        let high = self.hex4()?;
+        let code = if high >= 0xd800 && high < 0xdc00 {
+            if !self.eat("\\u") {
+                return Err("unpaired surrogate in string");
+            }
+            let low = self.hex4()?;
+            if low < 0xdc00 || low >= 0xe000 {
+                return Err("unpaired surrogate in string");
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or("invalid unicode escape")
+    }
+    fn hex4(&mut self) -> Result<u32, &'static str> {
+        let digits = match self.rest().get(..4) {
+            Some(digits) if digits.chars().all(|c| c.is_ascii_hexdigit()) => digits,
+            _ => return Err("invalid unicode escape"),
+        };
+        self.position += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let value = parse(" [1, -2.5e1, \"a\\\"\\u00e9\\ud83d\\ude00\", true, null, {}] ").unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Number(1.),
+                Value::Number(-25.),
+                Value::String("a\"é😀".into()),
+                Value::Boolean(true),
+                Value::Null,
+                Value::Object(Vec::new()),
+            ])
+        );
+        let object = parse("{\"op\": \"remove\", \"element\": 3}").unwrap();
+        assert_eq!(object.get("op").and_then(Value::as_str), Some("remove"));
+        assert_eq!(object.get("element").and_then(Value::as_usize), Some(3));
+        assert_eq!(object.get("missing"), None);
+        assert!(parse("[1,]").is_err());
+        assert!(parse("{\"a\" 1}").is_err());
+        assert!(parse("\"\\ud83d\"").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse(&"[".repeat(MAX_DEPTH + 2)).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use maud::{html, Markup, PreEscaped};
+
+use super::{lang, web, EditState, State};
+use super::{AtomToNamedAbstract, ChangeAtomValue, CreateAbstract, CreateAtom, CreateRelation};
+use super::{CompleteElement, DisplayElement, ElementIndex, ElementIndexQuery, Homepage};
+use super::{ListAllElements, ListDeleted, ListVersions};
+use super::{ListWarnings, LiveChannel, Login, LoginQuery, RemoveElement, SearchAtom};
+use super::{RecentChanges, SimilarElements, StaticAsset, VisitedElements};
+use relations::{Element, Index, Ref};
+
+/// Complete html page, with the content of a page in the skeleton shared by all pages:
+/// a header with a search box, the navigation bar, and a footer.
+/// The quick open palette is hidden until its hotkey is pressed, see client.js.
+pub fn page<T: AsRef<str>>(
+    state: &State,
+    edit_state: &EditState,
+    displayed: Option<Ref<Element>>,
+    title: PreEscaped<T>,
+    content: Markup,
+) -> String {
+    let template = html! {
+        (maud::DOCTYPE)
+        html {
+            head {
+                meta charset="UTF-8";
+                link rel="stylesheet" type="text/css" href=(StaticAsset::url("style.css"));
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { (title) };
+            }
+            body data-live=(LiveChannel::url()) data-complete=(CompleteElement::url())
+                data-prefix=(web::prefixed_path("")) {
+                header {
+                    form.hbox method="post" action=(SearchAtom::url(edit_state)) {
+                        input type="text" name="pattern" required? placeholder=(lang::SEARCH_BOX);
+                        button { (lang::SEARCH_ATOM_NAV) }
+                    }
+                }
+                div.columns {
+                    nav { (navigation_links(state, edit_state, displayed)) }
+                    main { (content) }
+                }
+                footer { (env!("CARGO_PKG_NAME")) " " (env!("CARGO_PKG_VERSION")) }
+                div#palette hidden? {
+                    input#palette_input type="text" placeholder=(lang::PALETTE_PLACEHOLDER);
+                    ul#palette_results {}
+                }
+                script src=(StaticAsset::url("client.js"));
+            }
+        }
+    };
+    template.into_string()
+}
+
+/// Class of elements of each kind, giving their colors.
+pub fn css_class_name(element: Ref<Element>) -> &'static str {
+    match element.value() {
+        Element::Abstract => "abstract",
+        Element::Atom(_) => "atom",
+        Element::Relation(_) => "relation",
+    }
+}
+
+/// Generates sequence of navigation links depending on state.
+fn navigation_links(
+    state: &State,
+    edit_state: &EditState,
+    displayed: Option<Ref<Element>>,
+) -> Markup {
+    let displayed_i = displayed.map(|e| e.index());
+    html! {
+        a href=(Homepage::url(edit_state)) { (lang::HOMEPAGE) }
+        a href=(ListAllElements::url(edit_state)) { (lang::ALL_ELEMENTS_NAV) }
+        a href=(ElementIndex::url(&ElementIndexQuery {
+            page: 0,
+            sort_by_name: false,
+            namespace: None,
+            edit_state: edit_state.clone(),
+        })) { (lang::INDEX_NAV) }
+        a href=(RecentChanges::url(edit_state)) { (lang::RECENT_NAV) }
+        a href=(VisitedElements::url(edit_state)) { (lang::HISTORY_NAV) }
+        a href=(ListWarnings::url(edit_state)) { (lang::WARNINGS_NAV) }
+        a.atom href=(SearchAtom::url(edit_state)) { (lang::SEARCH_ATOM_NAV) }
+        a href=(ListVersions::url(edit_state)) { (lang::VERSIONS_NAV) }
+        a href=(ListDeleted::url(edit_state)) { (lang::DELETED_NAV) }
+        @if !state.read_only {
+            a.atom href=(CreateAtom::url(edit_state)) { (lang::CREATE_ATOM_NAV) }
+            a.abstract href=(CreateAbstract::url(edit_state)) { (lang::CREATE_ABSTRACT_NAV) }
+            (selection_nav_link(lang::RELATION_SUBJECT, displayed_i, edit_state, |e| e.subject, |e,subject| EditState{ subject, ..e.clone() }))
+            (selection_nav_link(lang::RELATION_DESCRIPTOR, displayed_i, edit_state, |e| e.descriptor, |e,descriptor| EditState{ descriptor, ..e.clone() }))
+            (selection_nav_link(lang::RELATION_COMPLEMENT, displayed_i, edit_state, |e| e.complement, |e,complement| EditState{ complement, ..e.clone() }))
+            a.relation href=(CreateRelation::url(edit_state)) { (lang::CREATE_RELATION_NAV) }
+            @if state.auth.is_some() {
+                a href=(Login::url(&LoginQuery { next: None })) { (lang::LOGIN_NAV) }
+            }
+        }
+        @if let Some(displayed) = displayed {
+            @if !state.read_only {
+                a href=(RemoveElement::url(displayed.index(), edit_state)) { (lang::REMOVE_ELEMENT_NAV) }
+            }
+            a href=(SimilarElements::url(displayed.index(), edit_state)) { (lang::SIMILAR_NAV) }
+            @match (displayed.value(), state.read_only) {
+                (Element::Atom(_), false) => {
+                    a.atom href=(ChangeAtomValue::url(displayed.index(), edit_state)) { (lang::CHANGE_ATOM_VALUE_NAV) }
+                    a.atom href=(AtomToNamedAbstract::url(displayed.index(), edit_state)) { (lang::ATOM_TO_NAMED_ABSTRACT_NAV) }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+fn selection_nav_link<IFV, WFV>(
+    field_text: PreEscaped<&str>,
+    displayed: Option<Index>,
+    edit_state: &EditState,
+    init_field_value: IFV,
+    with_field_value: WFV,
+) -> Markup
+where
+    IFV: FnOnce(&EditState) -> Option<Index>,
+    WFV: FnOnce(&EditState, Option<Index>) -> EditState,
+{
+    html! {
+        @match (init_field_value(edit_state), displayed) {
+            (None, None) => {},
+            (Some(selected), Some(displayed)) => @if selected == displayed {
+                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, None))) {
+                    "-" (field_text) " #" (selected)
+                }
+            } @else {
+                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, Some(displayed)))) {
+                    "=" (field_text) " #" (selected)
+                }
+            },
+            (None, Some(displayed)) => {
+                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, Some(displayed)))) {
+                    "+" (field_text)
+                }
+            },
+            (Some(selected), None) => {
+                a.relation href=(DisplayElement::url(selected, edit_state)) {
+                    (field_text) " #" (selected)
+                }
+            }
+        }
+    }
+}
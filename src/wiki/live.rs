@@ -0,0 +1,255 @@
+use base64;
+use futures::future::{self, Either, Loop};
+use futures::stream;
+use futures::sync::mpsc::{self, Receiver, Sender};
+use hyper::upgrade::OnUpgrade;
+use sha1::Sha1;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{read_exact, write_all};
+use tokio::prelude::{AsyncRead, AsyncWrite, Future, Sink, Stream};
+
+use relations::{Change, Database, Element};
+
+/// Messages waiting to be sent to a client. Clients too slow to keep up are disconnected.
+const CLIENT_BUFFER: usize = 16;
+/// Longest frame accepted from clients. They only send control frames, limited to 125 bytes.
+const MAX_FRAME_LENGTH: u64 = 4096;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+/// Status code 1001 of the close frame sent to disconnected clients.
+const CLOSE_GOING_AWAY: [u8; 2] = [0x03, 0xe9];
+
+/// Browsers connected to the websocket, notified of database changes.
+/// Messages are only sent: from clients, only control frames are handled, answering pings and closes.
+pub struct LiveUpdates {
+    clients: Mutex<Vec<(usize, Sender<String>)>>,
+    next_id: AtomicUsize,
+}
+impl LiveUpdates {
+    pub fn new() -> Self {
+        LiveUpdates {
+            clients: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+    /// Send a change to all clients, forgetting disconnected ones and those with a full buffer.
+    /// Forgotten clients are sent a close frame after their pending messages.
+    pub fn broadcast(&self, database: &Database, change: Change) {
+        let message = change_message(database, change);
+        let mut clients = self.clients.lock().unwrap();
+        let mut connected = Vec::with_capacity(clients.len());
+        for (id, mut client) in clients.drain(..) {
+            if client.try_send(message.clone()).is_ok() {
+                connected.push((id, client))
+            }
+        }
+        *clients = connected
+    }
+    /// Forward future changes to a client once its connection is upgraded.
+    /// The client is forgotten when its connection ends.
+    pub fn connect(live: &Arc<LiveUpdates>, on_upgrade: OnUpgrade) {
+        let (sender, messages) = mpsc::channel::<String>(CLIENT_BUFFER);
+        let id = live.next_id.fetch_add(1, Ordering::Relaxed);
+        live.clients.lock().unwrap().push((id, sender));
+        let live = live.clone();
+        let connection = on_upgrade
+            .map_err(|e| eprintln!("[live] Websocket upgrade failed: {}", e))
+            .and_then(move |connection| {
+                let (reader, writer) = connection.split();
+                let (replies, queued_replies) = mpsc::channel::<Vec<u8>>(1);
+                hyper::rt::spawn(write_frames(writer, messages, queued_replies));
+                read_frames(reader, replies)
+            })
+            .then(move |_| {
+                live.disconnect(id);
+                Ok(())
+            });
+        hyper::rt::spawn(connection);
+    }
+    fn disconnect(&self, id: usize) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|&(client_id, _)| client_id != id)
+    }
+}
+
+/// Write messages and replies to control frames, until a close frame is written.
+/// Once the client is forgotten, a close frame is written after pending messages.
+/// When the client closes, it is also forgotten, so either close frame may be written first.
+fn write_frames<W: AsyncWrite>(
+    writer: W,
+    messages: Receiver<String>,
+    replies: Receiver<Vec<u8>>,
+) -> impl Future<Item = (), Error = ()> {
+    let messages = messages
+        .map(|message| frame(OPCODE_TEXT, message.as_bytes()))
+        .chain(stream::once(Ok(frame(OPCODE_CLOSE, &CLOSE_GOING_AWAY))));
+    messages
+        .select(replies)
+        .fold(writer, |writer, frame| {
+            let is_close = frame[0] & 0x0f == OPCODE_CLOSE;
+            write_all(writer, frame)
+                .map_err(|_| ()) // Client disconnected
+                .and_then(move |(writer, _)| if is_close { Err(()) } else { Ok(writer) })
+        })
+        .then(|_| Ok(()))
+}
+
+/// Read frames from the client until it closes the connection, queueing replies to control frames.
+/// Data frames are ignored.
+fn read_frames<R: AsyncRead>(
+    reader: R,
+    replies: Sender<Vec<u8>>,
+) -> impl Future<Item = (), Error = ()> {
+    future::loop_fn((reader, replies), |(reader, replies)| {
+        read_frame(reader)
+            .map_err(|_| ())
+            .and_then(|(reader, opcode, mut payload)| {
+                let reply = match opcode {
+                    OPCODE_CLOSE => {
+                        payload.truncate(2); // Echo the status code only
+                        Some(frame(OPCODE_CLOSE, &payload))
+                    }
+                    OPCODE_PING => Some(frame(OPCODE_PONG, &payload)),
+                    _ => None,
+                };
+                let sent = match reply {
+                    Some(reply) => Either::A(replies.send(reply).map_err(|_| ())),
+                    None => Either::B(future::ok(replies)),
+                };
+                sent.map(move |replies| match opcode {
+                    OPCODE_CLOSE => Loop::Break(()),
+                    _ => Loop::Continue((reader, replies)),
+                })
+            })
+    })
+}
+
+/// Read a frame, returning its opcode and unmasked payload. Fragments are returned one by one.
+fn read_frame<R: AsyncRead>(reader: R) -> impl Future<Item = (R, u8, Vec<u8>), Error = io::Error> {
+    read_exact(reader, [0u8; 2])
+        .and_then(|(reader, header)| {
+            let extended_length = match header[1] & 0x7f {
+                126 => 2,
+                127 => 8,
+                _ => 0,
+            };
+            read_exact(reader, vec![0u8; extended_length])
+                .map(move |(reader, extended)| (reader, header, extended))
+        })
+        .and_then(|(reader, header, extended)| {
+            let length = match extended.len() {
+                0 => u64::from(header[1] & 0x7f),
+                _ => extended
+                    .iter()
+                    .fold(0, |length, &byte| length << 8 | u64::from(byte)),
+            };
+            if length > MAX_FRAME_LENGTH {
+                let error = io::Error::new(io::ErrorKind::InvalidData, "Websocket frame too long");
+                return Either::A(future::err(error));
+            }
+            let mask_length = if header[1] & 0x80 != 0 { 4 } else { 0 };
+            let data = vec![0u8; mask_length + length as usize];
+            Either::B(read_exact(reader, data).map(move |(reader, data)| {
+                let (mask, payload) = data.split_at(mask_length);
+                let payload = payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &byte)| match mask_length {
+                        0 => byte,
+                        _ => byte ^ mask[i % 4],
+                    })
+                    .collect();
+                (reader, header[0] & 0x0f, payload)
+            }))
+        })
+}
+
+/// Json message with the change and elements whose pages should be refreshed.
+/// Pages of components display an inserted relation, so they are included.
+/// A reset has no element list, as all pages must be refreshed.
+fn change_message(database: &Database, change: Change) -> String {
+    let (name, index) = match change {
+        Change::Inserted(index) => ("inserted", index),
+        Change::Removed(index) => ("removed", index),
+        Change::Modified(index) => ("modified", index),
+        Change::Reset => return "{\"change\":\"reset\"}".to_string(),
+    };
+    let mut elements = vec![index];
+    if let Change::Inserted(_) = change {
+        if let Ok(element) = database.element(index) {
+            if let Element::Relation(r) = element.value() {
+                elements.push(r.subject);
+                elements.push(r.descriptor);
+                elements.extend(r.complement);
+            }
+        }
+    }
+    let elements: Vec<String> = elements.iter().map(|i| i.to_string()).collect();
+    format!(
+        "{{\"change\":\"{}\",\"elements\":[{}]}}",
+        name,
+        elements.join(",")
+    )
+}
+
+const WEBSOCKET_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Value of Sec-WebSocket-Accept for the Sec-WebSocket-Key of the client (RFC 6455).
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::encode(&hasher.digest().bytes())
+}
+
+/// Websocket frame, unmasked as sent by a server.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode]; // Final fragment
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= 0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&[(len >> 8) as u8, len as u8])
+        }
+        len => {
+            frame.push(127);
+            frame.extend((0..8).rev().map(|byte| (len as u64 >> (8 * byte)) as u8))
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        // Masked ping with payload "Hi", as sent by a browser.
+        let mask = [1, 2, 3, 4];
+        let ping = [0x89, 0x82, 1, 2, 3, 4, b'H' ^ mask[0], b'i' ^ mask[1]];
+        let (rest, opcode, payload) = read_frame(&ping[..]).wait().unwrap();
+        assert_eq!(
+            (rest.len(), opcode, payload),
+            (0, OPCODE_PING, b"Hi".to_vec())
+        );
+        assert_eq!(frame(OPCODE_PONG, b"Hi"), vec![0x8a, 2, b'H', b'i']);
+
+        let long_text = frame(OPCODE_TEXT, &[b'a'; 300]);
+        assert_eq!(&long_text[..4], &[0x81, 126, 1, 44]);
+        let (_, opcode, payload) = read_frame(&long_text[..]).wait().unwrap();
+        assert_eq!((opcode, payload.len()), (OPCODE_TEXT, 300));
+
+        let too_long = frame(OPCODE_TEXT, &[b'a'; 5000]);
+        assert!(read_frame(&too_long[..]).wait().is_err());
+    }
+}
@@ -2,62 +2,129 @@ use hyper::service::service_fn;
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use maud::{html, Markup, PreEscaped};
 use signal_hook::{self, iterator::Signals};
-use tokio::prelude::{Future, Stream};
-use tokio::runtime::current_thread;
+use tokio::prelude::{future, Future, Stream};
+use tokio::runtime::Runtime;
 use tokio::timer;
 
-use std::cell;
+use std::cell::RefCell;
 use std::fs;
-use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use config;
+use relations::cytoscape::{self, JsonEscaped};
+use relations::{dot, jsonld, ntriples};
 use relations::{read_database_from_file, write_database_to_file};
-use relations::{Abstract, Atom, Database, Element, ElementRef, Index, Ref, Relation};
-use utils::remove_prefix;
+use relations::write_database_to_file_with_backup;
+use relations::{Abstract, Atom, Database, Element, ElementKind, ElementRef, Index, Ref};
+use relations::{Snapshot, WeakSnapshot};
+use relations::schema::Schema;
+use relations::{list_versions, lock_database_file, versions_directory};
+use relations::NameIndex;
+use relations::{RelationRefSet, Rules};
+use relations::{words, BatchRef, Operation, Relation, Warning};
+use utils::{remove_prefix, Collation, Set};
 
 /// Mini web framework.
 mod web;
-use self::web::{EndPoint, FromRequestError, FromRequestOk};
+use self::web::QueryFormat;
+/// Building blocks of routes, for plugins.
+pub use self::web::{end_point_handler, response_html, BoxedFuture};
+pub use self::web::{EndPoint, Error as RequestError, FromRequestError, FromRequestOk};
+
+/// Parsing of JSON request bodies.
+mod json;
+
+/// Html skeleton shared by pages.
+mod layout;
+use self::layout::css_class_name;
+
+/// Elements visited by each browser.
+mod history;
+use self::history::VisitHistory;
+
+/// Password protection of mutation routes.
+mod auth;
+use self::auth::Auth;
+
+/// Websocket notifying pages of database changes.
+mod live;
+use self::live::LiveUpdates;
+
+/// Extensions compiled in the binary.
+pub mod plugin;
+use self::plugin::{Exporter, Plugin};
+
+/// Descriptor of relations giving names to abstract elements in pages.
+pub use self::lang::NAMED_ATOM;
 
 /******************************************************************************
  * Wiki runtime system.
- * Based on hyper/tokio, but uses the single threaded tokio runtime.
+ * Based on hyper/tokio, using the multi threaded tokio runtime.
+ * Requests share the database through a read-write lock.
  */
 
 /// Entry point, run the wiki server.
+/// The main database is served at the root, and the databases of the config under /db/<name>.
 pub fn run(
-    addr: &SocketAddr,
     database_file: &Path,
-    backup_file: &Path,
-    autosave_interval: Duration,
+    config: &config::Wiki,
+    plugins: Vec<Box<dyn Plugin>>,
 ) -> Result<(), String> {
-    let state = Rc::new(State::from_file(database_file, backup_file)?);
-
-    let create_service = || {
-        let state = state.clone();
-        service_fn(move |request| {
-            // Move cloned rc ref in this scope.
-            let handlers = [
-                web::end_point_handler::<DisplayElement>,
-                web::end_point_handler::<Homepage>,
-                web::end_point_handler::<ListAllElements>,
-                web::end_point_handler::<SearchAtom>,
-                web::end_point_handler::<CreateAtom>,
-                web::end_point_handler::<CreateAbstract>,
-                web::end_point_handler::<CreateRelation>,
-                web::end_point_handler::<RemoveElement>,
-                web::end_point_handler::<ChangeAtomValue>,
-                web::end_point_handler::<AtomToNamedAbstract>,
-                web::end_point_handler::<StaticAsset>,
-            ];
-            web::handle_request(request, state.clone(), handlers.iter())
+    let read_only = config.read_only;
+    let password = match config.password_file {
+        Some(ref path) => Some(read_password_file(path)?),
+        None => None,
+    };
+    let mut databases = vec![(
+        String::new(),
+        database_file.to_owned(),
+        config.backup_file(database_file),
+    )];
+    for (name, file) in config.databases.iter() {
+        let backup_file = config::default_backup_file(file);
+        databases.push((format!("/db/{}", name), file.clone(), backup_file))
+    }
+    // Released when returning, after the final writes.
+    let _database_locks = databases
+        .iter()
+        .map(|database| lock_database_file(&database.1))
+        .collect::<Result<Vec<_>, _>>()?;
+    let states = databases
+        .into_iter()
+        .map(|(path_prefix, file, backup_file)| {
+            let state = State::from_file(
+                &file,
+                &backup_file,
+                config,
+                password.clone().map(Auth::new),
+                path_prefix,
+                &plugins,
+            )?;
+            Ok(Arc::new(state))
         })
+        .collect::<Result<Vec<_>, String>>()?;
+    let states = Arc::new(states);
+    let plugins = Arc::new(plugins);
+
+    let handlers = Arc::new(handlers(read_only, password.is_some(), &plugins));
+
+    let create_service = {
+        let states = states.clone();
+        let handlers = handlers.clone();
+        move || {
+            let states = states.clone();
+            let handlers = handlers.clone();
+            service_fn(move |mut request| {
+                let state = route_to_database(&states, &mut request);
+                let prefix = state.path_prefix.clone();
+                web::handle_request_with_prefix(request, &prefix, state, handlers.iter())
+            })
+        }
     };
-    let server = Server::bind(&addr)
-        .executor(current_thread::TaskExecutor::current())
-        .serve(create_service);
+    let server = Server::bind(&config.addr).serve(create_service);
     let shutdown_signal = Signals::new(&[signal_hook::SIGTERM, signal_hook::SIGINT])
         .map_err(|e| e.to_string())?
         .into_async() // Stream of signals
@@ -67,11 +134,12 @@ pub fn run(
         .with_graceful_shutdown(shutdown_signal.map(|_| ()))
         .map_err(|e| e.to_string());
 
-    let database_autosave = timer::Interval::new_interval(autosave_interval)
+    let database_autosave = timer::Interval::new_interval(config.autosave_interval)
         .map_err(|e| e.to_string())
         .for_each({
-            let state = state.clone();
-            move |_instant| state.write_to_file()
+            let states = states.clone();
+            let plugins = plugins.clone();
+            move |_instant| autosave(&states, &plugins)
         });
 
     // Launch both autosave and wiki, stop whenever one terminates.
@@ -80,25 +148,273 @@ pub fn run(
         Ok((v, _)) => Ok(v),
         Err((e, _)) => Err(e),
     });
-    current_thread::block_on_all(which_terminates_first)?;
-    state.write_to_file()?;
+    let mut runtime = Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(which_terminates_first)?;
+    // Drop remaining connections.
+    runtime.shutdown_now().wait().unwrap();
+    for state in states.iter() {
+        state.write_to_file()?
+    }
+    Ok(())
+}
+
+/// Routes of the wiki, in the order they are tried.
+fn handlers(read_only: bool, password: bool, plugins: &[Box<dyn Plugin>]) -> Vec<Handler> {
+    let mut handlers: Vec<Handler> = vec![
+        cached_handler::<ElementGraph>, // Before DisplayElement, same prefix
+        cached_handler::<InteractiveGraph>,
+        web::end_point_handler::<ExportDatabase>,
+        display_element_handler,
+        cached_handler::<RecentChanges>,
+        web::end_point_handler::<VisitedElements>,
+        cached_handler::<Homepage>,
+        cached_handler::<ListAllElements>,
+        cached_handler::<ElementIndex>,
+        cached_handler::<ListWarnings>,
+        cached_handler::<SimilarElements>,
+        cached_handler::<SearchAtom>,
+        cached_handler::<CompleteElement>,
+        web::end_point_handler::<StaticAsset>,
+        web::end_point_handler::<LiveChannel>,
+        web::end_point_handler::<ListVersions>,
+        cached_handler::<ListDeleted>,
+    ];
+    if !read_only {
+        let mutation_handlers: Vec<Handler> = vec![
+            mutation_handler::<CreateAtom>,
+            mutation_handler::<CreateAbstract>,
+            mutation_handler::<CreateRelation>,
+            mutation_handler::<RemoveElement>,
+            mutation_handler::<ChangeAtomValue>,
+            mutation_handler::<AtomToNamedAbstract>,
+            mutation_handler::<ApplyBatch>,
+            mutation_handler::<Versions>,
+            mutation_handler::<DeletedElements>,
+        ];
+        handlers.extend(mutation_handlers);
+        if password {
+            handlers.push(web::end_point_handler::<Login>);
+            handlers.push(web::end_point_handler::<Logout>);
+        }
+    }
+    // Plugin routes are tried after the builtin ones.
+    // They are registered as given: see Plugin::handlers for mutation routes.
+    for plugin in plugins.iter() {
+        handlers.extend(plugin.handlers(read_only));
+    }
+    handlers
+}
+
+/// Run the maintenance jobs of plugins, then write the modified databases.
+fn autosave(states: &[Arc<State>], plugins: &[Box<dyn Plugin>]) -> Result<(), String> {
+    for state in states.iter() {
+        for plugin in plugins.iter() {
+            plugin.maintenance(state);
+        }
+        state.write_to_file()?
+    }
     Ok(())
 }
 
+/// State of the database serving the request, with the request path stripped of its prefix.
+/// Paths outside of all prefixes are served by the main database.
+fn route_to_database(states: &[Arc<State>], request: &mut Request<Body>) -> Arc<State> {
+    let main = &states[0];
+    let prefixed = states[1..]
+        .iter()
+        .find(|state| web::remove_path_prefix(request, &state.path_prefix));
+    prefixed.unwrap_or(main).clone()
+}
+
+/// Password is the first line of the file, so that it stays out of the command line and shell history.
+fn read_password_file(path: &Path) -> Result<String, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read password file {}: {}", path.display(), e))?;
+    match content.lines().next() {
+        Some(password) if !password.is_empty() => Ok(password.to_string()),
+        _ => Err(format!("Empty password file: {}", path.display())),
+    }
+}
+
+/// Route handler, tried in order until one matches the request.
+pub type Handler = fn(
+    Request<Body>,
+    Arc<State>,
+) -> Result<web::BoxedFuture<Response<Body>>, FromRequestError>;
+
+/// Handler for routes modifying the database.
+/// If a password is set, unauthorized requests are redirected to the login page.
+pub fn mutation_handler<E: EndPoint<State = State> + Send + 'static>(
+    request: Request<Body>,
+    state: Arc<State>,
+) -> Result<web::BoxedFuture<Response<Body>>, FromRequestError> {
+    let authorized = match state.auth {
+        Some(ref auth) => auth.is_authorized(request.headers()),
+        None => true,
+    };
+    if authorized {
+        return web::end_point_handler::<Authored<E>>(request, state);
+    }
+    // Forms cannot be resubmitted after login, only return to pages.
+    let next = match request.method() {
+        &Method::GET => request.uri().path_and_query().map(|p| web::prefixed_path(p.as_str())),
+        _ => None,
+    };
+    E::from_request(request).map(move |_| {
+        let login = Login::url(&LoginQuery { next });
+        let response: web::BoxedFuture<Response<Body>> =
+            Box::new(future::ok(web::response_redirection(&login)));
+        response
+    })
+}
+
+/// Handler for pages only depending on the database and the url.
+/// GET responses are tagged with the database revision, and answered with 304 Not Modified
+/// if the client already has the page for the current revision.
+fn cached_handler<E: EndPoint<State = State> + Send + 'static>(
+    request: Request<Body>,
+    state: Arc<State>,
+) -> Result<web::BoxedFuture<Response<Body>>, FromRequestError> {
+    if request.method() != &Method::GET {
+        return web::end_point_handler::<E>(request, state);
+    }
+    // Computed before the page, so that a concurrent change can only make the tag outdated.
+    let etag = state.etag();
+    let client_is_current = request
+        .headers()
+        .get(hyper::header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .map_or(false, |tags| tags.split(',').any(|tag| tag.trim() == etag));
+    if client_is_current {
+        return E::from_request(request).map(move |_| {
+            let not_modified = Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(hyper::header::ETAG, etag)
+                .body(Body::empty())
+                .unwrap();
+            let response: web::BoxedFuture<Response<Body>> = Box::new(future::ok(not_modified));
+            response
+        });
+    }
+    web::end_point_handler::<E>(request, state).map(move |response| {
+        let response: web::BoxedFuture<Response<Body>> = Box::new(response.map(move |mut r| {
+            if r.status() == StatusCode::OK {
+                let etag = hyper::header::HeaderValue::from_str(&etag).unwrap();
+                r.headers_mut().insert(hyper::header::ETAG, etag);
+            }
+            r
+        }));
+        response
+    })
+}
+
+/// Handler of element pages, recording visits in the history of the visitor.
+/// Visits are recorded before the cache, as pages not modified since the last visit are visits too.
+fn display_element_handler(
+    request: Request<Body>,
+    state: Arc<State>,
+) -> Result<web::BoxedFuture<Response<Body>>, FromRequestError> {
+    let index = match (request.method(), remove_prefix(request.uri().path(), "/element/")) {
+        (&Method::GET, Some(index)) => index.parse().ok(),
+        _ => None,
+    };
+    let cookie = match index {
+        Some(index) => state.history.record(request.headers(), index),
+        None => None,
+    };
+    cached_handler::<DisplayElement>(request, state).map(move |response| match cookie {
+        Some(cookie) => {
+            let response: web::BoxedFuture<Response<Body>> = Box::new(response.map(move |mut r| {
+                let cookie = hyper::header::HeaderValue::from_str(&cookie).unwrap();
+                r.headers_mut().insert(hyper::header::SET_COOKIE, cookie);
+                r
+            }));
+            response
+        }
+        None => response,
+    })
+}
+
+thread_local! {
+    /// Author of the mutation being generated on this thread, applied to the database by get_mut.
+    static CURRENT_AUTHOR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Mutation endpoint, with request headers to find the author of the session.
+/// Responses are generated synchronously, so the author is passed to get_mut through the thread.
+struct Authored<E> {
+    headers: hyper::HeaderMap,
+    end_point: E,
+}
+impl<E: EndPoint<State = State> + Send + 'static> EndPoint for Authored<E> {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        let headers = r.headers().clone();
+        E::from_request(r).map(move |ok| match ok {
+            FromRequestOk::Value(end_point) => FromRequestOk::Value(Authored { headers, end_point }),
+            FromRequestOk::Future(f) => FromRequestOk::Future(Box::new(
+                f.map(move |end_point| Authored { headers, end_point }),
+            )),
+        })
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let author = state.auth.as_ref().and_then(|auth| auth.author(&self.headers));
+        CURRENT_AUTHOR.with(|current| *current.borrow_mut() = author);
+        let response = self.end_point.generate_response(state);
+        CURRENT_AUTHOR.with(|current| *current.borrow_mut() = None);
+        response
+    }
+}
+
 /// Wiki web interface state.
-struct State {
-    mutable: cell::RefCell<InnerMutableState>,
+pub struct State {
+    mutable: RwLock<InnerMutableState>,
     database_file: PathBuf,
     backup_file: PathBuf,
+    collation: Collation,
+    /// Mutation routes and forms are disabled.
+    read_only: bool,
+    /// Mutation routes require authentication.
+    auth: Option<Auth>,
+    live: Arc<LiveUpdates>,
+    /// Elements by name, for completion.
+    names: Arc<NameIndex>,
+    /// Start time of the server, as database revisions restart from 0 at each run.
+    started: u64,
+    history: VisitHistory,
+    /// Prefix of the paths of this database, empty for the main database.
+    path_prefix: String,
+    /// Constraints checked when creating relations with forms.
+    schema: Schema,
+    /// Script of the cytoscape.js library, for interactive graphs.
+    cytoscape_url: String,
+    /// Relations implied by these rules are shown on element pages.
+    rules: Rules,
+    /// Directory of the named versions of the database.
+    versions_directory: PathBuf,
+    /// Last snapshot taken, reused while it is in use and the database is not modified.
+    snapshot: Mutex<Option<WeakSnapshot>>,
+    /// Export formats added by plugins.
+    exporters: Vec<Exporter>,
 }
 struct InnerMutableState {
     database: Database,
     modified_since_last_write: bool,
 }
 impl State {
-    fn from_file(database_file: &Path, backup_file: &Path) -> Result<Self, String> {
-        let init_database = match read_database_from_file(database_file) {
+    fn from_file(
+        database_file: &Path,
+        backup_file: &Path,
+        config: &config::Wiki,
+        auth: Option<Auth>,
+        path_prefix: String,
+        plugins: &[Box<dyn Plugin>],
+    ) -> Result<Self, String> {
+        let read_only = config.read_only;
+        let mut init_database = match read_database_from_file(database_file) {
             Ok(database) => database,
+            // Do not create a database that could not be filled.
+            Err(e) if read_only => return Err(e),
             Err(e) => {
                 eprintln!("[warning] {}", e);
                 eprintln!("[database] Starting with empty database");
@@ -108,32 +424,104 @@ impl State {
                 db
             }
         };
+        for warning in init_database.soft_limit_warnings() {
+            eprintln!("[warning] {}", warning);
+        }
+        let live = Arc::new(LiveUpdates::new());
+        {
+            let live = live.clone();
+            init_database.on_change(move |database, change| live.broadcast(database, change));
+        }
+        let names = NameIndex::attach(&mut init_database, lang::NAMED_ATOM);
         Ok(State {
-            mutable: cell::RefCell::new(InnerMutableState {
+            mutable: RwLock::new(InnerMutableState {
                 database: init_database,
                 modified_since_last_write: false,
             }),
             database_file: database_file.to_owned(),
             backup_file: backup_file.to_owned(),
+            collation: config.collation,
+            read_only: read_only,
+            auth: auth,
+            live: live,
+            names: names,
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            history: VisitHistory::new(),
+            path_prefix: path_prefix,
+            schema: config.schema.clone(),
+            cytoscape_url: config.cytoscape_url.clone(),
+            rules: config.rules.clone(),
+            versions_directory: versions_directory(database_file),
+            snapshot: Mutex::new(None),
+            exporters: plugins.iter().flat_map(|plugin| plugin.exporters()).collect(),
         })
     }
     fn write_to_file(&self) -> Result<(), String> {
-        let inner = &mut self.mutable.borrow_mut();
+        let inner = &mut self.mutable.write().unwrap();
         if inner.modified_since_last_write {
             inner.modified_since_last_write = false;
-            fs::rename(&self.database_file, &self.backup_file)
-                .map_err(|e| format!("Cannot move backup: {}", e))?;
-            write_database_to_file(&self.database_file, &inner.database)?
+            write_database_to_file_with_backup(
+                &self.database_file,
+                &self.backup_file,
+                &inner.database,
+            )?
         }
         Ok(())
     }
-    fn get(&self) -> cell::Ref<Database> {
-        cell::Ref::map(self.mutable.borrow(), |s| &s.database)
+    /// Entity tag of pages generated from the current database.
+    fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.started, self.get().revision())
+    }
+    /// Frozen copy of the database, for long reads that should not block changes.
+    /// Concurrent reads of the same revision share the copy, which is dropped after the last one.
+    pub fn snapshot(&self) -> Snapshot {
+        let database = self.get();
+        let mut shared = self.snapshot.lock().unwrap();
+        let current = shared.as_ref().and_then(|weak| weak.upgrade());
+        match current {
+            Some(ref current) if current.revision() == database.revision() => current.clone(),
+            _ => {
+                let current = database.snapshot();
+                *shared = Some(current.downgrade());
+                current
+            }
+        }
     }
-    fn get_mut(&self) -> cell::RefMut<Database> {
-        let mut inner = self.mutable.borrow_mut();
+    /// Lock the database for reading.
+    pub fn get(&self) -> DatabaseReadGuard {
+        DatabaseReadGuard(self.mutable.read().unwrap())
+    }
+    /// Lock the database for writing. The database is then considered modified.
+    pub fn get_mut(&self) -> DatabaseWriteGuard {
+        let mut inner = self.mutable.write().unwrap();
         inner.modified_since_last_write = true;
-        cell::RefMut::map(inner, |s| &mut s.database)
+        let author = CURRENT_AUTHOR.with(|current| current.borrow().clone());
+        inner.database.set_author(author);
+        DatabaseWriteGuard(inner)
+    }
+}
+/// Shared access to the database, other requests can read concurrently.
+pub struct DatabaseReadGuard<'a>(RwLockReadGuard<'a, InnerMutableState>);
+impl<'a> Deref for DatabaseReadGuard<'a> {
+    type Target = Database;
+    fn deref(&self) -> &Database {
+        &self.0.database
+    }
+}
+/// Exclusive access to the database.
+pub struct DatabaseWriteGuard<'a>(RwLockWriteGuard<'a, InnerMutableState>);
+impl<'a> Deref for DatabaseWriteGuard<'a> {
+    type Target = Database;
+    fn deref(&self) -> &Database {
+        &self.0.database
+    }
+}
+impl<'a> DerefMut for DatabaseWriteGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Database {
+        &mut self.0.database
     }
 }
 
@@ -147,7 +535,11 @@ struct EditState {
     subject: Option<Index>,
     descriptor: Option<Index>,
     complement: Option<Index>,
+    /// Elements displayed before, oldest first, shown as a breadcrumb.
+    trail: Vec<Index>,
 }
+/// Older elements are forgotten, to keep urls short.
+const MAX_TRAIL_LEN: usize = 6;
 impl EditState {
     fn remove_references_to(&self, index: Index) -> EditState {
         let filtered = |opt: &Option<Index>| opt.filter(|i| i != &index);
@@ -155,6 +547,27 @@ impl EditState {
             subject: filtered(&self.subject),
             descriptor: filtered(&self.descriptor),
             complement: filtered(&self.complement),
+            trail: self.trail.iter().cloned().filter(|&i| i != index).collect(),
+        }
+    }
+    /// State for links of the page of an element, which is added to the trail.
+    /// Coming back to an element of the trail forgets the elements visited since.
+    fn visiting(&self, index: Index) -> EditState {
+        let mut trail = self.trail_before(index).to_vec();
+        trail.push(index);
+        if trail.len() > MAX_TRAIL_LEN {
+            trail.remove(0);
+        }
+        EditState {
+            trail,
+            ..self.clone()
+        }
+    }
+    /// Part of the trail before the element, or all of it if it is not in the trail.
+    fn trail_before(&self, index: Index) -> &[Index] {
+        match self.trail.iter().position(|&i| i == index) {
+            Some(position) => &self.trail[..position],
+            None => &self.trail,
         }
     }
 }
@@ -163,12 +576,21 @@ impl web::QueryFormat for EditState {
         builder.optional_entry("subject", self.subject);
         builder.optional_entry("descriptor", self.descriptor);
         builder.optional_entry("complement", self.complement);
+        if !self.trail.is_empty() {
+            let trail: Vec<String> = self.trail.iter().map(|i| i.to_string()).collect();
+            builder.entry("trail", trail.join(",").as_str());
+        }
     }
     fn from_query(entries: &web::UrlDecodedEntries) -> Result<Self, web::Error> {
+        let trail = match entries.get("trail") {
+            Some(trail) => trail.split(',').map(parse_index).collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
         Ok(EditState {
             subject: parse_optional_index(entries.get("subject"))?,
             descriptor: parse_optional_index(entries.get("descriptor"))?,
             complement: parse_optional_index(entries.get("complement"))?,
+            trail,
         })
     }
 }
@@ -196,12 +618,26 @@ impl EndPoint for DisplayElement {
     }
     fn generate_response(self, state: &State) -> Response<Body> {
         match state.get().element(self.index) {
-            Ok(element) => web::response_html(display_element_page(element, &self.edit_state)),
+            Ok(element) => web::response_html(display_element_page(state, element, &self.edit_state)),
             Err(_) => web::response_empty_404(),
         }
     }
 }
-fn display_element_page(element: Ref<Element>, edit_state: &EditState) -> String {
+fn display_element_page(state: &State, element: Ref<Element>, edit_state: &EditState) -> String {
+    // Links of the breadcrumb go back in the trail.
+    let trail = edit_state.trail_before(element.index());
+    let trail_state = |i: usize| EditState {
+        trail: trail[..i].to_vec(),
+        ..edit_state.clone()
+    };
+    let breadcrumb = html! {
+        @for (i, &index) in trail.iter().enumerate() {
+            @if let Ok(visited) = element.database().element(index) {
+                (element_link(visited, &trail_state(i))) " › "
+            }
+        }
+    };
+    let edit_state = &edit_state.visiting(element.index());
     let basic_name = html! {
         (match element.value() {
             Element::Abstract => lang::ABSTRACT,
@@ -211,21 +647,16 @@ fn display_element_page(element: Ref<Element>, edit_state: &EditState) -> String
     };
     let name = element_name(element, 1);
     let title = html! { (basic_name) " - " (name) };
-    let descriptions = {
-        let mut v: Vec<_> =
-            Iterator::chain(element.subject_of().iter(), element.complement_of().iter()).collect();
+    // Relations grouped by the role of the element, then by descriptor.
+    // For the same descriptor, put element on top: this relies on None < Some(_).
+    let sort_by_descriptor = |v: &mut Vec<Ref<Relation>>| {
         v.sort_by_key(|r: &Ref<Relation>| {
-            // Group descriptions by descriptor.
-            // For the same descriptor, put element on top.
-            // This relies on None < Some(_)
             (
                 r.descriptor().index(),
                 Some(r.subject().index()).filter(|&i| i != element.index()),
             )
-        });
-        v
+        })
     };
-    let descriptor_of = element.descriptor_of();
     let relation_component_row = |r: Ref<Relation>| -> Markup {
         html! {
             tr {
@@ -237,7 +668,25 @@ fn display_element_page(element: Ref<Element>, edit_state: &EditState) -> String
             }
         }
     };
+    // Relations implied by the rules of the state, navigable as if they were in the database.
+    let database = element.database();
+    let implied = database.implied_relations(element.index(), &state.rules).unwrap();
+    let relations_with_role = |role: PreEscaped<&str>, relations: RelationRefSet| -> Markup {
+        let mut relations: Vec<_> = relations.iter().collect();
+        sort_by_descriptor(&mut relations);
+        html! {
+            @if !relations.is_empty() {
+                p { (role) " (" (relations.len()) ") :" }
+                table {
+                    @for r in relations { (relation_component_row(r)) }
+                }
+            }
+        }
+    };
     let content = html! {
+        @if !trail.is_empty() {
+            p.breadcrumb { (breadcrumb) (name) }
+        }
         h1 class=(css_class_name(element)) { (name) }
         p {
             (basic_name)
@@ -250,23 +699,368 @@ fn display_element_page(element: Ref<Element>, edit_state: &EditState) -> String
                     @if let Some(complement) = r.complement() { " " (element_link(complement, edit_state)) }
                 }
             }
-            @if descriptions.len() > 0 {
-                table {
-                    @for d in descriptions { (relation_component_row(d)) }
-                }
-            }
-            @if descriptor_of.len() > 0 {
-                p { (lang::DISPLAY_DESCRIBES) ":" }
+            (relations_with_role(lang::DISPLAY_SUBJECT_OF, element.subject_of()))
+            (relations_with_role(lang::DISPLAY_COMPLEMENT_OF, element.complement_of()))
+            (relations_with_role(lang::DISPLAY_DESCRIBES, element.descriptor_of()))
+            @if !implied.is_empty() {
+                p { (lang::DISPLAY_IMPLIED) " (" (implied.len()) ") :" }
                 table {
-                    @for d in descriptor_of.iter() { (relation_component_row(d)) }
+                    @for r in implied.iter() {
+                        tr {
+                            td {}
+                            td {
+                                (element_link(database.element(r.subject).unwrap(), edit_state)) " "
+                                (element_link(database.element(r.descriptor).unwrap(), edit_state))
+                                @if let Some(complement) = r.complement {
+                                    " " (element_link(database.element(complement).unwrap(), edit_state))
+                                }
+                            }
+                        }
+                    }
                 }
             }
         }
+        img.graph src=(ElementGraph::url(Some(element.index()), GraphFormat::Svg, 1, false)) alt=(lang::GRAPH_ALT);
+        (graph_download_links(Some(element.index())))
     };
-    let nav = navigation_links(edit_state, Some(element));
-    compose_wiki_page(title, content, nav)
+    layout::page(state, edit_state, Some(element), title, content)
+}
+
+/// Neighborhood of an element, or the whole database, as an image rendered by the graphviz dot program,
+/// as dot text, or as JSON for interactive cytoscape.js views.
+struct ElementGraph {
+    /// None for the whole database.
+    index: Option<Index>,
+    format: GraphFormat,
+    query: ElementGraphQuery,
+}
+#[derive(Clone, Copy)]
+enum GraphFormat {
+    Svg,
+    Json,
+    Dot,
+}
+impl GraphFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            GraphFormat::Svg => "svg",
+            GraphFormat::Json => "json",
+            GraphFormat::Dot => "dot",
+        }
+    }
+    fn mime(self) -> &'static str {
+        match self {
+            GraphFormat::Svg => "image/svg+xml",
+            GraphFormat::Json => "application/json",
+            GraphFormat::Dot => "text/vnd.graphviz",
+        }
+    }
+}
+struct ElementGraphQuery {
+    depth: usize,
+    direction: dot::Direction,
+    /// Served as an attachment, to be saved by the browser.
+    download: bool,
+}
+impl QueryFormat for ElementGraphQuery {
+    fn to_query(&self, builder: &mut web::PathQueryBuilder) {
+        builder.entry("depth", self.depth);
+        match self.direction {
+            dot::Direction::Components => builder.entry("direction", "components"),
+            dot::Direction::Referencing => builder.entry("direction", "referencing"),
+            dot::Direction::Both => (),
+        }
+        if self.download {
+            builder.entry("download", "1")
+        }
+    }
+    fn from_query(entries: &web::UrlDecodedEntries) -> Result<Self, web::Error> {
+        let depth = parse_optional_index(entries.get("depth"))?.unwrap_or(1);
+        if depth > ElementGraph::MAX_DEPTH {
+            return Err(web::Error::BadRequest);
+        }
+        let direction = match entries.get("direction") {
+            None => dot::Direction::Both,
+            Some("components") => dot::Direction::Components,
+            Some("referencing") => dot::Direction::Referencing,
+            Some(_) => return Err(web::Error::BadRequest),
+        };
+        let download = entries.get("download").is_some();
+        Ok(ElementGraphQuery {
+            depth,
+            direction,
+            download,
+        })
+    }
+}
+impl ElementGraph {
+    const MAX_DEPTH: usize = 4;
+    fn url(index: Option<Index>, format: GraphFormat, depth: usize, download: bool) -> String {
+        web::to_path_and_query(
+            ElementGraph::path(index, format.extension()),
+            &ElementGraphQuery {
+                depth,
+                direction: dot::Direction::Both,
+                download,
+            },
+        )
+    }
+    fn path(index: Option<Index>, extension: &str) -> String {
+        match index {
+            Some(index) => format!("/element/{}/graph.{}", index, extension),
+            None => format!("/graph.{}", extension),
+        }
+    }
+}
+impl EndPoint for ElementGraph {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        let formats = [
+            ("/graph.svg", GraphFormat::Svg),
+            ("/graph.json", GraphFormat::Json),
+            ("/graph.dot", GraphFormat::Dot),
+        ];
+        let path = r.uri().path();
+        let index_and_format = formats
+            .iter()
+            .find(|&&(suffix, _)| path.ends_with(suffix))
+            .and_then(|&(suffix, format)| match &path[..path.len() - suffix.len()] {
+                "" => Some((None, format)),
+                prefix => remove_prefix(prefix, "/element/").map(|index| (Some(index), format)),
+            });
+        match (r.method(), index_and_format) {
+            (&Method::GET, Some((index, format))) => Ok(FromRequestOk::Value(ElementGraph {
+                index: match index {
+                    Some(index) => Some(parse_index(index)?),
+                    None => None,
+                },
+                format,
+                query: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let options = dot::Options {
+            root: self.index,
+            depth: self.query.depth,
+            direction: self.query.direction,
+            ..dot::Options::default()
+        };
+        let text = match self.format {
+            GraphFormat::Json => cytoscape::render(&state.get(), &options),
+            GraphFormat::Svg | GraphFormat::Dot => dot::render(&state.get(), &options),
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(_) => return web::response_empty_404(),
+        };
+        let body = match self.format {
+            GraphFormat::Svg => match dot::layout_to_svg(text.as_bytes()) {
+                Ok(svg) => svg,
+                Err(e) => {
+                    eprintln!("[warning] Cannot render graph: {}", e);
+                    return Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body(Body::empty())
+                        .unwrap();
+                }
+            },
+            GraphFormat::Json | GraphFormat::Dot => text.into_bytes(),
+        };
+        let mut response = Response::builder();
+        response
+            .status(StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, self.format.mime());
+        if self.query.download {
+            let name = match self.index {
+                Some(index) => format!("element_{}", index),
+                None => "database".to_string(),
+            };
+            let disposition = format!(
+                "attachment; filename=\"{}.{}\"",
+                name,
+                self.format.extension()
+            );
+            response.header(hyper::header::CONTENT_DISPOSITION, disposition);
+        }
+        response.body(Body::from(body)).unwrap()
+    }
+}
+/// Links to save the graph of an element neighborhood, or of the whole database.
+fn graph_download_links(index: Option<Index>) -> Markup {
+    html! {
+        p.hbox {
+            a href=(InteractiveGraph::url(index, 1)) { (lang::INTERACTIVE_GRAPH) }
+            a href=(ElementGraph::url(index, GraphFormat::Dot, 1, true)) download? { (lang::DOWNLOAD_DOT) }
+            a href=(ElementGraph::url(index, GraphFormat::Svg, 1, true)) download? { (lang::DOWNLOAD_SVG) }
+        }
+    }
 }
 
+/// Page displaying the graph of ElementGraph with cytoscape.js, which can be moved and zoomed.
+/// The library is loaded by the browser from the configured url, see client.js for the view.
+struct InteractiveGraph {
+    index: Option<Index>,
+    query: ElementGraphQuery,
+}
+impl InteractiveGraph {
+    fn url(index: Option<Index>, depth: usize) -> String {
+        web::to_path_and_query(
+            ElementGraph::path(index, "html"),
+            &ElementGraphQuery {
+                depth,
+                direction: dot::Direction::Both,
+                download: false,
+            },
+        )
+    }
+}
+impl EndPoint for InteractiveGraph {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        let path = r.uri().path();
+        let index = match path.len().checked_sub("/graph.html".len()) {
+            Some(end) if path.ends_with("/graph.html") => match &path[..end] {
+                "" => Some(None),
+                prefix => remove_prefix(prefix, "/element/").map(Some),
+            },
+            _ => None,
+        };
+        match (r.method(), index) {
+            (&Method::GET, Some(index)) => Ok(FromRequestOk::Value(InteractiveGraph {
+                index: match index {
+                    Some(index) => Some(parse_index(index)?),
+                    None => None,
+                },
+                query: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let element = match self.index.map(|index| database.element(index)) {
+            Some(Ok(element)) => Some(element),
+            Some(Err(_)) => return web::response_empty_404(),
+            None => None,
+        };
+        let graph_url = web::to_path_and_query(ElementGraph::path(self.index, "json"), &self.query);
+        let depth_link = |depth: usize| {
+            html! { a href=(InteractiveGraph::url(self.index, depth)) { (depth) } }
+        };
+        let content = html! {
+            h1 {
+                (lang::INTERACTIVE_GRAPH)
+                @if let Some(element) = element { " - " (element_link(element, &EditState::default())) }
+            }
+            p.hbox {
+                (lang::GRAPH_DEPTH) " :"
+                @for depth in 1..ElementGraph::MAX_DEPTH + 1 {
+                    @if depth == self.query.depth { (depth) } @else { (depth_link(depth)) }
+                }
+            }
+            div#cytoscape data-graph=(graph_url) {}
+            script src=(state.cytoscape_url) {}
+        };
+        let page = layout::page(state, &EditState::default(), element, lang::INTERACTIVE_GRAPH, content);
+        web::response_html(page)
+    }
+}
+/// Whole database for download, serialized while it is sent.
+/// The serialization uses a snapshot shared by concurrent downloads, so that it does not block changes.
+struct ExportDatabase {
+    extension: String,
+}
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Json,
+    NTriples,
+    Dot,
+    Plugin(Exporter),
+}
+impl ExportFormat {
+    const ALL: [ExportFormat; 3] = [ExportFormat::Json, ExportFormat::NTriples, ExportFormat::Dot];
+    /// Builtin formats, then the ones of plugins.
+    fn available(state: &State) -> Vec<ExportFormat> {
+        let plugin_formats = state.exporters.iter().map(|e| ExportFormat::Plugin(*e));
+        ExportFormat::ALL.iter().cloned().chain(plugin_formats).collect()
+    }
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::NTriples => "nt",
+            ExportFormat::Dot => "dot",
+            ExportFormat::Plugin(exporter) => exporter.extension,
+        }
+    }
+    fn mime(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::NTriples => "application/n-triples",
+            ExportFormat::Dot => "text/vnd.graphviz",
+            ExportFormat::Plugin(exporter) => exporter.mime,
+        }
+    }
+}
+impl ExportDatabase {
+    fn url(format: ExportFormat) -> String {
+        web::prefixed_path(&format!("/export.{}", format.extension()))
+    }
+}
+impl EndPoint for ExportDatabase {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        // Formats of plugins are only known from the state.
+        match (r.method(), remove_prefix(r.uri().path(), "/export.")) {
+            (&Method::GET, Some(extension)) => Ok(FromRequestOk::Value(ExportDatabase {
+                extension: extension.to_string(),
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let formats = ExportFormat::available(state);
+        let format = match formats.into_iter().find(|f| f.extension() == self.extension) {
+            Some(format) => format,
+            None => return web::response_empty_404(),
+        };
+        let database = state.snapshot();
+        let mut response = web::response_streamed(format.mime(), move |w| {
+            let options = dot::Options {
+                max_label_len: None,
+                ..dot::Options::default()
+            };
+            let elements: Set<Index> = database.iter().map(|e| e.index()).collect();
+            match format {
+                ExportFormat::Json => cytoscape::write_subgraph(&database, &elements, w),
+                ExportFormat::NTriples => {
+                    let context = jsonld::Context::default();
+                    ntriples::write_subgraph(&database, &elements, &context, w)
+                }
+                ExportFormat::Dot => dot::write_subgraph(&database, &elements, &options, w),
+                ExportFormat::Plugin(exporter) => (exporter.write)(&database, w),
+            }
+        });
+        let disposition = format!("attachment; filename=\"database.{}\"", format.extension());
+        response.headers_mut().insert(
+            hyper::header::CONTENT_DISPOSITION,
+            hyper::header::HeaderValue::from_str(&disposition).unwrap(),
+        );
+        response
+    }
+}
+/// Links to download the whole database in each export format.
+fn export_links(state: &State) -> Markup {
+    html! {
+        p.hbox {
+            (lang::EXPORT_DATABASE)
+            @for format in ExportFormat::available(state) {
+                a href=(ExportDatabase::url(format)) download? { (format.extension()) }
+            }
+        }
+    }
+}
 /// Homepage : links to selected elements.
 struct Homepage {
     edit_state: EditState,
@@ -297,32 +1091,34 @@ impl EndPoint for Homepage {
                     }
                 }
             }
-            form.hbox method="post" action=(CreateAtom::url(&self.edit_state)) {
-                label for="wiki_homepage" { (lang::HOMEPAGE_HELP) }
-                button#wiki_homepage { "_wiki_homepage" }
-                input type="hidden" name="text" value="_wiki_homepage";
+            @if !state.read_only {
+                form.hbox method="post" action=(CreateAtom::url(&self.edit_state)) {
+                    label for="wiki_homepage" { (lang::HOMEPAGE_HELP) }
+                    button#wiki_homepage { "_wiki_homepage" }
+                    input type="hidden" name="text" value="_wiki_homepage";
+                }
             }
         };
-        let nav = navigation_links(&self.edit_state, None);
-        let page = compose_wiki_page(lang::HOMEPAGE, content, nav);
+        let page = layout::page(state, &self.edit_state, None, lang::HOMEPAGE, content);
         web::response_html(page)
     }
 }
 
-/// List all elements.
-struct ListAllElements {
+/// Elements most recently created or modified.
+struct RecentChanges {
     edit_state: EditState,
 }
-impl ListAllElements {
+const MAX_RECENT_CHANGES: usize = 50;
+impl RecentChanges {
     fn url(edit_state: &EditState) -> String {
-        web::to_path_and_query("/all", edit_state)
+        web::to_path_and_query("/recent", edit_state)
     }
 }
-impl EndPoint for ListAllElements {
+impl EndPoint for RecentChanges {
     type State = State;
     fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
         match (r.method(), r.uri().path()) {
-            (&Method::GET, "/all") => Ok(FromRequestOk::Value(ListAllElements {
+            (&Method::GET, "/recent") => Ok(FromRequestOk::Value(RecentChanges {
                 edit_state: web::from_query(r.uri().query())?,
             })),
             _ => Err(FromRequestError::NoMatch(r)),
@@ -331,84 +1127,506 @@ impl EndPoint for ListAllElements {
     fn generate_response(self, state: &State) -> Response<Body> {
         let database = state.get();
         let content = html! {
-            h1 { (lang::ALL_ELEMENTS_TITLE) }
+            h1 { (lang::RECENT_TITLE) }
             ul {
-                @for element in database.iter() {
-                    li { (element_link(element, &self.edit_state)) }
+                @for element in database.recently_modified(MAX_RECENT_CHANGES) {
+                    li {
+                        (element_link(element, &self.edit_state))
+                        @if let Some(author) = element.provenance().and_then(|p| p.author.as_ref()) {
+                            " (" (author) ")"
+                        }
+                    }
                 }
             }
         };
-        let nav = navigation_links(&self.edit_state, None);
-        let page = compose_wiki_page(lang::ALL_ELEMENTS_TITLE, content, nav);
+        let page = layout::page(state, &self.edit_state, None, lang::RECENT_TITLE, content);
         web::response_html(page)
     }
 }
 
-/// Search by name in the list of atoms.
-struct SearchAtom {
-    pattern: Option<String>,
+/// Elements whose page was visited by the request sender, most recent first.
+struct VisitedElements {
+    headers: hyper::HeaderMap,
     edit_state: EditState,
 }
-impl SearchAtom {
+impl VisitedElements {
     fn url(edit_state: &EditState) -> String {
-        web::to_path_and_query("/search/atom", edit_state)
+        web::to_path_and_query("/history", edit_state)
     }
 }
-impl EndPoint for SearchAtom {
+impl EndPoint for VisitedElements {
     type State = State;
     fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
         match (r.method(), r.uri().path()) {
-            (&Method::GET, "/search/atom") => Ok(FromRequestOk::Value(SearchAtom {
-                pattern: None,
+            (&Method::GET, "/history") => Ok(FromRequestOk::Value(VisitedElements {
                 edit_state: web::from_query(r.uri().query())?,
+                headers: r.headers().clone(),
             })),
-            (&Method::POST, "/search/atom") => {
-                let edit_state = web::from_query(r.uri().query())?;
-                web::with_post_entries(r, move |entries| {
-                    let pattern = entries.get("pattern").ok_or(web::Error::BadRequest)?;
-                    Ok(SearchAtom {
-                        pattern: Some(pattern.to_string()),
-                        edit_state,
-                    })
-                })
-            }
             _ => Err(FromRequestError::NoMatch(r)),
         }
     }
     fn generate_response(self, state: &State) -> Response<Body> {
+        let visits = state.history.visits(&self.headers);
+        let database = state.get();
         let content = html! {
-            h1.atom { (lang::SEARCH_ATOM_TITLE) }
-            form.vbox method="post" action=(SearchAtom::url(&self.edit_state)) {
-                input type="text" name="pattern" required? placeholder=(lang::ATOM_TEXT)
-                    value=(match self.pattern.as_ref() {
-                        Some(s) => s.as_str(),
-                        None => "",
-                    });
-                button { (lang::COMMIT_BUTTON) }
-            }
-            @if let Some(pattern) = self.pattern {
-                @let database = state.get();
-                @let results = database.text_atom_fuzzy_matches(&pattern);
-                table {
-                    @for (atom, score) in results.iter().take(40) {
-                        tr {
-                            td { (score) }
-                            td { (atom_link(atom, &self.edit_state)) }
-                        }
+            h1 { (lang::HISTORY_TITLE) }
+            ul {
+                @for &index in visits.iter() {
+                    @if let Ok(element) = database.element(index) {
+                        li { (element_link(element, &self.edit_state)) }
                     }
                 }
             }
         };
-        let nav = navigation_links(&self.edit_state, None);
-        let page = compose_wiki_page(lang::SEARCH_ATOM_TITLE, content, nav);
+        let page = layout::page(state, &self.edit_state, None, lang::HISTORY_TITLE, content);
         web::response_html(page)
     }
 }
 
-/// Create an atom.
-enum CreateAtom {
-    Get { edit_state: EditState },
-    Post { text: String, edit_state: EditState },
+/// List all elements.
+struct ListAllElements {
+    edit_state: EditState,
+}
+impl ListAllElements {
+    fn url(edit_state: &EditState) -> String {
+        web::to_path_and_query("/all", edit_state)
+    }
+}
+impl EndPoint for ListAllElements {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/all") => Ok(FromRequestOk::Value(ListAllElements {
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let elements = {
+            let mut v: Vec<_> = database
+                .iter()
+                .map(|e| (element_text_name(e, 1), e))
+                .collect();
+            v.sort_by(|l, r| state.collation.compare(&l.0, &r.0));
+            v
+        };
+        let content = html! {
+            h1 { (lang::ALL_ELEMENTS_TITLE) }
+            ul {
+                @for (_, element) in elements {
+                    li { (element_link(element, &self.edit_state)) }
+                }
+            }
+        };
+        let page = layout::page(state, &self.edit_state, None, lang::ALL_ELEMENTS_TITLE, content);
+        web::response_html(page)
+    }
+}
+
+/// Paginated list of all elements, grouped by kind.
+struct ElementIndex {
+    query: ElementIndexQuery,
+}
+#[derive(Clone)]
+struct ElementIndexQuery {
+    page: usize,
+    sort_by_name: bool,
+    /// Only list elements of this namespace.
+    namespace: Option<String>,
+    edit_state: EditState,
+}
+impl web::QueryFormat for ElementIndexQuery {
+    fn to_query(&self, builder: &mut web::PathQueryBuilder) {
+        builder.entry("page", self.page);
+        builder.entry("sort", if self.sort_by_name { "name" } else { "index" });
+        builder.optional_entry("namespace", self.namespace.as_ref().map(|s| s.as_str()));
+        self.edit_state.to_query(builder);
+    }
+    fn from_query(entries: &web::UrlDecodedEntries) -> Result<Self, web::Error> {
+        Ok(ElementIndexQuery {
+            page: parse_optional_index(entries.get("page"))?.unwrap_or(0),
+            sort_by_name: match entries.get("sort") {
+                None | Some("index") => false,
+                Some("name") => true,
+                Some(_) => return Err(web::Error::BadRequest),
+            },
+            namespace: entries.get("namespace").map(String::from),
+            edit_state: EditState::from_query(entries)?,
+        })
+    }
+}
+impl ElementIndex {
+    const PAGE_SIZE: usize = 100;
+    fn url(query: &ElementIndexQuery) -> String {
+        web::to_path_and_query("/index", query)
+    }
+}
+impl EndPoint for ElementIndex {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/index") => Ok(FromRequestOk::Value(ElementIndex {
+                query: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let query = &self.query;
+        let edit_state = &query.edit_state;
+        let elements = {
+            // Grouped by kind, then by index or name.
+            let mut v: Vec<_> = match query.namespace {
+                Some(ref name) => database.iter_namespace(name).collect(),
+                None => database.iter().collect(),
+            };
+            if query.sort_by_name {
+                let mut named: Vec<_> = v
+                    .into_iter()
+                    .map(|e| (element_text_name(e, 1), e))
+                    .collect();
+                named.sort_by(|l, r| {
+                    let (l_kind, r_kind) = (l.1.value().kind(), r.1.value().kind());
+                    l_kind
+                        .cmp(&r_kind)
+                        .then_with(|| state.collation.compare(&l.0, &r.0))
+                });
+                v = named.into_iter().map(|p| p.1).collect();
+            } else {
+                v.sort_by_key(|e| e.value().kind()); // Stable, keeps index order
+            }
+            v
+        };
+        let nb_pages = (elements.len() + Self::PAGE_SIZE - 1) / Self::PAGE_SIZE;
+        let page_groups = {
+            let mut groups: Vec<(ElementKind, Vec<Ref<Element>>)> = Vec::new();
+            let page_elements = elements
+                .iter()
+                .skip(query.page * Self::PAGE_SIZE)
+                .take(Self::PAGE_SIZE);
+            for element in page_elements {
+                let kind = element.value().kind();
+                if groups.last().map_or(false, |g| g.0 == kind) {
+                    groups.last_mut().unwrap().1.push(*element)
+                } else {
+                    groups.push((kind, vec![*element]))
+                }
+            }
+            groups
+        };
+        let with = |page: usize, sort_by_name: bool| ElementIndexQuery {
+            page,
+            sort_by_name,
+            namespace: query.namespace.clone(),
+            edit_state: edit_state.clone(),
+        };
+        let in_namespace = |namespace: Option<&str>| ElementIndexQuery {
+            page: 0,
+            sort_by_name: query.sort_by_name,
+            namespace: namespace.map(String::from),
+            edit_state: edit_state.clone(),
+        };
+        let namespaces = database.namespaces();
+        let content = html! {
+            h1 { (lang::INDEX_TITLE) }
+            p.hbox {
+                a href=(ElementIndex::url(&with(0, false))) { (lang::INDEX_SORT_BY_INDEX) }
+                a href=(ElementIndex::url(&with(0, true))) { (lang::INDEX_SORT_BY_NAME) }
+            }
+            @if !namespaces.is_empty() {
+                p.hbox {
+                    a href=(ElementIndex::url(&in_namespace(None))) { (lang::INDEX_ALL_NAMESPACES) }
+                    @for name in namespaces {
+                        a href=(ElementIndex::url(&in_namespace(Some(name)))) { (name) }
+                    }
+                }
+            }
+            @for (kind, group) in page_groups {
+                h2 {
+                    @match kind {
+                        ElementKind::Abstract => (lang::ABSTRACT),
+                        ElementKind::Atom => (lang::ATOM),
+                        ElementKind::Relation => (lang::RELATION),
+                    }
+                }
+                ul {
+                    @for element in group {
+                        li { (element_link(element, edit_state)) }
+                    }
+                }
+            }
+            p.hbox {
+                @if query.page > 0 {
+                    a href=(ElementIndex::url(&with(query.page - 1, query.sort_by_name))) { (lang::PREVIOUS_PAGE) }
+                }
+                (query.page + 1) " / " (nb_pages)
+                @if query.page + 1 < nb_pages {
+                    a href=(ElementIndex::url(&with(query.page + 1, query.sort_by_name))) { (lang::NEXT_PAGE) }
+                }
+            }
+            (graph_download_links(None))
+            (export_links(state))
+        };
+        let page = layout::page(state, edit_state, None, lang::INDEX_TITLE, content);
+        web::response_html(page)
+    }
+}
+
+/// List structures exceeding soft limits, which are probably modeling mistakes.
+struct ListWarnings {
+    edit_state: EditState,
+}
+impl ListWarnings {
+    fn url(edit_state: &EditState) -> String {
+        web::to_path_and_query("/warnings", edit_state)
+    }
+}
+impl EndPoint for ListWarnings {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/warnings") => Ok(FromRequestOk::Value(ListWarnings {
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let warnings = database.soft_limit_warnings();
+        let content = html! {
+            h1 { (lang::WARNINGS_TITLE) }
+            @if warnings.is_empty() {
+                p { (lang::WARNINGS_NONE) }
+            }
+            ul {
+                @for warning in warnings.iter() {
+                    li {
+                        // Index only, as the element name may be the problem.
+                        a href=(DisplayElement::url(warning.index(), &self.edit_state)) { "#" (warning.index()) }
+                        " : "
+                        @match *warning {
+                            Warning::ManyReferences { count, .. } => (lang::WARNING_MANY_REFERENCES) " (" (count) ")",
+                            Warning::LargeAtom { len, .. } => (lang::WARNING_LARGE_ATOM) " (" (len) ")",
+                            Warning::DescriptorUsedOnce { .. } => (lang::WARNING_DESCRIPTOR_USED_ONCE),
+                            Warning::DeepRelation { depth, .. } => (lang::WARNING_DEEP_RELATION) " (" (depth) ")",
+                            Warning::NotUnique { descriptor, .. } => {
+                                (lang::WARNING_NOT_UNIQUE) " "
+                                a href=(DisplayElement::url(descriptor, &self.edit_state)) { "#" (descriptor) }
+                            },
+                        }
+                    }
+                }
+            }
+        };
+        let page = layout::page(state, &self.edit_state, None, lang::WARNINGS_TITLE, content);
+        web::response_html(page)
+    }
+}
+
+/// List elements with the same structure as an example element.
+struct SimilarElements {
+    index: Index,
+    edit_state: EditState,
+}
+impl SimilarElements {
+    fn url(index: Index, edit_state: &EditState) -> String {
+        web::to_path_and_query(format!("/similar/{}", index), edit_state)
+    }
+}
+impl EndPoint for SimilarElements {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), remove_prefix(r.uri().path(), "/similar/")) {
+            (&Method::GET, Some(index)) => Ok(FromRequestOk::Value(SimilarElements {
+                index: parse_index(index)?,
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let (element, signature) = match database.signature(self.index) {
+            Ok(signature) => (database.element(self.index).unwrap(), signature),
+            Err(_) => return web::response_empty_404(),
+        };
+        let similar = database.matching_signature(&signature);
+        let content = html! {
+            h1 class=(css_class_name(element)) { (lang::SIMILAR_TITLE) }
+            p { (lang::SIMILAR_EXAMPLE) ": " (element_link(element, &self.edit_state)) }
+            table {
+                @for &(descriptor, complement_kind) in signature.descriptions.iter() {
+                    tr {
+                        td { (element_link(database.element(descriptor).unwrap(), &self.edit_state)) }
+                        td {
+                            @match complement_kind {
+                                Some(ElementKind::Abstract) => (lang::ABSTRACT),
+                                Some(ElementKind::Atom) => (lang::ATOM),
+                                Some(ElementKind::Relation) => (lang::RELATION),
+                                None => {},
+                            }
+                        }
+                    }
+                }
+            }
+            ul {
+                @for index in similar {
+                    li { (element_link(database.element(index).unwrap(), &self.edit_state)) }
+                }
+            }
+        };
+        let page = layout::page(state, &self.edit_state, Some(element), lang::SIMILAR_TITLE, content);
+        web::response_html(page)
+    }
+}
+
+/// Search by name in the list of atoms.
+struct SearchAtom {
+    pattern: Option<String>,
+    edit_state: EditState,
+}
+impl SearchAtom {
+    fn url(edit_state: &EditState) -> String {
+        web::to_path_and_query("/search/atom", edit_state)
+    }
+}
+impl EndPoint for SearchAtom {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/search/atom") => Ok(FromRequestOk::Value(SearchAtom {
+                pattern: None,
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            (&Method::POST, "/search/atom") => {
+                let edit_state = web::from_query(r.uri().query())?;
+                web::with_post_entries(r, move |entries| {
+                    let pattern = entries.get("pattern").ok_or(web::Error::BadRequest)?;
+                    Ok(SearchAtom {
+                        pattern: Some(pattern.to_string()),
+                        edit_state,
+                    })
+                })
+            }
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let content = html! {
+            h1.atom { (lang::SEARCH_ATOM_TITLE) }
+            form.vbox method="post" action=(SearchAtom::url(&self.edit_state)) {
+                input type="text" name="pattern" required? placeholder=(lang::ATOM_TEXT)
+                    value=(match self.pattern.as_ref() {
+                        Some(s) => s.as_str(),
+                        None => "",
+                    });
+                button { (lang::COMMIT_BUTTON) }
+            }
+            @if let Some(pattern) = self.pattern {
+                @let database = state.get();
+                @let results = database.text_atom_fuzzy_matches(&pattern);
+                table {
+                    @for (atom, score) in results.iter().take(40) {
+                        tr {
+                            td { (score) }
+                            td { (atom_link(atom, &self.edit_state)) }
+                        }
+                    }
+                }
+                @let in_texts = database.find_in_descriptions(&pattern);
+                @if !in_texts.is_empty() {
+                    h2 { (lang::SEARCH_IN_TEXTS) }
+                    @let words = words(&pattern);
+                    ul {
+                        @for atom in in_texts.iter().take(40) {
+                            li {
+                                a.atom href=(DisplayElement::url(atom.index(), &self.edit_state)) {
+                                    (highlighted_words(&atom.value().to_string(), &words))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let page = layout::page(state, &self.edit_state, None, lang::SEARCH_ATOM_TITLE, content);
+        web::response_html(page)
+    }
+}
+
+/// Elements whose text or name starts with a prefix, as JSON for the quick open palette.
+/// Results are objects `{"url": u, "kind": k, "text": t}`, named abstracts before their name atom.
+struct CompleteElement {
+    query: CompleteQuery,
+}
+struct CompleteQuery {
+    prefix: String,
+    edit_state: EditState,
+}
+impl web::QueryFormat for CompleteQuery {
+    fn to_query(&self, builder: &mut web::PathQueryBuilder) {
+        builder.entry("q", self.prefix.as_str());
+        self.edit_state.to_query(builder);
+    }
+    fn from_query(entries: &web::UrlDecodedEntries) -> Result<Self, web::Error> {
+        Ok(CompleteQuery {
+            prefix: entries.get("q").unwrap_or("").to_string(),
+            edit_state: EditState::from_query(entries)?,
+        })
+    }
+}
+const MAX_COMPLETIONS: usize = 20;
+impl CompleteElement {
+    fn url() -> String {
+        web::prefixed_path("/complete")
+    }
+}
+impl EndPoint for CompleteElement {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/complete") => Ok(FromRequestOk::Value(CompleteElement {
+                query: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let database = state.get();
+        let edit_state = &self.query.edit_state;
+        let completion = |index: Index, kind: &str, text: &str| {
+            format!(
+                "{{\"url\":\"{}\",\"kind\":\"{}\",\"text\":\"{}\"}}",
+                JsonEscaped(&DisplayElement::url(index, edit_state)),
+                kind,
+                JsonEscaped(text)
+            )
+        };
+        let mut completions = Vec::new();
+        for atom in database.text_atoms_with_prefix(&self.query.prefix) {
+            let text = atom.value().to_string();
+            for named in state.names.find_by_name(&text) {
+                completions.push(completion(named, "abstract", &text))
+            }
+            completions.push(completion(atom.index(), "atom", &text));
+            if completions.len() >= MAX_COMPLETIONS {
+                break;
+            }
+        }
+        completions.truncate(MAX_COMPLETIONS);
+        let body = format!("[{}]", completions.join(","));
+        web::response_json(StatusCode::OK, body)
+    }
+}
+
+/// Create an atom.
+enum CreateAtom {
+    Get { edit_state: EditState },
+    Post { text: String, edit_state: EditState },
 }
 impl CreateAtom {
     fn url(edit_state: &EditState) -> String {
@@ -446,8 +1664,7 @@ impl EndPoint for CreateAtom {
                         }
                     }
                 };
-                let nav = navigation_links(&edit_state, None);
-                let page = compose_wiki_page(lang::CREATE_ATOM_TITLE, content, nav);
+                let page = layout::page(state, &edit_state, None, lang::CREATE_ATOM_TITLE, content);
                 web::response_html(page)
             }
             CreateAtom::Post { text, edit_state } => {
@@ -507,8 +1724,7 @@ impl EndPoint for CreateAbstract {
                         }
                     }
                 };
-                let nav = navigation_links(&edit_state, None);
-                let page = compose_wiki_page(lang::CREATE_ABSTRACT_TITLE, content, nav);
+                let page = layout::page(state, &edit_state, None, lang::CREATE_ABSTRACT_TITLE, content);
                 web::response_html(page)
             }
             CreateAbstract::Post { name, edit_state } => {
@@ -582,6 +1798,36 @@ impl EndPoint for CreateRelation {
                         && valid_or(edit_state.descriptor, false)
                         && valid_or(edit_state.complement, true)
                 };
+                // Constraints of the schema the relation would break, with their text.
+                let violations: Vec<String> = match (edit_state.subject, edit_state.descriptor) {
+                    (Some(subject), Some(descriptor)) if enable_form => {
+                        let relation = Relation {
+                            subject,
+                            descriptor,
+                            complement: edit_state.complement,
+                        };
+                        let violations = database.relation_violations(&relation, &state.schema);
+                        let violations = violations.unwrap_or_else(|_| Vec::new());
+                        let text = |i: usize| state.schema.constraints[i].to_string();
+                        violations.into_iter().map(text).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                let can_commit = enable_form && violations.is_empty();
+                // Descriptors of the schema valid for the subject, to choose from.
+                let suggested_descriptors = edit_state
+                    .subject
+                    .and_then(|subject| {
+                        let complement = edit_state.complement;
+                        database
+                            .schema_descriptors(subject, complement, &state.schema)
+                            .ok()
+                    })
+                    .unwrap_or_else(Vec::new);
+                let with_descriptor = |descriptor: Index| EditState {
+                    descriptor: Some(descriptor),
+                    ..edit_state.clone()
+                };
                 let field_preview = |name: PreEscaped<&str>,
                                      index: Option<Index>,
                                      allow_missing: bool|
@@ -610,6 +1856,20 @@ impl EndPoint for CreateRelation {
                             (field_preview(lang::RELATION_DESCRIPTOR, edit_state.descriptor, false))
                             (field_preview(lang::RELATION_COMPLEMENT, edit_state.complement, true))
                         }
+                        @if !suggested_descriptors.is_empty() {
+                            p {
+                                (lang::SCHEMA_DESCRIPTORS) ":"
+                                @for descriptor in suggested_descriptors {
+                                    " "
+                                    a.atom href=(CreateRelation::url(&with_descriptor(descriptor))) {
+                                        (element_name(database.element(descriptor).unwrap(), 0))
+                                    }
+                                }
+                            }
+                        }
+                        @for violation in violations.iter() {
+                            p.error { (lang::SCHEMA_VIOLATION) ": " (violation) }
+                        }
                         @if let Some(subject) = edit_state.subject {
                             input type="hidden" name="subject" value=(subject);
                         }
@@ -619,21 +1879,23 @@ impl EndPoint for CreateRelation {
                         @if let Some(complement) = edit_state.complement {
                             input type="hidden" name="complement" value=(complement);
                         }
-                        button disabled?[!enable_form] { (lang::COMMIT_BUTTON) }
+                        button disabled?[!can_commit] { (lang::COMMIT_BUTTON) }
                     }
                 };
-                let nav = navigation_links(&edit_state, None);
-                let page = compose_wiki_page(lang::CREATE_RELATION_TITLE, content, nav);
+                let page = layout::page(state, &edit_state, None, lang::CREATE_RELATION_TITLE, content);
                 web::response_html(page)
             }
             CreateRelation::Post {
                 relation,
                 edit_state,
             } => {
-                let insertion = state.get_mut().insert_relation(relation);
+                let insertion = state
+                    .get_mut()
+                    .insert_relation_with_schema(relation, &state.schema);
                 web::response_redirection(&match insertion {
                     Ok(index) => DisplayElement::url(index, &EditState::default()),
-                    Err(_) => CreateRelation::url(&edit_state), // Allow retrying
+                    // Allow retrying, with schema violations shown by the form
+                    Err(_) => CreateRelation::url(&edit_state),
                 })
             }
         }
@@ -683,9 +1945,13 @@ impl EndPoint for RemoveElement {
                     Ok(element) => element,
                     Err(_) => return web::response_empty_404(),
                 };
+                let protected = database.is_protected(self.index);
                 let content = html! {
                     h1 class=(css_class_name(element)) { (lang::REMOVE_ELEMENT_TITLE) }
                     p { (lang::REMOVE_ELEMENT_TITLE) ": " (element_link(element, &self.edit_state)) }
+                    @if protected {
+                        p.error { (lang::PROTECTED_MESSAGE) }
+                    }
                     @if element.is_referenced() {
                         p.error { (lang::REMOVE_ELEMENT_REFERENCED_MESSAGE) }
                         ul {
@@ -697,31 +1963,30 @@ impl EndPoint for RemoveElement {
                         }
                     }
                     form.hbox method="post" action=(RemoveElement::url(self.index, &self.edit_state)) {
-                        button disabled?[element.is_referenced()] { (lang::COMMIT_BUTTON) }
+                        button disabled?[element.is_referenced() || protected] { (lang::COMMIT_BUTTON) }
                     }
                 };
-                let nav = navigation_links(&self.edit_state, None);
-                let page = compose_wiki_page(lang::REMOVE_ELEMENT_TITLE, content, nav);
+                let page = layout::page(state, &self.edit_state, None, lang::REMOVE_ELEMENT_TITLE, content);
                 web::response_html(page)
             }
             RemoveElementStep::Removal => {
-                let removed_element = match state.get_mut().remove_element(self.index) {
-                    Ok(e) => e,
-                    Err(_) => return web::response_empty_400(),
+                // Deleted elements are kept until purged, so that they can be restored.
+                let description = {
+                    let mut database = state.get_mut();
+                    if database.delete_element(self.index).is_err() {
+                        return web::response_empty_400();
+                    }
+                    deleted_element_description(&database, self.index)
                 };
                 let content = html! {
                     h1 { (lang::REMOVE_ELEMENT_REMOVED) }
-                    p {
-                        (lang::REMOVE_ELEMENT_REMOVED) ": "
-                        @match removed_element {
-                            Element::Abstract => (lang::ABSTRACT),
-                            Element::Atom(_) => (lang::ATOM),
-                            Element::Relation(_) => (lang::RELATION),
-                        } "#" (self.index)
+                    form.hbox method="post" action=(DeletedElements::restore_url()) {
+                        (lang::REMOVE_ELEMENT_REMOVED) ": " (description)
+                        input type="hidden" name="index" value=(self.index);
+                        button { (lang::DELETED_RESTORE_BUTTON) }
                     }
                 };
-                let nav = navigation_links(&self.edit_state.remove_references_to(self.index), None);
-                let page = compose_wiki_page(lang::REMOVE_ELEMENT_REMOVED, content, nav);
+                let page = layout::page(state, &self.edit_state.remove_references_to(self.index), None, lang::REMOVE_ELEMENT_REMOVED, content);
                 web::response_html(page)
             }
         }
@@ -793,8 +2058,7 @@ impl EndPoint for ChangeAtomValue {
                         }
                     }
                 };
-                let nav = navigation_links(&edit_state, None);
-                let page = compose_wiki_page(lang::CHANGE_ATOM_VALUE_TITLE, content, nav);
+                let page = layout::page(state, &edit_state, None, lang::CHANGE_ATOM_VALUE_TITLE, content);
                 web::response_html(page)
             }
             ChangeAtomValue::Post {
@@ -857,8 +2121,7 @@ impl EndPoint for AtomToNamedAbstract {
                         button { (lang::COMMIT_BUTTON) }
                     }
                 };
-                let nav = navigation_links(&edit_state, None);
-                let page = compose_wiki_page(lang::ATOM_TO_NAMED_ABSTRACT_TITLE, content, nav);
+                let page = layout::page(state, &edit_state, None, lang::ATOM_TO_NAMED_ABSTRACT_TITLE, content);
                 web::response_html(page)
             }
             AtomToNamedAbstract::Post { index, edit_state } => {
@@ -882,23 +2145,520 @@ impl EndPoint for AtomToNamedAbstract {
     }
 }
 
-///////////////////////////////////////////////////////////////////////////////
-/// Language specific configuration.
-/// Contains text constants.
-mod lang {
-    use maud::PreEscaped;
-    type ConstStr = PreEscaped<&'static str>;
-
-    pub const NAMED_ATOM: &'static str = "est nommé";
-
-    pub const COMMIT_BUTTON: ConstStr = PreEscaped("Valider");
-    pub const PREVIEW_BUTTON: ConstStr = PreEscaped("Prévisualiser");
-    pub const INVALID_ELEMENT_INDEX: ConstStr = PreEscaped("Index invalide");
+/// Apply a JSON array of operations at once, all or nothing, so that external tools can sync changes.
+/// Operations are objects with an "op" field, and elements are designated by their index,
+/// or by `{"result": n}` for the element of the n-th operation of the batch:
+/// - `{"op": "insert_atom", "text": t}`
+/// - `{"op": "use_link", "subject": e, "descriptor": e, "complement": e}`, complement being optional
+/// - `{"op": "set_description", "element": e, "text": t}`, replacing its description relations
+/// - `{"op": "remove", "element": e}`
+///
+/// Relations must follow the schema. Responds with `{"indexes": [...]}`, the element of each
+/// operation, or with `{"error": "...", "operation": n}`, n being the position of the failing one.
+struct ApplyBatch {
+    body: String,
+}
+impl EndPoint for ApplyBatch {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::POST, "/api/batch") | (&Method::PATCH, "/api/batch") => {
+                web::with_json_body(r, |body| {
+                    Ok(ApplyBatch {
+                        body: body.to_string(),
+                    })
+                })
+            }
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        // Messages of parsing and database errors have no characters to escape.
+        let error = |message: String, position: Option<usize>| {
+            let body = match position {
+                Some(position) => {
+                    format!("{{\"error\":\"{}\",\"operation\":{}}}", message, position)
+                }
+                None => format!("{{\"error\":\"{}\"}}", message),
+            };
+            web::response_json(StatusCode::BAD_REQUEST, body)
+        };
+        let operations = match json::parse(&self.body) {
+            Ok(value) => operations_from_json(&value),
+            Err(e) => return error(e.to_string(), None),
+        };
+        let operations = match operations {
+            Ok(operations) => operations,
+            Err((position, e)) => return error(e.to_string(), position),
+        };
+        match state.get_mut().apply_operations(&operations, &state.schema) {
+            Ok(indexes) => {
+                let indexes: Vec<String> = indexes.iter().map(|i| i.to_string()).collect();
+                let body = format!("{{\"indexes\":[{}]}}", indexes.join(","));
+                web::response_json(StatusCode::OK, body)
+            }
+            Err((position, e)) => error(e.to_string(), Some(position)),
+        }
+    }
+}
+
+/// Operations of a batch, or an error with the position of the invalid operation.
+fn operations_from_json(
+    value: &json::Value,
+) -> Result<Vec<Operation>, (Option<usize>, &'static str)> {
+    let operations = match value {
+        json::Value::Array(operations) => operations,
+        _ => return Err((None, "expected an array of operations")),
+    };
+    operations
+        .iter()
+        .enumerate()
+        .map(|(position, operation)| {
+            operation_from_json(operation).map_err(|e| (Some(position), e))
+        })
+        .collect()
+}
+
+fn operation_from_json(operation: &json::Value) -> Result<Operation, &'static str> {
+    let text = || {
+        let text = operation.get("text").and_then(json::Value::as_str);
+        text.ok_or("missing text")
+    };
+    let element = |key: &str| batch_ref_from_json(operation.get(key));
+    match operation.get("op").and_then(json::Value::as_str) {
+        Some("insert_atom") => Ok(Operation::InsertAtom(Atom::from(text()?))),
+        Some("use_link") => Ok(Operation::UseLink {
+            subject: element("subject")?,
+            descriptor: element("descriptor")?,
+            complement: match operation.get("complement") {
+                None | Some(json::Value::Null) => None,
+                Some(_) => Some(element("complement")?),
+            },
+        }),
+        Some("set_description") => Ok(Operation::SetDescription {
+            element: element("element")?,
+            text: text()?.to_string(),
+        }),
+        Some("remove") => Ok(Operation::Remove(element("element")?)),
+        _ => Err("unknown operation"),
+    }
+}
+
+fn batch_ref_from_json(value: Option<&json::Value>) -> Result<BatchRef, &'static str> {
+    let value = value.ok_or("missing element")?;
+    if let Some(index) = value.as_usize() {
+        return Ok(BatchRef::Existing(index));
+    }
+    match value.get("result").and_then(json::Value::as_usize) {
+        Some(i) => Ok(BatchRef::Item(i)),
+        None => Err("element must be an index or a result reference"),
+    }
+}
+
+/// List the named versions of the database.
+struct ListVersions {
+    edit_state: EditState,
+}
+impl ListVersions {
+    fn url(edit_state: &EditState) -> String {
+        web::to_path_and_query("/versions", edit_state)
+    }
+    /// Forms to tag and restore versions are only shown if the wiki is not read only.
+    fn page(state: &State, edit_state: &EditState, error: Option<String>) -> Response<Body> {
+        let versions = list_versions(&state.versions_directory);
+        let restore_url = web::to_path_and_query("/versions/restore", &EditState::default());
+        let content = html! {
+            h1 { (lang::VERSIONS_TITLE) }
+            @if let Some(error) = error {
+                p.error { (error) }
+            }
+            @if !state.read_only {
+                form.hbox method="post" action=(ListVersions::url(&EditState::default())) {
+                    input type="text" name="name" required? placeholder=(lang::VERSION_NAME);
+                    button { (lang::VERSION_TAG_BUTTON) }
+                }
+            }
+            @match versions {
+                Err(e) => p.error { (e) },
+                Ok(versions) => {
+                    @if versions.is_empty() {
+                        p { (lang::NO_VERSIONS) }
+                    }
+                    ul {
+                        @for version in versions {
+                            li {
+                                @if state.read_only {
+                                    (version.name)
+                                } @else {
+                                    form.hbox method="post" action=(restore_url) {
+                                        (version.name)
+                                        input type="hidden" name="name" value=(version.name);
+                                        button { (lang::VERSION_RESTORE_BUTTON) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+        };
+        let page = layout::page(state, edit_state, None, lang::VERSIONS_TITLE, content);
+        web::response_html(page)
+    }
+}
+impl EndPoint for ListVersions {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/versions") => Ok(FromRequestOk::Value(ListVersions {
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        ListVersions::page(state, &self.edit_state, None)
+    }
+}
+
+/// Tag the current content of the database as a named version, or restore one.
+enum Versions {
+    Tag { name: String },
+    Restore { name: String },
+}
+impl Versions {
+    fn name_entry(entries: &web::UrlDecodedEntries) -> Result<String, web::Error> {
+        let name = entries.get("name").ok_or(web::Error::BadRequest)?;
+        Ok(name.trim().to_string())
+    }
+}
+impl EndPoint for Versions {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::POST, "/versions") => web::with_post_entries(r, move |entries| {
+                Ok(Versions::Tag {
+                    name: Versions::name_entry(&entries)?,
+                })
+            }),
+            (&Method::POST, "/versions/restore") => web::with_post_entries(r, move |entries| {
+                Ok(Versions::Restore {
+                    name: Versions::name_entry(&entries)?,
+                })
+            }),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let result = match self {
+            Versions::Tag { name } => {
+                // Writing may be long, do not block changes meanwhile.
+                let database = state.snapshot();
+                database
+                    .tag_version(&state.versions_directory, &name)
+                    .map(|_| ())
+            }
+            Versions::Restore { name } => Database::load_version(&state.versions_directory, &name)
+                .map(|version| state.get_mut().restore(version)),
+        };
+        // Indexes of the edit state may not be valid after a restore.
+        match result {
+            Ok(()) => web::response_redirection(&ListVersions::url(&EditState::default())),
+            Err(e) => ListVersions::page(state, &EditState::default(), Some(e)),
+        }
+    }
+}
+
+/// Elements removed by the wiki, kept until purged.
+struct ListDeleted {
+    edit_state: EditState,
+}
+impl ListDeleted {
+    fn url(edit_state: &EditState) -> String {
+        web::to_path_and_query("/deleted", edit_state)
+    }
+    /// Forms to restore and purge are only shown if the wiki is not read only.
+    fn page(state: &State, edit_state: &EditState, error: Option<String>) -> Response<Body> {
+        let database = state.get();
+        let deleted = database.deleted_elements();
+        let content = html! {
+            h1 { (lang::DELETED_TITLE) }
+            @if let Some(error) = error {
+                p.error { (error) }
+            }
+            @if deleted.is_empty() {
+                p { (lang::NO_DELETED) }
+            }
+            ul {
+                @for index in deleted.iter() {
+                    li {
+                        @if state.read_only {
+                            (deleted_element_description(&database, *index))
+                        } @else {
+                            form.hbox method="post" action=(DeletedElements::restore_url()) {
+                                (deleted_element_description(&database, *index))
+                                input type="hidden" name="index" value=(index);
+                                button { (lang::DELETED_RESTORE_BUTTON) }
+                            }
+                        }
+                    }
+                }
+            }
+            @if !state.read_only && !deleted.is_empty() {
+                form.hbox method="post" action=(DeletedElements::purge_url()) {
+                    button { (lang::DELETED_PURGE_BUTTON) }
+                }
+            }
+        };
+        let page = layout::page(state, edit_state, None, lang::DELETED_TITLE, content);
+        web::response_html(page)
+    }
+}
+impl EndPoint for ListDeleted {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/deleted") => Ok(FromRequestOk::Value(ListDeleted {
+                edit_state: web::from_query(r.uri().query())?,
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        ListDeleted::page(state, &self.edit_state, None)
+    }
+}
+
+/// Kind, index and value of a deleted element. Components of relations may not exist anymore.
+fn deleted_element_description(database: &Database, index: Index) -> Markup {
+    html! {
+        @match database.deleted_element(index) {
+            Some(Element::Abstract) => (lang::ABSTRACT) "#" (index),
+            Some(Element::Atom(atom)) => (lang::ATOM) "#" (index) " : " (atom),
+            Some(Element::Relation(relation)) => {
+                (lang::RELATION) "#" (index) " : #" (relation.subject) " #" (relation.descriptor)
+                @if let Some(complement) = relation.complement { " #" (complement) }
+            },
+            None => "#" (index),
+        }
+    }
+}
+
+/// Restore a deleted element, or purge all of them.
+enum DeletedElements {
+    Restore { index: Index },
+    Purge,
+}
+impl DeletedElements {
+    fn restore_url() -> String {
+        web::to_path_and_query("/deleted/restore", &EditState::default())
+    }
+    fn purge_url() -> String {
+        web::to_path_and_query("/deleted/purge", &EditState::default())
+    }
+}
+impl EndPoint for DeletedElements {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::POST, "/deleted/restore") => web::with_post_entries(r, move |entries| {
+                Ok(DeletedElements::Restore {
+                    index: parse_required_index(entries.get("index"))?,
+                })
+            }),
+            (&Method::POST, "/deleted/purge") => Ok(FromRequestOk::Value(DeletedElements::Purge)),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        match self {
+            DeletedElements::Restore { index } => {
+                // Release the lock before generating the error page, which reads the database.
+                let result = state.get_mut().restore_element(index);
+                match result {
+                    Ok(()) => {
+                        web::response_redirection(&DisplayElement::url(index, &EditState::default()))
+                    }
+                    Err(e) => ListDeleted::page(state, &EditState::default(), Some(e.to_string())),
+                }
+            }
+            DeletedElements::Purge => {
+                state.get_mut().purge_deleted();
+                web::response_redirection(&ListDeleted::url(&EditState::default()))
+            }
+        }
+    }
+}
+
+/// Open an editing session, when the wiki is protected by a password.
+enum Login {
+    Get {
+        query: LoginQuery,
+    },
+    Post {
+        password: String,
+        author: Option<String>,
+        query: LoginQuery,
+    },
+}
+struct LoginQuery {
+    /// Page to go back to after login, with the path prefix.
+    next: Option<String>,
+}
+impl QueryFormat for LoginQuery {
+    fn to_query(&self, builder: &mut web::PathQueryBuilder) {
+        builder.optional_entry("next", self.next.as_ref().map(|s| s.as_str()));
+    }
+    fn from_query(entries: &web::UrlDecodedEntries) -> Result<Self, web::Error> {
+        // Only local paths, to prevent redirections to other sites.
+        // Browsers read backslashes as slashes, so "/\host" would also be a protocol-relative url.
+        let is_local_path = |next: &str| {
+            next.starts_with('/')
+                && !next.starts_with("//")
+                && !next.chars().any(|c| c == '\\' || c.is_control())
+        };
+        let next = match entries.get("next") {
+            Some(next) if is_local_path(next) => Some(next.to_string()),
+            Some(_) => return Err(web::Error::BadRequest),
+            None => None,
+        };
+        Ok(LoginQuery { next })
+    }
+}
+impl Login {
+    fn url(query: &LoginQuery) -> String {
+        web::to_path_and_query("/login", query)
+    }
+    fn page(state: &State, query: &LoginQuery, failed: bool) -> String {
+        let content = html! {
+            h1 { (lang::LOGIN_TITLE) }
+            @if failed {
+                p { (lang::LOGIN_FAILED) }
+            }
+            form.hbox method="post" action=(Login::url(query)) {
+                input type="text" name="author" placeholder=(lang::LOGIN_AUTHOR);
+                input type="password" name="password" required? placeholder=(lang::LOGIN_PASSWORD);
+                button { (lang::COMMIT_BUTTON) }
+            }
+            form method="post" action=(Logout::url()) {
+                button { (lang::LOGOUT_BUTTON) }
+            }
+        };
+        layout::page(state, &EditState::default(), None, lang::LOGIN_TITLE, content)
+    }
+}
+impl EndPoint for Login {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/login") => Ok(FromRequestOk::Value(Login::Get {
+                query: web::from_query(r.uri().query())?,
+            })),
+            (&Method::POST, "/login") => {
+                let query = web::from_query(r.uri().query())?;
+                web::with_post_entries(r, move |entries| {
+                    let password = entries.get("password").ok_or(web::Error::BadRequest)?;
+                    let password = password.to_string();
+                    let author = match entries.get("author").map(|author| author.trim()) {
+                        Some(author) if !author.is_empty() => Some(author.to_string()),
+                        _ => None,
+                    };
+                    Ok(Login::Post {
+                        password,
+                        author,
+                        query,
+                    })
+                })
+            }
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        match self {
+            Login::Get { query } => web::response_html(Login::page(state, &query, false)),
+            Login::Post {
+                password,
+                author,
+                query,
+            } => {
+                match state.auth.as_ref().and_then(|auth| auth.login(&password, author)) {
+                    Some(cookie) => {
+                        let next = query.next.unwrap_or_else(|| Homepage::url(&EditState::default()));
+                        Response::builder()
+                            .status(StatusCode::SEE_OTHER)
+                            .header(hyper::header::LOCATION, next)
+                            .header(hyper::header::SET_COOKIE, cookie)
+                            .body(Body::empty())
+                            .unwrap()
+                    }
+                    None => Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .header(hyper::header::CONTENT_TYPE, "text/html")
+                        .body(Body::from(Login::page(state, &query, true)))
+                        .unwrap(),
+                }
+            }
+        }
+    }
+}
+
+/// Close the editing session of the client.
+struct Logout {
+    headers: hyper::HeaderMap,
+}
+impl Logout {
+    fn url() -> String {
+        web::prefixed_path("/logout")
+    }
+}
+impl EndPoint for Logout {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::POST, "/logout") => Ok(FromRequestOk::Value(Logout {
+                headers: r.headers().clone(),
+            })),
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        let mut response = web::response_redirection(&Homepage::url(&EditState::default()));
+        if let Some(ref auth) = state.auth {
+            let cookie = auth.logout(&self.headers);
+            let cookie = hyper::header::HeaderValue::from_str(&cookie).unwrap();
+            response.headers_mut().insert(hyper::header::SET_COOKIE, cookie);
+        }
+        response
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Language specific configuration.
+/// Contains text constants.
+mod lang {
+    use maud::PreEscaped;
+    type ConstStr = PreEscaped<&'static str>;
+
+    /// Descriptor of names, in French.
+    pub const NAMED_ATOM: &'static str = "est nommé";
+
+    pub const COMMIT_BUTTON: ConstStr = PreEscaped("Valider");
+    pub const PREVIEW_BUTTON: ConstStr = PreEscaped("Prévisualiser");
+    pub const INVALID_ELEMENT_INDEX: ConstStr = PreEscaped("Index invalide");
 
     pub const RELATION: ConstStr = PreEscaped("Relation");
     pub const ATOM: ConstStr = PreEscaped("Atome");
     pub const ABSTRACT: ConstStr = PreEscaped("Abstrait");
     pub const DISPLAY_DESCRIBES: ConstStr = PreEscaped("Décrit");
+    pub const DISPLAY_SUBJECT_OF: ConstStr = PreEscaped("Sujet de");
+    pub const DISPLAY_COMPLEMENT_OF: ConstStr = PreEscaped("Objet de");
+    pub const DISPLAY_IMPLIED: ConstStr = PreEscaped("Déduites des règles");
+    pub const GRAPH_ALT: ConstStr = PreEscaped("Graphe du voisinage (nécessite graphviz)");
+    pub const INTERACTIVE_GRAPH: ConstStr = PreEscaped("Graphe interactif");
+    pub const GRAPH_DEPTH: ConstStr = PreEscaped("Profondeur");
+    pub const DOWNLOAD_DOT: ConstStr = PreEscaped("Télécharger le graphe (dot)");
+    pub const DOWNLOAD_SVG: ConstStr = PreEscaped("Télécharger le graphe (SVG)");
+    pub const EXPORT_DATABASE: ConstStr = PreEscaped("Exporter la base :");
 
     pub const HOMEPAGE: ConstStr = PreEscaped("Accueil");
     pub const HOMEPAGE_HELP: ConstStr =
@@ -907,8 +2667,37 @@ mod lang {
     pub const ALL_ELEMENTS_NAV: ConstStr = PreEscaped("Éléments");
     pub const ALL_ELEMENTS_TITLE: ConstStr = PreEscaped("Liste des éléments");
 
+    pub const INDEX_NAV: ConstStr = PreEscaped("Index");
+    pub const INDEX_TITLE: ConstStr = PreEscaped("Index des éléments");
+    pub const INDEX_SORT_BY_INDEX: ConstStr = PreEscaped("Trier par numéro");
+    pub const INDEX_SORT_BY_NAME: ConstStr = PreEscaped("Trier par nom");
+    pub const INDEX_ALL_NAMESPACES: ConstStr = PreEscaped("Tous les espaces");
+    pub const PREVIOUS_PAGE: ConstStr = PreEscaped("Précédent");
+    pub const NEXT_PAGE: ConstStr = PreEscaped("Suivant");
+
+    pub const RECENT_NAV: ConstStr = PreEscaped("Récents");
+    pub const RECENT_TITLE: ConstStr = PreEscaped("Éléments modifiés récemment");
+    pub const HISTORY_NAV: ConstStr = PreEscaped("Historique");
+    pub const HISTORY_TITLE: ConstStr = PreEscaped("Éléments visités récemment");
+
+    pub const WARNINGS_NAV: ConstStr = PreEscaped("Avertissements");
+    pub const WARNINGS_TITLE: ConstStr = PreEscaped("Structures suspectes");
+    pub const WARNINGS_NONE: ConstStr = PreEscaped("Aucune structure suspecte.");
+    pub const WARNING_MANY_REFERENCES: ConstStr = PreEscaped("Trop de relations");
+    pub const WARNING_LARGE_ATOM: ConstStr = PreEscaped("Texte trop long");
+    pub const WARNING_DESCRIPTOR_USED_ONCE: ConstStr = PreEscaped("Verbe utilisé une seule fois");
+    pub const WARNING_DEEP_RELATION: ConstStr = PreEscaped("Relation trop imbriquée");
+    pub const WARNING_NOT_UNIQUE: ConstStr = PreEscaped("Plusieurs relations pour le verbe unique");
+
+    pub const SIMILAR_NAV: ConstStr = PreEscaped("Similaires");
+    pub const SIMILAR_TITLE: ConstStr = PreEscaped("Éléments de même structure");
+    pub const SIMILAR_EXAMPLE: ConstStr = PreEscaped("Modèle");
+
     pub const SEARCH_ATOM_NAV: ConstStr = PreEscaped("Chercher");
+    pub const SEARCH_BOX: ConstStr = PreEscaped("Chercher un texte");
+    pub const PALETTE_PLACEHOLDER: ConstStr = PreEscaped("Aller à l'élément nommé...");
     pub const SEARCH_ATOM_TITLE: ConstStr = PreEscaped("Recherche par texte");
+    pub const SEARCH_IN_TEXTS: ConstStr = PreEscaped("Descriptions contenant tous les mots");
 
     pub const ATOM_TEXT: ConstStr = PreEscaped("Texte");
     pub const CREATE_ATOM_NAV: ConstStr = PreEscaped("Atome...");
@@ -924,11 +2713,14 @@ mod lang {
     pub const CREATE_RELATION_NAV: ConstStr = PreEscaped("Relation...");
     pub const CREATE_RELATION_TITLE: ConstStr = PreEscaped("Ajouter une relation");
     pub const CREATE_RELATION_MISSING: ConstStr = PreEscaped("Champ manquant !");
+    pub const SCHEMA_DESCRIPTORS: ConstStr = PreEscaped("Verbes du schéma");
+    pub const SCHEMA_VIOLATION: ConstStr = PreEscaped("Contrainte non respectée");
 
     pub const REMOVE_ELEMENT_NAV: ConstStr = PreEscaped("Supprimer");
     pub const REMOVE_ELEMENT_TITLE: ConstStr = PreEscaped("Supprimer un élément");
     pub const REMOVE_ELEMENT_REFERENCED_MESSAGE: ConstStr = PreEscaped("Élément référencé par :");
     pub const REMOVE_ELEMENT_REMOVED: ConstStr = PreEscaped("Élément supprimé");
+    pub const PROTECTED_MESSAGE: ConstStr = PreEscaped("Élément protégé.");
 
     pub const CHANGE_ATOM_VALUE_NAV: ConstStr = PreEscaped("Changer");
     pub const CHANGE_ATOM_VALUE_TITLE: ConstStr = PreEscaped("Changer atome...");
@@ -937,21 +2729,31 @@ mod lang {
     pub const ATOM_TO_NAMED_ABSTRACT_NAV: ConstStr = PreEscaped("En abstrait");
     pub const ATOM_TO_NAMED_ABSTRACT_TITLE: ConstStr =
         PreEscaped("Transformer atome en abstrait nommé");
-}
 
-fn css_class_name(element: Ref<Element>) -> &'static str {
-    match element.value() {
-        Element::Abstract => "abstract",
-        Element::Atom(_) => "atom",
-        Element::Relation(_) => "relation",
-    }
+    pub const LOGIN_NAV: ConstStr = PreEscaped("Connexion");
+    pub const LOGIN_TITLE: ConstStr = PreEscaped("Connexion pour modifier");
+    pub const LOGIN_PASSWORD: ConstStr = PreEscaped("Mot de passe");
+    pub const LOGIN_AUTHOR: ConstStr = PreEscaped("Nom (facultatif)");
+    pub const LOGIN_FAILED: ConstStr = PreEscaped("Mot de passe incorrect.");
+    pub const LOGOUT_BUTTON: ConstStr = PreEscaped("Déconnexion");
+
+    pub const VERSIONS_NAV: ConstStr = PreEscaped("Versions");
+    pub const VERSIONS_TITLE: ConstStr = PreEscaped("Versions enregistrées");
+    pub const NO_VERSIONS: ConstStr = PreEscaped("Aucune version enregistrée.");
+    pub const VERSION_NAME: ConstStr = PreEscaped("Nom de version");
+    pub const VERSION_TAG_BUTTON: ConstStr = PreEscaped("Enregistrer l'état actuel");
+    pub const VERSION_RESTORE_BUTTON: ConstStr = PreEscaped("Restaurer");
+
+    pub const DELETED_NAV: ConstStr = PreEscaped("Corbeille");
+    pub const DELETED_TITLE: ConstStr = PreEscaped("Éléments supprimés");
+    pub const NO_DELETED: ConstStr = PreEscaped("Aucun élément supprimé.");
+    pub const DELETED_RESTORE_BUTTON: ConstStr = PreEscaped("Restaurer");
+    pub const DELETED_PURGE_BUTTON: ConstStr = PreEscaped("Vider la corbeille");
 }
 
 /// Atom default representation: with its text.
 fn atom_name(r: Ref<Atom>) -> Markup {
-    match r.value() {
-        Atom::Text(s) => html! { (s) },
-    }
+    html! { (r.value()) }
 }
 /// Abstract default representation: find a naming atom, or use index.
 fn abstract_name(r: Ref<Abstract>) -> Markup {
@@ -964,17 +2766,14 @@ fn naming_atom(r: Ref<Abstract>) -> Option<Ref<Atom>> {
     r.database()
         .index_of_text_atom(lang::NAMED_ATOM)
         .and_then(|is_named| {
-            r.subject_of().iter().find_map(|r| {
-                // Search for first naming relation, restricted to atom names
-                if r.descriptor().index() == is_named {
+            // Search for first naming relation, restricted to atom names
+            r.subject_of_where(|r| r.value().descriptor == is_named)
+                .find_map(|r| {
                     r.complement().and_then(|r| match r.cases() {
                         ElementRef::Atom(r) => Some(r),
                         _ => None,
                     })
-                } else {
-                    None
-                }
-            })
+                })
         })
 }
 /// Relation representation: index, or components recursively.
@@ -1010,6 +2809,48 @@ fn element_name(r: Ref<Element>, depth: u64) -> Markup {
     }
 }
 
+/// Plain text version of element_name, used to sort elements.
+fn element_text_name(r: Ref<Element>, depth: u64) -> String {
+    let atom_text = |r: Ref<Atom>| r.value().to_string();
+    match r.cases() {
+        ElementRef::Atom(r) => atom_text(r),
+        ElementRef::Abstract(r) => naming_atom(r).map_or(String::new(), atom_text),
+        ElementRef::Relation(r) => {
+            if depth > 0 {
+                let mut text = element_text_name(r.subject(), depth - 1);
+                text.push(' ');
+                text.push_str(&element_text_name(r.descriptor(), depth - 1));
+                if let Some(complement) = r.complement() {
+                    text.push(' ');
+                    text.push_str(&element_text_name(complement, depth - 1));
+                }
+                text
+            } else {
+                String::new()
+            }
+        }
+    }
+}
+
+/// Text with occurrences of words (as split by relations::words) in mark elements.
+fn highlighted_words(text: &str, words: &[String]) -> Markup {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_word = false;
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() != in_word {
+            parts.push(&text[start..i]);
+            start = i;
+            in_word = !in_word;
+        }
+    }
+    parts.push(&text[start..]);
+    html! {
+        @for part in parts {
+            @if words.contains(&part.to_lowercase()) { mark { (part) } } @else { (part) }
+        }
+    }
+}
 fn atom_link(r: Ref<Atom>, edit_state: &EditState) -> Markup {
     html! {
         a.atom href=(DisplayElement::url(r.index(), edit_state)) { (atom_name(r)) }
@@ -1033,93 +2874,6 @@ fn element_link(r: Ref<Element>, edit_state: &EditState) -> Markup {
     }
 }
 
-/// Generates sequence of navigation links depending on state.
-fn navigation_links(edit_state: &EditState, displayed: Option<Ref<Element>>) -> Markup {
-    let displayed_i = displayed.map(|e| e.index());
-    html! {
-        a href=(Homepage::url(edit_state)) { (lang::HOMEPAGE) }
-        a href=(ListAllElements::url(edit_state)) { (lang::ALL_ELEMENTS_NAV) }
-        a.atom href=(SearchAtom::url(edit_state)) { (lang::SEARCH_ATOM_NAV) }
-        a.atom href=(CreateAtom::url(edit_state)) { (lang::CREATE_ATOM_NAV) }
-        a.abstract href=(CreateAbstract::url(edit_state)) { (lang::CREATE_ABSTRACT_NAV) }
-        (selection_nav_link(lang::RELATION_SUBJECT, displayed_i, edit_state, |e| e.subject, |e,subject| EditState{ subject, ..*e }))
-        (selection_nav_link(lang::RELATION_DESCRIPTOR, displayed_i, edit_state, |e| e.descriptor, |e,descriptor| EditState{ descriptor, ..*e }))
-        (selection_nav_link(lang::RELATION_COMPLEMENT, displayed_i, edit_state, |e| e.complement, |e,complement| EditState{ complement, ..*e }))
-        a.relation href=(CreateRelation::url(edit_state)) { (lang::CREATE_RELATION_NAV) }
-        @if let Some(displayed) = displayed {
-            a href=(RemoveElement::url(displayed.index(), edit_state)) { (lang::REMOVE_ELEMENT_NAV) }
-            @match displayed.value() {
-                Element::Atom(_) => {
-                    a.atom href=(ChangeAtomValue::url(displayed.index(), edit_state)) { (lang::CHANGE_ATOM_VALUE_NAV) }
-                    a.atom href=(AtomToNamedAbstract::url(displayed.index(), edit_state)) { (lang::ATOM_TO_NAMED_ABSTRACT_NAV) }
-                },
-                _ => {},
-            }
-        }
-    }
-}
-fn selection_nav_link<IFV, WFV>(
-    field_text: PreEscaped<&str>,
-    displayed: Option<Index>,
-    edit_state: &EditState,
-    init_field_value: IFV,
-    with_field_value: WFV,
-) -> Markup
-where
-    IFV: FnOnce(&EditState) -> Option<Index>,
-    WFV: FnOnce(&EditState, Option<Index>) -> EditState,
-{
-    html! {
-        @match (init_field_value(edit_state), displayed) {
-            (None, None) => {},
-            (Some(selected), Some(displayed)) => @if selected == displayed {
-                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, None))) {
-                    "-" (field_text) " #" (selected)
-                }
-            } @else {
-                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, Some(displayed)))) {
-                    "=" (field_text) " #" (selected)
-                }
-            },
-            (None, Some(displayed)) => {
-                a.relation href=(DisplayElement::url(displayed, &with_field_value(edit_state, Some(displayed)))) {
-                    "+" (field_text)
-                }
-            },
-            (Some(selected), None) => {
-                a.relation href=(DisplayElement::url(selected, edit_state)) {
-                    (field_text) " #" (selected)
-                }
-            }
-        }
-    }
-}
-
-/// Generated wiki page final assembly. Adds the navigation bar, overall html structure.
-fn compose_wiki_page<T: AsRef<str>>(
-    title: PreEscaped<T>,
-    content: Markup,
-    navigation_links: Markup,
-) -> String {
-    let template = html! {
-        (maud::DOCTYPE)
-        html {
-            head {
-                meta charset="UTF-8";
-                link rel="stylesheet" type="text/css" href=(StaticAsset::url("style.css"));
-                meta name="viewport" content="width=device-width, initial-scale=1.0";
-                title { (title) };
-            }
-            body {
-                nav { (navigation_links) }
-                main { (content) }
-                script src=(StaticAsset::url("client.js"));
-            }
-        }
-    };
-    template.into_string()
-}
-
 fn parse_index(s: &str) -> Result<Index, web::Error> {
     s.parse().map_err(|_| web::Error::BadRequest)
 }
@@ -1140,7 +2894,7 @@ struct StaticAsset {
 }
 impl StaticAsset {
     fn url(path: &str) -> String {
-        format!("/static/{}", path)
+        web::prefixed_path(&format!("/static/{}", path))
     }
 }
 impl EndPoint for StaticAsset {
@@ -1165,6 +2919,54 @@ impl EndPoint for StaticAsset {
         }
     }
 }
+/// Websocket endpoint, used by pages to refresh when elements they display change.
+struct LiveChannel {
+    key: String,
+    on_upgrade: hyper::upgrade::OnUpgrade,
+}
+impl LiveChannel {
+    fn url() -> String {
+        web::prefixed_path("/live")
+    }
+}
+impl EndPoint for LiveChannel {
+    type State = State;
+    fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+        match (r.method(), r.uri().path()) {
+            (&Method::GET, "/live") => {
+                let upgrade = r.headers().get(hyper::header::UPGRADE);
+                let is_websocket = upgrade
+                    .and_then(|upgrade| upgrade.to_str().ok())
+                    .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+                if !is_websocket {
+                    return Err(web::Error::BadRequest.into());
+                }
+                let key = r
+                    .headers()
+                    .get(hyper::header::SEC_WEBSOCKET_KEY)
+                    .and_then(|key| key.to_str().ok())
+                    .ok_or(web::Error::BadRequest)?
+                    .to_string();
+                Ok(FromRequestOk::Value(LiveChannel {
+                    key,
+                    on_upgrade: r.into_body().on_upgrade(),
+                }))
+            }
+            _ => Err(FromRequestError::NoMatch(r)),
+        }
+    }
+    fn generate_response(self, state: &State) -> Response<Body> {
+        LiveUpdates::connect(&state.live, self.on_upgrade);
+        Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(hyper::header::UPGRADE, "websocket")
+            .header(hyper::header::CONNECTION, "Upgrade")
+            .header(hyper::header::SEC_WEBSOCKET_ACCEPT, live::accept_key(&self.key))
+            .body(Body::empty())
+            .unwrap()
+    }
+}
+
 struct AssetDefinition<'a> {
     path: &'a str,
     mime: &'a str,
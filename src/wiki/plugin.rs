@@ -0,0 +1,161 @@
+use std::io;
+use std::path::Path;
+
+use super::{Handler, State};
+use relations::Database;
+
+/// Domain specific extension of the wiki.
+/// Plugins are compiled in the binary, and enabled by name at startup.
+/// They are shared by the server threads.
+pub trait Plugin: Send + Sync {
+    /// Name used to enable the plugin.
+    fn name(&self) -> &'static str;
+    /// Additional routes, tried after the builtin ones.
+    /// Handlers are registered as given, even for a read only wiki.
+    /// Routes modifying the database must be wrapped in mutation_handler to require a login,
+    /// and should only be returned if the wiki is not read only.
+    fn handlers(&self, _read_only: bool) -> Vec<Handler> {
+        Vec::new()
+    }
+    /// Additional formats of the wiki export links and of the export command.
+    fn exporters(&self) -> Vec<Exporter> {
+        Vec::new()
+    }
+    /// Additional formats of the import command.
+    fn importers(&self) -> Vec<Importer> {
+        Vec::new()
+    }
+    /// Maintenance job, run before each autosave.
+    fn maintenance(&self, _state: &State) {}
+}
+
+/// Format in which a plugin writes the whole database.
+#[derive(Clone, Copy)]
+pub struct Exporter {
+    /// Format name, also the file extension: the wiki serves it at /export.<extension>.
+    pub extension: &'static str,
+    /// Media type of the written files.
+    pub mime: &'static str,
+    /// Write the database. Streamed from another thread by the wiki.
+    pub write: fn(&Database, &mut dyn io::Write) -> io::Result<()>,
+}
+
+/// Format from which a plugin adds elements to the database.
+#[derive(Clone, Copy)]
+pub struct Importer {
+    /// Format name, selected by the import command.
+    pub name: &'static str,
+    /// Add the content of the file to the database, returning the number of imported elements.
+    pub read: fn(&mut Database, &Path) -> Result<usize, String>,
+}
+
+/// Select plugins by name among the ones available in the binary.
+pub fn enabled<'a, I>(
+    available: Vec<Box<dyn Plugin>>,
+    names: I,
+) -> Result<Vec<Box<dyn Plugin>>, String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut available = available;
+    names
+        .map(|name| match available.iter().position(|p| p.name() == name) {
+            Some(i) => Ok(available.swap_remove(i)),
+            None => Err(format!("Unknown or duplicated plugin: {}", name)),
+        })
+        .collect()
+}
+
+/******************************************************************************
+ * Tests.
+ */
+#[cfg(test)]
+mod tests {
+    use super::super::{autosave, end_point_handler, handlers, response_html, web};
+    use super::super::{EndPoint, FromRequestError, FromRequestOk};
+    use super::*;
+    use config;
+    use hyper::{Body, Method, Request, Response};
+    use relations::{read_database_from_file, write_database_to_file};
+    use std::sync::Arc;
+    use tokio::prelude::{Future, Stream};
+    use uuid::Uuid;
+
+    /// Counts elements on a page, and adds one at each maintenance.
+    struct Counter;
+    impl Plugin for Counter {
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+        fn handlers(&self, _read_only: bool) -> Vec<Handler> {
+            vec![end_point_handler::<CounterPage>]
+        }
+        fn exporters(&self) -> Vec<Exporter> {
+            vec![Exporter {
+                extension: "count",
+                mime: "text/plain",
+                write: |database, w| write!(w, "{}", database.iter().count()),
+            }]
+        }
+        fn maintenance(&self, state: &State) {
+            state.get_mut().create_abstract_element();
+        }
+    }
+    struct CounterPage;
+    impl EndPoint for CounterPage {
+        type State = State;
+        fn from_request(r: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError> {
+            match (r.method(), r.uri().path()) {
+                (&Method::GET, "/counter") => Ok(FromRequestOk::Value(CounterPage)),
+                _ => Err(FromRequestError::NoMatch(r)),
+            }
+        }
+        fn generate_response(self, state: &State) -> Response<Body> {
+            response_html(state.get().iter().count().to_string())
+        }
+    }
+
+    fn get(path: &str, state: &Arc<State>, handlers: &[Handler]) -> (u16, String) {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        let response = web::handle_request(request, state.clone(), handlers.iter())
+            .wait()
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = response.into_body().concat2().wait().unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn plugin_routes_and_maintenance() {
+        let available: Vec<Box<dyn Plugin>> = vec![Box::new(Counter)];
+        assert!(enabled(Vec::new(), vec!["counter"].into_iter()).is_err());
+        let plugins = enabled(available, vec!["counter"].into_iter()).unwrap();
+        assert_eq!(plugins.len(), 1);
+
+        let dir = std::env::temp_dir().join(format!("rett-test-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let database_file = dir.join("db");
+        write_database_to_file(&database_file, &Database::new()).unwrap();
+        let config = config::Config::default().wiki;
+        let state = State::from_file(
+            &database_file,
+            &dir.join("db.bak"),
+            &config,
+            None,
+            String::new(),
+            &plugins,
+        );
+        let state = Arc::new(state.unwrap());
+        let handlers = handlers(false, false, &plugins);
+        assert_eq!(get("/counter", &state, &handlers), (200, "0".into()));
+        assert_eq!(get("/export.count", &state, &handlers), (200, "0".into()));
+        assert_eq!(get("/export.unknown", &state, &handlers).0, 404);
+
+        // Maintenance changes are written by the autosave
+        autosave(&[state.clone()], &plugins).unwrap();
+        assert_eq!(get("/counter", &state, &handlers), (200, "1".into()));
+        let written = read_database_from_file(&database_file);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written.unwrap().iter().count(), 1);
+    }
+}
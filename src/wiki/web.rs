@@ -1,18 +1,28 @@
-use hyper::header;
+use futures::sync::mpsc;
+use futures::{Poll, Sink};
+use hyper::header::{self, HeaderMap};
 use hyper::rt::{Future, Stream};
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::{Body, Request, Response, StatusCode, Uri};
 use percent_encoding::{percent_decode, utf8_percent_encode, QUERY_ENCODE_SET};
 use std::borrow::{Borrow, Cow};
+use std::cell::RefCell;
 use std::fmt::{self, Write};
-use std::rc::Rc;
+use std::io;
+use std::mem;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
 use tokio::prelude::future;
 
-use utils::Map;
+use utils::{remove_prefix, Map};
 
+/// Failure of a request, answered with an error status.
 #[derive(Debug)]
 pub enum Error {
+    /// Invalid request: 400.
     BadRequest,
+    /// Failure of the server: 500.
     Internal,
 }
 impl fmt::Display for Error {
@@ -38,26 +48,34 @@ impl From<Error> for StatusCode {
 /******************************************************************************
  * Route selection.
  */
-pub type BoxedFuture<T> = Box<dyn Future<Item = T, Error = Error>>;
+/// Future that can run on any thread of the runtime.
+pub type BoxedFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
 
 /// Interface for an URL endpoint.
 pub trait EndPoint: Sized {
+    /// Data shared by all requests.
     type State: ?Sized;
     //TODO reintroduce if useful: fn url(&self) -> String;
+    /// Parse the request, or give it back if the route does not match.
     fn from_request(request: Request<Body>) -> Result<FromRequestOk<Self>, FromRequestError>;
+    /// Response to a parsed request.
     fn generate_response(self, state: &Self::State) -> Response<Body>;
 }
 
 /// Result of from_request: either a direct value, or a future.
 pub enum FromRequestOk<T> {
+    /// Parsed from the method, url and headers.
     Value(T),
+    /// Parsed from the body, when it has been received.
     Future(BoxedFuture<T>),
 }
 /// Error code used for routing.
 /// NoMatch returns the request so that it can be used by other handlers.
 /// Error stops the routing.
 pub enum FromRequestError {
+    /// Route of another handler.
     NoMatch(Request<Body>),
+    /// Invalid request for this route.
     Error(Error),
 }
 impl From<Error> for FromRequestError {
@@ -66,10 +84,14 @@ impl From<Error> for FromRequestError {
     }
 }
 
+/// Handler of the routes of an end point.
 pub fn end_point_handler<E: EndPoint + 'static>(
     request: Request<Body>,
-    state: Rc<E::State>,
-) -> Result<BoxedFuture<Response<Body>>, FromRequestError> {
+    state: Arc<E::State>,
+) -> Result<BoxedFuture<Response<Body>>, FromRequestError>
+where
+    E::State: Send + Sync,
+{
     E::from_request(request).map(move |ok_value| {
         let response_future: BoxedFuture<Response<Body>> = match ok_value {
             FromRequestOk::Value(v) => Box::new(future::ok(v.generate_response(state.as_ref()))),
@@ -90,13 +112,13 @@ pub fn end_point_handler<E: EndPoint + 'static>(
 /// Apply the first matching handler, or generate an error reponse (400 or 404).
 pub fn handle_request<S, I>(
     request: Request<Body>,
-    state: Rc<S>,
+    state: Arc<S>,
     handlers: I,
 ) -> BoxedFuture<Response<Body>>
 where
     I: Iterator,
     <I as Iterator>::Item:
-        Fn(Request<Body>, Rc<S>) -> Result<BoxedFuture<Response<Body>>, FromRequestError>,
+        Fn(Request<Body>, Arc<S>) -> Result<BoxedFuture<Response<Body>>, FromRequestError>,
 {
     let tried_all_handlers = handlers.fold(
         Err(FromRequestError::NoMatch(request)),
@@ -117,6 +139,77 @@ where
     }
 }
 
+/******************************************************************************
+ * Applications served under a path prefix, like "/db/name".
+ */
+
+thread_local! {
+    /// Path prefix of the application generating a response on this thread.
+    static PATH_PREFIX: RefCell<String> = RefCell::new(String::new());
+}
+
+/// Remove the prefix from the request path if it is under it, so that routes do not depend on it.
+/// "/db/a/x" and "/db/a" are under "/db/a", but "/db/ab" is not.
+pub fn remove_path_prefix(request: &mut Request<Body>, prefix: &str) -> bool {
+    let path_and_query = request.uri().path_and_query().map_or("/", |p| p.as_str());
+    let stripped = match remove_prefix(path_and_query, prefix) {
+        Some(rest) if rest.starts_with('/') => rest.to_string(),
+        Some(rest) if rest.is_empty() || rest.starts_with('?') => format!("/{}", rest),
+        _ => return false,
+    };
+    match stripped.parse::<Uri>() {
+        Ok(uri) => {
+            *request.uri_mut() = uri;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Apply handle_request for an application served under prefix, its request path being stripped of it.
+/// Paths generated by to_path_and_query and prefixed_path while responding start with the prefix.
+pub fn handle_request_with_prefix<S, I>(
+    request: Request<Body>,
+    prefix: &str,
+    state: Arc<S>,
+    handlers: I,
+) -> BoxedFuture<Response<Body>>
+where
+    I: Iterator,
+    <I as Iterator>::Item:
+        Fn(Request<Body>, Arc<S>) -> Result<BoxedFuture<Response<Body>>, FromRequestError>,
+{
+    let prefix = prefix.to_string();
+    let response = with_path_prefix(&prefix, || handle_request(request, state, handlers));
+    Box::new(PathPrefixed { prefix, response })
+}
+
+/// Path of the application, with the prefix of the response being generated.
+pub fn prefixed_path(path: &str) -> String {
+    PATH_PREFIX.with(|prefix| format!("{}{}", prefix.borrow(), path))
+}
+
+fn with_path_prefix<T, F: FnOnce() -> T>(prefix: &str, f: F) -> T {
+    PATH_PREFIX.with(|current| *current.borrow_mut() = prefix.to_string());
+    let result = f();
+    PATH_PREFIX.with(|current| current.borrow_mut().clear());
+    result
+}
+
+/// Responses of endpoints reading a request body are generated when the future is polled.
+struct PathPrefixed {
+    prefix: String,
+    response: BoxedFuture<Response<Body>>,
+}
+impl Future for PathPrefixed {
+    type Item = Response<Body>;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Response<Body>, Error> {
+        let response = &mut self.response;
+        with_path_prefix(&self.prefix, || response.poll())
+    }
+}
+
 /******************************************************************************
  * Short utility functions.
  */
@@ -129,6 +222,14 @@ pub fn response_html<B: Into<Body>>(body: B) -> Response<Body> {
         .body(body.into())
         .unwrap()
 }
+/// Create a response with a JSON body.
+pub fn response_json<B: Into<Body>>(status: StatusCode, body: B) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
 /// Create an empty 404 response.
 pub fn response_empty_400() -> Response<Body> {
     Response::builder()
@@ -158,8 +259,9 @@ pub trait QueryFormat: Sized {
     fn from_query(entries: &UrlDecodedEntries) -> Result<Self, Error>;
 }
 
-pub fn to_path_and_query<P: Into<String>, Q: QueryFormat>(path: P, q: &Q) -> String {
-    let mut builder = PathQueryBuilder::new(path.into());
+/// Path of the application with the query representing q, see prefixed_path.
+pub fn to_path_and_query<P: AsRef<str>, Q: QueryFormat>(path: P, q: &Q) -> String {
+    let mut builder = PathQueryBuilder::new(prefixed_path(path.as_ref()));
     q.to_query(&mut builder);
     builder.build()
 }
@@ -177,24 +279,161 @@ pub fn with_post_entries<E, F>(
     f: F,
 ) -> Result<FromRequestOk<E>, FromRequestError>
 where
-    E: EndPoint + 'static,
-    F: FnOnce(UrlDecodedEntries) -> Result<E, Error> + 'static,
+    E: EndPoint + Send + 'static,
+    F: FnOnce(UrlDecodedEntries) -> Result<E, Error> + Send + 'static,
+{
+    match media_type(&request) {
+        Some(ref t) if t == "application/x-www-form-urlencoded" => {
+            Ok(FromRequestOk::Future(Box::new(
+                request
+                    .into_body()
+                    .concat2()
+                    .map_err(|_| Error::Internal)
+                    .and_then(move |body| {
+                        let entries = UrlDecodedEntries::decode(body.as_ref())?;
+                        f(entries)
+                    }),
+            )))
+        }
+        _ => Err(FromRequestError::Error(Error::BadRequest)),
+    }
+}
+
+/// Content type of the request body without parameters like the charset, in lowercase.
+fn media_type(request: &Request<Body>) -> Option<String> {
+    let content_type = request.headers().get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let media_type = content_type.split(';').next().unwrap_or("");
+    Some(media_type.trim().to_ascii_lowercase())
+}
+
+/// Read a request body sent as JSON.
+pub fn with_json_body<E, F>(
+    request: Request<Body>,
+    f: F,
+) -> Result<FromRequestOk<E>, FromRequestError>
+where
+    E: EndPoint + Send + 'static,
+    F: FnOnce(&str) -> Result<E, Error> + Send + 'static,
 {
-    match request.headers().get(header::CONTENT_TYPE) {
-        Some(t) if t == "application/x-www-form-urlencoded" => Ok(FromRequestOk::Future(Box::new(
+    match media_type(&request) {
+        Some(ref t) if t == "application/json" => Ok(FromRequestOk::Future(Box::new(
             request
                 .into_body()
                 .concat2()
                 .map_err(|_| Error::Internal)
                 .and_then(move |body| {
-                    let entries = UrlDecodedEntries::decode(body.as_ref())?;
-                    f(entries)
+                    let text = str::from_utf8(body.as_ref()).map_err(|_| Error::BadRequest)?;
+                    f(text)
                 }),
         ))),
         _ => Err(FromRequestError::Error(Error::BadRequest)),
     }
 }
 
+/// Values of the cookies with this name in request headers.
+pub fn cookie_values<'h>(headers: &'h HeaderMap, name: &'h str) -> impl Iterator<Item = &'h str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(move |cookie| remove_prefix(cookie.trim(), name))
+        .filter_map(|cookie| remove_prefix(cookie, "="))
+}
+
+/******************************************************************************
+ * Streamed responses.
+ */
+
+/// Size of the body chunks of streamed responses.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Streamed responses being written, each by its own thread.
+static STREAMING_THREADS: AtomicUsize = AtomicUsize::new(0);
+/// Further streamed responses are refused until one completes.
+const MAX_STREAMING_THREADS: usize = 4;
+
+/// Create an ok response whose body is written by f in its own thread, and sent in chunks.
+/// Buffered output is bounded by a few chunks: writes wait for the client to receive previous ones.
+/// The data written by f is not copied, so it should be shared with f rather than cloned for it.
+/// If f fails the connection is aborted, so that the client cannot mistake the body for a complete one.
+/// If MAX_STREAMING_THREADS responses are already being written, returns 503 Service Unavailable.
+pub fn response_streamed<F>(content_type: &str, f: F) -> Response<Body>
+where
+    F: FnOnce(&mut dyn io::Write) -> io::Result<()> + Send + 'static,
+{
+    if STREAMING_THREADS.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMING_THREADS {
+        STREAMING_THREADS.fetch_sub(1, Ordering::SeqCst);
+        return Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::RETRY_AFTER, "10")
+            .body(Body::empty())
+            .unwrap();
+    }
+    let (sender, receiver) = mpsc::channel(1);
+    thread::spawn(move || {
+        let _running = StreamingThread;
+        let mut writer = ChunkWriter {
+            sender: Some(sender),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        };
+        if let Err(e) = f(&mut writer).and_then(|()| writer.send_buffer()) {
+            eprintln!("[web] Streamed response interrupted: {}", e);
+            let _ = writer.send(Err(Error::Internal));
+        }
+    });
+    let body = receiver
+        .map_err(|()| Error::Internal)
+        .and_then(|chunk: Result<Vec<u8>, Error>| chunk);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::wrap_stream(body))
+        .unwrap()
+}
+
+/// Counts a streaming thread until it terminates, even by a panic.
+struct StreamingThread;
+impl Drop for StreamingThread {
+    fn drop(&mut self) {
+        STREAMING_THREADS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Buffers writes into chunks sent to a response body stream.
+struct ChunkWriter {
+    /// None after the client disconnected.
+    sender: Option<mpsc::Sender<Result<Vec<u8>, Error>>>,
+    buffer: Vec<u8>,
+}
+impl ChunkWriter {
+    /// Blocks until the stream has room for the chunk.
+    fn send(&mut self, chunk: Result<Vec<u8>, Error>) -> io::Result<()> {
+        let disconnected = || io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected");
+        let sender = self.sender.take().ok_or_else(disconnected)?;
+        self.sender = Some(sender.send(chunk).wait().map_err(|_| disconnected())?);
+        Ok(())
+    }
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.send(Ok(chunk))
+    }
+}
+impl io::Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?
+        }
+        Ok(data.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
 /******************************************************************************
  * Query writing tools.
  */